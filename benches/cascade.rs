@@ -0,0 +1,205 @@
+use std::collections::HashMap;
+
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+
+use chain::game::State;
+use chain::grid::{Grid, Owner, Point, MAX_WAVES};
+use chain::render::{marble_rect, shadow_lift, shadow_rect};
+
+const CELLSIZE: Point = Point::new(100, 100);
+
+// Number of spreading steps that are resolved per iteration
+const WAVES: usize = 100;
+
+/* A board where almost every cell is one marble short of spreading, in blocks of alternating
+ * owners. One more marble sets off a cascade that soon covers the whole board. Such a board never
+ * comes to rest, so only a fixed number of spreading steps is measured.
+ */
+fn loaded_grid(dim: Point) -> Grid {
+    let mut grid = Grid::new(dim);
+    for p in grid.points().collect::<Vec<Point>>() {
+        if p.re % 4 == 0 && p.im % 4 == 0 {
+            continue;
+        }
+        let owner = Owner::new(((p.re / 4 + p.im / 4) % 2) as usize, 2).unwrap();
+        while !grid.cell(p).is_critical_soon() {
+            grid.add_marble(p, owner, CELLSIZE).unwrap();
+        }
+    }
+    grid
+}
+
+/* Resolve the cascade without animating, as when only the result matters. If scan is set, each
+ * step also visits every cell to look for cells at capacity, like spreading did before it kept a
+ * worklist of them.
+ */
+fn resolve(grid: &mut Grid, scan: bool) {
+    let (mut state, _) = grid.add_marble(Point::new(1, 1), Owner::new(0, 2).unwrap(), CELLSIZE).unwrap();
+    for _ in 0..WAVES {
+        if let State::AcceptingInput = state {
+            break;
+        }
+        if scan {
+            black_box(grid.cells().filter(|(_, cell)| cell.marbles().count() >= cell.threshold() as usize).count());
+        }
+        state = grid.step(state, 1.0, CELLSIZE).0;
+    }
+}
+
+/* A 10x8 board in the middle of a game, with both players spread over it */
+fn midgame_grid() -> Grid {
+    let mut grid = Grid::new(Point::new(10, 8));
+    for p in grid.points().collect::<Vec<Point>>() {
+        let count = (p.re * 7 + p.im * 3) % 4;
+        let owner = Owner::new(((p.re / 3 + p.im / 2) % 2) as usize, 2).unwrap();
+        for _ in 0..count {
+            if grid.cell(p).is_critical_soon() {
+                break;
+            }
+            grid.add_marble(p, owner, CELLSIZE).unwrap();
+        }
+    }
+    grid
+}
+
+/* Marbles of the player after the move was played on a copy of the grid */
+fn score_copy(grid: &Grid, p: Point, owner: Owner) -> usize {
+    let mut grid = grid.clone();
+    let (mut state, _) = grid.add_marble(p, owner, CELLSIZE).unwrap();
+    while let State::Animating(_) = state {
+        if grid.single_owner().is_some() || grid.chain() >= MAX_WAVES {
+            break;
+        }
+        state = grid.step(state, 1.0, CELLSIZE).0;
+    }
+    grid.marbles_of(owner).count()
+}
+
+/* Two-ply search that copies the grid for each move */
+fn search_copy(grid: &Grid) -> usize {
+    let (a, b) = (Owner::new(0, 2).unwrap(), Owner::new(1, 2).unwrap());
+    let mut best = 0;
+    for p in grid.points().filter(|&p| grid.check_move(p, a).is_ok()).collect::<Vec<_>>() {
+        let mut after = grid.clone();
+        let _ = after.cascade(p, a, CELLSIZE).unwrap().count();
+        let worst = after.points()
+            .filter(|&q| after.check_move(q, b).is_ok())
+            .map(|q| score_copy(&after, q, b))
+            .max()
+            .unwrap_or(0);
+        best = best.max(usize::MAX - worst);
+    }
+    best
+}
+
+/* The same search, undoing each move instead of copying the grid */
+fn search_simulated(grid: &mut Grid) -> usize {
+    let (a, b) = (Owner::new(0, 2).unwrap(), Owner::new(1, 2).unwrap());
+    let mut best = 0;
+    for p in grid.points().filter(|&p| grid.check_move(p, a).is_ok()).collect::<Vec<_>>() {
+        let worst = grid.with_simulated_move(p, a, CELLSIZE, |after| {
+            let replies: Vec<Point> = after.points().filter(|&q| after.check_move(q, b).is_ok()).collect();
+            replies.into_iter()
+                .map(|q| after.with_simulated_move(q, b, CELLSIZE, |grid| {
+                    grid.marbles_of(b).count()
+                }).unwrap())
+                .max()
+                .unwrap_or(0)
+        }).unwrap();
+        best = best.max(usize::MAX - worst);
+    }
+    best
+}
+
+fn search(c: &mut Criterion) {
+    let grid = midgame_grid();
+    let mut simulated = grid.clone();
+    assert_eq!(search_copy(&grid), search_simulated(&mut simulated));
+    c.bench_function("search 10x8 copying", |b| b.iter(|| search_copy(&grid)));
+    c.bench_function("search 10x8 undoing", |b| b.iter(|| search_simulated(&mut simulated)));
+}
+
+fn cascade(c: &mut Criterion) {
+    let position = loaded_grid(Point::new(40, 30)).encode();
+    c.bench_function("cascade 40x30", |b| b.iter_batched(
+        || Grid::decode(&position, 2, CELLSIZE).unwrap(),
+        |mut grid| resolve(&mut grid, false),
+        BatchSize::SmallInput,
+    ));
+    // The full board cascade on 26x20, with the worklist and with a scan of the board per step
+    let position = loaded_grid(Point::new(26, 20)).encode();
+    for (name, scan) in [("cascade 26x20 worklist", false), ("cascade 26x20 scanning", true)] {
+        c.bench_function(name, |b| b.iter_batched(
+            || Grid::decode(&position, 2, CELLSIZE).unwrap(),
+            |mut grid| resolve(&mut grid, scan),
+            BatchSize::SmallInput,
+        ));
+    }
+}
+
+/* Animate the cascade of a corner early in a game, frame by frame, on boards of different size.
+ * Only the cells taking part in a spreading step are animated, so each frame should cost about the
+ * same, no matter how many other cells the board has.
+ */
+fn animate(c: &mut Criterion) {
+    for dim in [Point::new(6, 5), Point::new(26, 20)] {
+        let mut grid = Grid::new(dim);
+        grid.add_marble(Point::new(0, 0), Owner::new(0, 2).unwrap(), CELLSIZE).unwrap();
+        grid.add_marble(dim - Point::new(1, 1), Owner::new(1, 2).unwrap(), CELLSIZE).unwrap();
+        c.bench_function(&format!("animate sparse {}x{}", dim.re, dim.im), |b| b.iter_batched(
+            || grid.clone(),
+            |mut grid| {
+                let (mut state, _) = grid.add_marble(Point::new(0, 0), Owner::new(0, 2).unwrap(), CELLSIZE).unwrap();
+                while let State::Animating(_) = state {
+                    state = grid.step(state, 0.05, CELLSIZE).0;
+                }
+                grid
+            },
+            BatchSize::SmallInput,
+        ));
+    }
+}
+
+/* Emptying the loaded 40x30 board for another game, compared to creating a new one */
+fn reset(c: &mut Criterion) {
+    let dim = Point::new(40, 30);
+    c.bench_function("new 40x30", |b| b.iter(|| Grid::new(dim)));
+    c.bench_function("reset 40x30", |b| b.iter_batched_ref(
+        || loaded_grid(dim),
+        |grid| grid.reset(),
+        BatchSize::LargeInput,
+    ));
+}
+
+/* What the marbles of a full 9x9 board in the middle of a cascade cost per frame, without and with
+ * their shadows. The shadows double the blits, which need a window to be measured, but everything
+ * else they add to a frame is here. The buffers are reused like the renderer does.
+ */
+fn shadows(c: &mut Criterion) {
+    let mut grid = loaded_grid(Point::new(9, 9));
+    let (mut state, _) = grid.add_marble(Point::new(1, 1), Owner::new(0, 2).unwrap(), CELLSIZE).unwrap();
+    for _ in 0..3 {
+        state = grid.step(state, 0.3, CELLSIZE).0;
+    }
+    assert!(matches!(state, State::Animating(_)) && grid.in_flight().count() > 0);
+    let mut rects = Vec::new();
+    let mut lifts = HashMap::new();
+    for (name, shadows) in [("marbles 9x9", false), ("marbles 9x9 with shadows", true)] {
+        c.bench_function(name, |b| b.iter(|| {
+            rects.clear();
+            rects.extend(grid.marbles().map(|marble| (marble.id(), marble_rect(marble.subpixel_pos(), 1, 20))));
+            if shadows {
+                lifts.clear();
+                lifts.extend(grid.in_flight().map(|marble| (marble.id, shadow_lift(marble.remaining))));
+                for i in 0..rects.len() {
+                    let (id, rect) = rects[i];
+                    rects.push((id, shadow_rect(rect, lifts.get(&id).copied().unwrap_or(0.0))));
+                }
+            }
+            black_box(&rects);
+        }));
+    }
+}
+
+criterion_group!(benches, cascade, animate, search, reset, shadows);
+criterion_main!(benches);
@@ -0,0 +1,114 @@
+/* Evaluation of the moves of the current player by looking ahead at the positions they lead to,
+ * which the threat map of the renderer shows as a hint. The moves are played with
+ * Grid::with_simulated_move(), so the game itself is never changed.
+ */
+
+use crate::game::Game;
+use crate::grid::{Grid, Owner, Point};
+
+// Score of a position where the player took over the board or lost all their marbles
+pub const WIN: f32 = 1000.0;
+
+// Boards with more pairs of a move and a reply than this are only looked at one move ahead, so
+// scoring does not hold up the game for long
+const MAX_REPLIES: usize = 20_000;
+
+/* Score of a position for a player: their marbles minus those of the strongest rival. Taking the
+ * last marble of all rivals wins and losing the own last marble loses.
+ */
+pub fn evaluate(grid: &Grid, owner: Owner, rivals: &[Owner], num_players: usize) -> f32 {
+    let counts = grid.count_per_owner(num_players);
+    let own = counts[owner.idx()].1;
+    let strongest = rivals.iter().map(|rival| counts[rival.idx()].1).max().unwrap_or(0);
+    if own == 0 {
+        -WIN
+    } else if !rivals.is_empty() && strongest == 0 {
+        WIN
+    } else {
+        own as f32 - strongest as f32
+    }
+}
+
+/* Score each legal move of the current player by the position after the best reply of the next
+ * rival, the higher the better. Rivals are the other players that have marbles on the board.
+ * Without any, or on large boards, the position right after the move is scored instead. Empty
+ * while no move can be played.
+ */
+pub fn score_moves(game: &mut Game) -> Vec<(Point, f32)> {
+    let mut scorer = MoveScorer::new(game);
+    while !scorer.done() {
+        scorer.score_next(game);
+    }
+    scorer.scores
+}
+
+/* Scoring of the moves of a position as score_moves() does it, one move at a time, so it can be
+ * spread over several frames
+ */
+pub struct MoveScorer {
+    owner: Owner,
+    num_players: usize,
+    // In the order of their turns after the current player
+    rivals: Vec<Owner>,
+    // Rival whose best reply is looked at, unless the board is too large for it
+    reply: Option<Owner>,
+    // Moves that are not scored yet
+    pending: Vec<Point>,
+    scores: Vec<(Point, f32)>,
+}
+impl MoveScorer {
+    pub fn new(game: &Game) -> MoveScorer {
+        let pending = game.legal_moves();
+        let owner = game.cur_player();
+        let num_players = game.num_players();
+        let counts = game.grid().count_per_owner(num_players);
+        let rivals: Vec<Owner> = (1..num_players)
+            .map(|offset| Owner::new((owner.idx() + offset) % num_players, num_players).unwrap())
+            .filter(|rival| counts[rival.idx()].1 > 0)
+            .collect();
+        let reply = rivals.first().copied().filter(|_| pending.len() * pending.len() <= MAX_REPLIES);
+        MoveScorer {
+            owner,
+            num_players,
+            rivals,
+            reply,
+            pending,
+            scores: Vec::new(),
+        }
+    }
+
+    pub fn owner(&self) -> Owner { self.owner }
+    pub fn done(&self) -> bool { self.pending.is_empty() }
+    /* Scores of the moves so far, in no particular order */
+    pub fn scores(&self) -> &[(Point, f32)] { &self.scores }
+
+    /* Score the next move on the board of the game, which must still be in the position the
+     * scorer was made for. The board is left as it was.
+     */
+    pub fn score_next(&mut self, game: &mut Game) {
+        let p = match self.pending.pop() {
+            Some(p) => p,
+            None => return,
+        };
+        let (owner, num_players, reply) = (self.owner, self.num_players, self.reply);
+        let rivals = &self.rivals;
+        let cellsize = game.cellsize();
+        let score = game.with_simulated_move(p, owner, |after| {
+            let score = evaluate(after, owner, rivals, num_players);
+            let rival = match reply {
+                Some(rival) if score.abs() < WIN => rival,
+                _ => return score,
+            };
+            let replies: Vec<Point> = after.points().filter(|&q| after.check_move(q, rival).is_ok()).collect();
+            replies.into_iter()
+                .filter_map(|q| after.with_simulated_move(q, rival, cellsize, |after| {
+                    evaluate(after, owner, rivals, num_players)
+                }).ok())
+                .reduce(f32::min)
+                .unwrap_or(score)
+        });
+        if let Ok(score) = score {
+            self.scores.push((p, score));
+        }
+    }
+}
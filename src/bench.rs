@@ -0,0 +1,119 @@
+/* Headless self-play with random moves, to see how games typically unfold. Runs only depend on
+ * the seed, so they can be repeated.
+ */
+
+use std::fmt;
+
+use sdl2::pixels::Color;
+
+use crate::game::{Game, Player};
+use crate::grid::Point;
+use crate::menu::Config;
+use crate::rng::Rng;
+
+// Colors of the simulated players, which only need to be told apart by Config::validate()
+const COLORS: [Color; 6] = [
+    Color::RGB(255, 0, 0),
+    Color::RGB(0, 0, 255),
+    Color::RGB(0, 160, 0),
+    Color::RGB(200, 160, 0),
+    Color::RGB(160, 0, 160),
+    Color::RGB(0, 160, 160),
+];
+
+// Games that take longer than this are given up and counted as undecided
+const MAX_MOVES: u32 = 10_000;
+
+/* Settings for simulated games on a rectangular board */
+pub fn config(players: usize, size: Point) -> Result<Config, String> {
+    if players > COLORS.len() {
+        return Err(format!("At most {} players can be simulated", COLORS.len()));
+    }
+    let config = Config {
+        players: COLORS[..players].iter().map(|&color| Player::new(color)).collect(),
+        size,
+        ..Config::default()
+    };
+    config.validate()?;
+    Ok(config)
+}
+
+/* Summary of a number of simulated games */
+pub struct Stats {
+    pub games: usize,
+    // Total and longest number of moves of all games, each placing one marble
+    pub moves: u64,
+    pub max_moves: u32,
+    // Number of moves that caused at least one spreading step, their total and deepest chain
+    pub cascades: u64,
+    pub depth: u64,
+    pub max_depth: u32,
+    // Wins by position in the turn order, where 0 is the player that began
+    pub wins: Vec<usize>,
+    pub undecided: usize,
+}
+
+/* Play the given number of games with random moves. The player that begins rotates from game to
+ * game like with a rematch.
+ */
+pub fn run(config: &Config, games: usize, seed: u64) -> Result<Stats, String> {
+    let num_players = config.players.len();
+    let mut stats = Stats {
+        games,
+        moves: 0,
+        max_moves: 0,
+        cascades: 0,
+        depth: 0,
+        max_depth: 0,
+        wins: vec![0; num_players],
+        undecided: 0,
+    };
+    let mut rng = Rng::new(seed);
+    let mut game = Game::new(config.clone())?;
+    for idx in 0..games {
+        if idx > 0 {
+            game.restart();
+        }
+        while game.winner().is_none() && game.marbles_placed() < MAX_MOVES {
+            let moves = game.legal_moves();
+            if moves.is_empty() {
+                break;
+            }
+            game.click(moves[rng.below(moves.len())]);
+            game.advance_until_stable();
+            let depth = game.chain_depth();
+            if depth > 0 {
+                stats.cascades += 1;
+                stats.depth += depth as u64;
+                stats.max_depth = stats.max_depth.max(depth);
+            }
+        }
+        stats.moves += game.marbles_placed() as u64;
+        stats.max_moves = stats.max_moves.max(game.marbles_placed());
+        match game.winner() {
+            Some(winner) => {
+                let position = (winner.idx() + num_players - game.first_player().idx()) % num_players;
+                stats.wins[position] += 1;
+            },
+            None => stats.undecided += 1,
+        }
+    }
+    Ok(stats)
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let average = |total: u64, count: u64| if count > 0 { total as f64 / count as f64 } else { 0.0 };
+        let share = |count: usize| 100.0 * average(count as u64, self.games as u64);
+        writeln!(f, "{:<16}{:>10}", "Games", self.games)?;
+        writeln!(f, "{:<16}{:>10}{:>10}", "", "average", "max")?;
+        writeln!(f, "{:<16}{:>10.1}{:>10}", "Moves", average(self.moves, self.games as u64), self.max_moves)?;
+        writeln!(f, "{:<16}{:>10.2}{:>10}", "Cascade depth", average(self.depth, self.cascades), self.max_depth)?;
+        writeln!(f, "{:<16}{:>9.1}%", "Moves spreading", 100.0 * average(self.cascades, self.moves))?;
+        writeln!(f, "Wins by position in the turn order")?;
+        for (position, &wins) in self.wins.iter().enumerate() {
+            writeln!(f, "{:<16}{:>10}{:>9.1}%", format!("  {}.", position + 1), wins, share(wins))?;
+        }
+        write!(f, "{:<16}{:>10}{:>9.1}%", "  Undecided", self.undecided, share(self.undecided))
+    }
+}
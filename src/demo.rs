@@ -0,0 +1,110 @@
+/* Attract mode: a small game between two players making random moves, which the menu shows while
+ * nobody uses it.
+ */
+
+use std::time::Duration;
+
+use crate::bench;
+use crate::game::{Game, State};
+use crate::grid::{Point, SpreadBias};
+use crate::rng::Rng;
+
+// Size of a cell of the demo board in pixels
+pub const CELLSIZE: Point = Point::new(24, 24);
+
+// Time between two moves and after the game is won, at normal speed
+const MOVE_PAUSE: Duration = Duration::from_millis(400);
+const END_PAUSE: Duration = Duration::from_secs(3);
+
+// Games that take longer than this are started anew, since random moves might never end them
+const MAX_MOVES: u32 = 300;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Settings {
+    pub size: Point,
+    // Factor for the animations and the pauses between moves
+    pub speed: f32,
+    // Time without any input before the demo starts
+    pub delay: Duration,
+}
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings {
+            size: Point::new(5, 4),
+            speed: 1.0,
+            delay: Duration::from_secs(5),
+        }
+    }
+}
+
+impl Settings {
+    /* Check that a demo can be shown with these settings */
+    pub fn validate(&self) -> Result<(), String> {
+        if !(self.speed > 0.0 && self.speed.is_finite()) {
+            return Err(format!("The demo speed must be a positive number, but is {}", self.speed));
+        }
+        bench::config(2, self.size)?;
+        Ok(())
+    }
+}
+
+pub struct Demo {
+    game: Game,
+    rng: Rng,
+    speed: f32,
+    // Time until the next move or the next game, at normal speed
+    pause: Duration,
+    // Whether the pause before the next game has started
+    finished: bool,
+}
+impl Demo {
+    pub fn new(settings: &Settings, seed: u64) -> Result<Demo, String> {
+        settings.validate()?;
+        let mut config = bench::config(2, settings.size)?;
+        config.cellsize = CELLSIZE;
+        // Marbles come to rest in slightly different places from demo to demo
+        let mut rng = Rng::new(seed);
+        config.spread_bias = SpreadBias::ALL[rng.below(SpreadBias::ALL.len())];
+        Ok(Demo {
+            game: Game::new(config)?,
+            rng,
+            speed: settings.speed,
+            pause: MOVE_PAUSE,
+            finished: false,
+        })
+    }
+
+    pub fn game(&self) -> &Game {
+        &self.game
+    }
+
+    /* Advance the animation by the given elapsed time and make the next move or start the next
+     * game once the pause after the last one is over.
+     */
+    pub fn advance(&mut self, dt: Duration) {
+        let dt = dt.mul_f32(self.speed);
+        self.game.step(dt);
+        // The marbles of the winner may keep spreading forever, so the next game does not wait
+        // for them
+        if self.game.winner().is_some() && !self.finished {
+            self.finished = true;
+            self.pause = END_PAUSE;
+        }
+        if let (State::Animating(_), false) = (self.game.state(), self.finished) {
+            return;
+        }
+        self.pause = self.pause.saturating_sub(dt);
+        if !self.pause.is_zero() {
+            return;
+        }
+        let moves = self.game.legal_moves();
+        if self.finished || moves.is_empty() || self.game.marbles_placed() >= MAX_MOVES {
+            self.game.restart();
+            self.pause = MOVE_PAUSE;
+            self.finished = false;
+            return;
+        }
+        self.game.click(moves[self.rng.below(moves.len())]);
+        self.pause = MOVE_PAUSE;
+    }
+}
@@ -0,0 +1,115 @@
+/* Font of all text that is not drawn by SDL_gfx. DejaVu Sans Mono is built into the binary (see
+ * assets/DejaVuSansMono-LICENSE), so the game does not depend on where the system keeps its fonts.
+ */
+
+use std::env;
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use sdl2::rwops::RWops;
+use sdl2::ttf::{Font, Sdl2TtfContext};
+
+const EMBEDDED: &[u8] = include_bytes!("../assets/DejaVuSansMono.ttf");
+
+// Environment variable with the path of a font to use instead of the built in one
+pub const FONT_VARIABLE: &str = "CHAINREACTION_FONT";
+
+// Shown in the menu if there is no font
+pub const MISSING: &str = "No font found, coordinates and help are not shown";
+
+// Whether it was already reported that no font could be loaded
+static REPORTED: AtomicBool = AtomicBool::new(false);
+
+// Common locations of monospaced fonts, tried if the built in font can not be loaded
+pub const SYSTEM_FONTS: &[&str] = &[
+    // Debian and Ubuntu
+    "/usr/share/fonts/truetype/dejavu/DejaVuSansMono.ttf",
+    "/usr/share/fonts/truetype/liberation/LiberationMono-Regular.ttf",
+    // Fedora
+    "/usr/share/fonts/dejavu-sans-mono-fonts/DejaVuSansMono.ttf",
+    "/usr/share/fonts/liberation/LiberationMono-Regular.ttf",
+    // Arch
+    "/usr/share/fonts/TTF/DejaVuSansMono.ttf",
+    // macOS
+    "/System/Library/Fonts/Monaco.ttf",
+    "/Library/Fonts/Courier New.ttf",
+    // Windows
+    "C:\\Windows\\Fonts\\consola.ttf",
+    "C:\\Windows\\Fonts\\cour.ttf",
+];
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum FontSource {
+    Path(PathBuf),
+    Embedded,
+}
+impl fmt::Display for FontSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FontSource::Path(path) => write!(f, "{}", path.display()),
+            FontSource::Embedded => write!(f, "built in font"),
+        }
+    }
+}
+
+/* Where to look for the font, in this order: the given override, the built in font and the
+ * common system locations
+ */
+pub fn sources(override_path: Option<PathBuf>) -> Vec<FontSource> {
+    override_path.map(FontSource::Path).into_iter()
+        .chain(std::iter::once(FontSource::Embedded))
+        .chain(SYSTEM_FONTS.iter().map(|path| FontSource::Path(PathBuf::from(path))))
+        .collect()
+}
+
+/* Open the first of the given sources that works, if any, and give the reasons why the sources
+ * before it failed. Paths that do not exist are skipped without trying to open them.
+ */
+pub fn first_working<T>(
+    sources: Vec<FontSource>,
+    mut open: impl FnMut(&FontSource) -> Result<T, String>,
+) -> (Option<T>, Vec<String>) {
+    let mut errors = Vec::new();
+    for source in sources {
+        if let FontSource::Path(path) = &source {
+            if !path.exists() {
+                errors.push(format!("{} does not exist", source));
+                continue;
+            }
+        }
+        match open(&source) {
+            Ok(font) => return (Some(font), errors),
+            Err(e) => errors.push(format!("Can not load {}: {}", source, e)),
+        }
+    }
+    (None, errors)
+}
+
+/* Load the first font of sources() that works at the given point size, with the override taken
+ * from FONT_VARIABLE. If none works, there is no text. Fonts are loaded for each window and scale,
+ * so the fonts that failed are only reported the first time.
+ */
+pub fn load(context: &Sdl2TtfContext, size: u16) -> Option<Font<'_, 'static>> {
+    let override_path = env::var_os(FONT_VARIABLE).map(PathBuf::from);
+    let explicit = override_path.is_some();
+    let (font, errors) = first_working(sources(override_path), |source| match source {
+        FontSource::Path(path) => context.load_font(path, size),
+        FontSource::Embedded => RWops::from_bytes(EMBEDDED)
+            .and_then(|rwops| context.load_font_from_rwops(rwops, size)),
+    });
+    // Most system fonts are expected to be missing, so the errors only matter if the override or
+    // the built in font failed, which are tried first
+    if !errors.is_empty() && !REPORTED.swap(true, Ordering::Relaxed) {
+        for error in &errors {
+            eprintln!("{}", error);
+        }
+        if explicit {
+            eprintln!("The font is given by {}", FONT_VARIABLE);
+        }
+        if font.is_none() {
+            eprintln!("No font could be loaded, text and coordinates are not shown");
+        }
+    }
+    font
+}
@@ -1,121 +1,945 @@
+use std::collections::HashSet;
 use std::vec::Vec;
+use std::time::Duration;
 
-use sdl2::keyboard::Keycode;
 use sdl2::pixels::Color;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
-use crate::grid::{Owner, Point, Grid};
+use crate::grid::{Owner, Point, Grid, GridEvent, ReplayError, ReplaySummary, MAX_WAVES};
 use crate::menu::Config;
+use crate::keymap::Action;
 
 /* Color and state for each player. Once the player places their first marble, they are started. If
  * they then at some point have no more marbles, they have lost and are no longer alive.
  */
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Player {
     pub started: bool,
     pub alive: bool,
+    // The player gave up and takes no further turns, even in sandbox mode
+    pub resigned: bool,
+    // Whether the player controlled enough cells for a domination win in every settled position
+    // since the end of their last turn
+    pub dominating: bool,
+    #[cfg_attr(feature = "serde", serde(with = "rgb"))]
     color: Color,
+    // Given in the menu, otherwise players are numbered
+    #[cfg_attr(feature = "serde", serde(default))]
+    name: Option<String>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub kind: PlayerKind,
+}
+
+/* Who makes the moves of a player */
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum PlayerKind {
+    // Moves are made by clicking or with the keyboard
+    #[default]
+    Human,
+    // Practice opponent that plays the mirror image of the last move of the given player, see
+    // Game::mirror_move()
+    Mirror(Owner),
+}
+
+/* Colors of players are opaque and serialized as their red, green and blue components */
+#[cfg(feature = "serde")]
+pub(crate) mod rgb {
+    use sdl2::pixels::Color;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(color: &Color, serializer: S) -> Result<S::Ok, S::Error> {
+        (color.r, color.g, color.b).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Color, D::Error> {
+        let (r, g, b) = <(u8, u8, u8)>::deserialize(deserializer)?;
+        Ok(Color::RGB(r, g, b))
+    }
 }
 impl Player {
     pub fn new(color: Color) -> Player {
         Player{
             started: false,
             alive: true,
-            color: color,
+            resigned: false,
+            dominating: false,
+            color,
+            name: None,
+            kind: PlayerKind::Human,
         }
     }
     pub fn color(&self) -> Color { self.color }
+    pub fn name(&self) -> Option<&str> { self.name.as_deref() }
+
+    /* Name the player, or number them again if the name is empty */
+    pub fn set_name(&mut self, name: &str) {
+        let name = name.trim();
+        self.name = if name.is_empty() { None } else { Some(name.to_string()) };
+    }
+
+    /* The same player before they made any move */
+    pub fn unstarted(&self) -> Player {
+        Player {
+            name: self.name.clone(),
+            kind: self.kind,
+            ..Player::new(self.color)
+        }
+    }
+}
+
+// Duration of one frame when running with a fixed time step
+pub const FRAME: Duration = Duration::from_nanos(1_000_000_000 / 60);
+// Duration of the animation of one spreading step
+pub const SPREAD_DURATION: Duration = Duration::from_nanos(16 * 1_000_000_000 / 60);
+// Factor by which slow motion stretches the animation
+pub const SLOW_MOTION: u32 = 4;
+
+/* Cell that has a different owner after a move than before it. Cells that were emptied by
+ * spreading have no owner afterwards.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OwnerChange {
+    pub at: Point,
+    pub from: Option<Owner>,
+    pub to: Option<Owner>,
+}
+
+/* Cells of the grid whose owner differs from the given owners, which are in the order of
+ * Grid::cells() for a board of the same size
+ */
+pub fn owner_changes(before: &[Option<Owner>], grid: &Grid) -> Vec<OwnerChange> {
+    grid.cells().zip(before.iter())
+        .filter(|((_, cell), &from)| cell.owner() != from)
+        .map(|((p, cell), &from)| OwnerChange { at: p, from, to: cell.owner() })
+        .collect()
+}
+
+/* How a game is won */
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum WinCondition {
+    // Eliminate all other players
+    LastStanding,
+    // Control at least the given fraction of all cells from the end of one's turn until the start
+    // of the next one
+    Domination(f32),
 }
 
 #[derive(Clone,Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum State {
     AcceptingInput,
-    Animating(i32), // number of steps for animation
+    Animating(f32), // progress of the animation, from 0 to 1
 }
 
 pub struct Game {
+    // Settings the game was started with, used to start a rematch
+    config: Config,
     players: Vec<Player>,
     state: State,
+    first_player: Owner,
     cur_player: Owner,
     selected: Point,
     grid: Grid,
-    cellsize: i32,
+    cellsize: Point,
+    // Advance the animation by one FRAME per step, regardless of the elapsed time
+    fixed_step: bool,
+    // Animate SLOW_MOTION times slower, which only changes how fast the marbles move
+    slow_motion: bool,
+    // Nobody is eliminated, see next_player_if_accepting()
+    sandbox: bool,
+    // Player that won by domination, see check_domination()
+    dominator: Option<Owner>,
+    // No region of the board has marbles of two players, so the game may be ended, see
+    // check_separation()
+    separated: bool,
+    // Player with the most marbles when the game was ended for that, see end_separated()
+    declared: Option<Owner>,
+    // Player for whom marbles are added while setting up a position in the board editor
+    editing: Option<Owner>,
+    // Number of marbles placed by the players so far, which is more than the turns if players
+    // place several marbles per turn
+    marbles_placed: u32,
+    // Pass the turn after each placed marble. Modes where a player places several marbles per
+    // turn switch this off and call end_turn() themselves.
+    auto_advance: bool,
+    // The turn passes to the next player once the marbles came to rest
+    turn_ending: bool,
+    // Player and cell of the most recently placed marble, marked by the renderer
+    last_move: Option<(Owner, Point)>,
+    // All marbles placed by the players, oldest first
+    history: Vec<(Owner, Point)>,
+    // Cell clicked while the marbles were moving, played once they come to rest, see click()
+    pending_move: Option<Point>,
+    // Extra marbles each player may place in their next turn for capturing bonus cells, and the
+    // marbles the current player has left to place in this turn, see collect_bonus()
+    bonus_placements: Vec<u32>,
+    placements_left: u32,
+    // Owner of each cell before the marble that is spreading was placed, see owners()
+    owners_before: Option<Vec<Option<Owner>>>,
+    // Cells that changed their owner with the last move, once its marbles came to rest
+    owner_changes: Vec<OwnerChange>,
+    // What happened on the grid during the last call to step(), including any placement since
+    // the call before, and what happened since then
+    events: Vec<GridEvent>,
+    pending_events: Vec<GridEvent>,
 }
 
 impl Game {
+    pub fn config(&self) -> &Config { &self.config }
     pub fn players(&self) -> impl Iterator<Item=&Player> { self.players.iter() }
     pub fn num_players(&self) -> usize { self.players.len() }
+    pub fn player(&self, owner: Owner) -> &Player { &self.players[owner.idx()] }
+    // Name of the player, or their number if they have none
+    pub fn player_name(&self, owner: Owner) -> String {
+        match self.player(owner).name() {
+            Some(name) => name.to_string(),
+            None => format!("Player {}", owner.idx() + 1),
+        }
+    }
     pub fn cur_player(&self) -> Owner { self.cur_player }
     pub fn grid(&self) -> &Grid { &self.grid }
     pub fn selected(&self) -> Point { self.selected }
     pub fn dim(&self) -> Point { self.grid.dim() }
-    pub fn cellsize(&self) -> i32 { self.cellsize }
+    pub fn cellsize(&self) -> Point { self.cellsize }
+    pub fn state(&self) -> State { self.state }
+    pub fn events(&self) -> &[GridEvent] { &self.events }
+    /* Cells that changed their owner with the last move, which is only known once its marbles
+     * came to rest. Empty while they are moving.
+     */
+    pub fn last_owner_changes(&self) -> &[OwnerChange] { &self.owner_changes }
+    pub fn editing(&self) -> Option<Owner> { self.editing }
+    pub fn first_player(&self) -> Owner { self.first_player }
+    pub fn marbles_placed(&self) -> u32 { self.marbles_placed }
+    pub fn last_move(&self) -> Option<(Owner, Point)> { self.last_move }
+    pub fn pending_move(&self) -> Option<Point> { self.pending_move }
+    // Marbles the current player may still place in this turn, more than one after a bonus
+    pub fn placements_left(&self) -> u32 { self.placements_left }
+    pub fn history(&self) -> &[(Owner, Point)] { &self.history }
+    // Number of spreading steps caused by the last placed marble
+    pub fn chain_depth(&self) -> u32 { self.grid.chain() }
+    pub fn slow_motion(&self) -> bool { self.slow_motion }
 
-    pub fn new(config: Config) -> Game {
+    /* Start a game with the given settings, if they are valid, see Config::validate() */
+    pub fn new(config: Config) -> Result<Game, String> {
+        config.validate()?;
+        let first = Owner::new(0, config.players.len())?;
+        let grid = Grid::from_mask(config.size, &config.shape.mask(config.size));
+        Ok(Game::on_grid(config, grid, first))
+    }
+
+    /* Start a game like new() on the given empty board, which must have the size and shape of the
+     * validated configuration
+     */
+    fn on_grid(config: Config, mut grid: Grid, first: Owner) -> Game {
+        grid.set_spread_bias(config.spread_bias);
+        grid.set_threshold_factor(config.threshold_factor).expect("The board of a new game is empty");
+        for &p in config.neutral.iter() {
+            grid.add_neutral(p, config.cellsize).expect("Neutral cells of valid settings do not spread");
+        }
+        for &p in config.bonus.iter() {
+            grid.add_bonus(p).expect("Bonus cells are part of the board of valid settings");
+        }
         Game {
-            players: config.players,
-            cur_player: 0,
+            players: config.players.clone(),
+            first_player: first,
+            cur_player: first,
             state: State::AcceptingInput,
-            grid: Grid::new(config.size),
+            grid,
             selected: Point::new(0, 0),
             cellsize: config.cellsize,
+            fixed_step: config.fixed_step,
+            slow_motion: false,
+            sandbox: config.sandbox,
+            dominator: None,
+            separated: false,
+            declared: None,
+            editing: None,
+            marbles_placed: 0,
+            auto_advance: true,
+            turn_ending: false,
+            last_move: None,
+            history: Vec::new(),
+            pending_move: None,
+            bonus_placements: vec![0; config.players.len()],
+            placements_left: 1,
+            owners_before: None,
+            owner_changes: Vec::new(),
+            events: Vec::new(),
+            pending_events: Vec::new(),
+            config,
+        }
+    }
+
+    /* Start a game like new(), but with the given player making the first move */
+    pub fn starting_with(config: Config, first: Owner) -> Result<Game, String> {
+        let mut game = Game::new(config)?;
+        game.first_player = Owner::new(first.idx(), game.players.len())?;
+        game.cur_player = game.first_player;
+        Ok(game)
+    }
+
+    /* Start a new game with the same players and settings. The player that begins rotates with
+     * each rematch so everyone gets to start equally often.
+     */
+    pub fn rematch(&self) -> Game {
+        self.next_game(Grid::from_mask(self.config.size, &self.config.shape.mask(self.config.size)))
+    }
+
+    /* Start a rematch in place, reusing the board of this game instead of creating a new one */
+    pub fn restart(&mut self) {
+        let mut grid = std::mem::take(&mut self.grid);
+        grid.reset();
+        *self = self.next_game(grid);
+    }
+
+    /* The rematch of this game on the given empty board, see rematch() */
+    fn next_game(&self, grid: Grid) -> Game {
+        let mut game = Game::on_grid(self.config.clone(), grid, self.first_player.next(self.players.len()));
+        game.auto_advance = self.auto_advance;
+        game.slow_motion = self.slow_motion;
+        game
+    }
+
+    /* The only player that is still alive, if all others have lost, or the player that won by
+     * domination. Nobody wins in sandbox mode.
+     */
+    pub fn winner(&self) -> Option<Owner> {
+        if self.sandbox {
+            return None;
+        }
+        if self.dominator.is_some() {
+            return self.dominator;
+        }
+        if self.declared.is_some() {
+            return self.declared;
+        }
+        let mut alive = Owner::all(self.players.len()).zip(self.players.iter()).filter(|(_, player)| player.alive);
+        match (alive.next(), alive.next()) {
+            (Some((owner, _)), None) => Some(owner),
+            _ => None,
         }
     }
 
-    pub fn keydown(&mut self, keycode: Keycode) {
+    /* Whether the game was won and its last move was fully played out: the marbles came to rest,
+     * or there are more of them than the board can hold at rest, so they keep spreading forever.
+     */
+    pub fn is_over(&self) -> bool {
+        if self.winner().is_none() {
+            return false;
+        }
+        match self.state {
+            State::AcceptingInput => true,
+            _ => self.grid.marbles().count() > self.grid.capacity(),
+        }
+    }
+
+    /* Map a position in logical pixels to the cell at that position. Returns None for the sidebar
+     * and anything outside of the board. The canvas of the game uses a logical size, so SDL
+     * already reports mouse positions in logical pixels regardless of the actual window size.
+     */
+    pub fn cell_at_pixel(&self, x: i32, y: i32) -> Option<Point> {
+        if x < 0 || y < 0 {
+            return None;
+        }
         let dim = self.grid.dim();
-        match keycode {
-            Keycode::Right =>
+        let p = Point::new(x / self.cellsize.re, y / self.cellsize.im);
+        if p.re < dim.re && p.im < dim.im {
+            Some(p)
+        } else {
+            None
+        }
+    }
+
+    /* Handle the actions that affect the game itself. Everything else is handled by run_game. */
+    pub fn keydown(&mut self, action: Action) {
+        let dim = self.grid.dim();
+        match action {
+            Action::MoveRight =>
                 self.selected.re = (self.selected.re + 1) % dim.re,
-            Keycode::Left =>
+            Action::MoveLeft =>
                 self.selected.re = (self.selected.re + dim.re - 1) % dim.re,
-            Keycode::Down =>
+            Action::MoveDown =>
                 self.selected.im = (self.selected.im + 1) % dim.im,
-            Keycode::Up =>
+            Action::MoveUp =>
                 self.selected.im = (self.selected.im + dim.im - 1) % dim.im,
-            Keycode::Return => {
+            Action::Place => {
                 self.click(self.selected);
             }
+            Action::FastForward => self.advance_until_stable(),
+            Action::SlowMotion => self.toggle_slow_motion(),
+            Action::Resign => self.resign(),
+            Action::EndSeparated => self.end_separated(),
+            Action::Edit => self.start_editing(),
             _ => return
         }
     }
 
+    /* Cells where the current player may place a marble: empty cells, their own cells and neutral
+     * cells that are not at capacity. There are none while marbles are moving, once the game is
+     * won, or in the board editor, where clicks do not play moves.
+     */
+    pub fn legal_moves(&self) -> Vec<Point> {
+        match self.state {
+            State::AcceptingInput => (),
+            _ => return Vec::new(),
+        }
+        if self.winner().is_some() || self.editing.is_some() {
+            return Vec::new();
+        }
+        self.grid.cells()
+            .filter(|(_, cell)| cell.accepts(self.cur_player))
+            .map(|(p, _)| p)
+            .collect()
+    }
+
+    /* Play a move on the board of the game, let the closure look at the resulting position and
+     * restore the board exactly as it was, see Grid::with_simulated_move()
+     */
+    pub fn with_simulated_move<F, R>(&mut self, p: Point, owner: Owner, f: F) -> Result<R, String>
+    where F: FnOnce(&mut Grid) -> R
+    {
+        let cellsize = self.cellsize;
+        self.grid.with_simulated_move(p, owner, cellsize, f)
+    }
+
+    /* Place a marble for the current player. With buffered input, a click while the marbles are
+     * moving is remembered instead, replacing any earlier one, and played once they came to rest.
+     */
     pub fn click(&mut self, p: Point) {
+        if self.grid.get(p).is_none() {
+            return;
+        }
         self.selected = p;
         match self.state {
             State::AcceptingInput => (),
-            _ => return
+            _ => {
+                if self.config.buffer_input && self.editing.is_none() {
+                    self.pending_move = Some(p);
+                }
+                return
+            }
+        }
+        if self.dominator.is_some() || self.declared.is_some() {
+            return;
         }
+        // The board editor adds marbles with insert_marble() instead
+        if self.editing.is_some() {
+            return;
+        }
+        // Other players move by themselves, see play_automatic()
+        if self.players[self.cur_player.idx()].kind == PlayerKind::Human {
+            self.place(p);
+        }
+    }
 
+    /* Place a marble for the current player, once the marbles came to rest */
+    fn place(&mut self, p: Point) {
         let cur_player = self.cur_player;
-        self.players[cur_player].started = true;
+        let owners_before = self.owners();
+        self.players[cur_player.idx()].started = true;
         match self.grid.add_marble(p, cur_player, self.cellsize) {
-            Ok(state) => {
+            Ok((state, events)) => {
+                self.marbles_placed += 1;
+                self.last_move = Some((cur_player, p));
+                self.history.push((cur_player, p));
+                self.owner_changes.clear();
+                self.owners_before = Some(owners_before);
                 self.state = state;
-                self.next_player_if_accepting();
+                self.pending_events.extend(events);
+                if self.config.instant {
+                    self.resolve();
+                }
+                self.record_owner_changes();
+                self.placements_left = self.placements_left.saturating_sub(1);
+                if self.auto_advance && self.placements_left == 0 {
+                    self.end_turn();
+                }
             },
             Err(_) => {}
         }
     }
 
-    pub fn step(&mut self) {
+    /* Whether the turn passes after each placed marble, which is the default. Otherwise, clicks
+     * keep placing marbles for the current player until end_turn() is called.
+     */
+    pub fn set_auto_advance(&mut self, auto_advance: bool) {
+        self.auto_advance = auto_advance;
+    }
+
+    /* Pass the turn to the next player, as soon as all marbles came to rest. This happens after
+     * each placed marble unless auto advance is switched off. Ending a turn without placing a
+     * marble passes.
+     */
+    pub fn end_turn(&mut self) {
+        if self.editing.is_some() {
+            return;
+        }
+        self.turn_ending = true;
+        self.next_player_if_accepting();
+    }
+
+    /* Replay moves given with the player making them, resolving each cascade without animation.
+     * Each move must be made by the player whose turn it is and be one of the legal_moves(). Turns
+     * pass and players are eliminated like when clicking. Stops at the first move that is refused,
+     * see Grid::apply_moves(). What happened on the board is not reported by events().
+     */
+    pub fn apply_moves(&mut self, moves: &[(Point, Owner)]) -> Result<ReplaySummary, ReplayError> {
+        let mut summary = ReplaySummary::default();
+        for (index, &(p, owner)) in moves.iter().enumerate() {
+            let error = |reason: String| ReplayError { index, reason };
+            if self.editing.is_some() {
+                return Err(error("The board editor is open".to_string()));
+            }
+            if let Some(winner) = self.winner() {
+                return Err(error(format!("Player {} already won", winner)));
+            }
+            if owner != self.cur_player {
+                return Err(error(format!("Player {} is to move, not player {}", self.cur_player, owner)));
+            }
+            self.grid.check_move(p, owner).map_err(error)?;
+            self.place(p);
+            self.resolve();
+            // A cascade that never ends without a winner is an error instead of running forever
+            if matches!(self.state, State::Animating(_)) && self.winner().is_none() {
+                return Err(error("The cascade does not end".to_string()));
+            }
+            self.events.clear();
+            self.pending_events.clear();
+            summary.moves += 1;
+            summary.waves += self.grid.chain() as u64;
+            summary.longest_chain = summary.longest_chain.max(self.grid.chain());
+        }
+        Ok(summary)
+    }
+
+    /* Switch to the board editor, which is only possible before the first move. Clicks then add
+     * marbles instead of playing them, see insert_marble() and remove_marble().
+     */
+    pub fn start_editing(&mut self) {
+        match self.state {
+            State::AcceptingInput => (),
+            _ => return
+        }
+        if self.players.iter().any(|player| player.started) {
+            return;
+        }
+        self.editing = Some(self.cur_player);
+    }
+
+    /* Choose the player for whom the board editor adds marbles */
+    pub fn edit_for(&mut self, idx: usize) {
+        if self.editing.is_some() {
+            if let Ok(owner) = Owner::new(idx, self.players.len()) {
+                self.editing = Some(owner);
+            }
+        }
+    }
+
+    /* Add a marble of the player the board editor is used for, see Grid::insert_marble() */
+    pub fn insert_marble(&mut self, p: Point) -> Result<(), String> {
+        let owner = self.editing.ok_or("Marbles can only be added in the board editor")?;
+        self.selected = p;
+        self.grid.insert_marble(p, owner, self.cellsize)
+    }
+
+    /* Remove a marble from a cell in the board editor */
+    pub fn remove_marble(&mut self, p: Point) -> Result<(), String> {
+        if self.editing.is_none() {
+            return Err("Marbles can only be removed in the board editor".to_string());
+        }
+        self.grid.remove_marble(p)
+    }
+
+    /* Leave the board editor and start playing from the position that was set up, with the player
+     * the editor was last used for to move. Players that own cells count as started.
+     */
+    pub fn finish_editing(&mut self) -> Result<(), String> {
+        let owner = match self.editing {
+            Some(owner) => owner,
+            None => return Ok(()),
+        };
+        self.grid.check_settled()?;
+        let mut players: Vec<Player> = self.players.iter().map(Player::unstarted).collect();
+        self.grid.check_players(&mut players);
+        self.players = players;
+        self.cur_player = owner;
+        self.editing = None;
+        Ok(())
+    }
+
+    /* The current player gives up. Their cells become neutral: the marbles stay where they are and
+     * block nobody, since any player may add to a neutral cell and take it over together with its
+     * marbles. If only one player remains, they win.
+     */
+    pub fn resign(&mut self) {
+        match self.state {
+            State::AcceptingInput => (),
+            _ => return
+        }
+        if self.winner().is_some() || self.editing.is_some() {
+            return;
+        }
+        let player = &mut self.players[self.cur_player.idx()];
+        player.started = true;
+        player.alive = false;
+        player.resigned = true;
+        self.grid.neutralize(self.cur_player);
+        self.end_turn();
+    }
+
+    /* Advance the animation by the given elapsed time (or by one FRAME in fixed step mode),
+     * slowed down in slow motion
+     */
+    pub fn step(&mut self, dt: Duration) {
+        let dt = if self.fixed_step { FRAME } else { dt };
+        let dt = if self.slow_motion { dt / SLOW_MOTION } else { dt };
+        self.events = std::mem::take(&mut self.pending_events);
+        self.advance(dt.as_secs_f32() / SPREAD_DURATION.as_secs_f32());
+        self.play_automatic();
+    }
+
+    /* Make the move of the current player if they are not human and it is their turn. A player
+     * without a legal move passes.
+     */
+    pub fn play_automatic(&mut self) {
+        let of = match self.players[self.cur_player.idx()].kind {
+            PlayerKind::Human => return,
+            PlayerKind::Mirror(of) => of,
+        };
+        match self.state {
+            State::AcceptingInput => (),
+            _ => return,
+        }
+        if self.winner().is_some() || self.editing.is_some() || self.dominator.is_some() || self.declared.is_some() {
+            return;
+        }
+        match self.mirror_move(of) {
+            Some(p) => self.place(p),
+            None => self.end_turn(),
+        }
+    }
+
+    /* Move of a player who mirrors the given one: the reflection of the given player's last move
+     * through the center of the board. If that cell can not be played, which is always the case
+     * for the center cell of a board with odd sides, the closest legal move is chosen instead,
+     * the first one in the order of the cells among equally close ones. Before the given player
+     * moved, the cell closest to the center is chosen. None if there is no legal move.
+     */
+    pub fn mirror_move(&self, of: Owner) -> Option<Point> {
+        let dim = self.dim();
+        let corner = dim - Point::new(1, 1);
+        // In units of half cells, so the center of a board with even sides is a point as well
+        let target = match self.history.iter().rev().find(|&&(owner, _)| owner == of) {
+            Some(&(_, p)) => (corner - p) * 2,
+            None => corner,
+        };
+        self.legal_moves().into_iter().min_by_key(|&p| (p * 2 - target).norm_sqr())
+    }
+
+    /* Switch slow motion on or off. The animation continues from where it is, only at a
+     * different speed, so the marbles do not jump. Cascades end the same either way.
+     */
+    pub fn toggle_slow_motion(&mut self) {
+        self.slow_motion = !self.slow_motion;
+    }
+
+    /* Advance the animation by the given fraction of one spreading step, adding what happened to
+     * the events.
+     */
+    fn advance(&mut self, delta: f32) {
         match self.state {
             State::AcceptingInput => (),
             _ => {
-                self.state = self.grid.step(self.state, self.cellsize);
+                let (state, events) = self.grid.step(self.state, delta, self.cellsize);
+                self.state = state;
+                self.events.extend(events);
                 self.grid.check_players(&mut self.players);
+                self.record_owner_changes();
                 self.next_player_if_accepting();
+                self.play_pending_move();
+            }
+        }
+    }
+
+    /* Owner of each cell in the order of Grid::cells(), to be compared with a later position by
+     * owner_changes()
+     */
+    pub fn owners(&self) -> Vec<Option<Owner>> {
+        self.grid.cells().map(|(_, cell)| cell.owner()).collect()
+    }
+
+    /* Cells whose owner now differs from the given owners, which were taken with owners() on
+     * the same board
+     */
+    pub fn owner_changes(&self, before: &[Option<Owner>]) -> Vec<OwnerChange> {
+        owner_changes(before, &self.grid)
+    }
+
+    /* Once the marbles of a move came to rest, compare the owners with those before the move */
+    fn record_owner_changes(&mut self) {
+        if let State::Animating(_) = self.state {
+            return;
+        }
+        if let Some(before) = self.owners_before.take() {
+            self.owner_changes = self.owner_changes(&before);
+        }
+    }
+
+    /* Play the move that was clicked during the animation, once the marbles came to rest. The
+     * cell might have been taken in the meantime or the game might be over, so moves that are no
+     * longer legal for the player to move are dropped.
+     */
+    fn play_pending_move(&mut self) {
+        if let State::Animating(_) = self.state {
+            return;
+        }
+        if let Some(p) = self.pending_move.take() {
+            if self.winner().is_none() && self.legal_moves().contains(&p) {
+                self.click(p);
             }
         }
     }
 
+    /* Run all animations to their end without waiting, as long as marbles are moving, see
+     * resolve(). All events are reported by the next call to events().
+     */
+    pub fn advance_until_stable(&mut self) {
+        self.events.clear();
+        self.resolve();
+    }
+
+    /* Let the marbles spread until they came to rest, without any animation. The board is the same
+     * as after the full animation. Players are only checked once, in the final position. Some
+     * cascades never come to rest, so the rest of them is left to the animation: those of a single
+     * owner that has more marbles than the board can hold at rest or that came back to a position
+     * of the cascade, which can not change the outcome, and with a low threshold factor, those
+     * that exceed MAX_WAVES steps. What happened is reported by the next call to step().
+     */
+    fn resolve(&mut self) {
+        let endless = self.grid.marbles().count() > self.grid.capacity();
+        // Positions between the steps, which decide the next step on their own
+        let mut seen = HashSet::new();
+        while let State::Animating(_) = self.state {
+            if self.grid.chain() >= MAX_WAVES {
+                break;
+            }
+            if self.grid.single_owner().is_some() && (endless || !seen.insert(self.grid.hash())) {
+                break;
+            }
+            let (state, events) = self.grid.step(self.state, 1.0, self.cellsize);
+            self.state = state;
+            self.pending_events.extend(events);
+        }
+        self.grid.check_players(&mut self.players);
+        self.record_owner_changes();
+        self.next_player_if_accepting();
+        self.play_pending_move();
+    }
+
+    /* Text encoding of the current position: dimension, player to move, number of players and
+     * cells, like "3x2 b 2 a1.2#b2". See Grid::encode() for the format of the cells. Only settled
+     * positions can be encoded.
+     */
+    pub fn encode(&self) -> Option<String> {
+        match self.state {
+            State::AcceptingInput => (),
+            _ => return None,
+        }
+        let grid = self.grid.encode();
+        let (dim, cells) = grid.split_once(' ').unwrap();
+        Some(format!(
+            "{} {} {} {}",
+            dim, self.cur_player, self.players.len(), cells,
+        ))
+    }
+
+    /* Continue from a position created by encode(). It must be for the same board size and number
+     * of players.
+     */
+    pub fn decode(&mut self, text: &str) -> Result<(), String> {
+        match self.state {
+            State::AcceptingInput => (),
+            _ => return Err("Can not load a position while marbles are moving".to_string()),
+        }
+        let parts: Vec<&str> = text.trim().split(' ').collect();
+        if parts.len() != 4 {
+            return Err(format!("Expected 4 parts separated by spaces, got {}", parts.len()));
+        }
+        let num_players: usize = parts[2].parse()
+            .map_err(|_| format!("Invalid number of players {}", parts[2]))?;
+        if num_players != self.players.len() {
+            return Err(format!("Position is for {} players, but there are {}", num_players, self.players.len()));
+        }
+        let mut letters = parts[1].chars();
+        let cur_player = match (letters.next(), letters.next()) {
+            (Some(letter), None) => Owner::from_letter(letter, num_players)
+                .map_err(|e| format!("Invalid player to move: {}", e))?,
+            _ => return Err(format!("Invalid player to move {}", parts[1])),
+        };
+        let cells = format!("{} {}", parts[0], parts[3]);
+        let mut grid = Grid::decode_with_threshold(&cells, num_players, self.cellsize, self.config.threshold_factor)?;
+        grid.set_spread_bias(self.config.spread_bias);
+        if grid.dim() != self.grid.dim() {
+            return Err(format!("Position is for a board of size {}, but the board is {}", grid.dim(), self.grid.dim()));
+        }
+        for &p in self.config.bonus.iter() {
+            grid.add_bonus(p).map_err(|e| format!("Bonus cell: {}", e))?;
+        }
+        // Players with cells have started, all others have yet to make their first move
+        let mut players: Vec<Player> = self.players.iter().map(Player::unstarted).collect();
+        grid.check_players(&mut players);
+        if !players[cur_player.idx()].alive {
+            return Err("The player to move has already lost".to_string());
+        }
+        self.grid = grid;
+        self.players = players;
+        self.cur_player = cur_player;
+        self.turn_ending = false;
+        self.dominator = None;
+        self.separated = false;
+        self.declared = None;
+        self.editing = None;
+        self.marbles_placed = 0;
+        self.last_move = None;
+        self.owners_before = None;
+        self.owner_changes.clear();
+        self.history.clear();
+        self.pending_move = None;
+        self.bonus_placements = vec![0; num_players];
+        self.placements_left = 1;
+        self.events.clear();
+        self.pending_events.clear();
+        Ok(())
+    }
+
+    /* Fraction of the cells needed for a domination win that the given player controls, capped
+     * at 1. None if the game is not won by domination.
+     */
+    pub fn domination_progress(&self, owner: Owner) -> Option<f32> {
+        match self.config.win_condition {
+            WinCondition::LastStanding => None,
+            WinCondition::Domination(threshold) => {
+                let share = self.share(owner);
+                Some((share / threshold).min(1.0))
+            },
+        }
+    }
+
+    /* Fraction of the cells of the board that belong to the given player */
+    fn share(&self, owner: Owner) -> f32 {
+        self.grid.cells_of(owner) as f32 / self.grid.playable_count().max(1) as f32
+    }
+
+    /* Called in each settled position, after the current player finished their turn and before
+     * the next one starts. The current player starts dominating if they reached the threshold and
+     * everyone that dropped below it stops. Values in the middle of a chain reaction therefore do
+     * not count. If the player whose turn starts next is still dominating, the position held for
+     * a full round and they win.
+     */
+    fn check_domination(&mut self, next: Owner) {
+        let threshold = match self.config.win_condition {
+            WinCondition::LastStanding => return,
+            WinCondition::Domination(threshold) => threshold,
+        };
+        for owner in Owner::all(self.players.len()) {
+            let reached = self.share(owner) >= threshold;
+            let player = &mut self.players[owner.idx()];
+            if owner == self.cur_player {
+                player.dominating = reached;
+            } else if !reached {
+                player.dominating = false;
+            }
+        }
+        if self.players[next.idx()].dominating {
+            self.dominator = Some(next);
+        }
+    }
+
+    /* Called in each settled position like check_domination(). With Config::end_when_separated,
+     * the game may be ended once no cascade can reach the cells of another player, see
+     * Grid::contested(), if one player has more marbles than all others.
+     */
+    fn check_separation(&mut self) {
+        self.separated = self.config.end_when_separated && !self.sandbox && self.winner().is_none()
+            && self.editing.is_none() && !self.grid.contested() && self.leader().is_some();
+    }
+
+    /* Whether the game can be ended with end_separated() */
+    pub fn separated(&self) -> bool { self.separated }
+
+    /* The player with more marbles than each other player, if there is one */
+    pub fn leader(&self) -> Option<Owner> {
+        let counts = self.grid.count_per_owner(self.players.len());
+        let most = counts.iter().map(|&(_, marbles)| marbles).max()?;
+        let mut leaders = Owner::all(self.players.len()).filter(|owner| counts[owner.idx()].1 == most);
+        match (leaders.next(), leaders.next()) {
+            (Some(owner), None) => Some(owner),
+            _ => None,
+        }
+    }
+
+    /* End a game where the players are separated, declaring the player with the most marbles the
+     * winner. Nothing happens unless separated() offers it.
+     */
+    pub fn end_separated(&mut self) {
+        if self.separated {
+            self.declared = self.leader();
+            self.separated = false;
+        }
+    }
+
+    /* Once the marbles came to rest, grant each bonus cell captured by the last placed marble as
+     * an extra placement in the next turn of the player that captured it. Until then, the grid
+     * keeps track of them, so a cell that changes hands several times in one cascade only counts
+     * once.
+     */
+    fn collect_bonus(&mut self) {
+        if let State::Animating(_) = self.state {
+            return;
+        }
+        for (_, owner) in self.grid.take_bonus_captures() {
+            if let Some(placements) = self.bonus_placements.get_mut(owner.idx()) {
+                *placements += 1;
+            }
+        }
+    }
+
+    /* Pass the turn to the next player that is still alive once the current turn is ending and
+     * the marbles came to rest, see end_turn(). In sandbox mode, players are never
+     * eliminated: a player that lost all their cells may re-enter by placing a marble on any
+     * empty cell, just like with their first move. Only players without any cell they may place
+     * a marble on are skipped, which can only happen if all cells belong to other players.
+     */
     fn next_player_if_accepting(&mut self) {
+        self.collect_bonus();
+        if !self.turn_ending {
+            return;
+        }
         match self.state {
             State::AcceptingInput => {
-                loop {
-                    self.cur_player = (self.cur_player + 1) % self.players.len();
-                    if self.players[self.cur_player].alive {
+                self.turn_ending = false;
+                let mut next = self.cur_player;
+                for _ in 0..self.players.len() {
+                    next = next.next(self.players.len());
+                    let can_play = if self.players[next.idx()].resigned {
+                        false
+                    } else if self.sandbox {
+                        self.grid.has_move(next)
+                    } else {
+                        self.players[next.idx()].alive
+                    };
+                    if can_play {
                         break;
                     }
                 }
+                self.check_domination(next);
+                self.check_separation();
+                self.cur_player = next;
+                self.placements_left = 1 + std::mem::take(&mut self.bonus_placements[next.idx()]);
             },
             _ => ()
         };
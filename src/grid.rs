@@ -1,13 +1,235 @@
+use std::collections::HashMap;
+use std::fmt;
 use std::ops::{Index,IndexMut};
 
 use num_complex::Complex;
 
 use array_macro::array;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
 use crate::game::{State, Player};
+use crate::points::Points;
 
 pub type Point = Complex<i32>;
-pub type Owner = usize;
+
+// Position in pixels that is not rounded to whole pixels, for marbles while they move
+pub type Subpixel = Complex<f32>;
+
+/* Exact sub-pixel position of a pixel */
+pub fn subpixel(p: Point) -> Subpixel {
+    Subpixel::new(p.re as f32, p.im as f32)
+}
+
+/* The pixel that contains a sub-pixel position, rounding to the nearest one */
+pub fn round_subpixel(p: Subpixel) -> Point {
+    Point::new(p.re.round() as i32, p.im.round() as i32)
+}
+
+/* Index of a player in turn order. Owners can only be created for an existing player, so they can
+ * be used to look up anything that is stored per player.
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Owner(usize);
+impl Owner {
+    /* The player with the given index, if there are that many players */
+    pub fn new(idx: usize, num_players: usize) -> Result<Owner, String> {
+        if idx < num_players {
+            Ok(Owner(idx))
+        } else {
+            Err(format!("There is no player {} in a game with {} players", idx + 1, num_players))
+        }
+    }
+
+    /* The player denoted by the given letter, a for the first player */
+    pub fn from_letter(letter: char, num_players: usize) -> Result<Owner, String> {
+        match letter {
+            'a'..='z' => Owner::new(letter as usize - 'a' as usize, num_players),
+            _ => Err(format!("Invalid player {}", letter)),
+        }
+    }
+
+    /* All players of a game with the given number of players, in turn order */
+    pub fn all(num_players: usize) -> impl Iterator<Item=Owner> {
+        (0..num_players).map(Owner)
+    }
+
+    pub fn idx(&self) -> usize { self.0 }
+
+    /* The player whose turn is after this one */
+    pub fn next(&self, num_players: usize) -> Owner {
+        Owner((self.0 + 1) % num_players)
+    }
+}
+
+/* Players are written as letters, a for the first player, and neutral as "*" */
+impl fmt::Display for Owner {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            NEUTRAL => write!(f, "*"),
+            Owner(idx) => write!(f, "{}", char::from_u32('a' as u32 + idx as u32).unwrap_or('?')),
+        }
+    }
+}
+
+/* Owner of neutral marbles. Any player may add to a neutral cell, which converts the cell and all its
+ * marbles to that player. Neutral marbles never spread by themselves: a neutral cell can not reach
+ * capacity without a player adding to it, and if it receives a marble from a spreading neighbor,
+ * it is captured by the owner of that marble like any other cell.
+ */
+pub const NEUTRAL: Owner = Owner(usize::MAX);
+
+// Largest number of players, one for each letter that denotes a player in the text encoding, see
+// Grid::encode()
+pub const MAX_PLAYERS: usize = 26;
+
+/* Random key for the Zobrist hash of a cell with the given index, owner and count. The keys are
+ * generated by SplitMix64 from a fixed seed, so the same position always has the same hash.
+ */
+fn zobrist_key(idx: usize, owner: Owner, count: u8) -> u64 {
+    const SEED: u64 = 0x2545_F491_4F6C_DD1D;
+    let input = (idx as u64) << 32 | (owner.0 as u64 & 0xFF_FFFF) << 8 | count as u64;
+    let mut z = SEED.wrapping_add(input.wrapping_mul(0x9E37_79B9_7F4A_7C15));
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+// Smallest number of cells that are handed to one thread when spreading in parallel
+#[cfg(feature = "parallel")]
+const PARALLEL_CHUNK: usize = 256;
+
+// Index into DIRECTIONS
+pub type Direction = usize;
+
+/* Something that happened while spreading, for effects that react to it */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GridEvent {
+    // A cell at capacity sent its marbles to its neighbors
+    CellFired { at: Point, owner: Owner },
+    // A marble was received from the neighbor in the given direction
+    MarbleArrived { at: Point, from_dir: Direction, owner: Owner },
+    // A cell changed its owner by receiving a marble
+    CellCaptured { at: Point, from: Owner, to: Owner },
+}
+
+/* New owner and count of a cell that differs between two settled positions */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CellChange {
+    pub at: Point,
+    pub owner: Option<Owner>,
+    pub count: u8,
+}
+
+/* Changes that turn one settled position into another on the same board, see Grid::diff(). The
+ * hashes of both positions are included, so a delta is only applied to the position it was made
+ * for.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct GridDelta {
+    pub from: u64,
+    pub to: u64,
+    pub changes: Vec<CellChange>,
+}
+impl GridDelta {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+/* One spreading step of a cascade, see Grid::cascade() */
+#[derive(Clone, Debug, PartialEq)]
+pub struct Wave {
+    // Cells at capacity that sent out their marbles, in the order they were handled
+    pub fired: Vec<Point>,
+    // Cells that received marbles, each listed once in the order of their first marble
+    pub received: Vec<Point>,
+    pub events: Vec<GridEvent>,
+}
+impl Wave {
+    fn new(events: Vec<GridEvent>) -> Wave {
+        let mut fired = Vec::new();
+        let mut received = Vec::new();
+        for event in events.iter() {
+            match *event {
+                GridEvent::CellFired { at, .. } => fired.push(at),
+                GridEvent::MarbleArrived { at, .. } if !received.contains(&at) => received.push(at),
+                _ => (),
+            }
+        }
+        Wave { fired, received, events }
+    }
+}
+
+/* A marble that is moving from one cell to a neighbor in the current spreading step, see
+ * Grid::in_flight()
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct InFlight {
+    pub id: u32,
+    pub owner: Owner,
+    // Position in pixels, like Marble::get_pos()
+    pub pos: Point,
+    // Cells the marble was sent from and is received by
+    pub from: Point,
+    pub to: Point,
+    // Fraction of the spreading step until the marble arrives, from 1 when it was sent to 0
+    pub remaining: f32,
+}
+
+/* What happened while replaying moves, see Grid::apply_moves() and Game::apply_moves() */
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ReplaySummary {
+    pub moves: usize,
+    // Spreading steps of all moves and of the longest cascade
+    pub waves: u64,
+    pub longest_chain: u32,
+}
+
+/* A move of a replay that could not be applied, by its index in the replayed moves. The moves
+ * before it were applied.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReplayError {
+    pub index: usize,
+    pub reason: String,
+}
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Move {} can not be applied: {}", self.index, self.reason)
+    }
+}
+
+// Number of marbles needed on the board before single_owner() reports an owner, unless changed
+pub const DEFAULT_MIN_MARBLES: usize = 2;
+
+// Factor between the number of neighbors of a cell and the number of marbles at which it spreads,
+// unless changed, and the largest factor. Beyond one marble per neighbor, the marbles rest in the
+// reserve slots of a cell, which have room for twice as many as the neighbors.
+pub const DEFAULT_THRESHOLD_FACTOR: f32 = 1.0;
+pub const MAX_THRESHOLD_FACTOR: f32 = 2.0;
+
+/* Number of marbles at which a cell with the given number of neighbors spreads, for the given
+ * factor. Cells with any neighbor spread with at least one marble, cells without neighbors can not
+ * hold any.
+ */
+pub fn threshold(neighbors: u8, factor: f32) -> u8 {
+    if neighbors == 0 {
+        return 0;
+    }
+    let factor = factor.clamp(0.0, MAX_THRESHOLD_FACTOR);
+    ((neighbors as f32 * factor).ceil() as u8).max(1)
+}
+
+// Number of waves after which a cascade is assumed to never come to rest
+pub const MAX_WAVES: u32 = 10_000;
+
+// Largest number of cells a board may have. Sizes from the command line, the menu or a pasted
+// position are checked against this before anything is allocated.
+pub const MAX_CELLS: i32 = 64 * 64;
 
 // main directions
 pub const DIRECTIONS: [Point; 4] = [
@@ -17,58 +239,145 @@ pub const DIRECTIONS: [Point; 4] = [
     Point::new(0, -1),
 ];
 
-pub struct PointIter {
-    dim: Point,
-    p: Point,
-}
-impl PointIter {
-    pub fn new(dim: Point) -> PointIter {
-        PointIter {
-            dim: dim,
-            p: Point::new(dim.re-1, dim.im),
+// The direction pointing the opposite way of each of DIRECTIONS
+pub const OPPOSITES: [Direction; 4] = [opposite(0), opposite(1), opposite(2), opposite(3)];
+
+/* The direction pointing the opposite way of the given one, found from the vectors in DIRECTIONS
+ * so it does not depend on their order
+ */
+const fn opposite(direction: Direction) -> Direction {
+    let (x, y) = (DIRECTIONS[direction].re, DIRECTIONS[direction].im);
+    let mut candidate = 0;
+    while candidate < DIRECTIONS.len() {
+        if DIRECTIONS[candidate].re == -x && DIRECTIONS[candidate].im == -y {
+            return candidate;
         }
+        candidate += 1;
     }
+    panic!("Direction without opposite");
 }
-impl Iterator for PointIter {
-    type Item = Point;
-    fn next(&mut self) -> Option<Self::Item> {
-        if self.p.im != 0 {
-            self.p.im -= 1;
-            Some(self.p)
-        } else {
-            self.p.re -= 1;
-            self.p.im = self.dim.im - 1;
-            if self.p.re >= 0 {
-                Some(self.p)
-            } else {
-                None
-            }
-        }
+
+/* Multiply two points component by component, like coordinates of a cell with the size of a cell */
+pub fn stretch(p: Point, factor: Point) -> Point {
+    Point::new(p.re * factor.re, p.im * factor.im)
+}
+
+/* Coordinates of p after turning a board of the given dimension clockwise by the given number of
+ * quarter turns, see Grid::rotated()
+ */
+pub fn rotate_point(p: Point, dim: Point, quarter_turns: u8) -> Point {
+    let (mut p, mut dim) = (p, dim);
+    for _ in 0..quarter_turns % 4 {
+        p = Point::new(dim.im - 1 - p.im, p.re);
+        dim = Point::new(dim.im, dim.re);
     }
+    p
+}
+
+/* Coordinates of p after mirroring a board of the given dimension left to right (horizontal) or
+ * top to bottom, see Grid::mirrored()
+ */
+pub fn mirror_point(p: Point, dim: Point, horizontal: bool) -> Point {
+    if horizontal {
+        Point::new(dim.re - 1 - p.re, p.im)
+    } else {
+        Point::new(p.re, dim.im - 1 - p.im)
+    }
+}
+
+/* Position in pixels of the slot in the given direction of the cell at coord. Cells may be wider
+ * than high or the other way around, so the slots are placed relative to both dimensions.
+ */
+pub fn slot_position(coord: Point, direction: Direction, cellsize: Point) -> Point {
+    stretch(coord, cellsize) + cellsize/2 + stretch(cellsize/4, DIRECTIONS[direction])
 }
 
+/* Position in pixels of the reserve slot of the cell at coord that is between the given direction
+ * and the next one, in a corner of the cell so it does not cover the other slots
+ */
+pub fn reserve_position(coord: Point, direction: Direction, cellsize: Point) -> Point {
+    let diagonal = DIRECTIONS[direction] + DIRECTIONS[(direction + 1) % 4];
+    stretch(coord, cellsize) + cellsize/2 + stretch(cellsize*3/10, diagonal)
+}
 
 #[derive(Clone,Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Marble {
-    // Absolute position in pixels
-    pos: Point,
+    // Absolute position in pixels. It is only rounded when drawing, so slow marbles move smoothly
+    // instead of standing still for some frames and then jumping by a pixel.
+    pos: Subpixel,
+    // Position at the start of the current animation
+    start: Subpixel,
     // Which owner the marble belongs to
     owner: Owner,
+    // Identifies the marble as long as it exists, regardless of which cell it moves to
+    id: u32,
+    // Direction of the neighbor that sent the marble in the current spreading step, if any
+    from_dir: Option<Direction>,
+    // Share of the time of the current animation that has passed, from 0 to 1
+    #[cfg_attr(feature = "serde", serde(default))]
+    progress: f32,
+}
+// How far marbles shoot past their slot before settling, see ease()
+const OVERSHOOT: f32 = 1.2;
+
+/* Share of the way a marble has traveled at the given share (0 to 1) of the time of a spreading
+ * step. It starts fast and slows down, passes the target by about 5% and settles back on it, like
+ * a marble rolling into a hollow. It is exactly 0 at the start and exactly 1 at the end.
+ */
+pub fn ease(progress: f32) -> f32 {
+    let t = progress - 1.0;
+    1.0 + (OVERSHOOT + 1.0) * t * t * t + OVERSHOOT * t * t
 }
+
 impl Marble {
-    /* Move one step towards target, with 'steps' remaining steps afterwards */
-    fn step(&mut self, target: Point, steps: i32) {
-        self.pos = target + ((self.pos - target) * steps) / (steps + 1);
+    /* Move towards target, being at the given fraction (0 to 1) of the time from the start, see
+     * ease(). The position is computed from the start each time instead of adding up small steps,
+     * so rounding errors do not accumulate and the marble is exactly at the target at the end.
+     */
+    fn step(&mut self, target: Point, progress: f32) {
+        self.progress = progress;
+        self.pos = if progress >= 1.0 {
+            subpixel(target)
+        } else {
+            self.start + (subpixel(target) - self.start) * ease(progress)
+        };
     }
     pub fn get_owner(&self) -> Owner {
         self.owner
     }
+    /* Position rounded to whole pixels */
     pub fn get_pos(&self) -> Point {
+        round_subpixel(self.pos)
+    }
+    /* Position without rounding, which is only needed for drawing */
+    pub fn subpixel_pos(&self) -> Subpixel {
         self.pos
     }
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+}
+
+/* Which set of slots of a cell a marble is in. Marbles reside in a cell between moves; while the
+ * cell spreads they are Outgoing, and marbles from spreading neighbors are Incoming until they are
+ * sorted into the Residing slots and the Reserve.
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SlotKind {
+    Residing,
+    Incoming,
+    Outgoing,
+    Reserve,
+}
+impl SlotKind {
+    // In the order of Cell::slots
+    pub const ALL: [SlotKind; 4] = [SlotKind::Residing, SlotKind::Incoming, SlotKind::Outgoing, SlotKind::Reserve];
 }
 
-// One set of slots, with up to one marble per direction. Residing, Incoming or Outgoing
+// One set of slots, with up to one marble per direction. Residing, Incoming, Outgoing or Reserve
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 struct Slots {
     marbles: [Option<Marble>; 4]
 }
@@ -91,43 +400,160 @@ impl IndexMut<usize> for Slots {
     }
 }
 
+/* Which way a marble turns first if the slot in its direction is taken, see assign_slots(). This
+ * only changes in which slots marbles come to rest, never the owners and counts of the cells.
+ */
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum SpreadBias {
+    #[default]
+    Counterclockwise,
+    Clockwise,
+}
+impl SpreadBias {
+    pub const ALL: [SpreadBias; 2] = [SpreadBias::Counterclockwise, SpreadBias::Clockwise];
+
+    pub fn next(&self) -> SpreadBias {
+        match self {
+            SpreadBias::Counterclockwise => SpreadBias::Clockwise,
+            SpreadBias::Clockwise => SpreadBias::Counterclockwise,
+        }
+    }
+
+    // Offsets from the direction of a slot to the slot it takes a marble from, in the order they
+    // are tried
+    fn rotations(&self) -> [usize; 4] {
+        match self {
+            SpreadBias::Counterclockwise => [0, 1, 3, 2],
+            SpreadBias::Clockwise => [0, 3, 1, 2],
+        }
+    }
+}
+
+/* Move marbles from the slots in `from` into the free slots of `to`, considering only directions in
+ * which the cell has a neighbor. Each marble stays in its direction if that slot is free and
+ * otherwise moves to the nearest free one: one step to the side given by the bias, then one step
+ * to the other side and the opposite direction last. This is done in rounds of increasing
+ * distance, so a marble is only moved away from its direction if another marble already holds
+ * that slot, and never further than needed. The result only depends on which slots are occupied,
+ * which keeps spreading deterministic. Marbles that do not fit remain in `from`.
+ */
+fn assign_slots<T>(from: &mut [Option<T>; 4], to: &mut [Option<T>; 4], has_neighbor: &[bool; 4], bias: SpreadBias) {
+    // Offset from the direction of a slot in `to` to the slot in `from` it takes a marble from
+    for rotation in bias.rotations() {
+        for direction in 0..4 {
+            if !has_neighbor[direction] || to[direction].is_some() {
+                continue
+            }
+            to[direction] = from[(direction+rotation)%4].take();
+        }
+    }
+}
+
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Cell {
     coord: Point,
+    // Cells outside of the shape of the board are not playable and have no neighbors
+    playable: bool,
+    // Capturing the cell grants an extra placement, see Grid::take_bonus_captures()
+    #[cfg_attr(feature = "serde", serde(default))]
+    bonus: bool,
     owner: Option<Owner>,
     neighbors: u8,
+    // Number of marbles at which the cell spreads, see threshold(). It follows from the neighbors
+    // and the threshold factor of the grid, so it is not serialized.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    threshold: u8,
     count: u8,
     has_neighbor: [bool; 4],
-    // Residing, Incoming and Outgoing for each direction
-    slots: [Slots; 3],
+    // Residing, Incoming, Outgoing and Reserve for each direction. Marbles beyond one per
+    // neighbor rest in the Reserve, which is only used if the cell spreads with more marbles than
+    // it has neighbors.
+    #[cfg_attr(feature = "serde", serde(deserialize_with = "deserialize_slots"))]
+    slots: [Slots; 4],
+}
+
+/* Slots of a cell, which were saved without the Reserve before it was added. Those cells can not
+ * hold more marbles than they have neighbors, so their Reserve is empty.
+ */
+#[cfg(feature = "serde")]
+fn deserialize_slots<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<[Slots; 4], D::Error> {
+    let mut slots = Vec::<Slots>::deserialize(deserializer)?;
+    if slots.len() == 3 {
+        slots.push(Slots::new());
+    }
+    slots.try_into().map_err(|slots: Vec<Slots>| serde::de::Error::invalid_length(slots.len(), &"3 or 4 sets of slots"))
 }
 impl Cell {
-    fn new(coord: Point, dim: Point) -> Cell {
-        let has_neighbor = [
-            coord.re < dim.re - 1,
-            coord.im < dim.im - 1,
-            coord.re > 0,
-            coord.im > 0,
-        ];
+    /* Create a cell, where playable tells which coordinates belong to the board */
+    fn new<Playable>(coord: Point, playable: Playable) -> Cell
+    where Playable: Fn(Point) -> bool
+    {
+        let mut has_neighbor = [false; 4];
+        if playable(coord) {
+            for direction in 0..4 {
+                has_neighbor[direction] = playable(coord + DIRECTIONS[direction]);
+            }
+        }
+        let neighbors = has_neighbor.into_iter().map(|x| x as u8).sum();
         Cell {
-            coord: coord,
+            coord,
+            playable: playable(coord),
+            bonus: false,
             owner: None,
-            has_neighbor: has_neighbor,
-            slots: array![_ => Slots::new(); 3],
-            neighbors: has_neighbor.into_iter().map(|x| x as u8).sum(),
+            has_neighbor,
+            slots: array![_ => Slots::new(); 4],
+            neighbors,
+            threshold: threshold(neighbors, DEFAULT_THRESHOLD_FACTOR),
             count: 0,
         }
     }
 
+    pub fn coord(&self) -> Point { self.coord }
+    pub fn playable(&self) -> bool { self.playable }
+    pub fn bonus(&self) -> bool { self.bonus }
+    pub fn owner(&self) -> Option<Owner> { self.owner }
+    pub fn threshold(&self) -> u8 { self.threshold }
+
+    /* Whether the given owner may add a marble to this cell. Cells that are not playable have no
+     * neighbors and are therefore always at capacity.
+     */
+    pub fn accepts(&self, owner: Owner) -> bool {
+        self.count < self.threshold && match self.owner {
+            None => true,
+            Some(current) => current == owner || current == NEUTRAL,
+        }
+    }
+
+    /* Whether one more marble would make this cell spread */
+    pub fn is_critical_soon(&self) -> bool {
+        self.count > 0 && self.count + 1 == self.threshold
+    }
     pub fn has_neighbor(&self, direction: usize) -> bool { self.has_neighbor[direction] }
     fn residing(&self) -> &Slots { &self.slots[0] }
     fn incoming(&self) -> &Slots { &self.slots[1] }
-    fn outgoing(&self) -> &Slots { &self.slots[2] }
     fn residing_mut(&mut self) -> &mut Slots { &mut self.slots[0] }
     fn incoming_mut(&mut self) -> &mut Slots { &mut self.slots[1] }
     fn outgoing_mut(&mut self) -> &mut Slots { &mut self.slots[2] }
 
+    /* Key of this cell's owner and count for the Zobrist hash of the grid, where idx is the index
+     * of the cell. Empty cells do not contribute to the hash.
+     */
+    fn zobrist_key(&self, idx: usize) -> u64 {
+        match self.owner {
+            Some(owner) if self.count > 0 => zobrist_key(idx, owner, self.count),
+            _ => 0,
+        }
+    }
+
     fn full(&self) -> bool {
-        self.count >= self.neighbors
+        self.count >= self.threshold
+    }
+
+    /* Player the cell belongs to. Empty and neutral cells have none. */
+    fn player(&self) -> Option<Owner> {
+        self.owner.filter(|&owner| owner != NEUTRAL && self.count > 0)
     }
 
     pub fn marbles(&self) -> impl Iterator<Item=&Marble> + '_ {
@@ -136,36 +562,122 @@ impl Cell {
         ).flatten()
     }
 
+    /* Marbles in the same order as marbles(), each with the set of slots it is in and the
+     * direction of its slot
+     */
+    pub fn marbles_by_slot(&self) -> impl Iterator<Item=(SlotKind, Direction, &Marble)> + '_ {
+        SlotKind::ALL.into_iter().zip(self.slots.iter()).flat_map(|(kind, slots)| {
+            slots.marbles.iter().enumerate()
+                .filter_map(move |(direction, marble)| Some((kind, direction, marble.as_ref()?)))
+        })
+    }
+
     fn marbles_mut(&mut self) -> impl Iterator<Item=&mut Marble> + '_ {
         self.slots.iter_mut().map(
             |slots: &mut Slots| slots.marbles.iter_mut().flatten()
         ).flatten()
     }
 
-    /* Add a marble to a cell that has room for it (in first slot)
+    /* Check a deserialized cell, whose neighbors and threshold were already compared with the
+     * shape of the board. In the middle of a cascade, the cells that are about to spread are at
+     * capacity and marbles may still be moving, but every marble must be in a slot of a direction
+     * it can be in.
+     */
+    #[cfg(feature = "serde")]
+    fn check(&self) -> Result<(), String> {
+        let valid = |owner: Owner| owner == NEUTRAL || owner.0 < MAX_PLAYERS;
+        if let Some(owner) = self.owner.filter(|&owner| !valid(owner)) {
+            return Err(format!("Cell {} belongs to player {}, but there are at most {}", self.coord, owner.0 + 1, MAX_PLAYERS));
+        }
+        if self.marbles().any(|marble| !valid(marble.owner)) {
+            return Err(format!("Cell {} holds a marble of a player that can not exist", self.coord));
+        }
+        if self.marbles().count() != self.count as usize {
+            return Err(format!("Cell {} counts {} marbles, but holds {}", self.coord, self.count, self.marbles().count()));
+        }
+        if (self.count == 0) != self.owner.is_none() {
+            return Err(format!("Cell {} must have an owner exactly if it holds marbles", self.coord));
+        }
+        if !self.playable && self.count > 0 {
+            return Err(format!("Cell {} holds marbles but is not part of the board", self.coord));
+        }
+        // Only the Reserve has slots in directions without a neighbor
+        if (0..4).any(|direction| !self.has_neighbor[direction] && self.slots[..3].iter().any(|slots| slots[direction].is_some())) {
+            return Err(format!("Cell {} holds a marble towards a missing neighbor", self.coord));
+        }
+        Ok(())
+    }
+
+    /* Hand the cell and all its marbles to the given owner */
+    fn set_owner(&mut self, owner: Owner) {
+        self.owner = Some(owner);
+        for marble in self.marbles_mut() {
+            marble.owner = owner;
+        }
+    }
+
+    /* Remove all marbles and the owner, returning how many marbles were removed */
+    fn clear(&mut self) -> usize {
+        let count = self.count as usize;
+        self.slots = array![_ => Slots::new(); 4];
+        self.count = 0;
+        self.owner = None;
+        count
+    }
+
+    /* Remove the marble with the highest id, which is the one added last. Marbles are added to the
+     * Reserve last, so it is taken from there if there are any, which keeps one marble per
+     * neighbor in the other slots.
+     */
+    fn remove_marble(&mut self) {
+        let slots = if self.slots[3].marbles.iter().any(|slot| slot.is_some()) { 3..4 } else { 0..3 };
+        let last = slots.flat_map(|slot| (0..4).map(move |direction| (slot, direction)))
+            .filter_map(|(slot, direction)| self.slots[slot][direction].map(|marble| (marble.id, slot, direction)))
+            .max();
+        if let Some((_, slot, direction)) = last {
+            self.slots[slot][direction] = None;
+            self.count -= 1;
+        }
+        // Once the cell is no longer full, its marbles rest in the directions they were sent in
+        for direction in 0..4 {
+            if let Some(marble) = self.outgoing_mut()[direction].take() {
+                self.residing_mut()[direction] = Some(marble);
+            }
+        }
+        if self.count == 0 {
+            self.owner = None;
+        }
+    }
+
+    /* Add a marble with the given id to a cell that has room for it (in first slot)
      * Returns Err variant if there is no room (should not happen) or if the owner does not match.
      */
-    fn add_marble(&mut self, owner: Owner, cellsize: i32) -> Result<(), ()>{
-        if *self.owner.get_or_insert(owner) != owner {
-            // Set owner if it is not yet set, but return an error if it is set differently
+    fn add_marble(&mut self, owner: Owner, id: u32, cellsize: Point) -> Result<(), ()>{
+        if !self.accepts(owner) {
             return Err(())
         }
-        if self.count == self.neighbors {
-            return Err(())
+        if self.owner == Some(NEUTRAL) {
+            // Any player may convert a neutral cell
+            for marble in self.marbles_mut() {
+                marble.owner = owner;
+            }
         }
+        self.owner = Some(owner);
         self.count += 1;
-        let center = self.coord * cellsize + Point::new(cellsize/2, cellsize/2);
-        for direction in 0..4 {
-            if !self.has_neighbor[direction] || self.residing()[direction].is_some() {
-                continue;
-            }
-            self.residing_mut()[direction].get_or_insert_with(|| 
-                Marble {
-                    owner: owner,
-                    pos: center + cellsize/4 * DIRECTIONS[direction],
-                }
-            );
-            break
+        // The first free slot with a neighbor, otherwise the first free one of the Reserve
+        let free = (0..4).find(|&direction| self.has_neighbor[direction] && self.residing()[direction].is_none())
+            .map(|direction| (0, direction, slot_position(self.coord, direction, cellsize)))
+            .or_else(|| (0..4).find(|&direction| self.slots[3][direction].is_none())
+                .map(|direction| (3, direction, reserve_position(self.coord, direction, cellsize))));
+        if let Some((slot, direction, pos)) = free {
+            self.slots[slot][direction] = Some(Marble {
+                owner,
+                pos: subpixel(pos),
+                start: subpixel(pos),
+                id,
+                from_dir: None,
+                progress: 0.0,
+            });
         }
         if self.full() {
             for direction in 0..4 {
@@ -177,8 +689,10 @@ impl Cell {
         Ok(())
     }
 
-    /* Remove and return one marble from each direction that is to be sent */
-    fn send(&mut self) -> [Option<Marble>; 4] {
+    /* Remove and return one marble from each direction that is to be sent. Marbles in the Reserve
+     * move up into the slots that became free.
+     */
+    fn send(&mut self, bias: SpreadBias) -> [Option<Marble>; 4] {
         let mut result = [None; 4];
         for idx in 0..4 {
             result[idx] = self.outgoing_mut()[idx].take();
@@ -186,6 +700,9 @@ impl Cell {
                 self.count -= 1;
             }
         }
+        let has_neighbor = self.has_neighbor;
+        let [residing, _, _, reserve] = &mut self.slots;
+        assign_slots(&mut reserve.marbles, &mut residing.marbles, &has_neighbor, bias);
         if self.count == 0 {
             self.owner = None;
         }
@@ -193,8 +710,10 @@ impl Cell {
     }
 
     /* Receive one marble from a neighbor */
-    fn receive(&mut self, direction: usize, marble: Marble) {
+    fn receive(&mut self, direction: usize, mut marble: Marble) {
         self.owner = Some(marble.owner);
+        marble.from_dir = Some(direction);
+        marble.progress = 0.0;
         self.incoming_mut()[direction] = Some(marble);
         self.count += 1;
     }
@@ -203,85 +722,388 @@ impl Cell {
      * neigbors receive()d them. The Outgoing slots are therefore empty and the Incoming slots
      * might be partially full.
      * Move all marbles from Incoming slot into Outgoing or Remaining slot, possibly changing the
-     * direction to make the directions balanced.
+     * direction to make the directions balanced, see assign_slots().
      */
-    fn sort_received(&mut self) {
-        let mut received = false;
-        for _ in self.incoming().marbles {
-            received = true;
-        }
-        if !received {
+    fn sort_received(&mut self, bias: SpreadBias) {
+        if self.incoming().marbles.iter().all(|slot| slot.is_none()) {
             return;
         }
-        if self.full() {
-            // Collect outgoing marbles, from incoming or residing
+        let has_neighbor = self.has_neighbor;
+        let full = self.full();
+        let [residing, incoming, outgoing, reserve] = &mut self.slots;
+        if full {
+            // Marbles leave in the direction they came from, the remaining directions are filled
+            // up from the residing marbles
             for direction in 0..4 {
-                self.outgoing_mut()[direction] = self.incoming_mut()[direction].take();
-            }
-            for rotation in [0, 1, 3, 2] {
-                for direction in 0..4 {
-                    if !self.has_neighbor[direction] || self.outgoing()[direction].is_some() {
-                        continue
-                    };
-                    self.outgoing_mut()[direction] = self.residing_mut()[(direction+rotation)%4].take();
-                }
+                outgoing[direction] = incoming[direction].take();
             }
+            assign_slots(&mut residing.marbles, &mut outgoing.marbles, &has_neighbor, bias);
         } else {
-            // Sort incoming marbles into residing
-            for rotation in [0, 1, 3, 2] {
-                for direction in 0..4 {
-                    if !self.has_neighbor[direction] || self.residing()[direction].is_some() {
-                        continue
-                    };
-                    self.residing_mut()[direction] = self.incoming_mut()[(direction+rotation)%4].take();
-                }
+            assign_slots(&mut incoming.marbles, &mut residing.marbles, &has_neighbor, bias);
+            // Marbles beyond one per neighbor rest in the Reserve
+            assign_slots(&mut incoming.marbles, &mut reserve.marbles, &[true; 4], bias);
+        }
+    }
+
+    /* Hand the ownership of the cell to all its marbles, which start their next animation from
+     * where they are now and have arrived in this cell
+     */
+    fn propagate_owner(&mut self) {
+        if let Some(owner) = self.owner {
+            for marble in self.marbles_mut() {
+                marble.owner = owner;
+                marble.start = marble.pos;
+                marble.from_dir = None;
             }
         }
     }
 
-    fn step(&mut self, steps: i32, cellsize: i32) {
-        let center = self.coord * cellsize + Point::new(cellsize/2, cellsize/2);
+    fn step(&mut self, progress: f32, cellsize: Point) {
+        if self.count == 0 {
+            return;
+        }
         for direction in 0..4 {
-            let target = center + cellsize/4 *DIRECTIONS[direction];
+            let target = slot_position(self.coord, direction, cellsize);
             for slot in 0..3 {
                 if let Some(marble) = self.slots[slot][direction].as_mut() {
-                    marble.step(target, steps);
+                    marble.step(target, progress);
                 }
             }
+            if let Some(marble) = self.slots[3][direction].as_mut() {
+                marble.step(reserve_position(self.coord, direction, cellsize), progress);
+            }
         }
     }
 }
 
+/* With the serde feature, a grid is serialized with all its cells, including the marbles in
+ * flight, so a position can be restored in the middle of a cascade. The hash and the number of
+ * cells per player are left out and recomputed when deserializing, which also checks that the
+ * cells fit the dimension and the shape of the board, that every cell holds as many marbles as it
+ * counts and that no marbles were lost.
+ */
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "GridData"))]
 pub struct Grid {
     dim: Point,
     cells: Vec<Cell>,
+    // Number of marbles successfully placed. Marbles are never created or destroyed otherwise,
+    // except by removing them in the board editor, so this minus the removed marbles always
+    // equals the number of marbles in all slots of all cells. It also serves as id for the next
+    // marble.
+    placed: usize,
+    removed: usize,
+    // Indices of cells that are at capacity and will spread out in the next call to spread()
+    critical: Vec<usize>,
+    // Indices of cells that sent or received marbles in the current spreading step
+    wave: Vec<usize>,
+    // Number of spreading steps since the last marble was placed
+    chain: u32,
+    // Zobrist hash of the owners and counts of all cells, updated whenever a cell changes
+    #[cfg_attr(feature = "serde", serde(skip))]
+    hash: u64,
+    // Number of cells of each player that has any, updated whenever a cell changes
+    #[cfg_attr(feature = "serde", serde(skip))]
+    owned: HashMap<Owner, usize>,
+    // Number of marbles needed on the board before single_owner() reports an owner
+    min_marbles: usize,
+    spread_bias: SpreadBias,
+    // Factor between the neighbors of a cell and the marbles at which it spreads, see threshold()
+    threshold_factor: f32,
+    // Bonus cells captured since the last placed marble and by whom, see take_bonus_captures()
+    #[cfg_attr(feature = "serde", serde(skip))]
+    bonus_captures: Vec<(Point, Owner)>,
+}
+/* Serialized fields of a grid, from which the rest is recomputed */
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+struct GridData {
+    dim: Point,
+    cells: Vec<Cell>,
+    placed: usize,
+    removed: usize,
+    critical: Vec<usize>,
+    wave: Vec<usize>,
+    chain: u32,
+    min_marbles: usize,
+    // Both were added later and default to how grids behaved before
+    #[serde(default)]
+    spread_bias: SpreadBias,
+    #[serde(default = "default_threshold_factor")]
+    threshold_factor: f32,
+}
+
+#[cfg(feature = "serde")]
+fn default_threshold_factor() -> f32 {
+    DEFAULT_THRESHOLD_FACTOR
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<GridData> for Grid {
+    type Error = String;
+
+    fn try_from(data: GridData) -> Result<Grid, String> {
+        Grid::check_dim(data.dim)?;
+        if data.cells.len() != (data.dim.re * data.dim.im) as usize {
+            return Err(format!("{} cells do not fit a board of size {}", data.cells.len(), data.dim));
+        }
+        // The neighbors and thresholds of the cells follow from which cells are playable, so they
+        // are taken from an empty board of the same shape
+        let mut mask = vec![false; data.cells.len()];
+        for cell in data.cells.iter() {
+            let p = cell.coord;
+            if p.re >= 0 && p.im >= 0 && p.re < data.dim.re && p.im < data.dim.im {
+                mask[(p.im * data.dim.re + p.re) as usize] = cell.playable;
+            }
+        }
+        let mut grid = Grid::from_mask(data.dim, &mask);
+        grid.set_threshold_factor(data.threshold_factor)?;
+        for (empty, cell) in grid.cells.iter_mut().zip(data.cells) {
+            if cell.coord != empty.coord {
+                return Err(format!("Cell {} is stored at the wrong place", cell.coord));
+            }
+            if cell.has_neighbor != empty.has_neighbor || cell.neighbors != empty.neighbors {
+                return Err(format!("Cell {} has neighbors that do not fit the shape of the board", cell.coord));
+            }
+            *empty = Cell { threshold: empty.threshold, ..cell };
+            empty.check()?;
+        }
+        grid.placed = data.placed;
+        grid.removed = data.removed;
+        grid.chain = data.chain;
+        grid.min_marbles = data.min_marbles;
+        grid.spread_bias = data.spread_bias;
+
+        // The cells about to spread are exactly those at capacity, and marbles can only be moving
+        // towards a cell of the current wave
+        if data.critical.iter().chain(data.wave.iter()).any(|&idx| idx >= grid.cells.len()) {
+            return Err("Reference to a cell outside of the board".to_string());
+        }
+        let mut critical = data.critical;
+        critical.sort_unstable();
+        critical.dedup();
+        let full: Vec<usize> = (0..grid.cells.len())
+            .filter(|&idx| grid.cells[idx].playable && grid.cells[idx].full())
+            .collect();
+        if critical != full {
+            return Err("The cells at capacity do not match the cells about to spread".to_string());
+        }
+        grid.critical = critical;
+        grid.wave = data.wave;
+        grid.wave.sort_unstable();
+        grid.wave.dedup();
+        for (idx, cell) in grid.cells.iter().enumerate() {
+            let moving = cell.marbles().any(|marble| marble.from_dir.is_some());
+            if (moving && grid.wave.binary_search(&idx).is_err()) || (!cell.playable && grid.wave.contains(&idx)) {
+                return Err(format!("Cell {} does not fit the current spreading step", cell.coord));
+            }
+        }
+        let marbles = grid.marbles().count();
+        if grid.placed.checked_sub(grid.removed) != Some(marbles) {
+            return Err(format!(
+                "{} marbles were placed and {} removed, but there are {}", grid.placed, grid.removed, marbles,
+            ));
+        }
+        grid.hash = grid.recompute_hash();
+        grid.owned = grid.recompute_owned();
+        Ok(grid)
+    }
+}
+
+/* A board without cells, which takes the place of a board that is moved out, see Game::restart() */
+impl Default for Grid {
+    fn default() -> Grid {
+        Grid::new(Point::new(0, 0))
+    }
 }
+
 impl Grid {
     pub fn new(dim: Point) -> Grid {
+        Grid::from_mask(dim, &vec![true; dim.re as usize * dim.im as usize])
+    }
+
+    /* Create a board with an arbitrary shape. The mask holds one entry per cell, row by row, and
+     * tells if the cell belongs to the board. Cells outside of the board can not hold marbles and
+     * do not count as neighbors.
+     */
+    pub fn from_mask(dim: Point, mask: &[bool]) -> Grid {
+        let playable = |p: Point| {
+            p.re >= 0 && p.im >= 0 && p.re < dim.re && p.im < dim.im
+                && mask[(p.im * dim.re + p.re) as usize]
+        };
         let mut cells: Vec<Cell> = Vec::with_capacity(dim.re as usize * dim.im as usize);
         for x in 0..dim.re {
             for y in 0..dim.im {
-                cells.push(Cell::new(Point::new(x as i32, y as i32), dim));
+                cells.push(Cell::new(Point::new(x as i32, y as i32), playable));
             }
         }
         Grid {
-            dim: dim,
-            cells: cells,
+            dim,
+            cells,
+            placed: 0,
+            removed: 0,
+            critical: Vec::new(),
+            wave: Vec::new(),
+            chain: 0,
+            hash: 0,
+            owned: HashMap::new(),
+            min_marbles: DEFAULT_MIN_MARBLES,
+            spread_bias: SpreadBias::Counterclockwise,
+            threshold_factor: DEFAULT_THRESHOLD_FACTOR,
+            bonus_captures: Vec::new(),
+        }
+    }
+
+    /* Empty the board for another game, which then behaves like a board freshly created with the
+     * same shape and settings. The cells and their neighbors are kept instead of being built
+     * again, which saves the allocation when many games are played in a row. Bonus cells and
+     * neutral marbles are removed and need to be added again.
+     */
+    pub fn reset(&mut self) {
+        for cell in self.cells.iter_mut() {
+            cell.clear();
+            cell.bonus = false;
+        }
+        self.placed = 0;
+        self.removed = 0;
+        self.critical.clear();
+        self.wave.clear();
+        self.chain = 0;
+        self.hash = 0;
+        self.owned.clear();
+        self.bonus_captures.clear();
+    }
+
+    /* Check that a board of the given dimension may be created, see MAX_CELLS */
+    pub fn check_dim(dim: Point) -> Result<(), String> {
+        if dim.re <= 0 || dim.im <= 0 {
+            return Err(format!("The board must not be empty, but is {}x{}", dim.re, dim.im));
+        }
+        if dim.re > MAX_CELLS / dim.im {
+            return Err(format!("The board must have at most {} cells, but has {}x{}", MAX_CELLS, dim.re, dim.im));
         }
+        Ok(())
     }
+
     pub fn dim(&self) -> Point { self.dim }
+    pub fn chain(&self) -> u32 { self.chain }
+    /* Identifier of the marble that was placed last, see Marble::id() */
+    pub fn last_placed(&self) -> Option<u32> { self.placed.checked_sub(1).map(|id| id as u32) }
+    pub fn hash(&self) -> u64 { self.hash }
+
+    /* Compute the hash from scratch, which must match the incrementally updated one */
+    fn recompute_hash(&self) -> u64 {
+        self.cells.iter().enumerate().fold(0, |hash, (idx, cell)| hash ^ cell.zobrist_key(idx))
+    }
+
+    /* Count the cells of each player from scratch, which must match the incrementally updated
+     * counts
+     */
+    fn recompute_owned(&self) -> HashMap<Owner, usize> {
+        let mut owned = HashMap::new();
+        for owner in self.cells.iter().filter_map(|cell| cell.player()) {
+            *owned.entry(owner).or_insert(0) += 1;
+        }
+        owned
+    }
+
+    /* Update the number of cells per player for a cell that changed from one player to another */
+    fn track_player(&mut self, before: Option<Owner>, after: Option<Owner>) {
+        if before == after {
+            return;
+        }
+        if let Some(owner) = before {
+            let count = self.owned.get_mut(&owner).expect("Cell of a player without cells");
+            *count -= 1;
+            if *count == 0 {
+                self.owned.remove(&owner);
+            }
+        }
+        if let Some(owner) = after {
+            *self.owned.entry(owner).or_insert(0) += 1;
+        }
+    }
+
+    /* Apply a change to the cell with the given index, keeping the hash and the number of cells
+     * per player up to date
+     */
+    fn modify<R, F: FnOnce(&mut Cell) -> R>(&mut self, idx: usize, change: F) -> R {
+        let before = self.cells[idx].player();
+        self.hash ^= self.cells[idx].zobrist_key(idx);
+        let result = change(&mut self.cells[idx]);
+        self.hash ^= self.cells[idx].zobrist_key(idx);
+        self.track_player(before, self.cells[idx].player());
+        result
+    }
+
+    /* Label of a column: A to Z, followed by AA, AB and so on */
+    pub fn column_label(x: i32) -> String {
+        let mut label = Vec::new();
+        let mut x = x + 1;
+        while x > 0 {
+            x -= 1;
+            label.push(b'A' + (x % 26) as u8);
+            x /= 26;
+        }
+        label.reverse();
+        String::from_utf8(label).unwrap()
+    }
+
+    /* Label of a row, counting from 1 */
+    pub fn row_label(y: i32) -> String {
+        (y + 1).to_string()
+    }
+
+    /* Label of a cell like B4 or AA12, from the labels of its column and row */
+    pub fn cell_label(p: Point) -> String {
+        Grid::column_label(p.re) + &Grid::row_label(p.im)
+    }
+
+    /* All coordinates of the grid, row by row */
+    pub fn points(&self) -> Points {
+        self.points_in(Point::new(0, 0), self.dim)
+    }
+
+    /* All coordinates in the rectangle with the given corner and size, clipped to the grid */
+    pub fn points_in(&self, corner: Point, size: Point) -> Points {
+        let min = Point::new(corner.re.clamp(0, self.dim.re), corner.im.clamp(0, self.dim.im));
+        let max = Point::new(
+            (corner.re + size.re).clamp(min.re, self.dim.re),
+            (corner.im + size.im).clamp(min.im, self.dim.im),
+        );
+        Points::new(min, max - min)
+    }
     
-    fn idx(&self, p: Point) -> usize {
-        (p.re * self.dim.im + p.im) as usize
+    // Index of the cell at the given coordinates, if they are inside of the grid
+    fn idx(&self, p: Point) -> Option<usize> {
+        if p.re < 0 || p.im < 0 || p.re >= self.dim.re || p.im >= self.dim.im {
+            return None;
+        }
+        Some((p.re * self.dim.im + p.im) as usize)
+    }
+
+    /* All cells together with their coordinates, in the order they are stored */
+    pub fn cells(&self) -> impl Iterator<Item=(Point, &Cell)> + '_ {
+        self.cells.iter().map(|cell| (cell.coord, cell))
+    }
+
+    pub fn get(&self, p: Point) -> Option<&Cell> {
+        self.idx(p).map(|idx| &self.cells[idx])
+    }
+
+    pub fn get_mut(&mut self, p: Point) -> Option<&mut Cell> {
+        let idx = self.idx(p)?;
+        Some(&mut self.cells[idx])
     }
 
+    /* Like get(), but panics if the coordinates are outside of the grid */
     pub fn cell(&self, p: Point) -> &Cell {
-        &self.cells[self.idx(p)]
+        self.get(p).unwrap_or_else(|| panic!("Cell {} is outside of the grid", p))
     }
 
     pub fn cell_mut(&mut self, p: Point) -> &mut Cell {
-        let idx = self.idx(p);
-        &mut self.cells[idx]
+        self.get_mut(p).unwrap_or_else(|| panic!("Cell {} is outside of the grid", p))
     }
 
     /* After a adding a marble that fills the field or at the end of an animation, this is called
@@ -290,46 +1112,194 @@ impl Grid {
      * belong to, which determines their target position. The owner of the neighboring cells is
      * also changed, but the owner of the already existing marbles is changed at the start of the
      * next call to spread().
+     * Everything that happens is reported to events, in the order in which the cells are handled.
      */
-    fn spread(&mut self) -> State {
-        // Change ownership of marbles
-        for cell in self.cells.iter_mut() {
-            match cell.owner {
-                None => (),
-                Some(owner) => {
-                    for marble in cell.marbles_mut() {
-                        marble.owner = owner;
+    fn spread(&mut self, events: &mut Vec<GridEvent>) -> State {
+        // Change ownership of marbles that took part in the last step and start the next animation
+        // from where they are now
+        self.propagate_owners();
+        self.wave.clear();
+
+        // Spread out all cells that are at capacity, in reverse order of their index, which is
+        // column by column starting with the last cell
+        let mut critical = std::mem::take(&mut self.critical);
+        if critical.is_empty() {
+            return State::AcceptingInput;
+        }
+        critical.sort_unstable_by(|a, b| b.cmp(a));
+        critical.dedup();
+        let arrivals = self.send_all(&critical, events);
+        critical.clear();
+        self.receive_all(arrivals);
+        self.wave.sort_unstable();
+        self.wave.dedup();
+
+        // Every cell that is at capacity after receiving is spread out in the next step
+        critical.extend(self.sort_received_all());
+        self.critical = critical;
+        self.check_conservation();
+        debug_assert_eq!(self.hash, self.recompute_hash(), "Incremental hash is out of sync");
+        debug_assert_eq!(self.owned, self.recompute_owned(), "Cells per player are out of sync");
+        self.chain += 1;
+        State::Animating(0.0)
+    }
+
+    /* Let the given cells send their marbles, in the given order, and report what happens to
+     * events. Returns the marbles for receive_all(). They are only handed to the neighbors once
+     * all cells have sent theirs. Until then, the owner a cell would have after receiving is
+     * remembered, so the events are the same as if each marble was received right away.
+     */
+    fn send_all(&mut self, critical: &[usize], events: &mut Vec<GridEvent>) -> Vec<(usize, Direction, Marble)> {
+        let mut arrivals: Vec<(usize, Direction, Marble)> = Vec::new();
+        // Owner of the last marble sent to each cell so far, sorted by the index of the cell
+        let mut received_owner: Vec<(usize, Owner)> = Vec::new();
+        for &idx in critical {
+            let coord = self.cells[idx].coord;
+            let received = received_owner.binary_search_by_key(&idx, |&(target, _)| target).ok();
+            if let Some(owner) = received.map(|pos| received_owner[pos].1).or(self.cells[idx].owner) {
+                events.push(GridEvent::CellFired { at: coord, owner });
+            }
+            let bias = self.spread_bias;
+            let sent = self.modify(idx, |cell| cell.send(bias));
+            self.wave.push(idx);
+            for direction in 0..4 {
+                if let Some(marble) = sent[direction] {
+                    // Marbles are only sent in directions where the cell has a neighbor
+                    let target = coord + DIRECTIONS[direction];
+                    let neighbor = self.idx(target).unwrap_or_else(
+                        || panic!("Cell {} sent a marble to {} outside of the grid", coord, target)
+                    );
+                    let from_dir = OPPOSITES[direction];
+                    let previous = match received_owner.binary_search_by_key(&neighbor, |&(target, _)| target) {
+                        Ok(pos) => Some(std::mem::replace(&mut received_owner[pos].1, marble.owner)),
+                        Err(pos) => {
+                            received_owner.insert(pos, (neighbor, marble.owner));
+                            self.cells[neighbor].owner
+                        },
+                    };
+                    arrivals.push((neighbor, from_dir, marble));
+                    self.wave.push(neighbor);
+                    events.push(GridEvent::MarbleArrived { at: target, from_dir, owner: marble.owner });
+                    match previous {
+                        Some(from) if from != marble.owner => {
+                            events.push(GridEvent::CellCaptured { at: target, from, to: marble.owner });
+                            // A bonus cell only counts once per cascade, even if it changes hands
+                            // again
+                            if self.cells[neighbor].bonus && self.bonus_captures.iter().all(|&(p, _)| p != target) {
+                                self.bonus_captures.push((target, marble.owner));
+                            }
+                        },
+                        _ => (),
                     }
                 }
             }
         }
-        // Spread out
-        let mut any_moved = false;
-        for coord in PointIter::new(self.dim) {
-            if !self.cell(coord).full() {
-                continue
-            }
-            let sent = self.cell_mut(coord).send();
+        arrivals
+    }
 
-            for direction in 0..4 {
-                match sent[direction] {
-                    None => continue,
-                    Some(marble) => {
-                        let neighbor = self.cell_mut(coord + DIRECTIONS[direction]);
-                        neighbor.receive((direction+2)%4, marble);
-                        any_moved = true;
-                    }
+    /* Hand the ownership of the cells of the last wave to their marbles. With the parallel
+     * feature, this and the other per cell passes of spread() are distributed over all cells
+     * once the wave is large enough for it to pay off.
+     */
+    fn propagate_owners(&mut self) {
+        #[cfg(feature = "parallel")]
+        if self.wave.len() >= PARALLEL_CHUNK {
+            return self.propagate_owners_parallel();
+        }
+        self.propagate_owners_serial();
+    }
+
+    fn propagate_owners_serial(&mut self) {
+        for &idx in self.wave.iter() {
+            self.cells[idx].propagate_owner();
+        }
+    }
+
+    #[cfg(feature = "parallel")]
+    fn propagate_owners_parallel(&mut self) {
+        let wave = &self.wave;
+        self.cells.par_iter_mut().with_min_len(PARALLEL_CHUNK).enumerate()
+            .filter(|(idx, _)| wave.binary_search(idx).is_ok())
+            .for_each(|(_, cell)| cell.propagate_owner());
+    }
+
+    /* Hand the marbles sent by spread() to their targets, given as (index, direction it came from,
+     * marble). Marbles arriving at the same cell are received in the given order.
+     */
+    fn receive_all(&mut self, arrivals: Vec<(usize, Direction, Marble)>) {
+        #[cfg(feature = "parallel")]
+        if arrivals.len() >= PARALLEL_CHUNK {
+            return self.receive_all_parallel(arrivals);
+        }
+        self.receive_all_serial(arrivals);
+    }
+
+    fn receive_all_serial(&mut self, arrivals: Vec<(usize, Direction, Marble)>) {
+        for (idx, from_dir, marble) in arrivals {
+            self.modify(idx, |cell| cell.receive(from_dir, marble));
+        }
+    }
+
+    #[cfg(feature = "parallel")]
+    fn receive_all_parallel(&mut self, mut arrivals: Vec<(usize, Direction, Marble)>) {
+        // The sort is stable, so the marbles arriving at one cell stay in order
+        arrivals.par_sort_by_key(|&(idx, _, _)| idx);
+        let arrivals = &arrivals;
+        // Change of the hash and the players before and after receiving, per receiving cell
+        let changes: Vec<(u64, Option<Owner>, Option<Owner>)> = self.cells.par_iter_mut()
+            .with_min_len(PARALLEL_CHUNK).enumerate()
+            .filter_map(|(idx, cell)| {
+                let start = arrivals.partition_point(|&(target, _, _)| target < idx);
+                let end = arrivals.partition_point(|&(target, _, _)| target <= idx);
+                if start == end {
+                    return None;
                 }
-            }
+                let (key, player) = (cell.zobrist_key(idx), cell.player());
+                for &(_, from_dir, marble) in arrivals[start..end].iter() {
+                    cell.receive(from_dir, marble);
+                }
+                Some((key ^ cell.zobrist_key(idx), player, cell.player()))
+            })
+            .collect();
+        for (key, before, after) in changes {
+            self.hash ^= key;
+            self.track_player(before, after);
         }
-        if any_moved {
-            for cell in self.cells.iter_mut() {
-                cell.sort_received();
+    }
+
+    /* Sort the received marbles of all cells of the current wave. Returns the indices of the cells
+     * that are at capacity afterwards, in ascending order.
+     */
+    fn sort_received_all(&mut self) -> Vec<usize> {
+        #[cfg(feature = "parallel")]
+        if self.wave.len() >= PARALLEL_CHUNK {
+            return self.sort_received_all_parallel();
+        }
+        self.sort_received_all_serial()
+    }
+
+    fn sort_received_all_serial(&mut self) -> Vec<usize> {
+        let mut full = Vec::new();
+        for &idx in self.wave.iter() {
+            let cell = &mut self.cells[idx];
+            cell.sort_received(self.spread_bias);
+            if cell.full() {
+                full.push(idx);
             }
-            State::Animating(15)
-        } else {
-            State::AcceptingInput
         }
+        full
+    }
+
+    #[cfg(feature = "parallel")]
+    fn sort_received_all_parallel(&mut self) -> Vec<usize> {
+        let (wave, bias) = (&self.wave, self.spread_bias);
+        self.cells.par_iter_mut().with_min_len(PARALLEL_CHUNK).enumerate()
+            .filter(|(idx, _)| wave.binary_search(idx).is_ok())
+            .filter_map(|(idx, cell)| {
+                cell.sort_received(bias);
+                if cell.full() { Some(idx) } else { None }
+            })
+            .collect()
     }
 
     pub fn marbles(&self) -> impl Iterator<Item=&Marble> + '_ {
@@ -338,39 +1308,776 @@ impl Grid {
         ).flatten()
     }
 
+    /* Marbles of one owner, including those that are still moving between cells */
+    pub fn marbles_of(&self, owner: Owner) -> impl Iterator<Item=&Marble> + '_ {
+        self.marbles().filter(move |marble| marble.get_owner() == owner)
+    }
+
+    /* Marbles that are moving between cells during the current spreading step, in the order of
+     * the cells receiving them. Marbles that only change their slot within a cell are not
+     * included. This is empty once the board has settled.
+     */
+    pub fn in_flight(&self) -> impl Iterator<Item=InFlight> + '_ {
+        self.wave.iter().flat_map(move |&idx| {
+            let cell = &self.cells[idx];
+            cell.slots.iter().flat_map(|slots| slots.marbles.iter())
+                .filter_map(move |marble| {
+                    let marble = marble.as_ref()?;
+                    let from_dir = marble.from_dir?;
+                    Some(InFlight {
+                        id: marble.id,
+                        owner: marble.owner,
+                        pos: marble.get_pos(),
+                        from: cell.coord + DIRECTIONS[from_dir],
+                        to: cell.coord,
+                        remaining: 1.0 - marble.progress,
+                    })
+                })
+        })
+    }
+
+    /* Verify that no marble got lost or duplicated while moving marbles between cells and slots.
+     * Each cell must hold exactly `count` marbles and the total must match the number of placed
+     * marbles.
+     */
+    fn check_conservation(&self) {
+        for cell in self.cells.iter() {
+            debug_assert_eq!(
+                cell.marbles().count(), cell.count as usize,
+                "Marble count mismatch in cell {}", cell.coord,
+            );
+        }
+        debug_assert_eq!(self.marbles().count(), self.placed - self.removed, "Marbles were lost or duplicated");
+    }
+
     /* Try to add a marble at the given coordinates.
-     * Returns the Err variant if the cell belongs to someone else.
-     * May be called in AcceptingInput state.
+     * Returns the Err variant if the cell belongs to someone else or is outside of the grid.
+     * May be called in AcceptingInput state. Also returns what happened if the marble caused the
+     * cell to spread.
      */
-    pub fn add_marble(&mut self, coord: Point, owner: Owner, cellsize: i32) -> Result<State, ()> {
-        let cell = self.cell_mut(coord);
-        cell.add_marble(owner, cellsize)?;
-        Ok(
-            if cell.full() {
-                self.spread()
+    pub fn add_marble(&mut self, coord: Point, owner: Owner, cellsize: Point)
+        -> Result<(State, Vec<GridEvent>), String>
+    {
+        let idx = self.idx(coord).ok_or(format!("Cell {} is outside of the board", coord))?;
+        let id = self.placed as u32;
+        self.modify(idx, |cell| cell.add_marble(owner, id, cellsize))
+            .map_err(|()| format!("Cell {} does not accept a marble of player {}", coord, owner))?;
+        self.placed += 1;
+        self.chain = 0;
+        self.bonus_captures.clear();
+        let mut events = Vec::new();
+        let state = if self.cells[idx].full() {
+            self.critical.push(idx);
+            self.spread(&mut events)
+        } else {
+            State::AcceptingInput
+        };
+        Ok((state, events))
+    }
+
+    /* Add a marble like add_marble() and resolve the resulting cascade without animation. The
+     * returned iterator yields one item per spreading step until the board settles. Each step is
+     * one call of step() that finishes the animation at once, so the waves and the position
+     * afterwards are the same as when animating, but the Game itself animates with step() frame
+     * by frame. Once one player has taken over the board, the cascade may never end, so after
+     * MAX_WAVES steps an error is yielded instead, leaving the board in the middle of the cascade.
+     */
+    pub fn cascade(&mut self, coord: Point, owner: Owner, cellsize: Point) -> Result<Cascade<'_>, String> {
+        let (state, events) = self.add_marble(coord, owner, cellsize)?;
+        Ok(Cascade {
+            grid: self,
+            state,
+            cellsize,
+            first: Some(events).filter(|events| !events.is_empty()),
+            failed: false,
+        })
+    }
+
+    /* Play a move, let the closure look at the resulting position and restore the grid exactly as
+     * it was. Only the cells the cascade reaches are saved before they change, which is much
+     * cheaper than playing the move on a copy of a large board. The cascade is resolved without
+     * animation like cascade(), but stops early if it can never end because one player has more
+     * marbles than the board holds at rest, see capacity(). The closure may simulate further
+     * moves, which restore the grid themselves, but must not change it otherwise. Moves that
+     * check_move() refuses are an error.
+     */
+    pub fn with_simulated_move<F, R>(&mut self, coord: Point, owner: Owner, cellsize: Point, f: F) -> Result<R, String>
+    where F: FnOnce(&mut Grid) -> R
+    {
+        self.check_move(coord, owner)?;
+        let mut undo = UndoLog::new(self);
+        let idx = self.idx(coord).unwrap();
+        undo.save_around(self, idx);
+        let (mut state, _) = self.add_marble(coord, owner, cellsize)?;
+        let overfull = self.placed - self.removed > self.capacity();
+        while let State::Animating(_) = state {
+            let endless = overfull && self.owned.len() <= 1;
+            if endless || self.chain >= MAX_WAVES {
+                break;
+            }
+            // Marbles of the current wave move and pass on their owner, then the cells at
+            // capacity send theirs to their neighbors
+            for &idx in self.wave.iter() {
+                undo.save(self, idx);
+            }
+            for &idx in self.critical.iter() {
+                undo.save_around(self, idx);
+            }
+            state = self.step(state, 1.0, cellsize).0;
+        }
+        let result = f(self);
+        undo.restore(self);
+        Ok(result)
+    }
+
+    /* Check whether the owner may place a marble at the given cell, giving the reason if not.
+     * Moves are only possible once all marbles came to rest.
+     */
+    pub fn check_move(&self, coord: Point, owner: Owner) -> Result<(), String> {
+        if owner == NEUTRAL {
+            return Err("Neutral marbles can not be played".to_string());
+        }
+        if !self.settled() {
+            return Err("Marbles are still moving".to_string());
+        }
+        let cell = self.get(coord).ok_or(format!("Cell {} is outside of the board", coord))?;
+        if !cell.playable {
+            return Err(format!("Cell {} does not belong to the board", coord));
+        }
+        match cell.owner {
+            Some(other) if other != owner && other != NEUTRAL =>
+                Err(format!("Cell {} belongs to player {}", coord, other)),
+            _ if !cell.accepts(owner) => Err(format!("Cell {} is at capacity", coord)),
+            _ => Ok(()),
+        }
+    }
+
+    /* Play the given moves one after the other, resolving each cascade without animation like
+     * cascade(). Each move is checked with check_move() first. Stops at the first move that is
+     * refused or whose cascade does not end, leaving the board as it is at that point.
+     */
+    pub fn apply_moves(&mut self, moves: &[(Point, Owner)], cellsize: Point)
+        -> Result<ReplaySummary, ReplayError>
+    {
+        let mut summary = ReplaySummary::default();
+        for (index, &(coord, owner)) in moves.iter().enumerate() {
+            let error = |reason: String| ReplayError { index, reason };
+            self.check_move(coord, owner).map_err(error)?;
+            let waves = self.cascade(coord, owner, cellsize)
+                .map_err(|_| error("Marble can not be placed".to_string()))?;
+            for wave in waves {
+                wave.map_err(error)?;
+            }
+            summary.moves += 1;
+            summary.waves += self.chain as u64;
+            summary.longest_chain = summary.longest_chain.max(self.chain);
+        }
+        Ok(summary)
+    }
+
+    /* Make the given cell a bonus cell. Only cells of the board can be one. */
+    pub fn add_bonus(&mut self, coord: Point) -> Result<(), String> {
+        let idx = self.playable_idx(coord)?;
+        self.cells[idx].bonus = true;
+        Ok(())
+    }
+
+    /* Bonus cells captured during the cascade of the last placed marble, with the player that
+     * captured them, which are then forgotten. A bonus cell is captured when a marble spreading
+     * into it changes its owner, which includes neutral cells but not empty ones or placing a
+     * marble. Each bonus cell is reported at most once per cascade.
+     */
+    pub fn take_bonus_captures(&mut self) -> Vec<(Point, Owner)> {
+        std::mem::take(&mut self.bonus_captures)
+    }
+
+    /* Place a neutral marble when setting up the board. This is refused if the cell belongs to a
+     * player or would reach capacity, since neutral marbles never spread.
+     */
+    pub fn add_neutral(&mut self, coord: Point, cellsize: Point) -> Result<(), String> {
+        let idx = self.playable_idx(coord)?;
+        if self.cells[idx].count + 1 >= self.cells[idx].threshold {
+            return Err(format!("Cell {} can not hold another neutral marble without spreading", Grid::cell_label(coord)));
+        }
+        let id = self.placed as u32;
+        self.modify(idx, |cell| cell.add_marble(NEUTRAL, id, cellsize))
+            .map_err(|()| format!("Cell {} belongs to a player", Grid::cell_label(coord)))?;
+        self.placed += 1;
+        Ok(())
+    }
+
+    /* Compact binary encoding of a settled board: the dimensions as two little endian u16,
+     * followed by owner and count (one byte each) for each cell in storage order. The owner is 255
+     * for empty cells, 254 for neutral cells and 253 for cells outside of the shape of the board.
+     * The positions of marbles are not stored since they follow from the count, so the state of a
+     * running animation is not preserved.
+     */
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(4 + 2*self.cells.len());
+        bytes.extend_from_slice(&(self.dim.re as u16).to_le_bytes());
+        bytes.extend_from_slice(&(self.dim.im as u16).to_le_bytes());
+        for cell in self.cells.iter() {
+            bytes.push(match cell.owner {
+                _ if !cell.playable => 253,
+                None => 255,
+                Some(NEUTRAL) => 254,
+                Some(owner) => owner.0 as u8,
+            });
+            bytes.push(cell.count);
+        }
+        bytes
+    }
+
+    /* Restore a board of the given dimension from the output of to_bytes(). Cells of players
+     * beyond the given number of players are refused.
+     */
+    pub fn from_bytes(dim: Point, bytes: &[u8], num_players: usize, cellsize: Point) -> Result<Grid, String> {
+        Grid::from_bytes_with_threshold(dim, bytes, num_players, cellsize, DEFAULT_THRESHOLD_FACTOR)
+    }
+
+    /* Like from_bytes(), for a board whose cells spread at the given threshold factor, see
+     * set_threshold_factor()
+     */
+    pub fn from_bytes_with_threshold(dim: Point, bytes: &[u8], num_players: usize, cellsize: Point, factor: f32)
+        -> Result<Grid, String>
+    {
+        Grid::check_dim(dim)?;
+        let size = dim.re as usize * dim.im as usize;
+        if bytes.len() != 4 + 2*size {
+            return Err(format!("Expected {} bytes, got {}", 4 + 2*size, bytes.len()));
+        }
+        let header = Point::new(
+            u16::from_le_bytes([bytes[0], bytes[1]]) as i32,
+            u16::from_le_bytes([bytes[2], bytes[3]]) as i32,
+        );
+        if header != dim {
+            return Err(format!("Board has dimension {}, expected {}", header, dim));
+        }
+        let data = &bytes[4..];
+        // The mask is row by row while the cells are stored column by column
+        let mut mask = vec![true; size];
+        for idx in 0..size {
+            if data[2*idx] == 253 {
+                mask[(idx % dim.im as usize) * dim.re as usize + idx / dim.im as usize] = false;
+            }
+        }
+        let mut grid = Grid::from_mask(dim, &mask);
+        grid.set_threshold_factor(factor)?;
+        for (cell, data) in grid.cells.iter_mut().zip(data.chunks(2)) {
+            let owner = match data[0] {
+                253 | 255 => None,
+                254 => Some(NEUTRAL),
+                owner => Some(Owner::new(owner as usize, num_players)
+                    .map_err(|e| format!("Cell {}: {}", cell.coord, e))?),
+            };
+            let count = data[1];
+            if count > 0 && count >= cell.threshold {
+                return Err(format!("Cell {} holds {} marbles, which is at capacity", cell.coord, count));
+            }
+            match owner {
+                None if count > 0 => {
+                    return Err(format!("Cell {} holds marbles but has no owner", cell.coord));
+                },
+                Some(_) if count == 0 => {
+                    return Err(format!("Cell {} has an owner but no marbles", cell.coord));
+                },
+                _ => (),
+            }
+            for _ in 0..count {
+                cell.add_marble(owner.unwrap(), grid.placed as u32, cellsize).unwrap();
+                grid.placed += 1;
+            }
+        }
+        grid.hash = grid.recompute_hash();
+        grid.owned = grid.recompute_owned();
+        Ok(grid)
+    }
+
+    /* Single line text encoding of a settled board, like "3x2 a1.2#b2". After the dimension, the
+     * cells follow row by row: "." for an empty cell, "#" for a cell outside of the shape of the
+     * board, a lowercase letter for the owner (a for the first player) or "*" for neutral, followed
+     * by the count. A number in front of a cell repeats it.
+     */
+    pub fn encode(&self) -> String {
+        let mut tokens = Vec::with_capacity(self.cells.len());
+        for p in self.points() {
+            let cell = self.cell(p);
+            tokens.push(match cell.owner {
+                _ if !cell.playable => "#".to_string(),
+                None => ".".to_string(),
+                Some(owner) => format!("{}{}", owner, cell.count),
+            });
+        }
+        let mut result = format!("{}x{} ", self.dim.re, self.dim.im);
+        let mut idx = 0;
+        while idx < tokens.len() {
+            let run = tokens[idx..].iter().take_while(|token| **token == tokens[idx]).count();
+            if run > 1 {
+                result += &run.to_string();
+            }
+            result += &tokens[idx];
+            idx += run;
+        }
+        result
+    }
+
+    /* Parse the output of encode() for a game with the given number of players */
+    pub fn decode(text: &str, num_players: usize, cellsize: Point) -> Result<Grid, String> {
+        Grid::decode_with_threshold(text, num_players, cellsize, DEFAULT_THRESHOLD_FACTOR)
+    }
+
+    /* Like decode(), for a board whose cells spread at the given threshold factor */
+    pub fn decode_with_threshold(text: &str, num_players: usize, cellsize: Point, factor: f32) -> Result<Grid, String> {
+        let (dim, cells) = text.trim().split_once(' ').ok_or("Missing board dimension")?;
+        let (width, height) = dim.split_once('x').ok_or(format!("Invalid dimension {}", dim))?;
+        let parse = |x: &str| x.parse::<u16>().ok().filter(|&x| x > 0).map(|x| x as i32)
+            .ok_or(format!("Invalid dimension {}", dim));
+        let dim = Point::new(parse(width)?, parse(height)?);
+        Grid::check_dim(dim)?;
+        let size = dim.re as usize * dim.im as usize;
+
+        // Owner and count per cell, in the format of to_bytes() but row by row
+        let mut rows: Vec<[u8; 2]> = Vec::with_capacity(size);
+        let mut chars = cells.chars().peekable();
+        while chars.peek().is_some() {
+            let mut run = String::new();
+            while let Some(c) = chars.next_if(|c| c.is_ascii_digit()) {
+                run.push(c);
+            }
+            let run = if run.is_empty() {
+                1
             } else {
-                State::AcceptingInput
+                run.parse::<usize>().ok().filter(|&x| x > 0).ok_or(format!("Invalid repetition {}", run))?
+            };
+            let owner = match chars.next() {
+                Some('.') => 255,
+                Some('#') => 253,
+                Some('*') => 254,
+                Some(c @ 'a'..='z') => Owner::from_letter(c, num_players)?.0 as u8,
+                Some(c) => return Err(format!("Invalid cell {}", c)),
+                None => return Err("Repetition without cell".to_string()),
+            };
+            let count = match owner {
+                253 | 255 => 0,
+                _ => chars.next().and_then(|c| c.to_digit(10)).filter(|&c| c > 0)
+                    .ok_or("Missing marble count")? as u8,
+            };
+            if rows.len() + run > size {
+                return Err(format!("More than {} cells", size));
+            }
+            rows.extend(std::iter::repeat_n([owner, count], run));
+        }
+        if rows.len() != size {
+            return Err(format!("Expected {} cells, got {}", size, rows.len()));
+        }
+
+        let mut bytes = Vec::with_capacity(4 + 2*size);
+        bytes.extend_from_slice(&(dim.re as u16).to_le_bytes());
+        bytes.extend_from_slice(&(dim.im as u16).to_le_bytes());
+        for x in 0..dim.re {
+            for y in 0..dim.im {
+                bytes.extend_from_slice(&rows[(y * dim.re + x) as usize]);
             }
-        )
+        }
+        Grid::from_bytes_with_threshold(dim, &bytes, num_players, cellsize, factor)
     }
 
-    /* Perform one animation step */
-    pub fn step(&mut self, state: State, cellsize: i32) -> State {
-        match state {
+    /* Advance the animation by the given fraction of the duration of one spreading step. Once the
+     * marbles reached their targets, the next spreading step is started, which is reported by the
+     * returned events.
+     */
+    pub fn step(&mut self, state: State, delta: f32, cellsize: Point) -> (State, Vec<GridEvent>) {
+        let mut events = Vec::new();
+        let state = match state {
             State::AcceptingInput => state,
-            State::Animating(steps) => {
-                for cell in self.cells.iter_mut() {
-                    cell.step(steps, cellsize);
+            State::Animating(progress) => {
+                let progress = (progress + delta).min(1.0);
+                // Marbles in all other cells are already resting at their targets
+                for &idx in self.wave.iter() {
+                    self.cells[idx].step(progress, cellsize);
                 }
-                if steps == 0 {
-                    self.spread()
+                if progress >= 1.0 {
+                    self.spread(&mut events)
                 } else {
-                    State::Animating(steps-1)
+                    State::Animating(progress)
                 }
             }
+        };
+        (state, events)
+    }
+
+    /* Turn all cells and marbles of the given owner neutral */
+    pub fn neutralize(&mut self, owner: Owner) {
+        for idx in 0..self.cells.len() {
+            if self.cells[idx].owner != Some(owner) {
+                continue;
+            }
+            self.modify(idx, |cell| cell.set_owner(NEUTRAL));
         }
     }
 
+    /* Add a marble of the given owner when setting up a position, taking over the cell if it
+     * belongs to someone else. Unlike add_marble(), a cell that reaches capacity does not spread,
+     * see check_settled().
+     */
+    pub fn insert_marble(&mut self, coord: Point, owner: Owner, cellsize: Point) -> Result<(), String> {
+        let idx = self.playable_idx(coord)?;
+        if self.cells[idx].full() {
+            return Err(format!("Cell {} is at capacity", Grid::cell_label(coord)));
+        }
+        if self.cells[idx].count > 0 {
+            self.modify(idx, |cell| cell.set_owner(owner));
+        }
+        let id = self.placed as u32;
+        self.modify(idx, |cell| cell.add_marble(owner, id, cellsize))
+            .map_err(|()| format!("Cell {} does not accept a marble of {}", Grid::cell_label(coord), owner))?;
+        self.placed += 1;
+        Ok(())
+    }
+
+    /* Remove the marble that was added last to a cell when setting up a position. The cell has no
+     * owner once its last marble is removed.
+     */
+    pub fn remove_marble(&mut self, coord: Point) -> Result<(), String> {
+        let idx = self.occupied_idx(coord)?;
+        self.modify(idx, |cell| cell.remove_marble());
+        self.removed += 1;
+        self.check_conservation();
+        Ok(())
+    }
+
+    /* Hand a cell with marbles to a different owner */
+    pub fn set_owner(&mut self, coord: Point, owner: Owner) -> Result<(), String> {
+        let idx = self.occupied_idx(coord)?;
+        self.modify(idx, |cell| cell.set_owner(owner));
+        Ok(())
+    }
+
+    /* Index of the cell at coord, which must be part of the board */
+    fn playable_idx(&self, coord: Point) -> Result<usize, String> {
+        match self.idx(coord) {
+            Some(idx) if self.cells[idx].playable => Ok(idx),
+            _ => Err(format!("Cell {} is not part of the board", Grid::cell_label(coord))),
+        }
+    }
+
+    /* Index of the cell at coord, which must hold marbles */
+    fn occupied_idx(&self, coord: Point) -> Result<usize, String> {
+        let idx = self.playable_idx(coord)?;
+        if self.cells[idx].count == 0 {
+            return Err(format!("Cell {} is empty", Grid::cell_label(coord)));
+        }
+        Ok(idx)
+    }
+
+    /* Check that no cell is at capacity, so play can start from this position */
+    pub fn check_settled(&self) -> Result<(), String> {
+        match self.cells.iter().find(|cell| cell.count > 0 && cell.full()) {
+            Some(cell) => Err(format!("Cell {} is at capacity", Grid::cell_label(cell.coord))),
+            None => Ok(()),
+        }
+    }
+
+    /* Copy of the grid turned clockwise by the given number of quarter turns. Cells, their slots
+     * and marbles, including moving ones, are moved along, so a running cascade continues the same
+     * way on the copy.
+     */
+    pub fn rotated(&self, quarter_turns: u8, cellsize: Point) -> Grid {
+        let dim = if quarter_turns & 1 == 0 { self.dim } else { Point::new(self.dim.im, self.dim.re) };
+        self.transformed(dim, |p| rotate_point(p, self.dim, quarter_turns), cellsize)
+    }
+
+    /* Copy of the grid mirrored left to right (horizontal) or top to bottom, like rotated(). The
+     * owners and counts of the cells stay the same as on the original while playing on both, but
+     * marbles may rest in different slots, since slots are balanced in a fixed rotational order,
+     * see assign_slots().
+     */
+    pub fn mirrored(&self, horizontal: bool, cellsize: Point) -> Grid {
+        self.transformed(self.dim, |p| mirror_point(p, self.dim, horizontal), cellsize)
+    }
+
+    /* Copy of the grid on a board of the given dimension, with each cell moved to the coordinates
+     * given by `point`. This must be a rotation or mirroring, whose linear part also maps the
+     * directions of the slots and the offsets of moving marbles from their targets.
+     */
+    fn transformed<F: Fn(Point) -> Point>(&self, dim: Point, point: F, cellsize: Point) -> Grid {
+        let origin = point(Point::new(0, 0));
+        let (ex, ey) = (point(Point::new(1, 0)) - origin, point(Point::new(0, 1)) - origin);
+        let direction: Vec<Direction> = DIRECTIONS.iter()
+            .map(|&d| DIRECTIONS.iter().position(|&e| e == ex * d.re + ey * d.im).unwrap())
+            .collect();
+        // Offsets in pixels are mapped in units of cells, so this also works for cells that are
+        // not square
+        let offset = |v: Subpixel| {
+            let (x, y) = (v.re / cellsize.re as f32, v.im / cellsize.im as f32);
+            Subpixel::new(
+                (ex.re as f32 * x + ey.re as f32 * y) * cellsize.re as f32,
+                (ex.im as f32 * x + ey.im as f32 * y) * cellsize.im as f32,
+            )
+        };
+
+        let mut mask = vec![false; dim.re as usize * dim.im as usize];
+        for cell in self.cells.iter().filter(|cell| cell.playable) {
+            let p = point(cell.coord);
+            mask[(p.im * dim.re + p.re) as usize] = true;
+        }
+        let mut grid = Grid::from_mask(dim, &mask);
+        let moved: Vec<usize> = self.cells.iter().map(|cell| grid.idx(point(cell.coord)).unwrap()).collect();
+        for (cell, &idx) in self.cells.iter().zip(moved.iter()) {
+            let target = &mut grid.cells[idx];
+            target.owner = cell.owner;
+            target.count = cell.count;
+            target.threshold = cell.threshold;
+            target.bonus = cell.bonus;
+            for slot in 0..3 {
+                for (d, &turned) in direction.iter().enumerate() {
+                    let from = slot_position(cell.coord, d, cellsize);
+                    let to = slot_position(target.coord, turned, cellsize);
+                    target.slots[slot][turned] = cell.slots[slot][d].map(|marble| Marble {
+                        pos: subpixel(to) + offset(marble.pos - subpixel(from)),
+                        start: subpixel(to) + offset(marble.start - subpixel(from)),
+                        from_dir: marble.from_dir.map(|d| direction[d]),
+                        ..marble
+                    });
+                }
+            }
+            // The Reserve slot between two directions moves to the one between the turned
+            // directions, which are in the other order after mirroring
+            for (d, &turned) in direction.iter().enumerate() {
+                let next = direction[(d + 1) % 4];
+                let corner = if next == (turned + 1) % 4 { turned } else { next };
+                let from = reserve_position(cell.coord, d, cellsize);
+                let to = reserve_position(target.coord, corner, cellsize);
+                target.slots[3][corner] = cell.slots[3][d].map(|marble| Marble {
+                    pos: subpixel(to) + offset(marble.pos - subpixel(from)),
+                    start: subpixel(to) + offset(marble.start - subpixel(from)),
+                    from_dir: marble.from_dir.map(|d| direction[d]),
+                    ..marble
+                });
+            }
+        }
+        grid.placed = self.placed;
+        grid.removed = self.removed;
+        grid.chain = self.chain;
+        grid.min_marbles = self.min_marbles;
+        grid.spread_bias = self.spread_bias;
+        grid.threshold_factor = self.threshold_factor;
+        grid.critical = self.critical.iter().map(|&idx| moved[idx]).collect();
+        grid.wave = self.wave.iter().map(|&idx| moved[idx]).collect();
+        grid.wave.sort_unstable();
+        grid.bonus_captures = self.bonus_captures.iter().map(|&(p, owner)| (point(p), owner)).collect();
+        grid.hash = grid.recompute_hash();
+        grid.owned = grid.recompute_owned();
+        grid.check_conservation();
+        grid
+    }
+
+    /* Whether no cell is spreading or about to spread */
+    fn settled(&self) -> bool {
+        self.critical.is_empty() && self.wave.is_empty()
+    }
+
+    /* Cells that differ between this and another settled position on a board of the same
+     * dimension and shape. Cells of the other board are given with their owner and count, so
+     * applying the result with apply_delta() turns this position into the other one.
+     */
+    pub fn diff(&self, other: &Grid) -> Result<GridDelta, String> {
+        if self.dim != other.dim {
+            return Err(format!(
+                "Boards of different dimension {}x{} and {}x{}",
+                self.dim.re, self.dim.im, other.dim.re, other.dim.im,
+            ));
+        }
+        let mut changes = Vec::new();
+        for (cell, theirs) in self.cells.iter().zip(other.cells.iter()) {
+            if cell.playable != theirs.playable {
+                return Err(format!("Boards of different shape at {}", Grid::cell_label(cell.coord)));
+            }
+            if cell.owner != theirs.owner || cell.count != theirs.count {
+                changes.push(CellChange { at: cell.coord, owner: theirs.owner, count: theirs.count });
+            }
+        }
+        Ok(GridDelta { from: self.hash, to: other.hash, changes })
+    }
+
+    /* Apply the output of diff() to the position it was made for. The delta is checked as a
+     * whole before changing anything, so the board is left untouched if it is refused. Changed
+     * cells hold new marbles resting in their slots.
+     */
+    pub fn apply_delta(&mut self, delta: &GridDelta, cellsize: Point) -> Result<(), String> {
+        if !self.settled() {
+            return Err("Board is not settled".to_string());
+        }
+        if self.hash != delta.from {
+            return Err("Delta was made for a different position".to_string());
+        }
+        let mut hash = self.hash;
+        let mut previous = None;
+        for change in delta.changes.iter() {
+            let idx = self.idx(change.at).ok_or(format!("Cell {} is outside of the board", change.at))?;
+            // Changes are in storage order like the output of diff(), so no cell is changed twice
+            if previous >= Some(idx) {
+                return Err(format!("Cell {} is out of order", change.at));
+            }
+            previous = Some(idx);
+            let cell = &self.cells[idx];
+            if !cell.playable {
+                return Err(format!("Cell {} does not belong to the board", change.at));
+            }
+            if change.count > 0 && change.count >= cell.threshold {
+                return Err(format!("Cell {} would be at capacity", change.at));
+            }
+            match (change.owner, change.count) {
+                (Some(owner), count) if count > 0 => hash ^= zobrist_key(idx, owner, count),
+                (None, 0) => (),
+                (Some(_), _) => return Err(format!("Cell {} has an owner but no marbles", change.at)),
+                (None, _) => return Err(format!("Cell {} holds marbles but has no owner", change.at)),
+            }
+            hash ^= cell.zobrist_key(idx);
+        }
+        if hash != delta.to {
+            return Err("Delta does not lead to the expected position".to_string());
+        }
+        for change in delta.changes.iter() {
+            let idx = self.idx(change.at).unwrap();
+            self.removed += self.modify(idx, |cell| cell.clear());
+            for _ in 0..change.count {
+                let id = self.placed as u32;
+                self.modify(idx, |cell| cell.add_marble(change.owner.unwrap(), id, cellsize)).unwrap();
+                self.placed += 1;
+            }
+        }
+        self.check_conservation();
+        debug_assert_eq!(self.hash, self.recompute_hash(), "Incremental hash is out of sync");
+        Ok(())
+    }
+
+    /* Whether there are no marbles on the board */
+    pub fn is_empty(&self) -> bool {
+        self.placed == self.removed
+    }
+
+    /* The only player that has cells, if there are at least as many marbles on the board as set
+     * by set_min_marbles(). Neutral cells are ignored. This is also valid in the middle of a
+     * cascade, since marbles in flight are already counted for the cell they are heading to.
+     */
+    pub fn single_owner(&self) -> Option<Owner> {
+        debug_assert_eq!(self.owned, self.recompute_owned(), "Cells per player are out of sync");
+        if self.placed - self.removed < self.min_marbles || self.owned.len() != 1 {
+            return None;
+        }
+        self.owned.keys().next().copied()
+    }
+
+    /* Set the number of marbles needed on the board before single_owner() reports an owner, so
+     * the first moves of a game do not count as won
+     */
+    pub fn set_min_marbles(&mut self, min_marbles: usize) {
+        self.min_marbles = min_marbles;
+    }
+
+    pub fn spread_bias(&self) -> SpreadBias { self.spread_bias }
+
+    /* Choose which way marbles turn when their slot is taken. This may be changed at any time,
+     * since it does not affect the owners and counts of the cells.
+     */
+    pub fn set_spread_bias(&mut self, bias: SpreadBias) {
+        self.spread_bias = bias;
+    }
+
+    pub fn threshold_factor(&self) -> f32 { self.threshold_factor }
+
+    /* Let cells spread once they hold their number of neighbors times the given factor, rounded
+     * up, instead of one marble per neighbor. The factor is capped at MAX_THRESHOLD_FACTOR and
+     * every cell with a neighbor spreads with at least one marble, see threshold(). Only empty
+     * boards can be changed, since cells with marbles could be at capacity afterwards.
+     */
+    pub fn set_threshold_factor(&mut self, factor: f32) -> Result<(), String> {
+        if !self.is_empty() {
+            return Err("The threshold factor can only be changed on an empty board".to_string());
+        }
+        self.threshold_factor = factor.clamp(0.0, MAX_THRESHOLD_FACTOR);
+        for cell in self.cells.iter_mut() {
+            cell.threshold = threshold(cell.neighbors, self.threshold_factor);
+        }
+        Ok(())
+    }
+
+    /* Number of cells of the given player, which is kept up to date while marbles move */
+    pub fn cells_of(&self, owner: Owner) -> usize {
+        self.owned.get(&owner).copied().unwrap_or(0)
+    }
+
+    /* Number of cells and of marbles owned by each of the given number of players */
+    pub fn count_per_owner(&self, num_players: usize) -> Vec<(usize, usize)> {
+        let mut counts = vec![(0, 0); num_players];
+        for cell in self.cells.iter() {
+            match cell.owner {
+                Some(NEUTRAL) | None => (),
+                Some(owner) => {
+                    counts[owner.0].0 += 1;
+                    counts[owner.0].1 += cell.count as usize;
+                },
+            }
+        }
+        counts
+    }
+
+    /* Whether marbles of two different players are in the same region of the board, which is a
+     * part of it where the cells are connected by their neighbors. Cells outside of the shape of
+     * the board separate the regions. If not, no cascade can ever reach the cells of another
+     * player, although each player can still place marbles in the empty cells of any region.
+     * Neutral marbles do not belong to a player.
+     */
+    pub fn contested(&self) -> bool {
+        let mut visited = vec![false; self.cells.len()];
+        for start in 0..self.cells.len() {
+            if visited[start] || !self.cells[start].playable {
+                continue;
+            }
+            visited[start] = true;
+            let mut stack = vec![start];
+            let mut found: Option<Owner> = None;
+            while let Some(idx) = stack.pop() {
+                let cell = &self.cells[idx];
+                if let Some(owner) = cell.player() {
+                    if found.is_some_and(|found| found != owner) {
+                        return true;
+                    }
+                    found = Some(owner);
+                }
+                for (&has_neighbor, &direction) in cell.has_neighbor.iter().zip(DIRECTIONS.iter()) {
+                    if !has_neighbor {
+                        continue;
+                    }
+                    let neighbor = self.idx(cell.coord + direction).unwrap();
+                    if !visited[neighbor] {
+                        visited[neighbor] = true;
+                        stack.push(neighbor);
+                    }
+                }
+            }
+        }
+        false
+    }
+
+    /* Number of cells that belong to the shape of the board */
+    pub fn playable_count(&self) -> usize {
+        self.cells.iter().filter(|cell| cell.playable).count()
+    }
+
+    /* Largest number of marbles that can be at rest on the board, one less than the threshold of
+     * each cell. With more marbles than this, some cell always spreads, so a cascade never ends.
+     */
+    pub fn capacity(&self) -> usize {
+        self.cells.iter().filter(|cell| cell.playable).map(|cell| cell.threshold.saturating_sub(1) as usize).sum()
+    }
+
+    /* Whether the given owner may add a marble anywhere */
+    pub fn has_move(&self, owner: Owner) -> bool {
+        self.cells.iter().any(|cell| cell.accepts(owner))
+    }
+
     // Check which players are no longer alive
     pub fn check_players(&self, players: &mut Vec<Player>) {
         for player in players.iter_mut() {
@@ -378,11 +2085,264 @@ impl Grid {
                 player.alive = false;
             }
         }
-        for cell in self.cells.iter() {
+        for (_, cell) in self.cells() {
             if let Some(owner) = cell.owner {
-                players[owner].started = true;
-                players[owner].alive = true;
+                if owner == NEUTRAL {
+                    continue
+                }
+                players[owner.0].started = true;
+                players[owner.0].alive = true;
             }
         }
     }
 }
+
+/* Cells and other state of a grid before a simulated move changed them, see
+ * Grid::with_simulated_move()
+ */
+struct UndoLog {
+    // Cells as they were before they first changed, by index
+    cells: HashMap<usize, Cell>,
+    placed: usize,
+    removed: usize,
+    critical: Vec<usize>,
+    wave: Vec<usize>,
+    chain: u32,
+    hash: u64,
+    owned: HashMap<Owner, usize>,
+    bonus_captures: Vec<(Point, Owner)>,
+}
+impl UndoLog {
+    fn new(grid: &Grid) -> UndoLog {
+        UndoLog {
+            cells: HashMap::new(),
+            placed: grid.placed,
+            removed: grid.removed,
+            critical: grid.critical.clone(),
+            wave: grid.wave.clone(),
+            chain: grid.chain,
+            hash: grid.hash,
+            owned: grid.owned.clone(),
+            bonus_captures: grid.bonus_captures.clone(),
+        }
+    }
+
+    /* Remember the cell with the given index, unless it was already saved */
+    fn save(&mut self, grid: &Grid, idx: usize) {
+        self.cells.entry(idx).or_insert_with(|| grid.cells[idx].clone());
+    }
+
+    /* Remember a cell that is about to spread and all its neighbors */
+    fn save_around(&mut self, grid: &Grid, idx: usize) {
+        self.save(grid, idx);
+        let cell = &grid.cells[idx];
+        for (&has_neighbor, &direction) in cell.has_neighbor.iter().zip(DIRECTIONS.iter()) {
+            if has_neighbor {
+                let neighbor = grid.idx(cell.coord + direction).unwrap();
+                self.save(grid, neighbor);
+            }
+        }
+    }
+
+    fn restore(self, grid: &mut Grid) {
+        for (idx, cell) in self.cells {
+            grid.cells[idx] = cell;
+        }
+        grid.placed = self.placed;
+        grid.removed = self.removed;
+        grid.critical = self.critical;
+        grid.wave = self.wave;
+        grid.chain = self.chain;
+        grid.hash = self.hash;
+        grid.owned = self.owned;
+        grid.bonus_captures = self.bonus_captures;
+    }
+}
+
+/* Iterator over the waves of a cascade, see Grid::cascade() */
+pub struct Cascade<'a> {
+    grid: &'a mut Grid,
+    state: State,
+    cellsize: Point,
+    // Wave that was started by placing the marble
+    first: Option<Vec<GridEvent>>,
+    failed: bool,
+}
+impl Iterator for Cascade<'_> {
+    type Item = Result<Wave, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(events) = self.first.take() {
+            return Some(Ok(Wave::new(events)));
+        }
+        if self.failed {
+            return None;
+        }
+        if let State::AcceptingInput = self.state {
+            return None;
+        }
+        if self.grid.chain >= MAX_WAVES {
+            self.failed = true;
+            return Some(Err(format!("Cascade did not come to rest after {} waves", MAX_WAVES)));
+        }
+        let (state, events) = self.grid.step(self.state, 1.0, self.cellsize);
+        self.state = state;
+        match state {
+            State::AcceptingInput => None,
+            State::Animating(_) => Some(Ok(Wave::new(events))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{assign_slots, SpreadBias, DIRECTIONS, OPPOSITES};
+    #[cfg(feature = "parallel")]
+    use super::{Cell, Grid, Owner, Point, State, PARALLEL_CHUNK};
+
+    // Neighbors of a corner, an edge and an interior cell, in all orientations
+    fn neighborhoods() -> Vec<[bool; 4]> {
+        let mut result = Vec::new();
+        for base in [[true, true, false, false], [true, true, true, false], [true; 4]] {
+            for rotation in 0..4 {
+                result.push([0, 1, 2, 3].map(|direction| base[(direction + rotation) % 4]));
+            }
+        }
+        result
+    }
+
+    // All ways to occupy the slots that have a neighbor, numbering the marbles from first
+    fn occupations(has_neighbor: &[bool; 4], first: u32) -> Vec<[Option<u32>; 4]> {
+        (0..16u32)
+            .filter(|mask| (0..4).all(|direction| mask & 1 << direction == 0 || has_neighbor[direction]))
+            .map(|mask| [0, 1, 2, 3].map(|direction| {
+                if mask & 1 << direction != 0 { Some(first + direction as u32) } else { None }
+            }))
+            .collect()
+    }
+
+    #[test]
+    fn opposite_directions_point_the_other_way() {
+        for direction in 0..DIRECTIONS.len() {
+            assert_eq!(DIRECTIONS[OPPOSITES[direction]], -DIRECTIONS[direction]);
+            assert_eq!(OPPOSITES[OPPOSITES[direction]], direction);
+        }
+    }
+
+    #[test]
+    fn assign_slots_is_balanced() {
+        let cases = neighborhoods().into_iter().flat_map(|n| SpreadBias::ALL.map(|bias| (n, bias)));
+        for (has_neighbor, bias) in cases {
+            for from in occupations(&has_neighbor, 0) {
+                for to in occupations(&has_neighbor, 10) {
+                    let (mut new_from, mut new_to) = (from, to);
+                    assign_slots(&mut new_from, &mut new_to, &has_neighbor, bias);
+                    let context = format!("{:?} {:?} {:?} -> {:?} {:?}", bias, from, to, new_from, new_to);
+
+                    // Nothing is lost or duplicated
+                    let mut before: Vec<u32> = from.iter().chain(to.iter()).flatten().copied().collect();
+                    let mut after: Vec<u32> = new_from.iter().chain(new_to.iter()).flatten().copied().collect();
+                    before.sort();
+                    after.sort();
+                    assert_eq!(before, after, "{}", context);
+
+                    let free = (0..4).filter(|&d| has_neighbor[d] && to[d].is_none()).count();
+                    let moving = from.iter().flatten().count();
+                    assert_eq!(new_from.iter().flatten().count(), moving.saturating_sub(free), "{}", context);
+                    for direction in 0..4 {
+                        // Occupied slots are kept and marbles only go where there is a neighbor
+                        if to[direction].is_some() {
+                            assert_eq!(new_to[direction], to[direction], "{}", context);
+                        }
+                        if !has_neighbor[direction] {
+                            assert_eq!(new_to[direction], None, "{}", context);
+                        }
+                        // A marble keeps its direction if it can
+                        if let Some(marble) = from[direction] {
+                            if to[direction].is_none() {
+                                assert_eq!(new_to[direction], Some(marble), "{}", context);
+                            }
+                        }
+                    }
+                    // A marble that has to move goes to a neighboring direction if one is free
+                    for direction in 0..4 {
+                        let marble = match from[direction] {
+                            Some(marble) if to[direction].is_some() => marble,
+                            _ => continue,
+                        };
+                        let side_free = [1, 3].iter().any(|offset| {
+                            let side = (direction + offset) % 4;
+                            has_neighbor[side] && to[side].is_none() && from[side].is_none()
+                        });
+                        if side_free {
+                            assert_ne!(new_to[OPPOSITES[direction]], Some(marble), "{}", context);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /* Cells of both grids hold the same marbles in the same slots, and the counters agree */
+    #[cfg(feature = "parallel")]
+    fn assert_same(a: &Grid, b: &Grid) {
+        assert_eq!(a.hash, b.hash);
+        assert_eq!(a.owned, b.owned);
+        for (a, b) in a.cells.iter().zip(b.cells.iter()) {
+            assert_eq!((a.owner, a.count), (b.owner, b.count), "{}", a.coord);
+            let marbles = |cell: &Cell| cell.marbles_by_slot()
+                .map(|(kind, direction, marble)| (kind, direction, marble.id, marble.owner, marble.from_dir))
+                .collect::<Vec<_>>();
+            assert_eq!(marbles(a), marbles(b), "{}", a.coord);
+        }
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn parallel_passes_match_serial() {
+        // Every cell is one marble short of spreading, in a checkerboard of two players, so the
+        // whole board takes part in the cascade and the waves grow large
+        let cellsize = Point::new(10, 10);
+        let mut grid = Grid::new(Point::new(48, 48));
+        for p in grid.points().collect::<Vec<_>>() {
+            let owner = Owner::new(((p.re + p.im) % 2) as usize, 2).unwrap();
+            for _ in 1..grid.cell(p).threshold() {
+                grid.insert_marble(p, owner, cellsize).unwrap();
+            }
+        }
+        let (mut state, _) = grid.add_marble(Point::new(24, 24), Owner::new(0, 2).unwrap(), cellsize).unwrap();
+        let mut largest = 0;
+        // The board holds one marble more than it can at rest, so the cascade only ends with the
+        // game once one player took all cells
+        while matches!(state, State::Animating(_)) && grid.owned.len() > 1 {
+            let (mut serial, mut parallel) = (grid.clone(), grid.clone());
+            serial.propagate_owners_serial();
+            parallel.propagate_owners_parallel();
+            assert_same(&serial, &parallel);
+
+            // Sending is the same in both, the marbles are received in parallel or not
+            let mut critical = grid.critical.clone();
+            critical.sort_unstable_by(|a, b| b.cmp(a));
+            critical.dedup();
+            serial.wave.clear();
+            parallel.wave.clear();
+            let arrivals = serial.send_all(&critical, &mut Vec::new());
+            largest = largest.max(arrivals.len());
+            serial.receive_all_serial(arrivals);
+            let arrivals = parallel.send_all(&critical, &mut Vec::new());
+            parallel.receive_all_parallel(arrivals);
+            for grid in [&mut serial, &mut parallel] {
+                grid.wave.sort_unstable();
+                grid.wave.dedup();
+            }
+            assert_same(&serial, &parallel);
+            assert_eq!(serial.sort_received_all_serial(), parallel.sort_received_all_parallel());
+            assert_same(&serial, &parallel);
+
+            state = grid.step(state, 1.0, cellsize).0;
+        }
+        assert!(largest >= PARALLEL_CHUNK, "The largest wave sent only {} marbles", largest);
+        assert_eq!(grid.single_owner(), Some(Owner::new(0, 2).unwrap()));
+        assert_eq!(grid.owned.len(), 1);
+    }
+}
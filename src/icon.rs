@@ -0,0 +1,38 @@
+/* Icon of the windows. It is built into the binary as a BMP, which SDL can load without further
+ * libraries, with an alpha channel for the rounded corners.
+ */
+
+use std::cell::OnceCell;
+
+use sdl2::rwops::RWops;
+use sdl2::surface::Surface;
+use sdl2::video::Window;
+
+pub const EMBEDDED: &[u8] = include_bytes!("../assets/icon.bmp");
+
+thread_local! {
+    // Loaded when the first window is opened and set on each window after it, or none if it could
+    // not be loaded, which is only reported once
+    static ICON: OnceCell<Option<Surface<'static>>> = const { OnceCell::new() };
+}
+
+pub fn load() -> Result<Surface<'static>, String> {
+    let mut rwops = RWops::from_bytes(EMBEDDED)?;
+    Surface::load_bmp_rw(&mut rwops)
+}
+
+/* Show the icon for the window. Platforms without window icons, like Wayland, ignore it. */
+pub fn apply(window: &mut Window) {
+    ICON.with(|icon| {
+        let icon = icon.get_or_init(|| match load() {
+            Ok(icon) => Some(icon),
+            Err(e) => {
+                eprintln!("Can not load the icon: {}", e);
+                None
+            },
+        });
+        if let Some(icon) = icon {
+            window.set_icon(icon);
+        }
+    });
+}
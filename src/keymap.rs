@@ -0,0 +1,283 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use sdl2::keyboard::Keycode;
+
+/* Everything that can be triggered by a single key while playing */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Action {
+    MoveUp,
+    MoveDown,
+    MoveLeft,
+    MoveRight,
+    Place,
+    FastForward,
+    SlowMotion,
+    Resign,
+    EndSeparated,
+    Edit,
+    Quit,
+    Rematch,
+    Menu,
+    ToggleCoords,
+    ToggleOwners,
+    ToggleParticles,
+    ToggleShake,
+    ToggleBreathing,
+    ToggleShadows,
+    RotateView,
+    CycleHighlight,
+    ToggleThreats,
+    ToggleSlotTint,
+    Screenshot,
+    Help,
+}
+impl Action {
+    pub const ALL: [Action; 25] = [
+        Action::MoveUp,
+        Action::MoveDown,
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::Place,
+        Action::FastForward,
+        Action::SlowMotion,
+        Action::Resign,
+        Action::EndSeparated,
+        Action::Edit,
+        Action::Quit,
+        Action::Rematch,
+        Action::Menu,
+        Action::ToggleCoords,
+        Action::ToggleOwners,
+        Action::ToggleParticles,
+        Action::ToggleShake,
+        Action::ToggleBreathing,
+        Action::ToggleShadows,
+        Action::RotateView,
+        Action::CycleHighlight,
+        Action::ToggleThreats,
+        Action::ToggleSlotTint,
+        Action::Screenshot,
+        Action::Help,
+    ];
+
+    // Name used in the key binding file
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::MoveUp => "move_up",
+            Action::MoveDown => "move_down",
+            Action::MoveLeft => "move_left",
+            Action::MoveRight => "move_right",
+            Action::Place => "place",
+            Action::FastForward => "fast_forward",
+            Action::SlowMotion => "slow_motion",
+            Action::Resign => "resign",
+            Action::EndSeparated => "end_separated",
+            Action::Edit => "edit",
+            Action::Quit => "quit",
+            Action::Rematch => "rematch",
+            Action::Menu => "menu",
+            Action::ToggleCoords => "toggle_coords",
+            Action::ToggleOwners => "toggle_owners",
+            Action::ToggleParticles => "toggle_particles",
+            Action::ToggleShake => "toggle_shake",
+            Action::ToggleBreathing => "toggle_breathing",
+            Action::ToggleShadows => "toggle_shadows",
+            Action::RotateView => "rotate_view",
+            Action::CycleHighlight => "cycle_highlight",
+            Action::ToggleThreats => "toggle_threats",
+            Action::ToggleSlotTint => "toggle_slot_tint",
+            Action::Screenshot => "screenshot",
+            Action::Help => "help",
+        }
+    }
+
+    // Description shown in the help overlay
+    pub fn description(&self) -> &'static str {
+        match self {
+            Action::MoveUp => "Move selection up",
+            Action::MoveDown => "Move selection down",
+            Action::MoveLeft => "Move selection left",
+            Action::MoveRight => "Move selection right",
+            Action::Place => "Place marble on selection",
+            Action::FastForward => "Skip to the end of the cascade",
+            Action::SlowMotion => "Slow motion",
+            Action::Resign => "Resign",
+            Action::EndSeparated => "End a game where nobody can reach the others",
+            Action::Edit => "Set up a position before the first move",
+            Action::Quit => "Quit",
+            Action::Rematch => "Rematch after the game",
+            Action::Menu => "Back to the menu after the game",
+            Action::ToggleCoords => "Show coordinates",
+            Action::ToggleOwners => "Show cell owners",
+            Action::ToggleParticles => "Show explosions",
+            Action::ToggleShake => "Shake the board in big cascades",
+            Action::ToggleBreathing => "Animate resting marbles",
+            Action::ToggleShadows => "Show shadows below the marbles",
+            Action::RotateView => "Rotate the board",
+            Action::CycleHighlight => "Highlight critical cells",
+            Action::ToggleThreats => "Hint how good each move looks",
+            Action::ToggleSlotTint => "Tint marbles by their slot, for debugging",
+            Action::Screenshot => "Save a screenshot",
+            Action::Help => "Show this help",
+        }
+    }
+
+    fn default_key(&self) -> Keycode {
+        match self {
+            Action::MoveUp => Keycode::Up,
+            Action::MoveDown => Keycode::Down,
+            Action::MoveLeft => Keycode::Left,
+            Action::MoveRight => Keycode::Right,
+            Action::Place => Keycode::Return,
+            Action::FastForward => Keycode::F,
+            Action::SlowMotion => Keycode::S,
+            Action::Resign => Keycode::Q,
+            Action::EndSeparated => Keycode::Y,
+            Action::Edit => Keycode::E,
+            Action::Quit => Keycode::Escape,
+            Action::Rematch => Keycode::R,
+            Action::Menu => Keycode::M,
+            Action::ToggleCoords => Keycode::C,
+            Action::ToggleOwners => Keycode::O,
+            Action::ToggleParticles => Keycode::X,
+            Action::ToggleShake => Keycode::K,
+            Action::ToggleBreathing => Keycode::B,
+            Action::ToggleShadows => Keycode::D,
+            Action::RotateView => Keycode::V,
+            Action::CycleHighlight => Keycode::H,
+            Action::ToggleThreats => Keycode::T,
+            Action::ToggleSlotTint => Keycode::F11,
+            Action::Screenshot => Keycode::F12,
+            Action::Help => Keycode::F1,
+        }
+    }
+}
+
+/* Location of a file of the game: the given environment variable if it is set, otherwise the file
+ * with the given name in the configuration directory
+ */
+pub fn config_file(variable: &str, name: &str) -> Option<PathBuf> {
+    if let Some(path) = env::var_os(variable) {
+        return Some(PathBuf::from(path));
+    }
+    let config = env::var_os("XDG_CONFIG_HOME").map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(config.join("chainreaction").join(name))
+}
+
+/* Read and parse a file of the game, see config_file(). None if there is no such file. */
+pub fn load_config<T, F>(variable: &str, name: &str, parse: F) -> Result<Option<T>, String>
+where F: FnOnce(&str) -> Result<T, String>
+{
+    match config_file(variable, name) {
+        Some(path) if path.exists() => {
+            let text = fs::read_to_string(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+            parse(&text).map(Some).map_err(|e| format!("{}: {}", path.display(), e))
+        },
+        _ => Ok(None),
+    }
+}
+
+/* Rename a file of the game that can not be read to the same name with .broken appended, so it is
+ * only reported once and whatever is in it is not overwritten. Returns the error together with
+ * where the file went.
+ */
+pub fn move_aside(variable: &str, name: &str, error: String) -> String {
+    let path = match config_file(variable, name) {
+        Some(path) => path,
+        None => return error,
+    };
+    let mut broken = path.clone().into_os_string();
+    broken.push(".broken");
+    match fs::rename(&path, &broken) {
+        Ok(()) => format!("{}, moved it to {}", error, Path::new(&broken).display()),
+        Err(rename) => format!("{}, and can not move it away: {}", error, rename),
+    }
+}
+
+/* Write a file of the game, see config_file() */
+pub fn save_config(variable: &str, name: &str, text: &str) -> Result<(), String> {
+    let path = config_file(variable, name).ok_or("No configuration directory")?;
+    create_config_dir(&path)?;
+    fs::write(&path, text).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/* Create the directory of a file of the game, if it does not exist yet */
+pub fn create_config_dir(path: &Path) -> Result<(), String> {
+    match path.parent() {
+        Some(dir) => fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir.display(), e)),
+        None => Ok(()),
+    }
+}
+
+/* Maps each action to the key that triggers it */
+#[derive(Clone)]
+pub struct KeyMap {
+    keys: Vec<(Action, Keycode)>,
+}
+impl KeyMap {
+    pub fn new() -> KeyMap {
+        KeyMap {
+            keys: Action::ALL.iter().map(|action| (*action, action.default_key())).collect(),
+        }
+    }
+
+    pub fn action(&self, keycode: Keycode) -> Option<Action> {
+        self.keys.iter().find(|(_, key)| *key == keycode).map(|(action, _)| *action)
+    }
+
+    pub fn key(&self, action: Action) -> Keycode {
+        self.keys.iter().find(|(a, _)| *a == action).unwrap().1
+    }
+
+    // Lines of the help overlay, the key and description of each action
+    pub fn help_lines(&self) -> Vec<String> {
+        Action::ALL.iter()
+            .map(|action| format!("{:<12} {}", self.key(*action).name(), action.description()))
+            .collect()
+    }
+
+    /* Parse key bindings, one per line in the form "place = Space", using SDL's key names. Empty
+     * lines and lines starting with # are ignored. Actions that are not mentioned keep their
+     * default key.
+     */
+    pub fn parse(text: &str) -> Result<KeyMap, String> {
+        let mut keymap = KeyMap::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (name, key) = line.split_once('=')
+                .ok_or(format!("Line {}: expected action = key", number+1))?;
+            let (name, key) = (name.trim(), key.trim());
+            let action = *Action::ALL.iter().find(|action| action.name() == name)
+                .ok_or(format!("Line {}: unknown action {}", number+1, name))?;
+            let keycode = Keycode::from_name(key)
+                .ok_or(format!("Line {}: unknown key {}", number+1, key))?;
+            keymap.keys.iter_mut().find(|(a, _)| *a == action).unwrap().1 = keycode;
+        }
+        for (idx, (action, key)) in keymap.keys.iter().enumerate() {
+            if let Some((other, _)) = keymap.keys[idx+1..].iter().find(|(_, other)| other == key) {
+                return Err(format!("Key {} is bound to both {} and {}", key.name(), action.name(), other.name()));
+            }
+        }
+        Ok(keymap)
+    }
+
+    /* Load the key bindings from the key binding file, $CHAINREACTION_KEYS or keys.conf in the
+     * configuration directory, using the defaults if there is none
+     */
+    pub fn load() -> Result<KeyMap, String> {
+        Ok(load_config("CHAINREACTION_KEYS", "keys.conf", KeyMap::parse)?.unwrap_or_default())
+    }
+}
+
+/* The default key of every action, see Action::default_key() */
+impl Default for KeyMap {
+    fn default() -> KeyMap {
+        KeyMap::new()
+    }
+}
@@ -0,0 +1,19 @@
+pub mod game;
+pub mod grid;
+pub mod points;
+pub mod shape;
+pub mod render;
+pub mod menu;
+pub mod keymap;
+pub mod bench;
+pub mod demo;
+pub mod font;
+pub mod particles;
+pub mod tally;
+pub mod screenshot;
+pub mod ai;
+pub mod placement;
+pub mod theme;
+pub mod icon;
+pub mod recovery;
+pub mod rng;
@@ -1,24 +1,222 @@
-mod game;
-mod grid;
-mod render;
-mod menu;
+use sdl2::messagebox::{show_message_box, ButtonData, ClickedButton, MessageBoxButtonFlag, MessageBoxFlag};
 
-use crate::game::Game;
-use crate::render::run_game;
-use crate::menu::show_menu;
+use chain::bench;
+use chain::demo;
+use chain::game::Game;
+use chain::grid::Point;
+use chain::render::{fit_cellsize, run_game};
+use chain::menu::{show_menu, MenuOptions, WINDOW_TITLE};
+use chain::keymap::KeyMap;
+use chain::tally::Tally;
+use chain::placement::Placements;
+use chain::recovery::{self, Recovery};
+use chain::rng::{self, Rng};
+
+// Options given on the command line
+struct Args {
+    // Fixed cell size instead of fitting the board to the display
+    cellsize: Option<Point>,
+    // Start in the board editor
+    edit: bool,
+    // Demo game shown while the menu is idle, if any
+    demo: Option<demo::Settings>,
+    // Display to open the windows on instead of where they were last time
+    display: Option<usize>,
+    // Title of the windows
+    title: String,
+    // Seed of what is left to chance, which differs from launch to launch unless given
+    seed: u64,
+}
+
+/* Parse a cell size given either as a single number for square cells or as WIDTHxHEIGHT */
+fn parse_cellsize(value: &str) -> Result<Point, String> {
+    let invalid = || format!("Invalid cell size {}", value);
+    let (w, h) = value.split_once('x').unwrap_or((value, value));
+    let w = w.parse().map_err(|_| invalid())?;
+    let h = h.parse().map_err(|_| invalid())?;
+    Ok(Point::new(w, h))
+}
+
+/* Run simulated games as given by the arguments after "bench" and print the statistics */
+fn run_bench(mut args: impl Iterator<Item=String>) -> Result<(), String> {
+    let (mut games, mut seed, mut players, mut size) = (1000, 0, 2, Point::new(8, 6));
+    while let Some(arg) = args.next() {
+        let value = args.next().ok_or(format!("{} needs a value", arg))?;
+        let invalid = || format!("Invalid value {} for {}", value, arg);
+        match arg.as_str() {
+            "--games" => games = value.parse().map_err(|_| invalid())?,
+            "--seed" => seed = value.parse().map_err(|_| invalid())?,
+            "--players" => players = value.parse().map_err(|_| invalid())?,
+            // Same format as the cell size
+            "--size" => size = parse_cellsize(&value).map_err(|_| invalid())?,
+            _ => return Err(format!("Unknown argument {}", arg)),
+        }
+    }
+    let config = bench::config(players, size)?;
+    println!("{}", bench::run(&config, games, seed)?);
+    Ok(())
+}
+
+/* Ask whether to continue the unfinished game of the last launch, if there is one. A game that is
+ * not continued is discarded, so it is only offered once.
+ */
+fn offer_recovery(title: &str) -> Result<Option<Recovery>, String> {
+    let recovery = match Recovery::load().map_err(|e| format!("Can not load the last game: {}", e))? {
+        Some(recovery) => recovery,
+        None => return Ok(None),
+    };
+    let buttons = [
+        ButtonData { flags: MessageBoxButtonFlag::RETURNKEY_DEFAULT, button_id: 1, text: "Resume" },
+        ButtonData { flags: MessageBoxButtonFlag::ESCAPEKEY_DEFAULT, button_id: 0, text: "New game" },
+    ];
+    let message = format!("The last game was not finished after {} moves. Resume it?", recovery.moves.len());
+    match show_message_box(MessageBoxFlag::INFORMATION, &buttons, title, &message, None, None) {
+        Ok(ClickedButton::CustomButton(button)) if button.button_id == 1 => Ok(Some(recovery)),
+        Ok(_) => {
+            recovery::remove().map_err(|e| format!("Can not remove the last game: {}", e))?;
+            Ok(None)
+        },
+        Err(e) => Err(format!("Can not offer to resume the last game: {}", e)),
+    }
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut args = std::env::args().skip(1);
+    let mut result = Args {
+        cellsize: None, edit: false, demo: Some(demo::Settings::default()), display: None,
+        title: WINDOW_TITLE.to_string(), seed: rng::time_seed(),
+    };
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--cellsize" => {
+                let value = args.next().ok_or("--cellsize needs a value")?;
+                result.cellsize = Some(parse_cellsize(&value)?);
+            },
+            "--edit" => result.edit = true,
+            "--display" => {
+                let value = args.next().ok_or("--display needs a value")?;
+                result.display = Some(value.parse().map_err(|_| format!("Invalid display {}", value))?);
+            },
+            "--title" => result.title = args.next().ok_or("--title needs a value")?,
+            "--seed" => {
+                let value = args.next().ok_or("--seed needs a value")?;
+                result.seed = value.parse().map_err(|_| format!("Invalid seed {}", value))?;
+            },
+            "--no-demo" => result.demo = None,
+            "--demo-size" | "--demo-speed" => {
+                let value = args.next().ok_or(format!("{} needs a value", arg))?;
+                let invalid = || format!("Invalid value {} for {}", value, arg);
+                if let Some(settings) = result.demo.as_mut() {
+                    if arg == "--demo-size" {
+                        settings.size = parse_cellsize(&value).map_err(|_| invalid())?;
+                    } else {
+                        settings.speed = value.parse().map_err(|_| invalid())?;
+                    }
+                }
+            },
+            _ => return Err(format!("Unknown argument {}", arg)),
+        }
+    }
+    if let Some(settings) = result.demo.as_ref() {
+        settings.validate()?;
+    }
+    Ok(result)
+}
 
 pub fn main() -> Result<(), String> {
+    let mut args = std::env::args().skip(1);
+    if args.next().as_deref() == Some("bench") {
+        return run_bench(args);
+    }
+    let args = parse_args()?;
+    let keymap = KeyMap::load().map_err(|e| format!("Can not load key bindings: {}", e))?;
+    // What went wrong while there was no window, shown in the menu
+    let mut notices = Vec::new();
+    let mut tally = match Tally::load() {
+        Ok(tally) => tally,
+        Err(e) => {
+            eprintln!("Can not load the tally, starting without any wins: {}", e);
+            notices.push(format!("Can not load the tally: {}", e));
+            Tally::new()
+        },
+    };
+    let mut windows = match Placements::load() {
+        Ok(windows) => windows,
+        Err(e) => {
+            eprintln!("Can not load the window positions: {}", e);
+            Placements::new()
+        },
+    };
     let sdl_context = sdl2::init()?;
     let video_subsystem = sdl_context.video()?;
     let mut event_pump = sdl_context.event_pump()?;
- 
-    let config = show_menu(&video_subsystem, &mut event_pump)?;
-    if config.players.len() == 0 {
-        return Ok(());
+    if let Some(display) = args.display {
+        let count = video_subsystem.num_video_displays()?;
+        if display >= count as usize {
+            return Err(format!("There is no display {}, only {}", display, count));
+        }
     }
+ 
+    let cellsize = |size: Point| match args.cellsize {
+        Some(cellsize) => Ok(cellsize),
+        None => fit_cellsize(&video_subsystem, size, args.display.unwrap_or(0)),
+    };
+    // An unfinished game of the last launch is offered once, before the menu
+    let mut recovery = offer_recovery(&args.title).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        notices.push(e);
+        None
+    });
+    // Each visit of the menu gets its own seed, so the demo and the colors differ from game to game
+    let mut seeds = Rng::new(args.seed);
 
-    let mut game = Game::new(config);
-    run_game(&video_subsystem, &mut event_pump, &mut game)?;
-
-    Ok(())
+    // After a game, the players may go back to the menu and start another one
+    loop {
+        if let Some(recovery) = recovery.take() {
+            match recovery.resume(cellsize(recovery.config.size)?) {
+                Ok(mut game) => {
+                    if !run_game(&video_subsystem, &mut event_pump, &mut game, &keymap, &mut tally, &mut windows, args.display)? {
+                        return Ok(());
+                    }
+                },
+                Err(e) => {
+                    eprintln!("Can not resume the last game: {}", e);
+                    notices.push(format!("Can not resume the last game: {}", e));
+                    if let Err(e) = recovery::remove() {
+                        eprintln!("Can not remove the last game: {}", e);
+                        notices.push(format!("Can not remove the last game: {}", e));
+                    }
+                },
+            }
+            continue;
+        }
+        let options = MenuOptions {
+            demo: args.demo.as_ref(),
+            display: args.display,
+            title: &args.title,
+            seed: seeds.next_u64(),
+            notices: &notices,
+        };
+        let mut config = match show_menu(&video_subsystem, &mut event_pump, &tally, &mut windows, options)? {
+            Some(config) => config,
+            None => return Ok(()),
+        };
+        notices.clear();
+        config.cellsize = cellsize(config.size)?;
+        // The menu only returns valid settings, but whatever is still refused goes back to it
+        let mut game = match Game::new(config) {
+            Ok(game) => game,
+            Err(e) => {
+                eprintln!("Can not start the game: {}", e);
+                notices.push(format!("Can not start the game: {}", e));
+                continue;
+            },
+        };
+        if args.edit {
+            game.start_editing();
+        }
+        if !run_game(&video_subsystem, &mut event_pump, &mut game, &keymap, &mut tally, &mut windows, args.display)? {
+            return Ok(());
+        }
+    }
 }
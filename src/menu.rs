@@ -1,17 +1,33 @@
 use std::vec::Vec;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use sdl2::EventPump;
 use sdl2::VideoSubsystem;
-use sdl2::event::{Event,WindowEvent};
-use sdl2::keyboard::Keycode;
+use sdl2::event::Event;
+use sdl2::keyboard::{Keycode, Mod};
+use sdl2::mouse::MouseButton;
 use sdl2::pixels::Color;
 use sdl2::rect::Rect;
+use sdl2::render::{Texture, TextureCreator};
+use sdl2::video::WindowContext;
 use sdl2::gfx::primitives::DrawRenderer;
+use sdl2::ttf;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
-use crate::grid::Point;
-use crate::game::Player;
-use crate::render::{create_texture, gradient};
+use crate::grid::{Grid, Owner, Point, SpreadBias, DEFAULT_THRESHOLD_FACTOR, DIRECTIONS, MAX_PLAYERS, MAX_THRESHOLD_FACTOR, threshold};
+use crate::points::Points;
+use crate::shape::Shape;
+use crate::game::{Player, PlayerKind, WinCondition};
+use crate::demo::{self, Demo};
+use crate::keymap::KeyMap;
+use crate::render::{create_texture, gradient, pattern, Fonts, Renderer, ScreenMap, ViewSettings, SIDEBAR_WIDTH};
+use crate::font;
+use crate::tally::Tally;
+use crate::placement::{self, Placements};
+use crate::theme::Theme;
+use crate::icon;
+use crate::rng::Rng;
 
 fn color(x: u8, y: u8) -> Color {
     // Map a 256x256 square onto a color, separating into six segments with the primary and
@@ -43,15 +59,299 @@ fn color(x: u8, y: u8) -> Color {
     }
 }
 
+// Saturation and value of the colors chosen by spread_colors(). They are bright enough that the
+// gradient of the swatches and marbles does not fade them into black.
+const SPREAD_SATURATION: f32 = 0.85;
+const SPREAD_VALUE: f32 = 0.95;
+
+/* Color of the given hue in degrees, saturation and value, both from 0 to 1 */
+fn hsv(hue: f32, saturation: f32, value: f32) -> Color {
+    let hue = hue.rem_euclid(360.0) / 60.0;
+    let chroma = value * saturation;
+    let x = chroma * (1.0 - (hue % 2.0 - 1.0).abs());
+    let (r, g, b) = match hue as u32 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    let channel = |c: f32| ((c + value - chroma) * 255.0).round() as u8;
+    Color::RGB(channel(r), channel(g), channel(b))
+}
+
+/* Colors for the given number of players with hues evenly spaced around the color wheel, starting
+ * at the given hue in degrees, so they are as far apart as possible
+ */
+pub fn spread_colors(count: usize, offset: f32) -> Vec<Color> {
+    (0..count).map(|idx| hsv(offset + 360.0 * idx as f32 / count as f32, SPREAD_SATURATION, SPREAD_VALUE)).collect()
+}
+
+/* Parse a color given as six hex digits like "#1E90FF", with or without the leading # */
+pub fn parse_hex_color(text: &str) -> Result<Color, String> {
+    let digits = text.strip_prefix('#').unwrap_or(text);
+    if digits.len() != 6 || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!("Expected a color like #1E90FF, got {}", text));
+    }
+    let value = u32::from_str_radix(digits, 16).map_err(|e| e.to_string())?;
+    Ok(Color::RGB((value >> 16) as u8, (value >> 8) as u8, value as u8))
+}
+
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Config {
     pub players: Vec<Player>,
     pub size: Point,
-    pub cellsize: i32,
+    pub shape: Shape,
+    // Cells that start with one neutral marble
+    pub neutral: Vec<Point>,
+    // Cells that grant an extra placement in the next turn when captured, see Game::collect_bonus()
+    pub bonus: Vec<Point>,
+    // Width and height of a cell in pixels
+    pub cellsize: Point,
+    // Advance animations by a fixed amount per frame instead of by the elapsed time
+    pub fixed_step: bool,
+    // Practice mode without elimination, see Game::next_player_if_accepting
+    pub sandbox: bool,
+    // Render at an integer multiple of the board size on HiDPI displays instead of stretching
+    pub integer_scale: bool,
+    pub win_condition: WinCondition,
+    // Accessibility mode with thick black lines on white, bold marbles and larger labels
+    pub high_contrast: bool,
+    // Mark the marbles of each player with a pattern, so they can be told apart without their
+    // colors, see render::pattern_mask()
+    pub patterns: bool,
+    // Mark the cell of the most recently placed marble until the next one is placed
+    pub show_last_move: bool,
+    // Which way marbles turn when their slot is taken, which only changes where they come to rest
+    pub spread_bias: SpreadBias,
+    // Resolve each move at once, without animating the marbles, see Game::resolve()
+    pub instant: bool,
+    // Remember a click while the marbles move and play it once they came to rest
+    pub buffer_input: bool,
+    // Cells spread with their number of neighbors times this, see Grid::set_threshold_factor()
+    pub threshold_factor: f32,
+    // Colors of the board and the sidebar, see Theme
+    pub theme: Theme,
+    // Width of the lines between the cells in pixels of the board at its original size, which
+    // hides them for 0
+    pub line_width: u8,
+    // Offer to end the game once the players can no longer reach each other, see
+    // Game::check_separation()
+    pub end_when_separated: bool,
+    // Title of the windows, for example with the name of the round in a tournament
+    pub window_title: String,
+    // Seed of what is left to chance in the game, like the sparks of the particles
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub seed: u64,
 }
 
-pub fn show_menu(video: &VideoSubsystem, event_pump: &mut EventPump) -> Result<Config, String> {
+// Title of the windows unless another one is given
+pub const WINDOW_TITLE: &str = "Chain reaction";
+
+// Fraction of the cells needed for a domination win when selected in the menu
+const DOMINATION: f32 = 0.6;
+
+// Smallest distance between the colors of two players, so they can be told apart
+const MIN_COLOR_DISTANCE: i32 = 40;
+
+/* The settings the menu starts with, without any players */
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            players: Vec::new(),
+            size: Point::new(8, 6),
+            shape: Shape::Rectangle,
+            neutral: Vec::new(),
+            bonus: Vec::new(),
+            cellsize: Point::new(100, 100),
+            fixed_step: false,
+            sandbox: false,
+            integer_scale: true,
+            win_condition: WinCondition::LastStanding,
+            high_contrast: false,
+            patterns: false,
+            show_last_move: true,
+            spread_bias: SpreadBias::Counterclockwise,
+            instant: false,
+            buffer_input: false,
+            threshold_factor: DEFAULT_THRESHOLD_FACTOR,
+            theme: Theme::CLASSIC,
+            line_width: 1,
+            end_when_separated: false,
+            window_title: WINDOW_TITLE.to_string(),
+            seed: 0,
+        }
+    }
+}
+
+impl Config {
+    /* Check that a game can be started with these settings */
+    pub fn validate(&self) -> Result<(), String> {
+        if self.size.re < 2 || self.size.im < 2 {
+            return Err(format!("The board must be at least 2x2, but is {}x{}", self.size.re, self.size.im));
+        }
+        Grid::check_dim(self.size)?;
+        if self.cellsize.re <= 0 || self.cellsize.im <= 0 {
+            return Err(format!("The cell size must be positive, but is {}", self.cellsize));
+        }
+        if self.players.len() < 2 {
+            return Err(format!("At least two players are needed, but there are {}", self.players.len()));
+        }
+        if self.players.len() > MAX_PLAYERS {
+            return Err(format!("At most {} players are possible, but there are {}", MAX_PLAYERS, self.players.len()));
+        }
+        for (i, a) in self.players.iter().enumerate() {
+            for (j, b) in self.players.iter().enumerate().skip(i+1) {
+                let (a, b) = (a.color(), b.color());
+                let distance = (a.r as i32 - b.r as i32).abs()
+                    + (a.g as i32 - b.g as i32).abs()
+                    + (a.b as i32 - b.b as i32).abs();
+                if distance < MIN_COLOR_DISTANCE {
+                    return Err(format!("Players {} and {} have colors that are too similar", i+1, j+1));
+                }
+            }
+        }
+        for (i, player) in self.players.iter().enumerate() {
+            if let PlayerKind::Mirror(of) = player.kind {
+                if of.idx() == i || of.idx() >= self.players.len() {
+                    return Err(format!("Player {} can not mirror player {}", i+1, of.idx()+1));
+                }
+            }
+        }
+        if !(self.threshold_factor > 0.0 && self.threshold_factor <= MAX_THRESHOLD_FACTOR) {
+            return Err(format!(
+                "The threshold factor must be above 0 and at most {}, but is {}", MAX_THRESHOLD_FACTOR, self.threshold_factor,
+            ));
+        }
+        if let WinCondition::Domination(threshold) = self.win_condition {
+            if !(threshold > 0.0 && threshold <= 1.0) {
+                return Err(format!("The domination threshold must be between 0 and 1, but is {}", threshold));
+            }
+        }
+        for (i, p) in self.neutral.iter().enumerate() {
+            if p.re < 0 || p.im < 0 || p.re >= self.size.re || p.im >= self.size.im {
+                return Err(format!("Neutral cell {} is outside of the board", p));
+            }
+            if !self.shape.contains(*p, self.size) {
+                return Err(format!("Neutral cell {} is not part of the shape of the board", p));
+            }
+            if self.neutral[..i].contains(p) {
+                return Err(format!("Neutral cell {} is given twice", p));
+            }
+            // Neutral marbles never spread, so the cell must hold more than one marble
+            let neighbors = DIRECTIONS.iter().filter(|&&d| {
+                let q = *p + d;
+                q.re >= 0 && q.im >= 0 && q.re < self.size.re && q.im < self.size.im && self.shape.contains(q, self.size)
+            }).count();
+            if threshold(neighbors as u8, self.threshold_factor) <= 1 {
+                return Err(format!("Neutral cell {} would spread with a single marble", p));
+            }
+        }
+        for p in self.bonus.iter() {
+            if p.re < 0 || p.im < 0 || p.re >= self.size.re || p.im >= self.size.im {
+                return Err(format!("Bonus cell {} is outside of the board", p));
+            }
+            if !self.shape.contains(*p, self.size) {
+                return Err(format!("Bonus cell {} is not part of the shape of the board", p));
+            }
+        }
+        Ok(())
+    }
+}
+
+// Position of the swatch of the given player, in the order in which the players take turns
+fn swatch_rect(idx: usize) -> Rect {
+    Rect::new(600 + idx as i32 * 70, 50, 61, 61)
+}
+
+// Index of the swatch at the given position, if any
+fn swatch_at(pos: (u32, u32), count: usize) -> Option<usize> {
+    (0..count).find(|&idx| swatch_rect(idx).contains_point((pos.0 as i32, pos.1 as i32)))
+}
+
+// Cell of the board preview at the given position, if any
+fn preview_cell(pos: (u32, u32), size: Point) -> Option<Point> {
+    let (x, y) = (pos.0 as i32 - 600, pos.1 as i32 - 220);
+    if x < 0 || y < 0 || x >= 50*size.re || y >= 50*size.im {
+        return None;
+    }
+    Some(Point::new(x/50, y/50))
+}
+
+// Position in the turn order that is selected by a number key
+pub fn turn_position(keycode: Keycode) -> Option<usize> {
+    match keycode {
+        Keycode::Num1 => Some(0),
+        Keycode::Num2 => Some(1),
+        Keycode::Num3 => Some(2),
+        Keycode::Num4 => Some(3),
+        Keycode::Num5 => Some(4),
+        Keycode::Num6 => Some(5),
+        Keycode::Num7 => Some(6),
+        Keycode::Num8 => Some(7),
+        Keycode::Num9 => Some(8),
+        _ => None,
+    }
+}
+
+// Add a player with the given color and the texture of its swatch
+fn add_player<'a>(
+    creator: &'a TextureCreator<WindowContext>,
+    players: &mut Vec<Player>,
+    marbles: &mut Vec<Texture<'a>>,
+    col: Color,
+) -> Result<(), String> {
+    players.push(Player::new(col));
+    marbles.push(
+        create_texture(creator, 61, 61, |canvas| {
+            gradient(&canvas, 30, 30, 30, col, Theme::CLASSIC.background)?;
+            Ok(())
+        })?
+    );
+    Ok(())
+}
+
+// Move a player to a different position in the turn order, keeping the marble textures aligned.
+fn move_player<T>(players: &mut Vec<Player>, marbles: &mut Vec<T>, from: usize, to: usize) {
+    let player = players.remove(from);
+    players.insert(to, player);
+    let marble = marbles.remove(from);
+    marbles.insert(to, marble);
+}
+
+/* How the menu is shown, mostly from the command line, see show_menu() */
+pub struct MenuOptions<'a> {
+    // Settings of the demo game that plays while there is no input, if any
+    pub demo: Option<&'a demo::Settings>,
+    // Display to open the menu on instead of where it was last time
+    pub display: Option<usize>,
+    pub title: &'a str,
+    // Decides the automatic colors, the demo games and the seed of the game that is started
+    pub seed: u64,
+    // What went wrong before the menu was shown, like a tally that could not be read
+    pub notices: &'a [String],
+}
+
+/* Show the menu until the game is started or the window is closed. While there is no input, a
+ * demo game with the given settings plays itself in the bottom right corner. The wins of the
+ * players so far are listed along the bottom. Like the game, the menu opens where it was last
+ * time unless a display is given. The game only starts with valid settings, otherwise the menu
+ * explains what is wrong. None if the menu was left without starting a game.
+ */
+pub fn show_menu(
+    video: &VideoSubsystem,
+    event_pump: &mut EventPump,
+    tally: &Tally,
+    windows: &mut Placements,
+    options: MenuOptions,
+) -> Result<Option<Config>, String> {
+    let MenuOptions { demo, display, title, seed, notices } = options;
+    let placement = placement::choose(windows.menu, &placement::displays(video)?, display, (800, 600));
     let mut canvas = video
-        .window("Chain reaction", 800, 600)
+        .window(title, placement.width, placement.height)
+        .position(placement.x, placement.y)
         .resizable()
         .allow_highdpi()
         .build()
@@ -61,6 +361,7 @@ pub fn show_menu(video: &VideoSubsystem, event_pump: &mut EventPump) -> Result<C
         .accelerated()
         .build()
         .map_err(|e| e.to_string())?;
+    icon::apply(canvas.window_mut());
 
     let creator = canvas.texture_creator();
     let texture_bg = create_texture(&creator, 256, 256, |canvas| {
@@ -72,32 +373,161 @@ pub fn show_menu(video: &VideoSubsystem, event_pump: &mut EventPump) -> Result<C
         Ok(())
     })?;
 
-    // In case of fractional scaling, this describes the "virtual" size in pixels, i.e. mouse
-    // events are relative to this.
-    let mut window_size = (0, 0);
-    let mut players = Vec::new();
+    let fontcontext = ttf::init().map_err(|e| e.to_string())?;
+    let font = font::load(&fontcontext, 18);
+    let keymap = KeyMap::new();
+    let mut last_input = Instant::now();
+    let mut last_frame = Instant::now();
+    let mut running_demo: Option<(Demo, Renderer)> = None;
+    // Chooses where the hues start when the colors are spread automatically, and the demo games
+    let mut rng = Rng::new(seed);
+
+    let mut players: Vec<Player> = Vec::new();
     let mut size = Point::new(8, 6);
+    let mut shape = Shape::Rectangle;
+    let mut sandbox = false;
+    let mut integer_scale = true;
+    let mut win_condition = WinCondition::LastStanding;
+    let mut high_contrast = false;
+    let mut patterns = false;
+    let mut show_last_move = true;
+    let mut instant = false;
+    let mut buffer_input = false;
+    let mut theme = Theme::CLASSIC;
+    let mut line_width = 1;
+    let mut end_when_separated = false;
+    let mut spread_bias = SpreadBias::Counterclockwise;
+    // Cells that start with a neutral marble, toggled by right clicks on the board preview
+    let mut neutral: Vec<Point> = Vec::new();
+    // The second player plays the mirror image of the moves of the first
+    let mut mirror = false;
     let mut marbles = Vec::new();
     let mut mousepos = (0u32, 0u32);
     let mut next_color: Option<Color> = None;
-    'running: loop {
+    // Digits typed after #, to give a color that is not part of the gradient
+    let mut hex_entry: Option<String> = None;
+    // Why the typed color was refused, shown next to it until it is edited
+    let mut hex_error: Option<String> = None;
+    // Name typed for the selected player after pressing F2
+    let mut name_entry: Option<String> = None;
+    // Text input, which may bring up an input method, is only on while typing one of them
+    video.text_input().stop();
+    // Swatch that is selected for reordering or removal and swatch that is currently dragged
+    let mut selected: Option<usize> = None;
+    let mut dragged: Option<usize> = None;
+    // The tally does not change while the menu is shown
+    let standings = tally.standings();
+    // Why the game could not be started or what else went wrong, shown until the next click or key
+    let mut problem: Option<String> = Some(notices.join(". ")).filter(|text| !text.is_empty());
+    let config = 'running: loop {
         // Actual number of pixels
         let output_size = canvas.output_size()?;
+        let screen = ScreenMap::of(&canvas)?;
         for event in event_pump.poll_iter() {
+            last_input = Instant::now();
+            running_demo = None;
+            // Clicks may select another player, so they end typing the name
+            if let Event::MouseButtonDown { .. } = event {
+                name_entry = None;
+            }
+            if let Event::MouseButtonDown { .. } | Event::KeyDown { .. } = event {
+                problem = None;
+            }
             match event {
+                // While typing a name, keys only edit it
+                Event::TextInput { text, .. } if name_entry.is_some() => {
+                    name_entry.as_mut().unwrap().push_str(&text);
+                },
+                Event::KeyDown { keycode: Some(Keycode::Escape), .. } if name_entry.is_some() => {
+                    name_entry = None;
+                },
+                Event::KeyDown { keycode: Some(Keycode::Return), .. } if name_entry.is_some() => {
+                    let name = name_entry.take().unwrap();
+                    if let Some(idx) = selected {
+                        players[idx].set_name(&name);
+                    }
+                },
+                Event::KeyDown { keycode: Some(Keycode::Backspace), .. } if name_entry.is_some() => {
+                    name_entry.as_mut().unwrap().pop();
+                },
+                Event::KeyDown { .. } if name_entry.is_some() => (),
+                Event::TextInput { text, .. } => if let Some(digits) = hex_entry.as_mut() {
+                    hex_error = None;
+                    for c in text.chars().filter(|c| c.is_ascii_hexdigit()) {
+                        if digits.len() < 6 {
+                            digits.push(c.to_ascii_uppercase());
+                        }
+                    }
+                },
+                // While typing a color, keys only edit it
+                Event::KeyDown { keycode: Some(Keycode::Escape), .. } if hex_entry.is_some() => {
+                    hex_entry = None;
+                    hex_error = None;
+                },
+                Event::KeyDown { keycode: Some(Keycode::Return), .. } if hex_entry.is_some() => {
+                    match parse_hex_color(hex_entry.as_ref().unwrap()) {
+                        Ok(col) => {
+                            add_player(&creator, &mut players, &mut marbles, col)?;
+                            hex_entry = None;
+                        },
+                        Err(e) => hex_error = Some(e),
+                    }
+                },
+                Event::KeyDown { keycode: Some(Keycode::Backspace), .. } if hex_entry.is_some() => {
+                    hex_error = None;
+                    hex_entry.as_mut().unwrap().pop();
+                },
+                Event::KeyDown { .. } if hex_entry.is_some() => (),
+                // The # has a key of its own on some layouts and is Shift+3 on others
+                Event::KeyDown { keycode: Some(Keycode::Hash), .. } => {
+                    hex_entry = Some(String::new());
+                },
+                Event::KeyDown { keycode: Some(Keycode::Num3), keymod, .. }
+                    if keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) =>
+                {
+                    hex_entry = Some(String::new());
+                },
                 Event::KeyDown { keycode: Some(Keycode::Escape | Keycode::Return), .. }
                 | Event::Quit {..} => {
-                    break 'running
-                },
-                Event::Window { win_event: WindowEvent::Resized(w, h), .. } => {
-                    window_size = (w, h);
+                    if players.is_empty() {
+                        break 'running None;
+                    }
+                    let mut config = Config{
+                        players: players.clone(),
+                        size,
+                        shape,
+                        sandbox,
+                        integer_scale,
+                        win_condition,
+                        high_contrast,
+                        patterns,
+                        show_last_move,
+                        instant,
+                        buffer_input,
+                        theme,
+                        line_width,
+                        end_when_separated,
+                        neutral: neutral.clone(),
+                        spread_bias,
+                        window_title: title.to_string(),
+                        seed: rng.next_u64(),
+                        ..Config::default()
+                    };
+                    if mirror && config.players.len() >= 2 {
+                        config.players[1].kind = PlayerKind::Mirror(Owner::new(0, config.players.len())?);
+                    }
+                    // Neutral cells that were placed before the board got smaller or changed its shape are dropped
+                    config.neutral.retain(|&p| p.re < size.re && p.im < size.im && shape.contains(p, size));
+                    match config.validate() {
+                        Ok(()) => break 'running Some(config),
+                        // Closing the window quits instead of going back to settings that do not work
+                        Err(_) if matches!(event, Event::Quit {..}) => break 'running None,
+                        Err(e) => problem = Some(e),
+                    }
                 },
                 Event::MouseMotion {x, y, ..} => {
-                    if window_size.0 > 0 {
-                        mousepos = (
-                            (x as f32 / window_size.0 as f32 * output_size.0 as f32) as u32,
-                            (y as f32 / window_size.1 as f32 * output_size.1 as f32) as u32,
-                        );
+                    if let Some((x, y)) = screen.to_drawing(x, y) {
+                        mousepos = (x as u32, y as u32);
                         let offset = (output_size.1 as u32-512)/2;
                         if mousepos.0 >= 50 && mousepos.0 < 562
                             && mousepos.1 >= offset && mousepos.1 < offset + 512 {
@@ -110,15 +540,28 @@ pub fn show_menu(video: &VideoSubsystem, event_pump: &mut EventPump) -> Result<C
                         }
                     }
                 },
+                Event::MouseButtonDown { mouse_btn: MouseButton::Right, .. } => {
+                    if let Some(p) = preview_cell(mousepos, size).filter(|&p| shape.contains(p, size)) {
+                        match neutral.iter().position(|&q| q == p) {
+                            Some(idx) => { neutral.remove(idx); },
+                            None => neutral.push(p),
+                        }
+                    }
+                },
+                Event::MouseButtonDown { .. } if swatch_at(mousepos, players.len()).is_some() => {
+                    selected = swatch_at(mousepos, players.len());
+                    dragged = selected;
+                },
+                Event::MouseButtonUp { .. } => {
+                    if let (Some(from), Some(to)) = (dragged.take(), swatch_at(mousepos, players.len())) {
+                        move_player(&mut players, &mut marbles, from, to);
+                        selected = Some(to);
+                    }
+                },
                 Event::MouseButtonDown { .. } => {
+                    selected = None;
                     if let Some(col) = next_color {
-                        players.push(Player::new(col));
-                        marbles.push(
-                            create_texture(&creator, 61, 61, |canvas| {
-                                gradient(&canvas, 30, 30, 30, col)?;
-                                Ok(())
-                            })?
-                        );
+                        add_player(&creator, &mut players, &mut marbles, col)?;
                     }
                     if mousepos.0 > 600 && mousepos.1 > 320 {
                         size.re = ((mousepos.0 - 600)/50) as i32;
@@ -131,9 +574,93 @@ pub fn show_menu(video: &VideoSubsystem, event_pump: &mut EventPump) -> Result<C
                         }
                     }
                 },
+                Event::KeyDown { keycode: Some(Keycode::F2), .. } if selected.is_some() => {
+                    name_entry = Some(players[selected.unwrap()].name().unwrap_or("").to_string());
+                },
+                Event::KeyDown { keycode: Some(Keycode::S), .. } => {
+                    shape = shape.next();
+                },
+                Event::KeyDown { keycode: Some(Keycode::P), .. } => {
+                    sandbox = !sandbox;
+                },
+                Event::KeyDown { keycode: Some(Keycode::I), .. } => {
+                    integer_scale = !integer_scale;
+                },
+                Event::KeyDown { keycode: Some(Keycode::D), .. } => {
+                    win_condition = match win_condition {
+                        WinCondition::LastStanding => WinCondition::Domination(DOMINATION),
+                        WinCondition::Domination(_) => WinCondition::LastStanding,
+                    };
+                },
+                Event::KeyDown { keycode: Some(Keycode::H), .. } => {
+                    high_contrast = !high_contrast;
+                },
+                Event::KeyDown { keycode: Some(Keycode::A), .. } => {
+                    patterns = !patterns;
+                },
+                Event::KeyDown { keycode: Some(Keycode::K), .. } => {
+                    theme = theme.next();
+                },
+                Event::KeyDown { keycode: Some(Keycode::E), .. } => {
+                    end_when_separated = !end_when_separated;
+                },
+                Event::KeyDown { keycode: Some(Keycode::M), .. } => {
+                    mirror = !mirror;
+                },
+                Event::KeyDown { keycode: Some(Keycode::B), .. } => {
+                    spread_bias = spread_bias.next();
+                },
+                // Give all players, and at least two, new colors that are easy to tell apart,
+                // keeping their names
+                Event::KeyDown { keycode: Some(Keycode::R), .. } => {
+                    let offset = rng.below(360) as f32;
+                    let previous = std::mem::take(&mut players);
+                    marbles.clear();
+                    for (idx, col) in spread_colors(previous.len().max(2), offset).into_iter().enumerate() {
+                        add_player(&creator, &mut players, &mut marbles, col)?;
+                        if let Some(name) = previous.get(idx).and_then(Player::name) {
+                            players[idx].set_name(name);
+                        }
+                    }
+                    selected = None;
+                },
+                Event::KeyDown { keycode: Some(Keycode::G), .. } => {
+                    line_width = match line_width {
+                        1 => 2,
+                        2 => 4,
+                        4 => 0,
+                        _ => 1,
+                    };
+                },
+                Event::KeyDown { keycode: Some(Keycode::L), .. } => {
+                    show_last_move = !show_last_move;
+                },
+                Event::KeyDown { keycode: Some(Keycode::N), .. } => {
+                    instant = !instant;
+                },
+                Event::KeyDown { keycode: Some(Keycode::Q), .. } => {
+                    buffer_input = !buffer_input;
+                },
                 Event::KeyDown { keycode: Some(Keycode::Backspace), .. } => {
-                    players.pop();
-                    marbles.pop();
+                    match selected.take() {
+                        Some(idx) => {
+                            players.remove(idx);
+                            marbles.remove(idx);
+                        },
+                        None => {
+                            players.pop();
+                            marbles.pop();
+                        },
+                    }
+                },
+                // Shift is left out, since Shift+3 types the # that starts entering a color
+                Event::KeyDown { keycode: Some(keycode), keymod, .. }
+                    if selected.is_some() && turn_position(keycode).is_some()
+                        && !keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) =>
+                {
+                    let to = turn_position(keycode).unwrap().min(players.len() - 1);
+                    move_player(&mut players, &mut marbles, selected.unwrap(), to);
+                    selected = Some(to);
                 },
                 _ => continue,
             }
@@ -149,22 +676,166 @@ pub fn show_menu(video: &VideoSubsystem, event_pump: &mut EventPump) -> Result<C
         if let Some(col) = next_color {
             canvas.filled_circle(mousepos.0 as i16, mousepos.1 as i16, 20, col)?;
         };
+        let black = Color::RGB(0, 0, 0);
         for (i, marble) in marbles.iter().enumerate() {
-            canvas.copy(&marble, None, Some(Rect::new(600 + i as i32 * 70, 50, 61, 61)))?;
+            let rect = swatch_rect(i);
+            canvas.copy(&marble, None, Some(rect))?;
+            // The pattern goes with the position in the turn order, not with the player
+            if patterns {
+                pattern(&canvas, i, 30, rect.x() as i16 + 30, rect.y() as i16 + 30)?;
+            }
+            canvas.string(rect.x() as i16 + 27, rect.bottom() as i16 + 6, &(i+1).to_string(), black)?;
+            if selected == Some(i) {
+                canvas.rectangle(
+                    rect.left() as i16 - 3, rect.top() as i16 - 3,
+                    rect.right() as i16 + 3, rect.bottom() as i16 + 18,
+                    black,
+                )?;
+            }
+        }
+        for p in Points::new(Point::new(0, 0), size) {
+            if !shape.contains(p, size) {
+                let x = 600 + 50*p.re as i16;
+                let y = 220 + 50*p.im as i16;
+                canvas.box_(x, y, x+50, y+50, Color::RGB(90, 90, 90))?;
+            }
+        }
+        for p in neutral.iter().filter(|&&p| shape.contains(p, size) && p.re < size.re && p.im < size.im) {
+            canvas.filled_circle(625 + 50*p.re as i16, 245 + 50*p.im as i16, 12, Color::RGB(128, 128, 128))?;
+        }
+        canvas.string(600, 200, shape.name(), black)?;
+        if sandbox {
+            canvas.string(600, 185, "Sandbox", black)?;
+        }
+        if !integer_scale {
+            canvas.string(600, 170, "Stretched", black)?;
+        }
+        if let WinCondition::Domination(threshold) = win_condition {
+            canvas.string(600, 155, &format!("Domination {}%", (threshold * 100.0).round()), black)?;
+        }
+        if high_contrast {
+            canvas.string(600, 140, "High contrast", black)?;
+        }
+        if !show_last_move {
+            canvas.string(600, 125, "Last move hidden", black)?;
+        }
+        if instant {
+            canvas.string(600, 110, "Instant moves", black)?;
+        }
+        if buffer_input {
+            canvas.string(600, 95, "Clicks queued", black)?;
+        }
+        if patterns {
+            canvas.string(600, 80, "Patterns", black)?;
+        }
+        let mut looks = Vec::new();
+        if theme != Theme::CLASSIC {
+            looks.push(format!("Theme: {}", theme.name()));
+        }
+        match line_width {
+            0 => looks.push("No grid lines".to_string()),
+            1 => (),
+            width => looks.push(format!("Grid lines {}px", width)),
+        }
+        if !looks.is_empty() {
+            canvas.string(600, 65, &looks.join(", "), black)?;
+        }
+        let mut rules = Vec::new();
+        if end_when_separated {
+            rules.push("Separated players may end the game");
+        }
+        if mirror {
+            rules.push("Player 2 mirrors player 1");
+        }
+        if !neutral.is_empty() {
+            rules.push("Neutral cells (right click)");
+        }
+        if spread_bias == SpreadBias::Clockwise {
+            rules.push("Marbles turn clockwise");
+        }
+        if !rules.is_empty() {
+            canvas.string(600, 35, &rules.join(", "), black)?;
+        }
+        // Standings in one line, as many as fit left of the demo
+        let bottom = output_size.1 as i16 - 20;
+        let mut x = 50;
+        if !standings.is_empty() {
+            canvas.string(x, bottom, "Wins", black)?;
+            x += 40;
+        }
+        for (key, wins) in standings.iter().take(8) {
+            if let Ok(col) = parse_hex_color(key) {
+                canvas.box_(x, bottom - 2, x + 10, bottom + 9, col)?;
+            }
+            canvas.string(x + 14, bottom, &wins.to_string(), black)?;
+            x += 50;
+        }
+        if font.is_none() {
+            canvas.string(600, 50, font::MISSING, Color::RGB(160, 0, 0))?;
+        }
+        if let Some(problem) = problem.as_ref() {
+            canvas.string(50, bottom - 20, problem, Color::RGB(160, 0, 0))?;
+        }
+        if let Some(name) = name_entry.as_ref() {
+            canvas.string(600, 20, &format!("Name: {}_", name), black)?;
+        } else if let Some(idx) = selected.filter(|_| hex_entry.is_none()) {
+            let name = players[idx].name().unwrap_or("none");
+            canvas.string(600, 20, &format!("Name: {} (F2 renames)", name), black)?;
+        }
+        if let Some(digits) = hex_entry.as_ref() {
+            canvas.string(600, 20, &format!("#{:_<6}", digits), black)?;
+            // Preview of the color, with the digits that are not typed yet taken as 0
+            let col = parse_hex_color(&format!("{:0<6}", digits))?;
+            canvas.box_(670, 12, 694, 36, col)?;
+            canvas.rectangle(670, 12, 694, 36, black)?;
+            if let Some(error) = hex_error.as_ref() {
+                canvas.string(704, 20, error, Color::RGB(160, 0, 0))?;
+            }
         }
-        let black = Color::RGB(0, 0, 0);
         for x in 0..=size.re as i16 {
             canvas.vline(600+50*x, 220, 220+50*size.im as i16, black)?;
         }
         for y in 0..=size.im as i16 {
             canvas.hline(600, 600+50*size.re as i16, 220+50*y, black)?;
         }
+
+        let now = Instant::now();
+        if let Some(settings) = demo.filter(|settings| now - last_input >= settings.delay) {
+            if running_demo.is_none() {
+                let game = Demo::new(settings, rng.next_u64())?;
+                // Labels do not fit the small cells
+                let settings = ViewSettings { coords: false, ..ViewSettings::new(game.game().config()) };
+                let fonts = font.as_ref().map(|font| Fonts { text: font, labels: font });
+                let renderer = Renderer::new(&creator, fonts, &keymap, game.game(), &theme, 1, settings)?;
+                running_demo = Some((game, renderer));
+            }
+            let (game, renderer) = running_demo.as_mut().unwrap();
+            game.advance(now - last_frame);
+            let size = settings.size + Point::new(SIDEBAR_WIDTH, 0);
+            let (w, h) = ((size.re * demo::CELLSIZE.re) as u32, (size.im * demo::CELLSIZE.im) as u32);
+            let area = Rect::new(output_size.0 as i32 - w as i32 - 10, output_size.1 as i32 - h as i32 - 10, w, h);
+            canvas.set_viewport(area);
+            renderer.update(&mut canvas, game.game(), None)?;
+            canvas.set_viewport(None);
+        }
+        last_frame = now;
+
+        let typing = name_entry.is_some() || hex_entry.is_some();
+        if typing != video.text_input().is_active() {
+            if typing {
+                video.text_input().start();
+            } else {
+                video.text_input().stop();
+            }
+        }
+
         canvas.present();
         std::thread::sleep(Duration::new(0, 1_000_000_000u32 / 60));
     };
-    Ok(Config{
-        players: players,
-        size: size,
-        cellsize: 100,
-    })
+    video.text_input().stop();
+    windows.menu = Some(placement::of(canvas.window()));
+    if let Err(e) = windows.save() {
+        eprintln!("Can not save the window positions: {}", e);
+    }
+    Ok(config)
 }
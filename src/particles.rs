@@ -0,0 +1,220 @@
+/* Sparks that burst from cells when they fire, so cascades look like explosions. They are only
+ * decoration and have no effect on the game.
+ */
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::time::Duration;
+
+use num_complex::Complex;
+use sdl2::pixels::Color;
+
+use crate::grid::{Owner, Point};
+use crate::rng::Rng;
+
+// Largest number of particles alive at the same time, so large waves do not slow down drawing.
+// Bursts beyond this get fewer particles or none.
+pub const MAX_PARTICLES: usize = 400;
+
+// Smallest and largest number of particles of one burst
+const BURST: (usize, usize) = (8, 16);
+
+// Time until a particle has faded out completely
+const LIFETIME: Duration = Duration::from_millis(500);
+
+// Fraction of its speed a particle keeps per second
+const DRAG: f32 = 0.05;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Particle {
+    pub pos: Complex<f32>,
+    // In pixels per second
+    pub vel: Complex<f32>,
+    pub color: Color,
+    // Time until the particle is gone
+    pub remaining: Duration,
+}
+impl Particle {
+    /* Opacity, fading from 1 to 0 over the lifetime */
+    pub fn alpha(&self) -> f32 {
+        self.remaining.as_secs_f32() / LIFETIME.as_secs_f32()
+    }
+}
+
+pub struct Particles {
+    particles: Vec<Particle>,
+    rng: Rng,
+}
+impl Particles {
+    pub fn new(seed: u64) -> Particles {
+        Particles {
+            particles: Vec::new(),
+            rng: Rng::new(seed),
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item=&Particle> {
+        self.particles.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.particles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.particles.is_empty()
+    }
+
+    // Random number between 0 and 1
+    fn unit(&mut self) -> f32 {
+        (self.rng.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    /* Spawn particles at the given position, flying in random directions with up to the given
+     * speed in pixels per second
+     */
+    pub fn burst(&mut self, center: Complex<f32>, color: Color, speed: f32) {
+        let count = BURST.0 + self.rng.below(BURST.1 - BURST.0 + 1);
+        for _ in 0..count.min(MAX_PARTICLES - self.particles.len()) {
+            let angle = self.unit() * std::f32::consts::TAU;
+            let vel = Complex::from_polar(speed * (0.3 + 0.7 * self.unit()), angle);
+            let remaining = LIFETIME.mul_f32(0.5 + 0.5 * self.unit());
+            self.particles.push(Particle {
+                pos: center,
+                vel,
+                color,
+                remaining,
+            });
+        }
+    }
+
+    /* Move all particles by the given elapsed time, slowing them down, and remove those that
+     * faded out
+     */
+    pub fn advance(&mut self, dt: Duration) {
+        let secs = dt.as_secs_f32();
+        let keep = DRAG.powf(secs);
+        for particle in self.particles.iter_mut() {
+            particle.pos += particle.vel * secs;
+            particle.vel *= keep;
+            particle.remaining = particle.remaining.saturating_sub(dt);
+        }
+        self.particles.retain(|particle| !particle.remaining.is_zero());
+    }
+
+    pub fn clear(&mut self) {
+        self.particles.clear();
+    }
+}
+
+// Number of recent positions of a travelling marble that are kept for its trail
+pub const TRAIL_LENGTH: usize = 6;
+
+/* Recent positions of the marbles that travel between cells, which leave a fading trail so fast
+ * cascades are easier to follow. Marbles are identified by their id.
+ */
+#[derive(Default)]
+pub struct Trails {
+    trails: HashMap<u32, (Owner, VecDeque<Point>)>,
+}
+impl Trails {
+    pub fn new() -> Trails {
+        Trails::default()
+    }
+
+    /* Add the current position of each travelling marble, given with its id and owner, dropping
+     * the oldest position of a full trail. Marbles that are no longer given have arrived and lose
+     * their trail.
+     */
+    pub fn record(&mut self, marbles: impl Iterator<Item=(u32, Owner, Point)>) {
+        let mut seen = HashSet::new();
+        for (id, owner, pos) in marbles {
+            let (trail_owner, positions) = self.trails.entry(id).or_insert_with(|| (owner, VecDeque::with_capacity(TRAIL_LENGTH)));
+            *trail_owner = owner;
+            if positions.len() == TRAIL_LENGTH {
+                positions.pop_front();
+            }
+            positions.push_back(pos);
+            seen.insert(id);
+        }
+        self.trails.retain(|id, _| seen.contains(id));
+    }
+
+    /* Dots of all trails with their owner and position, and their size and opacity from 0 to 1,
+     * which get smaller and fainter the older the position is
+     */
+    pub fn dots(&self) -> impl Iterator<Item=(Owner, Point, f32)> + '_ {
+        self.trails.values().flat_map(|(owner, positions)| {
+            positions.iter().rev().enumerate().map(move |(age, &pos)| {
+                (*owner, pos, 1.0 - (age + 1) as f32 / (TRAIL_LENGTH + 1) as f32)
+            })
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.trails.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.trails.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.trails.clear();
+    }
+}
+
+// Energy the screen shake gains per cell that fires, up to 1, and the share of it that is left
+// after each frame
+const SHAKE_PER_CELL: f32 = 0.05;
+const SHAKE_DECAY: f32 = 0.85;
+// Energy below which the shake stops
+const SHAKE_REST: f32 = 0.01;
+
+/* Shaking of the board during cascades, stronger the more cells fire. Like the particles, it is
+ * only decoration: the board is drawn at an offset, but clicks still go to the cell under the
+ * mouse.
+ */
+pub struct Shake {
+    energy: f32,
+    rng: Rng,
+}
+impl Shake {
+    pub fn new(seed: u64) -> Shake {
+        Shake {
+            energy: 0.0,
+            rng: Rng::new(seed),
+        }
+    }
+
+    pub fn energy(&self) -> f32 {
+        self.energy
+    }
+
+    /* Shake for the given number of cells that fired */
+    pub fn add(&mut self, fired: usize) {
+        self.energy = (self.energy + fired as f32 * SHAKE_PER_CELL).min(1.0);
+    }
+
+    /* Random offset of the board for the next frame of up to the given number of pixels in each
+     * direction at full energy, after which the shake decays
+     */
+    pub fn next_offset(&mut self, max: i32) -> (i32, i32) {
+        if self.energy == 0.0 {
+            return (0, 0);
+        }
+        let reach = (max as f32 * self.energy).round() as i32;
+        let offset = (
+            self.rng.below(2 * reach as usize + 1) as i32 - reach,
+            self.rng.below(2 * reach as usize + 1) as i32 - reach,
+        );
+        self.energy *= SHAKE_DECAY;
+        if self.energy < SHAKE_REST {
+            self.energy = 0.0;
+        }
+        offset
+    }
+
+    pub fn clear(&mut self) {
+        self.energy = 0.0;
+    }
+}
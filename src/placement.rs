@@ -0,0 +1,136 @@
+/* Where the windows of the menu and the game were when they were last closed, kept across
+ * launches in a small file, so they open on the same display again. Positions on a display that is
+ * no longer connected are ignored and the window is centered instead.
+ */
+
+use sdl2::rect::Rect;
+use sdl2::video::Window;
+use sdl2::VideoSubsystem;
+
+use crate::keymap::{load_config, save_config};
+
+// Environment variable with the path of the file, which is windows.conf in the configuration
+// directory otherwise
+pub const PLACEMENT_VARIABLE: &str = "CHAINREACTION_WINDOWS";
+
+// Pixels of a window in both directions that must be on a display for it to be found again
+const MIN_VISIBLE: i32 = 100;
+
+/* Position of the top left corner and size of a window in screen coordinates */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Placement {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+impl Placement {
+    /* Whether enough of the window is on one of the displays, given by their bounds, to be moved
+     * back into view. Its top edge has to be on the display, since the window is dragged by its
+     * title bar.
+     */
+    pub fn visible(&self, displays: &[Rect]) -> bool {
+        displays.iter().any(|display| {
+            let overlap = |start: i32, len: u32, display_start: i32, display_len: u32| {
+                (start + len as i32).min(display_start + display_len as i32) - start.max(display_start)
+            };
+            let needed = |len: u32| MIN_VISIBLE.min(len as i32);
+            self.y >= display.y() && self.y < display.y() + display.height() as i32
+                && overlap(self.x, self.width, display.x(), display.width()) >= needed(self.width)
+                && overlap(self.y, self.height, display.y(), display.height()) >= needed(self.height)
+        })
+    }
+
+    /* Window of the given size in the center of a display with the given bounds */
+    pub fn centered(size: (u32, u32), display: Rect) -> Placement {
+        Placement {
+            x: display.x() + (display.width() as i32 - size.0 as i32) / 2,
+            y: display.y() + (display.height() as i32 - size.1 as i32) / 2,
+            width: size.0,
+            height: size.1,
+        }
+    }
+}
+
+/* Saved placement of each window */
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Placements {
+    pub menu: Option<Placement>,
+    pub game: Option<Placement>,
+}
+impl Placements {
+    pub fn new() -> Placements {
+        Placements::default()
+    }
+
+    /* Parse placements written by to_text(), one window per line in the form
+     * "game 100 50 1200 800" with the position and the size. Empty lines are ignored.
+     */
+    pub fn parse(text: &str) -> Result<Placements, String> {
+        let mut placements = Placements::new();
+        for (number, line) in text.lines().enumerate() {
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            if parts.is_empty() {
+                continue;
+            }
+            if parts.len() != 5 {
+                return Err(format!("Line {}: expected a window, its position and its size", number+1));
+            }
+            let invalid = |value: &str| format!("Line {}: invalid number {}", number+1, value);
+            let placement = Placement {
+                x: parts[1].parse().map_err(|_| invalid(parts[1]))?,
+                y: parts[2].parse().map_err(|_| invalid(parts[2]))?,
+                width: parts[3].parse().map_err(|_| invalid(parts[3]))?,
+                height: parts[4].parse().map_err(|_| invalid(parts[4]))?,
+            };
+            match parts[0] {
+                "menu" => placements.menu = Some(placement),
+                "game" => placements.game = Some(placement),
+                window => return Err(format!("Line {}: unknown window {}", number+1, window)),
+            }
+        }
+        Ok(placements)
+    }
+
+    pub fn to_text(&self) -> String {
+        [("menu", self.menu), ("game", self.game)].iter()
+            .filter_map(|(name, placement)| placement.map(|p| format!("{} {} {} {} {}\n", name, p.x, p.y, p.width, p.height)))
+            .collect()
+    }
+
+    /* Load the placements from their file, with none if there is no file */
+    pub fn load() -> Result<Placements, String> {
+        Ok(load_config(PLACEMENT_VARIABLE, "windows.conf", Placements::parse)?.unwrap_or_else(Placements::new))
+    }
+
+    /* Write the placements to their file, creating the configuration directory if needed */
+    pub fn save(&self) -> Result<(), String> {
+        save_config(PLACEMENT_VARIABLE, "windows.conf", &self.to_text())
+    }
+}
+
+/* Where the window is now */
+pub fn of(window: &Window) -> Placement {
+    let (x, y) = window.position();
+    let (width, height) = window.size();
+    Placement { x, y, width, height }
+}
+
+/* Bounds of all displays in screen coordinates */
+pub fn displays(video: &VideoSubsystem) -> Result<Vec<Rect>, String> {
+    (0..video.num_video_displays()?).map(|idx| video.display_bounds(idx)).collect()
+}
+
+/* Where to open a window: where it was saved if it is still visible, or with the given size
+ * centered on the given display. A display given on the command line overrides the saved
+ * placement. Without a display, the first one is used.
+ */
+pub fn choose(saved: Option<Placement>, displays: &[Rect], display: Option<usize>, size: (u32, u32)) -> Placement {
+    if let Some(saved) = saved.filter(|saved| display.is_none() && saved.visible(displays)) {
+        return saved;
+    }
+    match displays.get(display.unwrap_or(0)).or(displays.first()) {
+        Some(&bounds) => Placement::centered(size, bounds),
+        None => Placement { x: 0, y: 0, width: size.0, height: size.1 },
+    }
+}
@@ -0,0 +1,49 @@
+use crate::grid::Point;
+
+/* Iterates over all points in a rectangle, row by row */
+pub struct Points {
+    corner: Point,
+    width: i32,
+    // Index of the next point to be returned from the front and one after the next point to be
+    // returned from the back
+    front: i32,
+    back: i32,
+}
+impl Points {
+    pub fn new(corner: Point, size: Point) -> Points {
+        Points {
+            corner,
+            width: size.re,
+            front: 0,
+            back: size.re * size.im,
+        }
+    }
+
+    fn point(&self, idx: i32) -> Point {
+        self.corner + Point::new(idx % self.width, idx / self.width)
+    }
+}
+impl Iterator for Points {
+    type Item = Point;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.front >= self.back {
+            return None;
+        }
+        self.front += 1;
+        Some(self.point(self.front - 1))
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = (self.back - self.front).max(0) as usize;
+        (len, Some(len))
+    }
+}
+impl DoubleEndedIterator for Points {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.front >= self.back {
+            return None;
+        }
+        self.back -= 1;
+        Some(self.point(self.back))
+    }
+}
+impl ExactSizeIterator for Points {}
@@ -0,0 +1,164 @@
+/* Recovery file of an unfinished game, which is written after each turn and offered for resuming
+ * on the next launch. It is removed once the game ends or the players return to the menu. Saving
+ * needs the serde feature; without it, nothing is saved and nothing is offered.
+ */
+
+use std::fs;
+use std::thread;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::game::{Game, State};
+use crate::grid::{Owner, Point};
+use crate::keymap::config_file;
+#[cfg(feature = "serde")]
+use crate::keymap::{create_config_dir, load_config, move_aside};
+use crate::menu::Config;
+
+// Environment variable with the path of the recovery file, which is recovery.json in the
+// configuration directory otherwise
+pub const RECOVERY_VARIABLE: &str = "CHAINREACTION_RECOVERY";
+
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Recovery {
+    pub config: Config,
+    pub first_player: Owner,
+    // Moves since the start, which restore the history and the bonus placements when replayed
+    pub moves: Vec<(Point, Owner)>,
+    // Position after the moves, see Game::encode(). Games that were set up in the board editor
+    // can not be replayed and continue from here.
+    pub position: String,
+}
+impl Recovery {
+    /* What is needed to continue the game, once its marbles came to rest. There is nothing to
+     * recover in the board editor and after the game is won.
+     */
+    pub fn of(game: &Game) -> Option<Recovery> {
+        if game.editing().is_some() || game.winner().is_some() || !matches!(game.state(), State::AcceptingInput) {
+            return None;
+        }
+        Some(Recovery {
+            config: game.config().clone(),
+            first_player: game.first_player(),
+            moves: game.history().iter().map(|&(owner, p)| (p, owner)).collect(),
+            position: game.encode()?,
+        })
+    }
+
+    /* Continue the game with the given cell size, which may differ from the one it was saved
+     * with if the display changed
+     */
+    pub fn resume(&self, cellsize: Point) -> Result<Game, String> {
+        let config = Config { cellsize, ..self.config.clone() };
+        let mut game = Game::starting_with(config.clone(), self.first_player)?;
+        if game.apply_moves(&self.moves).is_ok() && game.encode().as_deref() == Some(self.position.as_str()) {
+            return Ok(game);
+        }
+        let mut game = Game::starting_with(config, self.first_player)?;
+        game.decode(&self.position)?;
+        Ok(game)
+    }
+
+    #[cfg(feature = "serde")]
+    pub fn parse(text: &str) -> Result<Recovery, String> {
+        serde_json::from_str(text).map_err(|e| e.to_string())
+    }
+
+    #[cfg(feature = "serde")]
+    pub fn to_text(&self) -> Result<String, String> {
+        serde_json::to_string(self).map_err(|e| e.to_string())
+    }
+
+    /* Load the recovery file, if there is one. A file that can not be read is renamed to
+     * recovery.json.broken, so it is only reported once and not on every launch.
+     */
+    #[cfg(feature = "serde")]
+    pub fn load() -> Result<Option<Recovery>, String> {
+        load_config(RECOVERY_VARIABLE, "recovery.json", Recovery::parse)
+            .map_err(|e| move_aside(RECOVERY_VARIABLE, "recovery.json", e))
+    }
+
+    #[cfg(not(feature = "serde"))]
+    pub fn load() -> Result<Option<Recovery>, String> {
+        Ok(None)
+    }
+
+    /* Write the recovery file in the background, so the next turn does not have to wait for the
+     * disk. It is written to a temporary file first and then renamed, so a crash while writing
+     * keeps the previous one. Returns the thread that writes it, if anything is written.
+     */
+    #[cfg(feature = "serde")]
+    pub fn save(&self) -> Result<Option<thread::JoinHandle<Result<(), String>>>, String> {
+        let path = config_file(RECOVERY_VARIABLE, "recovery.json").ok_or("No configuration directory")?;
+        let text = self.to_text()?;
+        Ok(Some(thread::spawn(move || {
+            create_config_dir(&path)?;
+            let temporary = path.with_extension("json.tmp");
+            fs::write(&temporary, text).map_err(|e| format!("{}: {}", temporary.display(), e))?;
+            fs::rename(&temporary, &path).map_err(|e| format!("{}: {}", path.display(), e))
+        })))
+    }
+
+    #[cfg(not(feature = "serde"))]
+    pub fn save(&self) -> Result<Option<thread::JoinHandle<Result<(), String>>>, String> {
+        Ok(None)
+    }
+}
+
+/* Remove the recovery file, if there is one */
+pub fn remove() -> Result<(), String> {
+    match config_file(RECOVERY_VARIABLE, "recovery.json") {
+        Some(path) if path.exists() => fs::remove_file(&path).map_err(|e| format!("{}: {}", path.display(), e)),
+        _ => Ok(()),
+    }
+}
+
+/* Writes the recovery file whenever the marbles of a turn came to rest, see Recovery::save() */
+pub struct Autosave {
+    // Thread writing the last recovery file, which is waited for before the next one is written
+    writer: Option<thread::JoinHandle<Result<(), String>>>,
+    // Number of moves and player to move when the file was last written
+    saved: (usize, Owner),
+}
+impl Autosave {
+    /* Start with the current position of the game, which is only saved once it changes */
+    pub fn new(game: &Game) -> Autosave {
+        Autosave {
+            writer: None,
+            saved: (game.history().len(), game.cur_player()),
+        }
+    }
+
+    /* Save the game if a turn ended since the last call. A failure of the previous write is
+     * reported here as well, since it is only known once the next one starts.
+     */
+    pub fn update(&mut self, game: &Game) -> Result<(), String> {
+        let current = (game.history().len(), game.cur_player());
+        if current == self.saved {
+            return Ok(());
+        }
+        if let Some(recovery) = Recovery::of(game) {
+            self.saved = current;
+            self.wait()?;
+            self.writer = recovery.save().map_err(|e| format!("Can not save the game for recovery: {}", e))?;
+        }
+        Ok(())
+    }
+
+    /* Wait until the last recovery file is written */
+    pub fn wait(&mut self) -> Result<(), String> {
+        match self.writer.take().map(|writer| writer.join()) {
+            None | Some(Ok(Ok(()))) => Ok(()),
+            Some(Ok(Err(e))) => Err(format!("Can not save the game for recovery: {}", e)),
+            Some(Err(_)) => Err("Can not save the game for recovery".to_string()),
+        }
+    }
+
+    /* Remove the recovery file once the game ended, after any write that is still going on */
+    pub fn discard(&mut self) -> Result<(), String> {
+        self.wait()?;
+        remove().map_err(|e| format!("Can not remove the recovery file: {}", e))
+    }
+}
@@ -1,20 +1,38 @@
-use std::str;
-use std::time::Duration;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use num_complex::Complex;
 
 use sdl2::EventPump;
 use sdl2::VideoSubsystem;
-use sdl2::event::Event;
-use sdl2::keyboard::Keycode;
+use sdl2::event::{Event, WindowEvent};
+use sdl2::keyboard::{Keycode, Mod};
+use sdl2::mouse::MouseButton;
 use sdl2::video::{Window,WindowContext};
-use sdl2::render::{Canvas,Texture,TextureCreator};
+use sdl2::render::{Canvas,RenderTarget,Texture,TextureCreator};
 use sdl2::surface::Surface;
 use sdl2::rect::Rect;
 use sdl2::pixels::{Color,PixelFormatEnum};
 use sdl2::gfx::primitives::DrawRenderer;
-use sdl2::ttf;
+use sdl2::ttf::{self, Font};
+
+use crate::grid::{ease, round_subpixel, rotate_point, stretch, slot_position, Grid, GridEvent, Owner, Point, SlotKind, Subpixel, NEUTRAL};
+use crate::game::{owner_changes, Game, OwnerChange, State, SLOW_MOTION};
+use crate::keymap::{Action, KeyMap};
+use crate::menu::{parse_hex_color, turn_position, Config};
+use crate::tally::Tally;
+use crate::font;
+use crate::particles::{Particles, Shake, Trails};
+use crate::screenshot;
+use crate::placement::{self, Placements};
+use crate::ai;
+use crate::theme::Theme;
+use crate::icon;
+use crate::recovery::Autosave;
 
-use crate::grid::{Point, PointIter, DIRECTIONS};
-use crate::game::Game;
+const CTRL: Mod = Mod::from_bits_truncate(Mod::LCTRLMOD.bits() | Mod::RCTRLMOD.bits());
+const SHIFT: Mod = Mod::from_bits_truncate(Mod::LSHIFTMOD.bits() | Mod::RSHIFTMOD.bits());
 
 // Create a canvas, allow the given CanvasDrawer function to fill it, and convert to a texture.
 pub fn create_texture<CanvasDrawer>(
@@ -33,199 +51,2181 @@ where CanvasDrawer: Fn(&mut Canvas<Surface>) -> Result<(), String>
         .map_err(|e| e.to_string())?)
 }
 
-pub fn gradient(canvas: &Canvas<Surface>, radius: i16, cx: i16, cy: i16, color: Color) -> Result<(), String> {
+/* First and last pixel of a line of the given width along the boundary between cells at the given
+ * position. The line is centered on the boundary, but kept inside of the board of the given size,
+ * so the lines around the board are as wide as those between the cells.
+ */
+pub fn line_span(pos: i32, width: i32, size: i32) -> (i32, i32) {
+    let start = (pos - width / 2).clamp(0, (size - width).max(0));
+    (start, start + width - 1)
+}
+
+/* Marble or slot that fades from the given color at the bottom into the background at the top */
+pub fn gradient(canvas: &Canvas<Surface>, radius: i16, cx: i16, cy: i16, color: Color, background: Color)
+    -> Result<(), String>
+{
     let size = 2*radius+1;
     for i in 0..size {
         let mut color = color;
         color.a = (256 - (((size-i) as u32 * 180)/(size as u32+1)) as u16) as u8;
         let halflength = ((radius*radius-(i-radius)*(i-radius)) as f64).sqrt() as i16;
-        canvas.hline(cx-halflength, cx+halflength, cy-radius+i, Color::RGB(200, 200, 200))?;
+        canvas.hline(cx-halflength, cx+halflength, cy-radius+i, background)?;
         canvas.hline(cx-halflength, cx+halflength, cy-radius+i, color)?;
     }
     Ok(())
 }
 
+/* Stretch the channels of a color to the full range, which gives the most saturated and bright
+ * color of the same hue. Grays are left unchanged.
+ */
+fn saturate(color: Color) -> Color {
+    let min = color.r.min(color.g).min(color.b) as u32;
+    let max = color.r.max(color.g).max(color.b) as u32;
+    if max == min {
+        return color;
+    }
+    let stretch = |c: u8| ((c as u32 - min) * 255 / (max - min)) as u8;
+    Color::RGB(stretch(color.r), stretch(color.g), stretch(color.b))
+}
+
+/* Relative luminance of a color as defined by WCAG, from 0 for black to 1 for white */
+pub fn luminance(color: Color) -> f32 {
+    let linear = |c: u8| {
+        let c = c as f32 / 255.0;
+        if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) }
+    };
+    0.2126 * linear(color.r) + 0.7152 * linear(color.g) + 0.0722 * linear(color.b)
+}
+
+/* Black or white, whichever has the higher contrast ratio against the given background, so text
+ * on it stays legible
+ */
+pub fn contrasting(background: Color) -> Color {
+    let l = luminance(background);
+    // Contrast ratios of black and white text against the background
+    let (black, white) = ((l + 0.05) / 0.05, 1.05 / (l + 0.05));
+    if black >= white { Color::RGB(0, 0, 0) } else { Color::RGB(255, 255, 255) }
+}
+
+/* Gray of the same luminance as the color, faded halfway into the background, for players that
+ * were eliminated
+ */
+pub fn greyed(color: Color, background: Color) -> Color {
+    let l = luminance(color);
+    let gray = if l <= 0.0031308 { 12.92 * l } else { 1.055 * l.powf(1.0 / 2.4) - 0.055 };
+    let gray = (gray * 255.0).round();
+    let mix = |c: u8| ((gray + c as f32) / 2.0).round() as u8;
+    Color::RGB(mix(background.r), mix(background.g), mix(background.b))
+}
+
+/* Five pointed star with the given outer radius, marking bonus cells */
+fn star(canvas: &Canvas<Surface>, radius: f32, cx: i16, cy: i16, color: Color) -> Result<(), String> {
+    let (mut xs, mut ys) = (Vec::with_capacity(10), Vec::with_capacity(10));
+    for idx in 0..10 {
+        let r = if idx % 2 == 0 { radius } else { radius * 0.4 };
+        let angle = std::f32::consts::PI * (idx as f32 / 5.0 - 0.5);
+        xs.push(cx + (r * angle.cos()).round() as i16);
+        ys.push(cy + (r * angle.sin()).round() as i16);
+    }
+    canvas.filled_polygon(&xs, &ys, color)?;
+    canvas.polygon(&xs, &ys, Color::RGB(0, 0, 0))
+}
+
+/* Marble for the high contrast mode: a disk of the saturated color with a bold black outline */
+fn bold_marble(canvas: &Canvas<Surface>, radius: i16, cx: i16, cy: i16, color: Color) -> Result<(), String> {
+    canvas.filled_circle(cx, cy, radius, Color::RGB(0, 0, 0))?;
+    canvas.filled_circle(cx, cy, radius - (radius / 5).max(2), saturate(color))
+}
+
+// Number of different patterns, after which they repeat
+const PATTERNS: usize = 8;
+
+/* Pixels of the pattern for the player at the given position in the turn order, as offsets from
+ * the center of a marble with the given radius. The first player has none, the next ones a dot, a
+ * ring, a diagonal cross, an upright cross, a bar, a square and a triangle. The patterns keep a
+ * minimum size, so they can still be told apart on the marbles of the smallest cells.
+ */
+pub fn pattern_mask(idx: usize, radius: i32) -> Vec<(i32, i32)> {
+    let size = (radius as f32 * 0.6).max(3.0);
+    let stroke = (size / 3.0).max(1.0);
+    let extent = size.ceil() as i32;
+    let mut mask = Vec::new();
+    for y in -extent..=extent {
+        for x in -extent..=extent {
+            let (fx, fy) = (x as f32, y as f32);
+            let distance = fx.hypot(fy);
+            let box_distance = fx.abs().max(fy.abs());
+            let inside = match idx % PATTERNS {
+                0 => false,
+                1 => distance <= (size * 0.5).max(1.5),
+                2 => distance <= size && distance >= size - stroke,
+                3 => box_distance <= size * 0.75
+                    && ((fx - fy).abs() <= stroke * 0.75 || (fx + fy).abs() <= stroke * 0.75),
+                4 => box_distance <= size * 0.8 && (fx.abs() <= stroke / 2.0 || fy.abs() <= stroke / 2.0),
+                5 => fx.abs() <= size * 0.8 && fy.abs() <= stroke / 2.0,
+                6 => box_distance <= size * 0.7 && box_distance >= size * 0.7 - stroke,
+                _ => fy >= -size * 0.7 && fy <= size * 0.6 && fx.abs() <= (fy + size * 0.7) * 0.6,
+            };
+            if inside {
+                mask.push((x, y));
+            }
+        }
+    }
+    mask
+}
+
+/* Draw the pattern of the player at the given position in the turn order on a marble, in white
+ * with a black outline, so it stands out against any color
+ */
+pub fn pattern<T: RenderTarget>(canvas: &Canvas<T>, idx: usize, radius: i16, cx: i16, cy: i16) -> Result<(), String> {
+    let mask = pattern_mask(idx, radius as i32);
+    let covered = |x: i32, y: i32| mask.contains(&(x, y));
+    for &(x, y) in mask.iter() {
+        for (dx, dy) in [(1, 0), (-1, 0), (0, 1), (0, -1)] {
+            let (ox, oy) = (x + dx, y + dy);
+            if !covered(ox, oy) {
+                canvas.pixel(cx + ox as i16, cy + oy as i16, Color::RGB(0, 0, 0))?;
+            }
+        }
+    }
+    for &(x, y) in mask.iter() {
+        canvas.pixel(cx + x as i16, cy + y as i16, Color::RGB(255, 255, 255))?;
+    }
+    Ok(())
+}
+
+// Radius of a marble in a cell of the given size. Marbles stay round in cells that are not square.
+// In the high contrast mode, they are as large as possible without touching each other.
+fn marble_radius(cellsize: Point, high_contrast: bool) -> i32 {
+    let unit = cellsize.re.min(cellsize.im);
+    if high_contrast { unit * 7 / 40 } else { unit * 3 / 20 }
+}
+
+// Width of the sidebar in cells. The first column has the markers of the players, the second
+// their names and numbers of cells and marbles.
+pub const SIDEBAR_WIDTH: i32 = 2;
+
+/* The text, cut off with an ellipsis if it is wider than the given number of pixels */
+fn shorten(font: &Font, text: &str, width: u32) -> Result<String, String> {
+    let fits = |text: &str| font.size_of(text).map(|size| size.0 <= width).map_err(|e| e.to_string());
+    if fits(text)? {
+        return Ok(text.to_string());
+    }
+    let mut chars: Vec<char> = text.chars().collect();
+    while !chars.is_empty() {
+        chars.pop();
+        let short = format!("{}…", chars.iter().collect::<String>().trim_end());
+        if fits(&short)? {
+            return Ok(short);
+        }
+    }
+    Ok("…".to_string())
+}
+
+// Vertical center of the row of the given player in the sidebar
+fn sidebar_row(cellsize: Point, idx: usize) -> i32 {
+    let unit = cellsize.re.min(cellsize.im);
+    unit * 3 / 10 + idx as i32 * unit * 2 / 5
+}
+
+/* What is shown in the cell of the game below the mouse */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Preview {
+    // The current player may place a marble there
+    Marble(Owner),
+    // The cell belongs to someone else or is at capacity
+    Refused,
+}
+
+/* Preview for the given cell, if the game is waiting for the current player to place a marble.
+ * Nothing is shown while the marbles move, in the board editor or once the game is won.
+ */
+pub fn preview(game: &Game, cell: Point) -> Option<Preview> {
+    match game.state() {
+        State::AcceptingInput => (),
+        _ => return None,
+    }
+    if game.winner().is_some() || game.editing().is_some() {
+        return None;
+    }
+    let owner = game.cur_player();
+    match game.grid().check_move(cell, owner) {
+        Ok(()) => Some(Preview::Marble(owner)),
+        Err(_) => Some(Preview::Refused),
+    }
+}
+
+/* Cells that would change their owner if the current player placed a marble in the given cell,
+ * found by playing the move until the marbles come to rest, which leaves the game as it was. None
+ * if the player can not place a marble there, see preview().
+ */
+pub fn chain_preview(game: &mut Game, cell: Point) -> Option<Vec<OwnerChange>> {
+    let owner = match preview(game, cell)? {
+        Preview::Marble(owner) => owner,
+        Preview::Refused => return None,
+    };
+    let before = game.owners();
+    game.with_simulated_move(cell, owner, |after| owner_changes(&before, after)).ok()
+}
+
+// Limits of the zoom of the board
+const MIN_ZOOM: f32 = 0.5;
+const MAX_ZOOM: f32 = 8.0;
+// Zoom factor per step of the mouse wheel
+const ZOOM_STEP: f32 = 1.25;
+// Time over which the ring around the most recently placed marble fades out
+const LAST_MOVE_FADE: Duration = Duration::from_millis(800);
+// Number of moves, the last move and when it was first drawn, see Renderer::draw_last_move()
+type LastMove = (usize, Option<(Owner, Point)>, Option<Instant>);
+// Size of a cell on the mini-map at most, at the size of the game, and the share of the board's
+// width and height it takes at most
+const MINIMAP_CELL: i32 = 6;
+const MINIMAP_SHARE: i32 = 4;
+// Frames of one breath of a resting marble, and how many pixels of the game it grows on each side
+const BREATHING_PERIOD: u32 = 150;
+const BREATHING_AMPLITUDE: i32 = 2;
+// Opacity and size relative to a marble of the marble shown in the cell below the mouse
+const PREVIEW_ALPHA: u8 = 110;
+const PREVIEW_SIZE: f32 = 0.6;
+
+// Opacity of the names and numbers of eliminated players in the sidebar
+const DIMMED_ALPHA: u8 = 90;
+// How long the row of an eliminated player flashes
+const ELIMINATION_FLASH: Duration = Duration::from_millis(1000);
+
+// How long a message from notify() is shown
+const NOTICE_DURATION: Duration = Duration::from_secs(3);
+
+/* Zoom and position of the board within its area on the screen, left of the sidebar, in rendered
+ * pixels. At zoom 1 and without an offset, the board fills its area exactly.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Viewport {
+    // Size of the area, which is also the size of the board at zoom 1
+    area: Subpixel,
+    // Size of a cell at zoom 1. At least one cell of the board always stays within the area.
+    cellsize: Subpixel,
+    zoom: f32,
+    // Position of the top left corner of the board
+    offset: Subpixel,
+}
+impl Viewport {
+    pub fn new(dim: Point, cellsize: Point) -> Viewport {
+        let size = stretch(dim, cellsize);
+        Viewport {
+            area: Subpixel::new(size.re as f32, size.im as f32),
+            cellsize: Subpixel::new(cellsize.re as f32, cellsize.im as f32),
+            zoom: 1.0,
+            offset: Subpixel::new(0.0, 0.0),
+        }
+    }
+
+    pub fn zoom(&self) -> f32 { self.zoom }
+    pub fn offset(&self) -> Subpixel { self.offset }
+
+    /* Zoom in (positive steps) or out around the given position on the screen, which keeps
+     * showing the same part of the board.
+     */
+    pub fn zoom_at(&mut self, pos: Subpixel, steps: i32) {
+        let zoom = (self.zoom * ZOOM_STEP.powi(steps)).clamp(MIN_ZOOM, MAX_ZOOM);
+        self.offset = pos - (pos - self.offset) * (zoom / self.zoom);
+        self.zoom = zoom;
+        self.clamp();
+    }
+
+    /* Move the board by the given distance on the screen */
+    pub fn pan(&mut self, delta: Subpixel) {
+        self.offset += delta;
+        self.clamp();
+    }
+
+    /* Show the given position on the board, unzoomed, in the middle of the area */
+    pub fn center_on(&mut self, p: Subpixel) {
+        self.offset = self.area / 2.0 - p * self.zoom;
+        self.clamp();
+    }
+
+    /* The same part of the board, after the board was rendered at a different size */
+    pub fn rescaled(&self, dim: Point, cellsize: Point) -> Viewport {
+        let fresh = Viewport::new(dim, cellsize);
+        let mut viewport = Viewport { zoom: self.zoom, offset: self.offset * (fresh.area.re / self.area.re), ..fresh };
+        viewport.clamp();
+        viewport
+    }
+
+    // Keep at least one cell of the board within the area
+    fn clamp(&mut self) {
+        let size = self.shown();
+        self.offset.re = self.offset.re.clamp(self.cellsize.re - size.re, self.area.re - self.cellsize.re);
+        self.offset.im = self.offset.im.clamp(self.cellsize.im - size.im, self.area.im - self.cellsize.im);
+    }
+
+    pub fn area(&self) -> Subpixel { self.area }
+
+    /* Size of the board on the screen */
+    pub fn shown(&self) -> Subpixel {
+        self.area * self.zoom
+    }
+
+    /* Whether all of the board is within its area */
+    pub fn fits(&self) -> bool {
+        let (start, end) = (self.offset, self.offset + self.shown());
+        start.re > -0.5 && start.im > -0.5 && end.re < self.area.re + 0.5 && end.im < self.area.im + 0.5
+    }
+
+    /* Position on the screen of the given position on the unzoomed board */
+    pub fn to_screen(&self, p: Subpixel) -> Subpixel {
+        self.offset + p * self.zoom
+    }
+
+    /* Position on the unzoomed board that is shown at the given position on the screen. There is
+     * none outside of the area, where the sidebar is, or next to the board.
+     */
+    pub fn to_board(&self, pos: Subpixel) -> Option<Subpixel> {
+        if pos.re < 0.0 || pos.im < 0.0 || pos.re >= self.area.re || pos.im >= self.area.im {
+            return None;
+        }
+        let p = (pos - self.offset) / self.zoom;
+        if p.re < 0.0 || p.im < 0.0 || p.re >= self.area.re || p.im >= self.area.im {
+            return None;
+        }
+        Some(p)
+    }
+}
+
+// Hash of the position, cell and what changes with a move there, see Renderer::draw_chain()
+type ChainPreview = (u64, Point, Option<Vec<OwnerChange>>);
+
+// Opacity of the cells a held move would take
+const CHAIN_ALPHA: u8 = 120;
+
+// Number of moves, frame and cells with their new owner, see Renderer::update_flashes()
+type Flashes = (usize, u32, Vec<(Point, Owner)>);
+
+// Hash and view of the position the minimap shows and its pixels, see Renderer::draw_minimap()
+type MinimapPixels = (Option<(u64, u8)>, Vec<u8>);
+
+// Frames over which the cells that changed their owner with a move flash, how opaque the flash
+// starts and how many cells flash at most
+const FLASH_FRAMES: u32 = 30;
+const FLASH_ALPHA: u8 = 180;
+const MAX_FLASHES: usize = 256;
+
+// Opacity of the tint of the threat map and the explanation shown with it
+const THREAT_ALPHA: u8 = 100;
+const THREAT_CAPTION: &str = "Hint: green moves look good, red ones risky";
+// Time in each frame that scoring the moves for the threat map may take at most, so it does not
+// hold up the animation. The map is shown once all moves are scored.
+const THREAT_BUDGET: Duration = Duration::from_millis(4);
+
+// Opacity of the debug tint of the marbles by their slot
+const SLOT_TINT_ALPHA: u8 = 200;
+
+/* Color of the debug tint of marbles in the given set of slots */
+pub fn slot_tint(kind: SlotKind) -> Color {
+    let (r, g, b) = match kind {
+        SlotKind::Residing => (0, 110, 255),
+        SlotKind::Incoming => (0, 200, 0),
+        SlotKind::Outgoing => (230, 0, 0),
+        SlotKind::Reserve => (230, 200, 0),
+    };
+    Color::RGBA(r, g, b, SLOT_TINT_ALPHA)
+}
+
+/* Short lines along the edges at each corner of a cell, which mark the cell of the last move until
+ * the next one. They are kept away from the edges, so they do not merge with the frame around
+ * the selected cell or the grid lines.
+ */
+pub fn corner_ticks(cell: Rect) -> [Rect; 8] {
+    let unit = cell.width().min(cell.height()) as i32;
+    let (inset, length, width) = (unit / 10, unit / 5, (unit / 25).max(2));
+    let (left, top) = (cell.left() + inset, cell.top() + inset);
+    let (right, bottom) = (cell.right() - inset - width, cell.bottom() - inset - width);
+    let corners = [(left, top, 1, 1), (right, top, -1, 1), (left, bottom, 1, -1), (right, bottom, -1, -1)];
+    let mut ticks = [Rect::new(0, 0, 1, 1); 8];
+    for (idx, (x, y, dx, dy)) in corners.into_iter().enumerate() {
+        // Arms from the corner pixel towards the inside of the cell
+        let arm_x = if dx > 0 { x } else { x + width - length };
+        let arm_y = if dy > 0 { y } else { y + width - length };
+        ticks[2*idx] = Rect::new(arm_x, y, length as u32, width as u32);
+        ticks[2*idx + 1] = Rect::new(x, arm_y, width as u32, length as u32);
+    }
+    ticks
+}
+
+/* Position in pixels after turning a board of the given size in pixels clockwise by the given
+ * number of quarter turns, like rotate_point() does for cells
+ */
+pub fn rotate_subpixel(pos: Subpixel, size: Subpixel, quarter_turns: u8) -> Subpixel {
+    let (mut pos, mut size) = (pos, size);
+    for _ in 0..quarter_turns % 4 {
+        pos = Subpixel::new(size.im - pos.im, pos.re);
+        size = Subpixel::new(size.im, size.re);
+    }
+    pos
+}
+
+/* Rectangle of the texture of a marble at the given position, scaled to the output. The position is
+ * only rounded to whole pixels after scaling, so moving marbles do not jump by whole logical pixels.
+ */
+pub fn marble_rect(pos: Subpixel, scale: i32, radius: i32) -> Rect {
+    let center = round_subpixel(pos * scale as f32);
+    let size = 2*radius as u32 + 1;
+    Rect::new(center.re - radius, center.im - radius, size, size)
+}
+
+// Opacity of the newest dot of the trail of a travelling marble, see Trails
+const TRAIL_ALPHA: u8 = 140;
+
+// Largest offset of the board while it shakes, relative to the cell size
+const SHAKE_SHARE: i32 = 10;
+
+// Opacity of the shadows of the marbles below their center
+const SHADOW_ALPHA: u8 = 90;
+
+/* Opacity of the shadow of a marble at the given distance from its center, relative to the radius
+ * of the shadow. It fades out towards the edge, so the shadow looks soft.
+ */
+pub fn shadow_alpha(distance: f32) -> u8 {
+    if distance >= 1.0 {
+        return 0;
+    }
+    let falloff = 1.0 - distance;
+    (SHADOW_ALPHA as f32 * falloff * falloff).round() as u8
+}
+
+/* How far a travelling marble is lifted, given the fraction of the way it has left. It rises from
+ * 0 when it is sent to 1 halfway and falls back to 0 when it arrives.
+ */
+pub fn shadow_lift(remaining: f32) -> f32 {
+    (std::f32::consts::PI * (1.0 - remaining)).sin().max(0.0)
+}
+
+/* Rectangle of the shadow of a marble drawn at the given rectangle, below and right of it and
+ * flatter than the marble. Marbles are lifted by up to 1 while they travel between cells, which
+ * moves the shadow further away and flattens it more, see shadow_lift().
+ */
+pub fn shadow_rect(marble: Rect, lift: f32) -> Rect {
+    let size = marble.width() as f32;
+    let offset = (size * (0.12 + 0.12 * lift)).round() as i32;
+    let height = (size * (0.75 - 0.2 * lift)).round().max(1.0) as u32;
+    let center = marble.center();
+    Rect::new(
+        center.x() - marble.width() as i32 / 2 + offset,
+        center.y() - height as i32 / 2 + offset,
+        marble.width(),
+        height,
+    )
+}
+
+// Frames over which a placed marble grows to its full size
+const SPAWN_FRAMES: u32 = 10;
+// Size at which a placed marble appears, relative to its full size
+const SPAWN_START: f32 = 0.3;
+
+/* Size of a marble the given number of frames after it was placed, relative to its full size. It
+ * grows from SPAWN_START, slightly past its full size and back, like the marbles settling into
+ * their slots, see ease().
+ */
+pub fn spawn_scale(age: u32) -> f32 {
+    if age >= SPAWN_FRAMES {
+        return 1.0;
+    }
+    SPAWN_START + (1.0 - SPAWN_START) * ease(age as f32 / SPAWN_FRAMES as f32)
+}
+
+/* The rectangle scaled by the given factor around its center */
+pub fn scaled_rect(rect: Rect, scale: f32) -> Rect {
+    let width = (rect.width() as f32 * scale).round().max(1.0) as u32;
+    let height = (rect.height() as f32 * scale).round().max(1.0) as u32;
+    Rect::from_center(rect.center(), width, height)
+}
+
+/* Grow the rectangle of a resting marble by up to the given number of pixels on each side, on a
+ * slow sine of the frame. Each marble is out of phase with the others, and it never grows beyond
+ * its cell.
+ */
+pub fn breathe(rect: Rect, cell: Rect, frame: u32, id: u32, amplitude: i32) -> Rect {
+    // Spread the phases evenly by the golden ratio, so neighbors do not breathe in sync
+    let phase = (id as f32 * 0.618_034).fract();
+    let angle = ((frame % BREATHING_PERIOD) as f32 / BREATHING_PERIOD as f32 + phase) * std::f32::consts::TAU;
+    let grow = (amplitude as f32 * (0.5 + 0.5 * angle.sin())).round() as i32;
+    // Marbles stay round, so they grow only as far as the closest edge of the cell allows
+    let room = (rect.left() - cell.left()).min(rect.top() - cell.top())
+        .min(cell.right() - rect.right()).min(cell.bottom() - rect.bottom());
+    let grow = grow.min(room).max(0);
+    Rect::new(
+        rect.x() - grow, rect.y() - grow,
+        rect.width() + 2 * grow as u32, rect.height() + 2 * grow as u32,
+    )
+}
+
+// Which cells that are about to spread are highlighted
+#[derive(Clone, Copy, PartialEq)]
+pub enum CriticalHighlight {
+    Off,
+    All,
+    CurrentPlayer,
+}
+
+/* How the board is shown, which the players change while playing. run_game() hands them to each
+ * renderer it creates, so they stay as they were when the board is turned or the window resized.
+ */
+#[derive(Clone, Copy)]
+pub struct ViewSettings {
+    // Quarter turns the board is shown with
+    pub view: u8,
+    pub coords: bool,
+    // Frame each cell with marbles in the color of its owner
+    pub owners: bool,
+    pub shadows: bool,
+    // Sparks of cells that fired and trails of travelling marbles
+    pub particles: bool,
+    pub shake: bool,
+    pub breathing: bool,
+    // Tint each cell the current player may place a marble on by how good the move looks
+    pub threats: bool,
+    // Debug overlay that marks each marble with the color of the set of slots it is in, see
+    // slot_tint(). Screenshots leave it out unless they are taken with Shift.
+    pub slot_tint: bool,
+    pub critical_highlight: CriticalHighlight,
+    pub help: bool,
+}
+impl ViewSettings {
+    pub fn new(config: &Config) -> ViewSettings {
+        ViewSettings {
+            view: 0,
+            coords: true,
+            owners: false,
+            // They would only blur the outlines that make the marbles stand out
+            shadows: !config.high_contrast,
+            particles: true,
+            // Off unless toggled
+            shake: false,
+            breathing: true,
+            threats: false,
+            slot_tint: false,
+            critical_highlight: CriticalHighlight::Off,
+            help: false,
+        }
+    }
+}
+
+/* Fonts the renderer draws with, loaded at the sizes for the scale it renders at */
+#[derive(Clone, Copy)]
+pub struct Fonts<'a> {
+    // Text that changes while playing
+    pub text: &'a Font<'a, 'static>,
+    // Coordinate labels, which are larger in the high contrast mode
+    pub labels: &'a Font<'a, 'static>,
+}
+
 // Rendering helper. This pre-renders all required textures and copies them to the board
 // accordingly.
 pub struct Renderer<'a> {
+    creator: &'a TextureCreator<WindowContext>,
+    // Colors of the board and the sidebar, which is the high contrast theme in that mode
+    theme: Theme,
+    // Font for text that changes while playing. Without a font, no text is shown.
+    font: Option<&'a Font<'a, 'static>>,
+    // Dimension of the board as shown, which is turned clockwise by the quarter turns of the view
     dim: Point,
+    // Factor between the coordinates of the game and the pixels the board is rendered at
+    scale: i32,
+    // Size of a cell in rendered pixels
+    cellsize: Point,
+    // Radius of the marbles, which scales with the cell size
+    radius: i32,
     background: Texture<'a>,
-    marbles: Vec<Texture<'a>>,
+    // Everything that pans and zooms with the board is drawn here first
+    board: RefCell<Texture<'a>>,
+    viewport: Viewport,
+    // One pixel per cell in the color of its owner, shown while the board does not fit
+    minimap: RefCell<Texture<'a>>,
+    minimap_pixels: RefCell<MinimapPixels>,
+    // One marble per player, which is also shown translucent as preview below the mouse
+    marbles: RefCell<Vec<Texture<'a>>>,
+    // Position of the mouse on the screen while it is in the window
+    hover: Option<(i32, i32)>,
+    // Cell of the game the mouse button is held on, whose move is previewed until it is released
+    held: Option<Point>,
+    // Hash of the position, cell and changes of the last preview, see chain_preview()
+    chain: Option<ChainPreview>,
+    neutral_marble: Texture<'a>,
+    // Soft dark ellipse drawn below each marble, see shadow_rect()
+    shadow: Texture<'a>,
+    // Marbles of the last frame with their rectangle, slot, id and owner, and how far each marble in
+    // flight is lifted, kept to draw the next frame without allocating, see draw_board()
+    placed: RefCell<Vec<(Rect, SlotKind, u32, Owner)>>,
+    lifts: RefCell<HashMap<u32, f32>>,
+    // Frames around the cells of each player in the last frame, kept to draw the next one without
+    // allocating, see draw_owners()
+    owner_frames: RefCell<Vec<Vec<Rect>>>,
+    settings: ViewSettings,
+    // Marble of each player in gray, shown in the sidebar once they are eliminated
+    grey_marbles: Vec<Texture<'a>>,
     active_marker: Texture<'a>,
     dead_marker: Texture<'a>,
+    // Whether each player was alive in the last frame and when they were eliminated, so their row
+    // in the sidebar flashes once
+    eliminated: RefCell<Vec<(bool, Option<Instant>)>>,
     selected: Texture<'a>,
+    coords: Vec<(Texture<'a>, Rect)>,
+    // One line per action with its key
+    help: Vec<Texture<'a>>,
+    // Keys for a rematch, the menu and quitting, shown once the game is over
+    game_over_hint: String,
+    // Key for ending the game once the players are separated, shown while it is offered
+    separated_hint: String,
+    // Short message at the bottom of the board and when it was given, see notify()
+    notice: Option<(String, Instant)>,
+    // Number of the most recent moves that are scrolled past in the move history
+    history_scroll: usize,
+    // Scoring of the moves of the current player and the position it is for, so it is only done
+    // once per turn, see look_ahead()
+    threats: Option<(u64, ai::MoveScorer)>,
+    // Hash of the last settled position and the cells and marbles of each player in it, shown in
+    // the sidebar
+    counts: RefCell<(u64, Vec<(usize, usize)>)>,
+    // Cells and marbles of each player as shown and the rendered text of them
+    count_texts: RefCell<Vec<((usize, usize), Texture<'a>)>>,
+    // Name of each player as given and the rendered text of it, shortened to fit the sidebar
+    names: RefCell<Vec<(String, Texture<'a>)>>,
+    // White ring inside a cell, tinted in the color of the player and faded to highlight cells
+    // that spread with one more marble
+    critical_ring: RefCell<Texture<'a>>,
+    // Reference for time based effects
+    created: Instant,
+    // Sparks of cells that fired, in rendered pixels of the board, and when they last moved
+    particles: RefCell<Particles>,
+    last_update: Cell<Instant>,
+    // Recent positions of the travelling marbles in rendered pixels of the board
+    trails: RefCell<Trails>,
+    // Shaking of the board when cells fire
+    shake: RefCell<Shake>,
+    // Frames drawn so far, which drive the breathing of resting marbles
+    frame: Cell<u32>,
+    // Text of draw_text() rendered in white and the frame it was last drawn in. Text that was not
+    // drawn in the last frame is dropped.
+    texts: RefCell<HashMap<String, (Texture<'a>, u32)>>,
+    // Cells that changed their owner with the last move and their new owner, with the number of
+    // moves when that move settled and the frame their flash started, see update_flashes()
+    flashes: RefCell<Flashes>,
+    // Identifier of the marble placed last and the frame it appeared in, which grows into its slot,
+    // see spawn_scale()
+    spawn: Cell<(Option<u32>, u32)>,
+    // Fading ring of draw_last_move(). There is none for a move played before the renderer was
+    // created.
+    last_move_at: Cell<LastMove>,
 }
 impl<'a> Renderer<'a> {
 
-    fn add_coords(background: &mut Canvas<Surface>, dim: Point, cellsize: i32) -> Result<(), String> {
-        let fontcontext = ttf::init().map_err(|e| e.to_string())?;
-        let font = fontcontext.load_font("/usr/share/fonts/liberation/LiberationMono-Regular.ttf", 18)?;
-        let creator = background.texture_creator();
-        let mut render = |character: u8, posx: i32, posy: i32| -> Result<(), String> {
-            let bytes: [u8; 1] = [character];
-            let s = str::from_utf8(&bytes).map_err(|e| e.to_string())?;
-            let rendered = font.render(&s).blended(Color::RGB(0,0,0))
+    // Render the coordinate labels along the top and left edge of the board in the given color,
+    // moved away from the edge by the given factor, which is the size of the font for the labels
+    // relative to the one for the text
+    fn create_coords(
+        creator: &'a TextureCreator<WindowContext>,
+        font: Option<&Font>,
+        columns: &[String],
+        rows: &[String],
+        cellsize: Point,
+        color: Color,
+        enlarge: f32,
+    ) -> Result<Vec<(Texture<'a>, Rect)>, String> {
+        let mut coords = Vec::with_capacity(columns.len() + rows.len());
+        let font = match font {
+            Some(font) => font,
+            None => return Ok(coords),
+        };
+        // Render a label centered vertically at posy and either centered horizontally at posx or
+        // starting at posx.
+        let mut render = |label: &str, posx: i32, posy: i32, centered: bool| -> Result<(), String> {
+            let rendered = font.render(label).blended(color)
                 .map_err(|e| e.to_string())?;
-            let texture = rendered.as_texture(&creator)
+            let (width, height) = (rendered.width(), rendered.height());
+            let posx = if centered { posx - width as i32/2 } else { posx };
+            let rect = Rect::new(posx, posy - height as i32/2, width, height);
+            let texture = rendered.as_texture(creator)
                 .map_err(|e| e.to_string())?;
-            background.copy(
-                &texture,
-                None,
-                Some(
-                    Rect::new(
-                        posx - rendered.width() as i32/2,
-                        posy - rendered.height() as i32/2,
-                        rendered.width(),
-                        rendered.height()
-                    )
-                )
-            )?;
+            coords.push((texture, rect));
             Ok(())
         };
-        let cellsize = cellsize as i32;
-        for i in 0..dim.re {
-            render(65+i as u8, cellsize * i + cellsize/2, 10)?;
+        for (i, label) in (0..).zip(columns.iter()) {
+            render(label, cellsize.re * i + cellsize.re/2, (10.0 * enlarge) as i32, true)?;
         };
-        for i in 0..dim.im{
-            render(49+i as u8, 10, cellsize * i + cellsize/2)?;
+        for (i, label) in (0..).zip(rows.iter()) {
+            render(label, (3.0 * enlarge) as i32, cellsize.im * i + cellsize.im/2, false)?;
         }
-        Ok(())
+        Ok(coords)
     }
 
-    pub fn new(creator: &'a TextureCreator<WindowContext>, game: &Game)
-        -> Result<Renderer<'a>, String>
-    {
-        let black = Color::RGB(0, 0, 0);
+    pub fn new(
+        creator: &'a TextureCreator<WindowContext>,
+        fonts: Option<Fonts<'a>>,
+        keymap: &KeyMap,
+        game: &Game,
+        theme: &Theme,
+        scale: i32,
+        settings: ViewSettings,
+    ) -> Result<Renderer<'a>, String> {
+        let view = settings.view % 4;
+        let settings = ViewSettings { view, ..settings };
+        let grid = game.grid().rotated(view, game.cellsize());
+        let dim = grid.dim();
+        // Labels of the columns and rows as shown, which keep naming the same cells when the board
+        // is turned
+        let label = |p: Point, q: Point| {
+            let (p, q) = (rotate_point(p, dim, 4 - view % 4), rotate_point(q, dim, 4 - view % 4));
+            if p.re == q.re { Grid::column_label(p.re) } else { Grid::row_label(p.im) }
+        };
+        let columns: Vec<String> = (0..dim.re).map(|x| label(Point::new(x, 0), Point::new(x, 1))).collect();
+        let rows: Vec<String> = (0..dim.im).map(|y| label(Point::new(1, y), Point::new(0, y))).collect();
+        let cellsize = game.cellsize() * scale;
+        let (cell_w, cell_h) = (cellsize.re as u32, cellsize.im as u32);
+        let high_contrast = game.config().high_contrast;
+        let patterns = game.config().patterns;
+        let theme = if high_contrast { Theme::HIGH_CONTRAST } else { *theme };
+        let radius = marble_radius(cellsize, high_contrast);
+        let size = 2*radius as u32 + 1;
+        let marble = |canvas: &Canvas<Surface>, radius: i16, cx: i16, cy: i16, color: Color| {
+            if high_contrast {
+                bold_marble(canvas, radius, cx, cy, color)
+            } else {
+                gradient(canvas, radius, cx, cy, color, theme.background)
+            }
+        };
+        // Width of the grid lines, which grow with the scale so they remain visible on HiDPI
+        // displays, and of the frame around the selected cell, which needs to stand out against
+        // the grid lines and grows with the cells
+        let unit = cellsize.re.min(cellsize.im);
+        let line = game.config().line_width as i32 * scale * if high_contrast { 3 } else { 1 };
+        let frame = if high_contrast { (unit * 6 / 100).max(4) } else { (unit / 50).max(2) }.max(line + 2);
+        let enlarge = if high_contrast { LABEL_ENLARGE } else { 1.0 };
+        let font = fonts.map(|fonts| fonts.text);
+        let background = theme.background;
 
         // Marbles
         let mut marbles = Vec::with_capacity(game.num_players());
-        for player in game.players() {
-            marbles.push(
-                create_texture(creator, 31, 31, |canvas| {
-                    gradient(&canvas, 15, 15, 15, player.color())?;
-                    Ok(())
-                })?
-            );
+        let mut grey_marbles = Vec::with_capacity(game.num_players());
+        for (idx, player) in game.players().enumerate() {
+            for (textures, color) in [(&mut marbles, player.color()), (&mut grey_marbles, greyed(player.color(), background))] {
+                textures.push(
+                    create_texture(creator, size, size, |canvas| {
+                        marble(&canvas, radius as i16, radius as i16, radius as i16, color)?;
+                        if patterns {
+                            pattern(&canvas, idx, radius as i16, radius as i16, radius as i16)?;
+                        }
+                        Ok(())
+                    })?
+                );
+            }
         }
 
-        let dim = game.dim();
-        let cellsize = game.cellsize();
-        let ucellsize = cellsize as u32;
-
         Ok(Renderer{
-            dim: dim,
+            dim,
+            scale,
+            cellsize,
+            radius,
             background: create_texture(
-                creator, ucellsize*(dim.re+1) as u32, ucellsize*dim.im as u32,
-                |mut canvas| {
-                    canvas.set_draw_color(Color::RGB(200, 200, 200));
+                creator, cell_w*(dim.re+SIDEBAR_WIDTH) as u32, cell_h*dim.im as u32,
+                |canvas| {
+                    canvas.set_draw_color(background);
                     canvas.clear();
-                    Renderer::add_coords(&mut canvas, dim, cellsize)?;
-                    let cellsize = cellsize as i16;
-                    let dimx = dim.re as i16;
-                    let dimy = dim.im as i16;
-                    for x in 0..=dimx {
-                        canvas.vline(x * cellsize, 0, cellsize * dimy, black)?;
+                    canvas.box_(
+                        (dim.re * cellsize.re) as i16, 0,
+                        ((dim.re + SIDEBAR_WIDTH) * cellsize.re) as i16, (dim.im * cellsize.im) as i16,
+                        theme.sidebar,
+                    )?;
+                    // Cells outside of the shape of the board look like the surroundings of the
+                    // board and only playable cells get a border.
+                    let (w, h) = (cellsize.re as i16, cellsize.im as i16);
+                    for (coord, cell) in grid.cells() {
+                        if !cell.playable() {
+                            let corner = stretch(coord, cellsize);
+                            let (x, y) = (corner.re as i16, corner.im as i16);
+                            canvas.box_(x, y, x+w-1, y+h-1, theme.clear)?;
+                        }
                     }
-                    for y in 0..dimy as i16 {
-                        canvas.hline(0, cellsize * dimx, y*cellsize, black)?;
+                    // Without grid lines, the slots alone show where the cells are
+                    let (board_w, board_h) = (dim.re * cellsize.re, dim.im * cellsize.im);
+                    for (coord, _) in grid.cells().filter(|(_, cell)| cell.playable() && line > 0) {
+                        let corner = stretch(coord, cellsize);
+                        let (left, _) = line_span(corner.re, line, board_w);
+                        let (_, right) = line_span(corner.re + cellsize.re, line, board_w);
+                        let (top, _) = line_span(corner.im, line, board_h);
+                        let (_, bottom) = line_span(corner.im + cellsize.im, line, board_h);
+                        for x in [corner.re, corner.re + cellsize.re] {
+                            let (start, end) = line_span(x, line, board_w);
+                            canvas.box_(start as i16, top as i16, end as i16, bottom as i16, theme.lines)?;
+                        }
+                        for y in [corner.im, corner.im + cellsize.im] {
+                            let (start, end) = line_span(y, line, board_h);
+                            canvas.box_(left as i16, start as i16, right as i16, end as i16, theme.lines)?;
+                        }
                     }
-                    let cellsize = cellsize as i32;
-                    for coord in PointIter::new(dim) {
-                        let cell = game.grid().cell(coord);
-                        let center = coord*cellsize + Point::new(cellsize/2, cellsize/2);
+                    for (coord, _) in grid.cells().filter(|(_, cell)| cell.bonus()) {
+                        let center = stretch(coord, cellsize) + cellsize / 2;
+                        star(&canvas, unit as f32 / 6.0, center.re as i16, center.im as i16, Color::RGB(240, 190, 30))?;
+                    }
+                    for (coord, cell) in grid.cells() {
                         for direction in 0..4 {
                             if !cell.has_neighbor(direction) {
                                 continue
                             }
-                            let pos = center + cellsize/4*DIRECTIONS[direction];
+                            let pos = slot_position(coord, direction, cellsize);
                             let cx = pos.re as i16;
                             let cy = pos.im as i16;
-                            gradient(&canvas, 15, cx, cy, Color::RGB(255, 255, 255))?;
+                            gradient(&canvas, radius as i16, cx, cy, theme.slot, background)?;
                         }
                     }
 
                     for (idx, player) in game.players().enumerate() {
-                        let x = (dim.re * cellsize + cellsize/2) as i16;
-                        let y = (30 + idx * 40) as i16;
-                        gradient(&canvas, 15, x, y, player.color())?;
+                        let x = (dim.re * cellsize.re + cellsize.re/2) as i16;
+                        let y = sidebar_row(cellsize, idx) as i16;
+                        marble(&canvas, radius as i16, x, y, player.color())?;
+                        if patterns {
+                            pattern(&canvas, idx, radius as i16, x, y)?;
+                        }
                     }
                     Ok(())
                 },
             )?,
-            marbles: marbles,
+            board: RefCell::new(
+                creator.create_texture_target(PixelFormatEnum::RGBA8888, cell_w*dim.re as u32, cell_h*dim.im as u32)
+                    .map_err(|e| e.to_string())?
+            ),
+            viewport: Viewport::new(dim, cellsize),
+            minimap: RefCell::new(
+                creator.create_texture_streaming(PixelFormatEnum::RGBA32, dim.re as u32, dim.im as u32)
+                    .map_err(|e| e.to_string())?
+            ),
+            minimap_pixels: RefCell::new((None, vec![0; (dim.re * dim.im * 4) as usize])),
+            marbles: RefCell::new(marbles),
+            hover: None,
+            held: None,
+            chain: None,
+            neutral_marble: create_texture(creator, size, size, |canvas| {
+                marble(&canvas, radius as i16, radius as i16, radius as i16, Color::RGB(120, 120, 120))?;
+                Ok(())
+            })?,
+            shadow: create_texture(creator, size, size, |canvas| {
+                let center = radius as f32;
+                for y in 0..size as i16 {
+                    for x in 0..size as i16 {
+                        let distance = Complex::new(x as f32 - center, y as f32 - center).norm() / (center + 0.5);
+                        canvas.pixel(x, y, Color::RGBA(0, 0, 0, shadow_alpha(distance)))?;
+                    }
+                }
+                Ok(())
+            })?,
+            placed: RefCell::new(Vec::new()),
+            lifts: RefCell::new(HashMap::new()),
+            owner_frames: RefCell::new(vec![Vec::new(); game.num_players()]),
+            settings,
+            grey_marbles,
+            eliminated: RefCell::new(game.players().map(|player| (player.alive, None)).collect()),
+            // The markers in the sidebar have the size of the marbles next to them
             active_marker: create_texture(
-                creator, 31, 31, |canvas| {
-                    canvas.filled_pie(25, 15, 20, 160, 200, black)?;
+                creator, size, size, |canvas| {
+                    let s = size as i16 - 1;
+                    canvas.filled_pie(s*5/6, s/2, s*2/3, 160, 200, theme.text)?;
                     Ok(())
                 },
             )?,
             dead_marker: create_texture(
-                creator, 31, 31, |canvas| {
-                    canvas.thick_line(0, 0, 30, 30, 3, black)?;
-                    canvas.thick_line(0, 30, 30, 0, 3, black)?;
+                creator, size, size, |canvas| {
+                    let s = size as i16 - 1;
+                    let width = (s / 10).max(1) as u8;
+                    canvas.thick_line(0, 0, s, s, width, theme.text)?;
+                    canvas.thick_line(0, s, s, 0, width, theme.text)?;
                     Ok(())
                 },
             )?,
             selected: create_texture(
-                creator, ucellsize, ucellsize, |canvas| {
-                    let cellsize = cellsize as i16;
-                    canvas.thick_line(1, 1, cellsize, 1, 2, black)?;
-                    canvas.thick_line(1, 1, 1, cellsize, 2, black)?;
-                    canvas.thick_line(cellsize, 1, cellsize, cellsize, 2, black)?;
-                    canvas.thick_line(1, cellsize, cellsize, cellsize, 2, black)?;
+                creator, cell_w, cell_h, |canvas| {
+                    // Inside of the texture, covering the half of the grid lines in the cell
+                    let (w, h, f) = (cellsize.re as i16 - 1, cellsize.im as i16 - 1, frame as i16);
+                    canvas.box_(0, 0, w, f - 1, theme.selection)?;
+                    canvas.box_(0, h - f + 1, w, h, theme.selection)?;
+                    canvas.box_(0, 0, f - 1, h, theme.selection)?;
+                    canvas.box_(w - f + 1, 0, w, h, theme.selection)?;
                     Ok(())
                 },
             )?,
+            coords: Renderer::create_coords(creator, fonts.map(|fonts| fonts.labels), &columns, &rows, cellsize, contrasting(background), enlarge)?,
+            help: Renderer::create_help(creator, font, keymap)?,
+            game_over_hint: format!(
+                "{} rematch   {} menu   {} quit",
+                keymap.key(Action::Rematch).name(), keymap.key(Action::Menu).name(), keymap.key(Action::Quit).name(),
+            ),
+            separated_hint: format!(
+                "Nobody can reach the others: {} ends the game, most marbles win",
+                keymap.key(Action::EndSeparated).name(),
+            ),
+            notice: None,
+            history_scroll: 0,
+            creator,
+            theme,
+            font,
+            threats: None,
+            counts: RefCell::new((game.grid().hash(), game.grid().count_per_owner(game.num_players()))),
+            count_texts: RefCell::new(Vec::new()),
+            names: RefCell::new(Vec::new()),
+            critical_ring: RefCell::new(create_texture(
+                creator, cell_w, cell_h, |canvas| {
+                    let (w, h) = (cellsize.re as i16, cellsize.im as i16);
+                    for inset in 3..6 {
+                        canvas.rectangle(inset, inset, w - inset, h - inset, Color::RGB(255, 255, 255))?;
+                    }
+                    Ok(())
+                },
+            )?),
+            created: Instant::now(),
+            particles: RefCell::new(Particles::new(game.config().seed)),
+            last_update: Cell::new(Instant::now()),
+            trails: RefCell::new(Trails::new()),
+            shake: RefCell::new(Shake::new(game.config().seed ^ 0x5EED_5A4E)),
+            frame: Cell::new(0),
+            texts: RefCell::new(HashMap::new()),
+            flashes: RefCell::new((game.history().len(), 0, Vec::new())),
+            // Marbles that are already there do not grow
+            spawn: Cell::new((game.grid().last_placed(), 0u32.wrapping_sub(SPAWN_FRAMES))),
+            last_move_at: Cell::new((game.history().len(), game.last_move(), None)),
         })
     }
 
-    pub fn update(&self, canvas: &mut Canvas<Window>, game: &Game) -> Result<(), String>{
-        let grid = game.grid();
-        let cellsize = game.cellsize();
-        canvas.copy(&self.background, None, None)?;
-        for marble in grid.marbles() {
-            let rect = Rect::new(marble.get_pos().re-15, marble.get_pos().im-15, 31, 31);
-            canvas.copy(
-                &self.marbles[marble.get_owner()],
-                None,
-                Some(rect),
-            )?
-        }
-        let rect = Rect::new(self.dim.re as i32*cellsize as i32 + 5, game.cur_player() as i32*40 + 15, 30, 31);
-        canvas.copy(
-            &self.active_marker,
-            None,
-            Some(rect),
+    // Render the lines of the help overlay, listing the key for each action
+    fn create_help(creator: &'a TextureCreator<WindowContext>, font: Option<&Font>, keymap: &KeyMap)
+        -> Result<Vec<Texture<'a>>, String>
+    {
+        let mut help = Vec::with_capacity(Action::ALL.len());
+        let font = match font {
+            Some(font) => font,
+            None => return Ok(help),
+        };
+        for line in keymap.help_lines() {
+            let rendered = font.render(&line).blended(Color::RGB(255, 255, 255))
+                .map_err(|e| e.to_string())?;
+            help.push(rendered.as_texture(creator).map_err(|e| e.to_string())?);
+        }
+        Ok(help)
+    }
+
+    pub fn toggle_help(&mut self) {
+        self.settings.help = !self.settings.help;
+    }
+
+    fn draw_help(&self, canvas: &mut Canvas<Window>) -> Result<(), String> {
+        let height: u32 = self.help.iter().map(|line| line.query().height + 4).sum();
+        let width = self.help.iter().map(|line| line.query().width).max().unwrap_or(0);
+        let (x, y) = (20, 20);
+        canvas.box_(
+            x as i16 - 10, y as i16 - 10,
+            (x + width as i32 + 10) as i16, (y + height as i32 + 10) as i16,
+            Color::RGBA(0, 0, 0, 200),
         )?;
+        let mut y = y;
+        for line in self.help.iter() {
+            let query = line.query();
+            canvas.copy(line, None, Some(Rect::new(x, y, query.width, query.height)))?;
+            y += query.height as i32 + 4;
+        }
+        Ok(())
+    }
+
+    /* Colors everything is drawn with */
+    pub fn theme(&self) -> &Theme {
+        &self.theme
+    }
+
+    /* Render everything at another scale, see render_scale(), keeping what goes on on the board.
+     * Sparks and trails are in rendered pixels, so they are dropped.
+     */
+    pub fn rescale(
+        &mut self, fonts: Option<Fonts<'a>>, keymap: &KeyMap, game: &Game, scale: i32,
+    ) -> Result<(), String> {
+        let fresh = Renderer::new(self.creator, fonts, keymap, game, &self.theme, scale, self.settings)?;
+        let old = std::mem::replace(self, fresh);
+        self.viewport = old.viewport.rescaled(self.dim, self.cellsize);
+        self.held = old.held;
+        self.chain = old.chain;
+        self.eliminated = old.eliminated;
+        self.notice = old.notice;
+        self.history_scroll = old.history_scroll;
+        self.threats = old.threats;
+        self.counts = old.counts;
+        self.created = old.created;
+        self.last_update = old.last_update;
+        self.shake = old.shake;
+        self.frame = old.frame;
+        self.flashes = old.flashes;
+        self.spawn = old.spawn;
+        self.last_move_at = old.last_move_at;
+        Ok(())
+    }
+
+    /* How the board is shown, to be handed to the next renderer */
+    pub fn settings(&self) -> ViewSettings {
+        self.settings
+    }
+
+    pub fn toggle_coords(&mut self) {
+        self.settings.coords = !self.settings.coords;
+    }
+
+    pub fn toggle_owners(&mut self) {
+        self.settings.owners = !self.settings.owners;
+    }
+
+    pub fn toggle_particles(&mut self) {
+        self.settings.particles = !self.settings.particles;
+        self.particles.borrow_mut().clear();
+        self.trails.borrow_mut().clear();
+    }
+
+    pub fn toggle_shake(&mut self) {
+        self.settings.shake = !self.settings.shake;
+        self.shake.borrow_mut().clear();
+    }
+
+    pub fn toggle_shadows(&mut self) {
+        self.settings.shadows = !self.settings.shadows;
+    }
+
+    pub fn toggle_breathing(&mut self) {
+        self.settings.breathing = !self.settings.breathing;
+    }
+
+    /* Move the particles by the time since the last frame and add a burst for each cell that
+     * fired since then
+     */
+    fn update_particles(&self, game: &Game) {
+        let now = Instant::now();
+        let mut particles = self.particles.borrow_mut();
+        let elapsed = now - self.last_update.replace(now);
+        // Sparks slow down with the marbles
+        particles.advance(if game.slow_motion() { elapsed / SLOW_MOTION } else { elapsed });
+        if !self.settings.particles {
+            return;
+        }
+        let unit = self.cellsize.re.min(self.cellsize.im) as f32;
+        for event in game.events() {
+            if let GridEvent::CellFired { at, owner } = *event {
+                let center = stretch(rotate_point(at, game.dim(), self.settings.view), self.cellsize) + self.cellsize/2;
+                let color = match owner {
+                    NEUTRAL => Color::RGB(120, 120, 120),
+                    owner => game.player(owner).color(),
+                };
+                particles.burst(Complex::new(center.re as f32, center.im as f32), color, 2.0 * unit);
+            }
+        }
+    }
+
+    /* Offset of the board in this frame, after adding the shake of the cells that fired since the
+     * last one. Only the drawn board moves; clicks are still mapped without the offset.
+     */
+    fn update_shake(&self, game: &Game) -> Point {
+        if !self.settings.shake {
+            return Point::new(0, 0);
+        }
+        let mut shake = self.shake.borrow_mut();
+        shake.add(game.events().iter().filter(|event| matches!(event, GridEvent::CellFired { .. })).count());
+        let (x, y) = shake.next_offset(self.cellsize.re.min(self.cellsize.im) / SHAKE_SHARE);
+        Point::new(x, y)
+    }
+
+    /* Start flashing the cells a move gained once its marbles came to rest, at most MAX_FLASHES of
+     * them. A move that starts before the flashes faded out ends them.
+     */
+    fn update_flashes(&self, game: &Game) {
+        let mut flashes = self.flashes.borrow_mut();
+        let moves = game.history().len();
+        if flashes.0 == moves {
+            return;
+        }
+        flashes.2.clear();
+        if let State::AcceptingInput = game.state() {
+            let gained = game.last_owner_changes().iter()
+                .filter_map(|change| change.to.filter(|&owner| owner != NEUTRAL).map(|owner| (change.at, owner)));
+            flashes.2.extend(gained.take(MAX_FLASHES));
+            flashes.0 = moves;
+            flashes.1 = self.frame.get();
+        }
+    }
+
+    /* Start growing the marble that was placed since the last frame, if any. A marble that makes
+     * its cell spread is shown at its full size at once, so it does not hold up the cascade.
+     */
+    fn update_spawn(&self, game: &Game) {
+        let placed = game.grid().last_placed();
+        let frame = self.frame.get();
+        if placed != self.spawn.get().0 {
+            self.spawn.set((placed, frame));
+        }
+        if let State::Animating(_) = game.state() {
+            self.spawn.set((placed, frame.wrapping_sub(SPAWN_FRAMES)));
+        }
+    }
+
+    /* Cover each cell that changed its owner with a bright tint of the new owner's color, fading
+     * out over FLASH_FRAMES
+     */
+    fn draw_flashes(&self, canvas: &mut Canvas<Window>, game: &Game) -> Result<(), String> {
+        let flashes = self.flashes.borrow();
+        let age = self.frame.get().wrapping_sub(flashes.1);
+        if age >= FLASH_FRAMES {
+            return Ok(());
+        }
+        let alpha = (FLASH_ALPHA as u32 * (FLASH_FRAMES - age) / FLASH_FRAMES) as u8;
+        let (w, h) = (self.cellsize.re as i16, self.cellsize.im as i16);
+        for &(p, owner) in flashes.2.iter() {
+            let color = game.player(owner).color();
+            // Halfway to white, so it stands out against the marbles of the same color
+            let bright = |c: u8| ((c as u16 + 255) / 2) as u8;
+            let corner = stretch(rotate_point(p, game.dim(), self.settings.view), self.cellsize);
+            let (x, y) = (corner.re as i16, corner.im as i16);
+            canvas.box_(x, y, x + w - 1, y + h - 1, Color::RGBA(bright(color.r), bright(color.g), bright(color.b), alpha))?;
+        }
+        Ok(())
+    }
+
+    /* Add the positions of the marbles that travel on the given grid, as it is shown, to their
+     * trails. Trails are only kept while the marbles move.
+     */
+    fn update_trails(&self, game: &Game) {
+        let mut trails = self.trails.borrow_mut();
+        match game.state() {
+            State::Animating(_) if self.settings.particles => {
+                trails.record(game.grid().in_flight().map(|marble| {
+                    let pos = Subpixel::new(marble.pos.re as f32, marble.pos.im as f32);
+                    (marble.id, marble.owner, round_subpixel(self.shown_pos(game, pos)) * self.scale)
+                }));
+            },
+            _ => trails.clear(),
+        }
+    }
+
+    /* Dots that get smaller and fainter along the trail of each travelling marble */
+    fn draw_trails(&self, canvas: &mut Canvas<Window>, game: &Game) -> Result<(), String> {
+        for (owner, pos, share) in self.trails.borrow().dots() {
+            let mut color = match owner {
+                NEUTRAL => Color::RGB(120, 120, 120),
+                owner => game.player(owner).color(),
+            };
+            color.a = (TRAIL_ALPHA as f32 * share) as u8;
+            let radius = (self.radius as f32 * share).round() as i16;
+            if radius > 0 {
+                canvas.filled_circle(pos.re as i16, pos.im as i16, radius, color)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn draw_particles(&self, canvas: &mut Canvas<Window>) -> Result<(), String> {
+        let radius = (self.cellsize.re.min(self.cellsize.im) / 40).max(2) as i16;
+        for particle in self.particles.borrow().iter() {
+            let mut color = particle.color;
+            color.a = (255.0 * particle.alpha()) as u8;
+            canvas.filled_circle(particle.pos.re as i16, particle.pos.im as i16, radius, color)?;
+        }
+        Ok(())
+    }
+
+    /* Cell of the game that is shown at the given cell of the turned board */
+    pub fn game_cell(&self, p: Point) -> Point {
+        rotate_point(p, self.dim, 4 - self.settings.view)
+    }
+
+    /* Turn the keys that move the selection along with the board, so they move it in the
+     * direction of the arrow on the screen
+     */
+    pub fn game_action(&self, action: Action) -> Action {
+        const MOVES: [Action; 4] = [Action::MoveRight, Action::MoveDown, Action::MoveLeft, Action::MoveUp];
+        match MOVES.iter().position(|&a| a == action) {
+            Some(d) => MOVES[(d + 4 - self.settings.view as usize) % 4],
+            None => action,
+        }
+    }
+
+    pub fn toggle_threats(&mut self) {
+        self.settings.threats = !self.settings.threats;
+    }
+
+    pub fn toggle_slot_tint(&mut self) {
+        self.settings.slot_tint = !self.settings.slot_tint;
+    }
+
+    pub fn cycle_critical_highlight(&mut self) {
+        self.settings.critical_highlight = match self.settings.critical_highlight {
+            CriticalHighlight::Off => CriticalHighlight::All,
+            CriticalHighlight::All => CriticalHighlight::CurrentPlayer,
+            CriticalHighlight::CurrentPlayer => CriticalHighlight::Off,
+        };
+    }
+
+    /* Draw a thin frame in the color of its owner inside each cell that belongs to a player. While
+     * marbles are moving, this is the owner the cell has as soon as the marbles arrived. The frames
+     * of each player are drawn in one batch, which keeps this cheap on large boards.
+     */
+    fn draw_owners(&self, canvas: &mut Canvas<Window>, game: &Game) -> Result<(), String> {
+        if !self.settings.owners {
+            return Ok(());
+        }
+        let mut frames = self.owner_frames.borrow_mut();
+        for rects in frames.iter_mut() {
+            rects.clear();
+        }
+        let (w, h) = (self.cellsize.re as u32, self.cellsize.im as u32);
+        for (coord, cell) in game.grid().cells() {
+            let owner = match cell.owner() {
+                Some(owner) if owner != NEUTRAL => owner,
+                _ => continue,
+            };
+            let corner = stretch(rotate_point(coord, game.dim(), self.settings.view), self.cellsize);
+            for inset in 1..3 {
+                frames[owner.idx()].push(Rect::new(
+                    corner.re + inset as i32, corner.im + inset as i32, w - 2*inset, h - 2*inset,
+                ));
+            }
+        }
+        for (owner, rects) in Owner::all(game.num_players()).zip(frames.iter()) {
+            canvas.set_draw_color(game.player(owner).color());
+            canvas.draw_rects(rects)?;
+        }
+        Ok(())
+    }
+
+    /* Mark the cell of the most recently placed marble with ticks at its corners in the color of
+     * its player until the next marble is placed, even if the cell spread in the meantime. Right
+     * after the move, a ring around the cell also draws the eye to it, which fades out over
+     * LAST_MOVE_FADE. The fade depends on the time, not on the frames.
+     */
+    fn draw_last_move(&self, canvas: &mut Canvas<Window>, game: &Game) -> Result<(), String> {
+        let (moves, last_move, _) = self.last_move_at.get();
+        if (moves, last_move) != (game.history().len(), game.last_move()) {
+            self.last_move_at.set((game.history().len(), game.last_move(), Some(Instant::now())));
+        }
+        if !game.config().show_last_move {
+            return Ok(());
+        }
+        let (owner, p) = match game.last_move() {
+            Some(last_move) => last_move,
+            None => return Ok(()),
+        };
+        let corner = stretch(rotate_point(p, game.dim(), self.settings.view), self.cellsize);
+        let cell = Rect::new(corner.re, corner.im, self.cellsize.re as u32, self.cellsize.im as u32);
+        let color = game.player(owner).color();
+        // With an outline, so they can be seen on the marbles of the same color
+        let ticks = corner_ticks(cell);
+        for tick in ticks {
+            let (x0, y0, x1, y1) = (tick.left() as i16, tick.top() as i16, tick.right() as i16 - 1, tick.bottom() as i16 - 1);
+            canvas.rectangle(x0 - 1, y0 - 1, x1 + 1, y1 + 1, self.theme.lines)?;
+        }
+        for tick in ticks {
+            canvas.box_(tick.left() as i16, tick.top() as i16, tick.right() as i16 - 1, tick.bottom() as i16 - 1, color)?;
+        }
+        let elapsed = match self.last_move_at.get().2 {
+            Some(at) if at.elapsed() < LAST_MOVE_FADE => at.elapsed(),
+            _ => return Ok(()),
+        };
+        let mut color = color;
+        color.a = (255.0 * (1.0 - elapsed.as_secs_f32() / LAST_MOVE_FADE.as_secs_f32())) as u8;
+        let center = corner + self.cellsize/2;
+        let radius = self.cellsize.re.min(self.cellsize.im) * 2 / 5;
+        for ring in 0..(radius / 10).max(2) {
+            canvas.circle(center.re as i16, center.im as i16, (radius - ring) as i16, color)?;
+        }
+        Ok(())
+    }
+
+    /* Outline cells that spread with one more marble, pulsing over time. Counts are only
+     * meaningful while no marbles are moving, so nothing is highlighted during animations. Only
+     * the tint and the transparency of the pre-rendered ring change from frame to frame.
+     */
+    fn highlight_critical(&self, canvas: &mut Canvas<Window>, game: &Game) -> Result<(), String> {
+        if self.settings.critical_highlight == CriticalHighlight::Off {
+            return Ok(());
+        }
+        if let State::Animating(_) = game.state() {
+            return Ok(());
+        }
+        let phase = self.created.elapsed().as_secs_f32() * std::f32::consts::TAU;
+        let mut ring = self.critical_ring.borrow_mut();
+        ring.set_alpha_mod((160.0 + 95.0 * phase.sin()) as u8);
+        let cellsize = self.cellsize;
+        for (coord, cell) in game.grid().cells() {
+            let owner = match cell.owner() {
+                Some(owner) if owner != NEUTRAL && cell.is_critical_soon() => owner,
+                _ => continue,
+            };
+            if self.settings.critical_highlight == CriticalHighlight::CurrentPlayer && owner != game.cur_player() {
+                continue;
+            }
+            let color = game.player(owner).color();
+            ring.set_color_mod(color.r, color.g, color.b);
+            let corner = stretch(rotate_point(coord, game.dim(), self.settings.view), cellsize);
+            canvas.copy(&ring, None, Some(Rect::new(corner.re, corner.im, cellsize.re as u32, cellsize.im as u32)))?;
+        }
+        Ok(())
+    }
+
+    /* Whether the threat map is shown, which is only while the current player may place a marble */
+    fn threats_shown(&self, game: &Game) -> bool {
+        self.settings.threats && game.winner().is_none() && game.editing().is_none()
+            && matches!(game.state(), State::AcceptingInput)
+    }
+
+    /* Tint each cell the current player may place a marble on from red for moves that look risky
+     * to green for moves that look good, see ai::score_moves(). The scores are spread over the
+     * whole range in each position, so even small differences show.
+     */
+    fn draw_threats(&self, canvas: &mut Canvas<Window>, game: &Game) -> Result<(), String> {
+        if !self.threats_shown(game) {
+            return Ok(());
+        }
+        let scores = match &self.threats {
+            Some((hash, scorer)) if *hash == game.grid().hash() && scorer.owner() == game.cur_player()
+                && scorer.done() => scorer.scores(),
+            _ => return Ok(()),
+        };
+        let min = scores.iter().map(|&(_, score)| score).fold(f32::INFINITY, f32::min);
+        let max = scores.iter().map(|&(_, score)| score).fold(f32::NEG_INFINITY, f32::max);
+        let cellsize = self.cellsize;
+        for &(p, score) in scores.iter() {
+            let t = if max > min { (score - min) / (max - min) } else { 0.5 };
+            // Through yellow, so the middle is not a muddy brown
+            let color = Color::RGBA(
+                (220.0 * (2.0 - 2.0*t).min(1.0)) as u8,
+                (200.0 * (2.0*t).min(1.0)) as u8,
+                40,
+                THREAT_ALPHA,
+            );
+            let corner = stretch(rotate_point(p, game.dim(), self.settings.view), cellsize);
+            let end = corner + cellsize - Point::new(1, 1);
+            canvas.box_(corner.re as i16, corner.im as i16, end.re as i16, end.im as i16, color)?;
+        }
+        Ok(())
+    }
+
+    /* Scroll the move history by the given number of steps of the mouse wheel, if the given
+     * horizontal position is in the sidebar. Returns whether it is.
+     */
+    pub fn scroll_history(&mut self, game: &Game, x: i32, steps: i32) -> bool {
+        if (x as f32) < self.board_size().re {
+            return false;
+        }
+        let scroll = self.history_scroll as i32 - steps;
+        self.history_scroll = scroll.clamp(0, game.history().len().saturating_sub(1) as i32) as usize;
+        true
+    }
+
+    /* List the most recent moves in the sidebar below the players, the latest first, each with
+     * the color of its player and the label of its cell. Labels that are too long for the
+     * sidebar are shrunk.
+     */
+    fn draw_history(&self, canvas: &mut Canvas<Window>, game: &Game) -> Result<(), String> {
+        let font = match self.font {
+            Some(font) => font,
+            None => return Ok(()),
+        };
+        let cellsize = self.cellsize;
+        let left = self.dim.re * cellsize.re;
+        let bottom = self.dim.im * cellsize.im;
+        let line = font.height();
+        let swatch = (cellsize.re.min(cellsize.im) / 10).max(3);
+        // Labels are centered in the part of the sidebar right of the swatches
+        let text_left = left + cellsize.re/10 + 2*swatch;
+        let available = (left + cellsize.re*SIDEBAR_WIDTH - text_left - cellsize.re/20).max(1);
+        let mut y = sidebar_row(cellsize, game.num_players()) - self.radius;
+        let skip = self.history_scroll.min(game.history().len().saturating_sub(1));
+        for &(owner, p) in game.history().iter().rev().skip(skip) {
+            if y + line > bottom {
+                break;
+            }
+            let color = game.player(owner).color();
+            let (x, mid) = ((left + cellsize.re/10) as i16, (y + line/2) as i16);
+            canvas.box_(x, mid - swatch as i16/2, x + swatch as i16, mid + swatch as i16/2, color)?;
+            let label = Grid::cell_label(p);
+            let width = font.size_of(&label).map_err(|e| e.to_string())?.0 as i32;
+            let scale = (available as f32 / width.max(1) as f32).min(1.0);
+            self.draw_text(canvas, &label, self.theme.text, Point::new(text_left + available/2, y + line/2), scale)?;
+            y += line;
+        }
+        Ok(())
+    }
+
+    /* Show how many games each player won so far in the middle of the board, the most wins first,
+     * with the color of the player if the identifier is one. The list takes at most three fifths
+     * of the height, leaving room for the lines of the game-over overlay.
+     */
+    fn draw_standings(&self, canvas: &mut Canvas<Window>, standings: &[(String, u32)]) -> Result<(), String> {
+        let font = match self.font {
+            Some(font) => font,
+            None => return Ok(()),
+        };
+        let area = self.board_size();
+        let line = font.height() + 4;
+        let max_lines = ((area.im as i32 * 3/5 - 20) / line - 1).max(0) as usize;
+        let lines: Vec<String> = std::iter::once("Wins".to_string())
+            .chain(standings.iter().take(max_lines).map(|(key, wins)| format!("{:>4}  {}", wins, key)))
+            .collect();
+        let width = lines.iter()
+            .map(|text| font.size_of(text).map(|size| size.0 as i32))
+            .collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())?
+            .into_iter().max().unwrap_or(0) + line;
+        let height = line * lines.len() as i32;
+        let (x, y) = ((area.re as i32 - width) / 2, (area.im as i32 - height) / 2);
+        canvas.box_(
+            (x - 10) as i16, (y - 10) as i16, (x + width + 10) as i16, (y + height + 10) as i16,
+            Color::RGBA(255, 255, 255, 220),
+        )?;
+        for (idx, text) in lines.iter().enumerate() {
+            let top = y + idx as i32 * line;
+            // The first line is the heading
+            let key = idx.checked_sub(1).map(|idx| standings[idx].0.as_str());
+            if let Some(color) = key.and_then(|key| parse_hex_color(key).ok()) {
+                canvas.box_(x as i16, (top + 4) as i16, (x + line - 8) as i16, (top + line - 4) as i16, color)?;
+            }
+            let text_width = font.size_of(text).map_err(|e| e.to_string())?.0 as i32;
+            self.draw_text(canvas, text, Color::RGB(0, 0, 0), Point::new(x + line + text_width/2, top + line/2), 1.0)?;
+        }
+        Ok(())
+    }
+
+    /* Darken the board once the game is over and announce the winner above the standings, with the
+     * keys for what to do next below them
+     */
+    fn draw_game_over(&self, canvas: &mut Canvas<Window>, game: &Game, standings: &[(String, u32)])
+        -> Result<(), String>
+    {
+        let area = self.board_size();
+        let (width, height) = (area.re as i32, area.im as i32);
+        canvas.box_(0, 0, width as i16, height as i16, Color::RGBA(0, 0, 0, 160))?;
+        let font = match self.font {
+            Some(font) => font,
+            None => return Ok(()),
+        };
+        if let Some(winner) = game.winner() {
+            let text = match game.player(winner).name() {
+                Some(name) => format!("Winner: {}", name),
+                None => "Winner".to_string(),
+            };
+            let (w, h) = font.size_of(&text).map_err(|e| e.to_string())?;
+            let scale = (width as f32 * 0.9 / w.max(1) as f32).min(2.5);
+            let (w, h) = ((w as f32 * scale) as i32 / 2 + 10, (h as f32 * scale) as i32 / 2 + 4);
+            let center = Point::new(width / 2, height / 10 + h);
+            canvas.box_(
+                (center.re - w) as i16, (center.im - h) as i16, (center.re + w) as i16, (center.im + h) as i16,
+                Color::RGBA(255, 255, 255, 200),
+            )?;
+            self.draw_text(canvas, &text, game.player(winner).color(), center, scale)?;
+        }
+        self.draw_standings(canvas, standings)?;
+        let w = font.size_of(&self.game_over_hint).map_err(|e| e.to_string())?.0;
+        let scale = (width as f32 * 0.9 / w.max(1) as f32).min(1.0);
+        let center = Point::new(width / 2, height * 9/10);
+        self.draw_text(canvas, &self.game_over_hint, Color::RGB(255, 255, 255), center, scale)
+    }
+
+    /* Mark whose turn it is in the sidebar and cross out the players that were eliminated, whose
+     * marbles are shown in gray. The row of a player that was just eliminated flashes in their
+     * color, fading like the marker of the last move.
+     */
+    fn draw_markers(&self, canvas: &mut Canvas<Window>, game: &Game) -> Result<(), String> {
+        let cellsize = self.cellsize;
+        let size = 2*self.radius as u32 + 1;
+        let left = self.dim.re*cellsize.re;
+        // The markers are drawn at the size of the marbles next to them
+        let top = |idx: usize| sidebar_row(cellsize, idx) - self.radius;
+        let mut eliminated = self.eliminated.borrow_mut();
         for (idx, player) in game.players().enumerate() {
+            let (was_alive, at) = eliminated.get(idx).copied().unwrap_or((player.alive, None));
+            let at = if was_alive && !player.alive { Some(Instant::now()) } else { at.filter(|_| !player.alive) };
+            match eliminated.get_mut(idx) {
+                Some(entry) => *entry = (player.alive, at),
+                None => eliminated.push((player.alive, at)),
+            }
             if player.alive {
-                continue
+                continue;
             }
-            let rect = Rect::new(self.dim.re as i32*cellsize+35, 15+idx as i32*40, 31, 31);
-            canvas.copy(
-                &self.dead_marker,
-                None,
-                Some(rect),
-            )?;
+            let marble = Rect::new(left + cellsize.re/2 - self.radius, top(idx), size, size);
+            canvas.copy(&self.grey_marbles[idx], None, Some(marble))?;
+            canvas.copy(&self.dead_marker, None, Some(Rect::new(left + cellsize.re*7/20, top(idx), size, size)))?;
+            let elapsed = at.map_or(ELIMINATION_FLASH, |at| at.elapsed());
+            if elapsed < ELIMINATION_FLASH {
+                let mut color = player.color();
+                color.a = (200.0 * (1.0 - elapsed.as_secs_f32() / ELIMINATION_FLASH.as_secs_f32())) as u8;
+                let row = sidebar_row(cellsize, idx);
+                let half = cellsize.im / 2;
+                canvas.box_(
+                    left as i16, (row - half) as i16, (left + cellsize.re*SIDEBAR_WIDTH - 1) as i16, (row + half - 1) as i16,
+                    color,
+                )?;
+            }
+        }
+        // In the board editor, the marker shows for whom marbles are added. Eliminated players
+        // never get a turn, so it skips their rows.
+        let marked = game.editing().unwrap_or(game.cur_player());
+        if game.player(marked).alive {
+            canvas.copy(&self.active_marker, None, Some(Rect::new(left + cellsize.re/20, top(marked.idx()), size, size)))?;
+        }
+        Ok(())
+    }
+
+    /* Write the name of each player and their numbers of cells and marbles below it in the
+     * second column of the sidebar. Both are shrunk to fit, and names that are too long are cut
+     * off with an ellipsis. The names and counts are rendered again only if they change.
+     */
+    fn draw_players(&self, canvas: &mut Canvas<Window>, game: &Game) -> Result<(), String> {
+        let font = match self.font {
+            Some(font) => font,
+            None => return Ok(()),
+        };
+        let cellsize = self.cellsize;
+        let unit = cellsize.re.min(cellsize.im);
+        let left = self.dim.re * cellsize.re;
+        let width = cellsize.re * 9 / 10;
+        // Each line takes half of a row
+        let scale = (unit as f32 / 5.0 / font.height() as f32).min(1.0);
+        let mut names = self.names.borrow_mut();
+        if names.len() != game.num_players() {
+            names.clear();
+        }
+        for owner in Owner::all(game.num_players()) {
+            let name = game.player_name(owner);
+            if names.get(owner.idx()).is_some_and(|(shown, _)| *shown == name) {
+                continue;
+            }
+            let short = shorten(font, &name, (width as f32 / scale) as u32)?;
+            let texture = font.render(&short).blended(self.theme.text).map_err(|e| e.to_string())?
+                .as_texture(self.creator).map_err(|e| e.to_string())?;
+            let entry = (name, texture);
+            match names.get_mut(owner.idx()) {
+                Some(shown) => *shown = entry,
+                None => names.push(entry),
+            }
+        }
+        let counts = &self.counts.borrow().1;
+        let mut count_texts = self.count_texts.borrow_mut();
+        count_texts.truncate(counts.len());
+        for (idx, &count) in counts.iter().enumerate() {
+            if count_texts.get(idx).is_some_and(|(shown, _)| *shown == count) {
+                continue;
+            }
+            let text = format!("{}c {}m", count.0, count.1);
+            let texture = font.render(&text).blended(self.theme.text).map_err(|e| e.to_string())?
+                .as_texture(self.creator).map_err(|e| e.to_string())?;
+            match count_texts.get_mut(idx) {
+                Some(shown) => *shown = (count, texture),
+                None => count_texts.push((count, texture)),
+            }
+        }
+        // Eliminated players are dimmed
+        let rows = names.iter_mut().zip(count_texts.iter_mut()).zip(game.players());
+        for (idx, (((_, name), (_, count)), player)) in rows.enumerate() {
+            let alive = player.alive;
+            let row = sidebar_row(cellsize, idx);
+            let query = name.query();
+            let (w, h) = ((query.width as f32 * scale) as u32, (query.height as f32 * scale) as u32);
+            let x = left + cellsize.re*3/2 - w as i32/2;
+            name.set_alpha_mod(if alive { 255 } else { DIMMED_ALPHA });
+            canvas.copy(name, None, Some(Rect::new(x, row - unit/10 - h as i32/2, w, h)))?;
+            let query = count.query();
+            let fit = scale.min(width as f32 / query.width.max(1) as f32);
+            let (w, h) = ((query.width as f32 * fit) as u32, (query.height as f32 * fit) as u32);
+            let x = left + cellsize.re*3/2 - w as i32/2;
+            count.set_alpha_mod(if alive { 255 } else { DIMMED_ALPHA });
+            canvas.copy(count, None, Some(Rect::new(x, row + unit/10 - h as i32/2, w, h)))?;
+        }
+        Ok(())
+    }
+
+    /* Draw text centered at the given position, enlarged by the given factor. The text is only
+     * rendered once as long as it is drawn in each frame, and tinted in the given color.
+     */
+    fn draw_text(&self, canvas: &mut Canvas<Window>, text: &str, color: Color, center: Point, scale: f32)
+        -> Result<(), String>
+    {
+        let font = match self.font {
+            Some(font) => font,
+            None => return Ok(()),
+        };
+        let mut texts = self.texts.borrow_mut();
+        if !texts.contains_key(text) {
+            let texture = font.render(text).blended(Color::RGB(255, 255, 255)).map_err(|e| e.to_string())?
+                .as_texture(self.creator).map_err(|e| e.to_string())?;
+            texts.insert(text.to_string(), (texture, 0));
+        }
+        let (texture, drawn) = texts.get_mut(text).unwrap();
+        *drawn = self.frame.get();
+        texture.set_color_mod(color.r, color.g, color.b);
+        texture.set_alpha_mod(color.a);
+        let query = texture.query();
+        let width = (query.width as f32 * scale) as u32;
+        let height = (query.height as f32 * scale) as u32;
+        canvas.copy(
+            texture,
+            None,
+            Some(Rect::new(center.re - width as i32/2, center.im - height as i32/2, width, height)),
+        )
+    }
+
+    // Size of the board in rendered pixels, which is also the area it is shown in on the screen
+    fn board_size(&self) -> Complex<f32> {
+        let size = stretch(self.dim, self.cellsize);
+        Complex::new(size.re as f32, size.im as f32)
+    }
+
+    /* Zoom in (positive steps) or out around the given position on the screen, see
+     * Viewport::zoom_at()
+     */
+    pub fn zoom_at(&mut self, x: i32, y: i32, steps: i32) {
+        self.viewport.zoom_at(Subpixel::new(x as f32, y as f32), steps);
+    }
+
+    /* Move the board by the given distance on the screen */
+    pub fn pan(&mut self, dx: i32, dy: i32) {
+        self.viewport.pan(Subpixel::new(dx as f32, dy as f32));
+    }
+
+    /* Area of the mini-map in the bottom left corner of the board, if the board does not fit in
+     * its area, because it is zoomed in or moved
+     */
+    fn minimap_rect(&self) -> Option<Rect> {
+        if self.viewport.fits() {
+            return None;
+        }
+        let area = self.board_size();
+        let block = (MINIMAP_CELL * self.scale)
+            .min(area.re as i32 / MINIMAP_SHARE / self.dim.re)
+            .min(area.im as i32 / MINIMAP_SHARE / self.dim.im)
+            .max(1);
+        let margin = self.cellsize.re.min(self.cellsize.im) / 10;
+        let (w, h) = (block * self.dim.re, block * self.dim.im);
+        Some(Rect::new(margin, area.im as i32 - margin - h, w as u32, h as u32))
+    }
+
+    /* Center the board on the position on the mini-map at the given position on the screen.
+     * Returns whether there is a mini-map at this position.
+     */
+    pub fn minimap_click(&mut self, x: i32, y: i32) -> bool {
+        let rect = match self.minimap_rect() {
+            Some(rect) if rect.contains_point((x, y)) => rect,
+            _ => return false,
+        };
+        let area = self.board_size();
+        let p = Complex::new(
+            (x - rect.x()) as f32 / rect.width() as f32 * area.re,
+            (y - rect.y()) as f32 / rect.height() as f32 * area.im,
+        );
+        self.viewport.center_on(p);
+        true
+    }
+
+    /* Draw the owners of all cells and a frame around the part of the board that is shown, if
+     * the board does not fit. Only the pixels of the small texture are replaced each frame.
+     */
+    fn draw_minimap(&self, canvas: &mut Canvas<Window>, game: &Game) -> Result<(), String> {
+        let rect = match self.minimap_rect() {
+            Some(rect) => rect,
+            None => return Ok(()),
+        };
+        let mut minimap = self.minimap.borrow_mut();
+        // The texture is only filled again once an owner changed or the view turned
+        let shown = Some((game.grid().hash(), self.settings.view));
+        let (drawn, pixels) = &mut *self.minimap_pixels.borrow_mut();
+        if *drawn != shown {
+            for (coord, cell) in game.grid().cells() {
+                let color = match cell.owner() {
+                    _ if !cell.playable() => self.theme.clear,
+                    None => self.theme.background,
+                    Some(NEUTRAL) => Color::RGB(120, 120, 120),
+                    Some(owner) => game.player(owner).color(),
+                };
+                let coord = rotate_point(coord, game.dim(), self.settings.view);
+                let idx = 4 * (coord.im * self.dim.re + coord.re) as usize;
+                pixels[idx..idx+4].copy_from_slice(&[color.r, color.g, color.b, 255]);
+            }
+            minimap.update(None, pixels, 4 * self.dim.re as usize).map_err(|e| e.to_string())?;
+            *drawn = shown;
+        }
+        canvas.copy(&minimap, None, Some(rect))?;
+
+        let area = self.board_size();
+        let to_map = |p: Complex<f32>| {
+            let p = (p - self.viewport.offset()) / self.viewport.zoom();
+            (
+                rect.x() as i16 + (p.re.clamp(0.0, area.re) / area.re * rect.width() as f32) as i16,
+                rect.y() as i16 + (p.im.clamp(0.0, area.im) / area.im * rect.height() as f32) as i16,
+            )
+        };
+        let (x0, y0) = to_map(Complex::new(0.0, 0.0));
+        let (x1, y1) = to_map(area);
+        canvas.rectangle(
+            rect.left() as i16 - 1, rect.top() as i16 - 1, rect.right() as i16, rect.bottom() as i16,
+            Color::RGB(0, 0, 0),
+        )?;
+        canvas.rectangle(x0, y0, x1, y1, Color::RGB(255, 255, 255))?;
+        Ok(())
+    }
+
+    /* Show a message at the bottom of the board for a few seconds, like where a screenshot was
+     * saved or why it could not be
+     */
+    pub fn notify(&mut self, text: String) {
+        self.notice = Some((text, Instant::now()));
+    }
+
+    /* Remember where the mouse is, or that it left the window */
+    pub fn hover(&mut self, pos: Option<(i32, i32)>) {
+        self.hover = pos;
+    }
+
+    /* Preview the move in the given cell of the game while the mouse button is held on it */
+    pub fn hold(&mut self, cell: Option<Point>) {
+        self.held = cell;
+    }
+
+    /* Play the moves the overlays show on the board of the game, which is left as it was: the
+     * held move for draw_chain() and the moves of the current player for the threat map. Each is
+     * only played again once the position, the cell or the player changes. This is done before
+     * update() in each frame, and scoring the moves is spread over frames, see THREAT_BUDGET.
+     */
+    pub fn look_ahead(&mut self, game: &mut Game) {
+        let hash = game.grid().hash();
+        if let Some(held) = self.held {
+            if !self.chain.as_ref().is_some_and(|(shown, cell, _)| *shown == hash && *cell == held) {
+                self.chain = Some((hash, held, chain_preview(game, held)));
+            }
+        }
+        if !self.threats_shown(game) {
+            return;
+        }
+        if !self.threats.as_ref().is_some_and(|(scored, scorer)| *scored == hash && scorer.owner() == game.cur_player()) {
+            self.threats = Some((hash, ai::MoveScorer::new(game)));
         }
-        let x = game.selected().re as i32;
-        let y = game.selected().im as i32;
+        let scorer = &mut self.threats.as_mut().unwrap().1;
+        let start = Instant::now();
+        while !scorer.done() && start.elapsed() < THREAT_BUDGET {
+            scorer.score_next(game);
+        }
+    }
+
+    /* Tint the cells the current player would take with a marble in the held cell and frame the
+     * held cell, see look_ahead()
+     */
+    fn draw_chain(&self, canvas: &mut Canvas<Window>, game: &Game) -> Result<(), String> {
+        let held = match self.held {
+            Some(held) => held,
+            None => return Ok(()),
+        };
+        let changes = match &self.chain {
+            Some((hash, cell, Some(changes))) if *hash == game.grid().hash() && *cell == held => changes,
+            _ => return Ok(()),
+        };
+        let mut color = game.player(game.cur_player()).color();
+        let (w, h) = (self.cellsize.re as i16, self.cellsize.im as i16);
+        for change in changes.iter().filter(|change| change.to == Some(game.cur_player())) {
+            let corner = stretch(rotate_point(change.at, game.dim(), self.settings.view), self.cellsize);
+            let (x, y) = (corner.re as i16, corner.im as i16);
+            color.a = CHAIN_ALPHA;
+            canvas.box_(x, y, x + w - 1, y + h - 1, color)?;
+        }
+        let corner = stretch(rotate_point(held, game.dim(), self.settings.view), self.cellsize);
+        let (x, y) = (corner.re as i16, corner.im as i16);
+        color.a = 255;
+        for inset in 0..(w.min(h) / 25).max(2) {
+            canvas.rectangle(x + inset, y + inset, x + w - inset, y + h - inset, color)?;
+        }
+        Ok(())
+    }
+
+    /* Show whose turn it is with a small translucent marble of the current player in the cell
+     * below the mouse, or a crossed out circle if they can not place a marble there, see
+     * preview(). Nothing is shown over the sidebar and the mini-map.
+     */
+    fn draw_preview(&self, canvas: &mut Canvas<Window>, game: &Game) -> Result<(), String> {
+        let (x, y) = match self.hover {
+            Some(pos) => pos,
+            None => return Ok(()),
+        };
+        if self.minimap_rect().is_some_and(|rect| rect.contains_point((x, y))) {
+            return Ok(());
+        }
+        let shown = match self.board_point(x, y).and_then(|p| game.cell_at_pixel(p.re, p.im)) {
+            Some(p) => p,
+            None => return Ok(()),
+        };
+        let preview = match preview(game, self.game_cell(shown)) {
+            Some(preview) => preview,
+            None => return Ok(()),
+        };
+        let cellsize = Complex::new(self.cellsize.re as f32, self.cellsize.im as f32);
+        let corner = Complex::new(shown.re as f32 * cellsize.re, shown.im as f32 * cellsize.im);
+        let center = self.viewport.to_screen(corner + cellsize / 2.0);
+        let radius = self.radius as f32 * self.viewport.zoom() * PREVIEW_SIZE;
+        let owner = match preview {
+            Preview::Marble(owner) => owner,
+            Preview::Refused => {
+                let (cx, cy, r) = (center.re as i16, center.im as i16, radius as i16);
+                let color = Color::RGBA(200, 0, 0, 200);
+                let width = (radius / 4.0).max(2.0) as u8;
+                let slant = (radius * std::f32::consts::FRAC_1_SQRT_2) as i16;
+                for inset in 0..width as i16 {
+                    canvas.aa_circle(cx, cy, r - inset, color)?;
+                }
+                return canvas.thick_line(cx - slant, cy - slant, cx + slant, cy + slant, width, color);
+            },
+        };
+        let mut marbles = self.marbles.borrow_mut();
+        let texture = marbles.get_mut(owner.idx()).ok_or(format!("No marble texture for player {}", owner))?;
+        let size = (2.0 * radius) as u32 + 1;
+        texture.set_alpha_mod(PREVIEW_ALPHA);
+        let result = canvas.copy(
+            texture, None, Some(Rect::new((center.re - radius) as i32, (center.im - radius) as i32, size, size)),
+        );
+        texture.set_alpha_mod(255);
+        result
+    }
+
+    /* Position in pixels of the game on the board as shown, which is turned by the view */
+    fn shown_pos(&self, game: &Game, pos: Subpixel) -> Subpixel {
+        let size = stretch(game.dim(), game.cellsize());
+        rotate_subpixel(pos, Subpixel::new(size.re as f32, size.im as f32), self.settings.view)
+    }
+
+    /* Cell of the game that is shown at the given drawn position, see ScreenMap::to_drawing() */
+    pub fn cell_at(&self, game: &Game, (x, y): (i32, i32)) -> Option<Point> {
+        let p = self.board_point(x, y)?;
+        game.cell_at_pixel(p.re, p.im).map(|p| self.game_cell(p))
+    }
+
+    /* Position on the board in the coordinates of the game that is shown at the given position on
+     * the screen, if any
+     */
+    fn board_point(&self, x: i32, y: i32) -> Option<Point> {
+        let p = self.viewport.to_board(Subpixel::new(x as f32, y as f32))?;
+        Some(Point::new(p.re as i32 / self.scale, p.im as i32 / self.scale))
+    }
+
+    /* Draw everything that pans and zooms with the board, in board coordinates */
+    fn draw_board(&self, canvas: &mut Canvas<Window>, game: &Game) -> Result<(), String> {
+        let grid = game.grid();
+        let cellsize = self.cellsize;
+        let area = self.board_size();
+        let area = Rect::new(0, 0, area.re as u32, area.im as u32);
+        canvas.copy(&self.background, area, area)?;
+        if self.settings.coords {
+            for (texture, rect) in self.coords.iter() {
+                canvas.copy(texture, None, Some(*rect))?;
+            }
+        }
+        self.draw_owners(canvas, game)?;
+        self.draw_threats(canvas, game)?;
+        self.highlight_critical(canvas, game)?;
+        self.draw_flashes(canvas, game)?;
+        self.draw_chain(canvas, game)?;
+        self.draw_last_move(canvas, game)?;
+        // Resting marbles breathe, but not while they travel
+        let breathing = self.settings.breathing && !matches!(game.state(), State::Animating(_));
+        let marbles = self.marbles.borrow();
+        let mut placed = self.placed.borrow_mut();
+        placed.clear();
+        let (spawned, spawn_frame) = self.spawn.get();
+        for (kind, _, marble) in grid.cells().flat_map(|(_, cell)| cell.marbles_by_slot()) {
+            let shown = self.shown_pos(game, marble.subpixel_pos());
+            let mut rect = marble_rect(shown, self.scale, self.radius);
+            if breathing {
+                let pos = round_subpixel(shown) * self.scale;
+                let corner = stretch(Point::new(pos.re / cellsize.re, pos.im / cellsize.im), cellsize);
+                let cell = Rect::new(corner.re, corner.im, cellsize.re as u32, cellsize.im as u32);
+                rect = breathe(rect, cell, self.frame.get(), marble.id(), BREATHING_AMPLITUDE * self.scale);
+            }
+            if Some(marble.id()) == spawned {
+                rect = scaled_rect(rect, spawn_scale(self.frame.get().wrapping_sub(spawn_frame)));
+            }
+            placed.push((rect, kind, marble.id(), marble.get_owner()));
+        }
+        // All shadows first, so no shadow falls onto a neighboring marble
+        if self.settings.shadows {
+            // Marbles rise and fall again on their way to the neighbor
+            let mut lifts = self.lifts.borrow_mut();
+            lifts.clear();
+            lifts.extend(grid.in_flight().map(|marble| (marble.id, shadow_lift(marble.remaining))));
+            for &(rect, _, id, _) in placed.iter() {
+                let lift = lifts.get(&id).copied().unwrap_or(0.0);
+                canvas.copy(&self.shadow, None, Some(shadow_rect(rect, lift)))?;
+            }
+        }
+        self.draw_trails(canvas, game)?;
+        for &(rect, kind, _, owner) in placed.iter() {
+            let texture = match owner {
+                NEUTRAL => &self.neutral_marble,
+                owner => marbles.get(owner.idx())
+                    .ok_or(format!("No marble texture for player {}", owner))?,
+            };
+            canvas.copy(texture, None, Some(rect))?;
+            if self.settings.slot_tint {
+                let center = rect.center();
+                canvas.filled_circle(center.x() as i16, center.y() as i16, (self.radius / 2) as i16, slot_tint(kind))?;
+            }
+        }
+        self.draw_particles(canvas)?;
+        let corner = stretch(rotate_point(game.selected(), game.dim(), self.settings.view), cellsize);
         canvas.copy(
             &self.selected,
             None,
-            Some(Rect::new(x*cellsize, y*cellsize, cellsize as u32, cellsize as u32)),
+            Some(Rect::new(corner.re, corner.im, cellsize.re as u32, cellsize.im as u32)),
+        )?;
+        Ok(())
+    }
+
+    /* Draw the board and the sidebar. Once the game is over, the board is covered by the
+     * game-over overlay with the given standings, if there are any. The demo in the menu has none.
+     */
+    pub fn update(&self, canvas: &mut Canvas<Window>, game: &Game, standings: Option<&[(String, u32)]>)
+        -> Result<(), String>
+    {
+        let cellsize = self.cellsize;
+        self.update_particles(game);
+        self.update_trails(game);
+        let shake = self.update_shake(game);
+        self.update_flashes(game);
+        self.frame.set(self.frame.get().wrapping_add(1));
+        let last_frame = self.frame.get().wrapping_sub(1);
+        self.texts.borrow_mut().retain(|_, (_, drawn)| *drawn == last_frame);
+        self.update_spawn(game);
+        let mut board = self.board.borrow_mut();
+        let mut result = Ok(());
+        canvas.with_texture_canvas(&mut board, |canvas| result = self.draw_board(canvas, game))
+            .map_err(|e| e.to_string())?;
+        result?;
+        let (area, shown, offset) = (self.viewport.area(), self.viewport.shown(), self.viewport.offset());
+        canvas.set_clip_rect(Rect::new(0, 0, area.re as u32, area.im as u32));
+        canvas.copy(
+            &board,
+            None,
+            Some(Rect::new(
+                offset.re.round() as i32 + shake.re, offset.im.round() as i32 + shake.im,
+                shown.re.round() as u32, shown.im.round() as u32,
+            )),
         )?;
+        self.draw_preview(canvas, game)?;
+        canvas.set_clip_rect(None);
+        self.draw_minimap(canvas, game)?;
+
+        // The sidebar stays in place
+        let sidebar = Rect::new(area.re as i32, 0, (cellsize.re*SIDEBAR_WIDTH) as u32, area.im as u32);
+        canvas.copy(&self.background, sidebar, sidebar)?;
+        self.draw_markers(canvas, game)?;
+        let left = self.dim.re*cellsize.re;
+        // Progress towards a domination win below each player
+        for owner in Owner::all(game.num_players()) {
+            if let Some(progress) = game.domination_progress(owner) {
+                let x = (left + cellsize.re/10) as i16;
+                let y = (sidebar_row(cellsize, owner.idx()) + self.radius + 2) as i16;
+                let width = (cellsize.re*4/5) as i16;
+                let filled = (width as f32 * progress) as i16;
+                if filled > 0 {
+                    canvas.box_(x, y, x + filled, y + 3, game.player(owner).color())?;
+                }
+                canvas.rectangle(x, y, x + width, y + 3, Color::RGB(0, 0, 0))?;
+            }
+        }
+        // The numbers only change once the marbles came to rest, so they do not flicker during
+        // chain reactions
+        if let State::AcceptingInput = game.state() {
+            let mut counts = self.counts.borrow_mut();
+            if counts.0 != game.grid().hash() {
+                *counts = (game.grid().hash(), game.grid().count_per_owner(game.num_players()));
+            }
+        }
+        self.draw_players(canvas, game)?;
+        self.draw_history(canvas, game)?;
+        // Show the length of long chain reactions, growing with each step
+        let chain = game.chain_depth();
+        if chain >= 2 {
+            if let State::Animating(_) = game.state() {
+                let emphasis = (chain.min(16) - 2) as f32 / 14.0;
+                let color = Color::RGB((emphasis * 200.0) as u8, 0, 0);
+                let center = Point::new(left / 2, cellsize.im / 3);
+                self.draw_text(canvas, &format!("Chain x{}!", chain), color, center, 1.5 + 1.5*emphasis)?;
+            }
+        }
+        if let Some(owner) = game.editing() {
+            let center = Point::new(left / 2, cellsize.im / 3);
+            canvas.box_(
+                0, (center.im - cellsize.im/6) as i16, left as i16, (center.im + cellsize.im/6) as i16,
+                Color::RGBA(255, 255, 255, 200),
+            )?;
+            let text = format!("Editing for {}, Return starts", game.player_name(owner));
+            self.draw_text(canvas, &text, game.player(owner).color(), center, 1.0)?;
+        } else if let (State::AcceptingInput, None) = (game.state(), game.winner()) {
+            // Whose turn it is, hidden while the marbles react
+            let owner = game.cur_player();
+            let text = match game.placements_left() {
+                0 | 1 => format!("{}'s turn", game.player_name(owner)),
+                left => format!("{}'s turn, {} marbles", game.player_name(owner), left),
+            };
+            let center = Point::new(left / 2, cellsize.im / 3);
+            if let Some(font) = self.font {
+                let (w, h) = font.size_of(&text).map_err(|e| e.to_string())?;
+                let (w, h) = (w as i32 / 2 + 8, h as i32 / 2 + 2);
+                canvas.box_(
+                    (center.re - w) as i16, (center.im - h) as i16, (center.re + w) as i16, (center.im + h) as i16,
+                    Color::RGBA(255, 255, 255, 200),
+                )?;
+            }
+            self.draw_text(canvas, &text, game.player(owner).color(), center, 1.0)?;
+        }
+        if let (Some(standings), true) = (standings, game.is_over()) {
+            self.draw_game_over(canvas, game, standings)?;
+        }
+        // Notices cover the explanation of the threat map and the slow motion hint for a moment
+        let notice = self.notice.as_ref()
+            .filter(|(_, given)| given.elapsed() < NOTICE_DURATION)
+            .map(|(text, _)| text.as_str())
+            .or(Some(self.separated_hint.as_str()).filter(|_| game.separated()))
+            .or(Some(THREAT_CAPTION).filter(|_| self.threats_shown(game)))
+            .or(Some("Slow motion").filter(|_| game.slow_motion()));
+        if let Some(text) = notice {
+            let center = Point::new(left / 2, self.dim.im*cellsize.im - cellsize.im / 3);
+            if let Some(font) = self.font {
+                let (w, h) = font.size_of(text).map_err(|e| e.to_string())?;
+                let (w, h) = (w as i32 / 2 + 8, h as i32 / 2 + 2);
+                canvas.box_(
+                    (center.re - w) as i16, (center.im - h) as i16, (center.re + w) as i16, (center.im + h) as i16,
+                    Color::RGBA(0, 0, 0, 200),
+                )?;
+            }
+            self.draw_text(canvas, text, Color::RGB(255, 255, 255), center, 1.0)?;
+        }
+        if self.settings.help {
+            self.draw_help(canvas)?;
+        }
 
         Ok(())
     }
 }
 
-pub fn run_game(video: &VideoSubsystem, event_pump: &mut EventPump, game: &mut Game) -> Result<(), String> {
+// Smallest cell size that is chosen automatically, even if the board then does not fit
+pub const MIN_CELLSIZE: i32 = 40;
+
+// Cells are at most this many times wider than high or the other way around
+const MAX_ASPECT: i32 = 2;
+
+/* Cell size for which the board and the sidebar fill the given display, leaving some room for
+ * window decorations and panels. Cells are stretched to use the available area, but not beyond
+ * MAX_ASPECT.
+ */
+pub fn fit_cellsize(video: &VideoSubsystem, dim: Point, display: usize) -> Result<Point, String> {
+    let bounds = video.display_bounds(display as i32)?;
+    let width = bounds.width() as i32 * 9 / 10 / (dim.re + SIDEBAR_WIDTH);
+    let height = bounds.height() as i32 * 9 / 10 / dim.im;
+    Ok(Point::new(
+        width.min(MAX_ASPECT * height).max(MIN_CELLSIZE),
+        height.min(MAX_ASPECT * width).max(MIN_CELLSIZE),
+    ))
+}
+
+// Largest factor the board is rendered at on large windows and HiDPI displays
+const MAX_RENDER_SCALE: u32 = 4;
+
+// Point size of the text at a scale of 1, and how much larger the coordinate labels are in the
+// high contrast mode
+const FONT_SIZE: u16 = 18;
+const LABEL_ENLARGE: f32 = 1.5;
+
+/* Factor to render the board of the given size at in a window with the given number of pixels.
+ * With integer scaling, this is the largest integer multiple of the size that fits, so lines and
+ * marbles stay crisp. Otherwise, the board is rendered at its size and SDL stretches it.
+ */
+pub fn render_scale(output: (u32, u32), size: (u32, u32), integer_scale: bool) -> u32 {
+    if !integer_scale {
+        return 1;
+    }
+    (output.0 / size.0).min(output.1 / size.1).clamp(1, MAX_RENDER_SCALE)
+}
+
+/* Size of a window showing content of the given size within the given bounds, shrunk with the
+ * same aspect ratio if needed
+ */
+pub fn fit_window(size: (u32, u32), bounds: (u32, u32)) -> (u32, u32) {
+    let shrink = (bounds.0 as f32 / size.0 as f32).min(bounds.1 as f32 / size.1 as f32).min(1.0);
+    (((size.0 as f32 * shrink) as u32).max(1), ((size.1 as f32 * shrink) as u32).max(1))
+}
+
+/* Mapping from the positions of mouse events to the pixels that are drawn. Mouse events are in
+ * the units of the window size, which on HiDPI displays has fewer units than the output has
+ * pixels. With a logical size, SDL scales what is drawn to the output, keeping the aspect ratio,
+ * and letterboxes the rest. It then also maps the mouse events to the logical size, so they may
+ * only fall onto the bars.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ScreenMap {
+    pub window: (u32, u32),
+    pub output: (u32, u32),
+    pub logical: Option<(u32, u32)>,
+}
+impl ScreenMap {
+    pub fn of(canvas: &Canvas<Window>) -> Result<ScreenMap, String> {
+        Ok(ScreenMap {
+            window: canvas.window().size(),
+            output: canvas.output_size()?,
+            logical: match canvas.logical_size() {
+                (0, 0) => None,
+                size => Some(size),
+            },
+        })
+    }
+
+    /* Drawn position at the given position in the window, if it is not outside of the drawn
+     * area
+     */
+    pub fn to_drawing(&self, x: i32, y: i32) -> Option<(i32, i32)> {
+        if self.window.0 == 0 || self.window.1 == 0 {
+            return None;
+        }
+        let (pos, size) = match self.logical {
+            Some((w, h)) => ((x as f32, y as f32), (w as f32, h as f32)),
+            None => {
+                let (out_w, out_h) = (self.output.0 as f32, self.output.1 as f32);
+                ((x as f32 * out_w / self.window.0 as f32, y as f32 * out_h / self.window.1 as f32), (out_w, out_h))
+            },
+        };
+        if pos.0 < 0.0 || pos.1 < 0.0 || pos.0 >= size.0 || pos.1 >= size.1 {
+            return None;
+        }
+        Some((pos.0 as i32, pos.1 as i32))
+    }
+}
+
+/* Play the game until the window is closed, adding each win to the tally. Returns whether the
+ * players asked to go back to the menu instead of quitting. The window opens where the last game
+ * was, or centered on the given display, and where it was at the end is saved.
+ */
+pub fn run_game(
+    video: &VideoSubsystem,
+    event_pump: &mut EventPump,
+    game: &mut Game,
+    keymap: &KeyMap,
+    tally: &mut Tally,
+    windows: &mut Placements,
+    display: Option<usize>,
+) -> Result<bool, String> {
     let dim = game.dim();
-    let cellsize = game.cellsize() as u32;
+    let cellsize = game.cellsize();
+    let (width, height) = ((cellsize.re*(dim.re+SIDEBAR_WIDTH)) as u32, (cellsize.im*dim.im) as u32);
+    // Leave some room for window decorations and panels
+    let bounds = video.display_bounds(display.unwrap_or(0) as i32)?;
+    let size = fit_window((width, height), (bounds.width() * 9 / 10, bounds.height() * 9 / 10));
+    // Where the last game was, unless it is no longer on any display
+    let placement = placement::choose(windows.game, &placement::displays(video)?, display, size);
     let mut canvas = video
-        .window("Chain reaction", cellsize*(dim.re+1) as u32, cellsize*dim.im as u32)
-        .position_centered()
+        .window(&game.config().window_title, placement.width, placement.height)
+        .position(placement.x, placement.y)
+        .resizable()
+        .allow_highdpi()
         .build()
         .map_err(|e| e.to_string())?
         .into_canvas()
@@ -233,35 +2233,250 @@ pub fn run_game(video: &VideoSubsystem, event_pump: &mut EventPump, game: &mut G
         .accelerated()
         .build()
         .map_err(|e| e.to_string())?;
-    canvas.set_logical_size(100*dim.re as u32 + 100, 100*dim.im as u32).map_err(|e| e.to_string())?;
+    icon::apply(canvas.window_mut());
+    // On HiDPI displays and in large windows, there are more pixels than the size of the board,
+    // so it is rendered at a multiple of its size, see render_scale(). SDL scales this logical
+    // size to the window, keeping the aspect ratio and letterboxing the rest, see ScreenMap.
+    let integer_scale = game.config().integer_scale;
+    let mut scale = render_scale(canvas.output_size()?, (width, height), integer_scale);
+    canvas.set_logical_size(width*scale, height*scale).map_err(|e| e.to_string())?;
 
     let texture_creator = canvas.texture_creator();
-    let renderer = Renderer::new(&texture_creator, &game)?;
+    let fontcontext = ttf::init().map_err(|e| e.to_string())?;
+    // The fonts for each scale are loaded in advance, since the renderer borrows them. If there is
+    // no font at all, it is only reported once. Labels in the high contrast mode are larger.
+    let scales = if integer_scale { MAX_RENDER_SCALE } else { 1 };
+    let mut fonts = vec![font::load(&fontcontext, FONT_SIZE)];
+    if fonts[0].is_some() {
+        fonts.extend((2..=scales).map(|s| font::load(&fontcontext, FONT_SIZE * s as u16)));
+    }
+    let mut label_fonts = Vec::new();
+    if fonts[0].is_some() && game.config().high_contrast {
+        let size = (FONT_SIZE as f32 * LABEL_ENLARGE) as u16;
+        label_fonts.extend((1..=scales).map(|s| font::load(&fontcontext, size * s as u16)));
+    }
+    let font = |scale: u32| {
+        let text = fonts.get(scale as usize - 1)?.as_ref()?;
+        let labels = label_fonts.get(scale as usize - 1).and_then(|font| font.as_ref()).unwrap_or(text);
+        Some(Fonts { text, labels })
+    };
+    // Boards that are not square can only be turned upside down, since they would not fit the
+    // window otherwise
+    let view_step = if dim.re == dim.im && cellsize.re == cellsize.im { 1 } else { 2 };
+    let theme = game.config().theme;
+    let mut renderer = Renderer::new(&texture_creator, font(scale), keymap, game, &theme, scale as i32, ViewSettings::new(game.config()))?;
 
+    // Last position of the mouse, which the mouse wheel zooms around
+    let mut mousepos = (0, 0);
+    // Whether the left button was pressed on the board and not released yet
+    let mut holding = false;
+    let mut last_frame = Instant::now();
+    // Whether the win of the current game is already in the tally
+    let mut recorded = false;
+    // Whether the next frame is saved before it is presented
+    let mut screenshot = false;
+    let mut tinted_screenshot = false;
+    // Whether the players went back to the menu rather than quitting
+    let mut menu = false;
+    let mut autosave = Autosave::new(game);
+    // Wins shown once the game is over, which only change when a win is recorded
+    let mut standings = tally.standings();
     'running: loop {
-        canvas.set_draw_color(Color::RGB(90, 90, 90));
+        canvas.set_draw_color(renderer.theme().clear);
         canvas.clear();
+        let mut screen = ScreenMap::of(&canvas)?;
         for event in event_pump.poll_iter() {
             match event {
-                Event::Quit {..} |
-                Event::KeyDown { keycode: Some(Keycode::Escape), .. } => {
+                Event::Quit {..} => {
                     break 'running
                 },
-                Event::KeyDown { keycode, .. } => game.keydown(keycode.unwrap()),
-                Event::MouseButtonDown {x, y, .. } => {
-                    let x = x/cellsize as i32;
-                    let y = y/cellsize as i32;
-                    if x < dim.re && y < dim.im {
-                        game.click(Point::new(x, y));
+                Event::Window { win_event: WindowEvent::SizeChanged(..), .. } => {
+                    let changed = render_scale(canvas.output_size()?, (width, height), integer_scale);
+                    if changed != scale {
+                        scale = changed;
+                        canvas.set_logical_size(width*scale, height*scale).map_err(|e| e.to_string())?;
+                        screen = ScreenMap::of(&canvas)?;
+                        renderer.rescale(font(scale), keymap, game, scale as i32)?;
+                    }
+                },
+                Event::KeyDown { keycode: Some(Keycode::C), keymod, .. } if keymod.intersects(CTRL) => {
+                    if let Some(position) = game.encode() {
+                        video.clipboard().set_clipboard_text(&position)?;
+                    }
+                },
+                Event::KeyDown { keycode: Some(Keycode::V), keymod, .. } if keymod.intersects(CTRL) => {
+                    match game.decode(&video.clipboard().clipboard_text()?) {
+                        Ok(()) => {
+                            recorded = false;
+                            renderer = Renderer::new(&texture_creator, font(scale), keymap, game, &theme, scale as i32, renderer.settings())?;
+                        },
+                        Err(e) => renderer.notify(format!("Can not paste position: {}", e)),
+                    }
+                },
+                Event::KeyDown { keycode: Some(keycode), .. } if game.editing().is_some() && turn_position(keycode).is_some() => {
+                    game.edit_for(turn_position(keycode).unwrap());
+                },
+                Event::KeyDown { keycode: Some(keycode), keymod, .. } => match keymap.action(keycode) {
+                    Some(Action::Quit) => break 'running,
+                    Some(Action::Menu) if game.winner().is_some() => {
+                        menu = true;
+                        break 'running;
+                    },
+                    Some(Action::Rematch) if game.winner().is_some() => {
+                        game.restart();
+                        recorded = false;
+                        renderer = Renderer::new(&texture_creator, font(scale), keymap, game, &theme, scale as i32, renderer.settings())?;
+                    },
+                    Some(Action::ToggleCoords) => renderer.toggle_coords(),
+                    Some(Action::ToggleOwners) => renderer.toggle_owners(),
+                    Some(Action::ToggleParticles) => renderer.toggle_particles(),
+                    Some(Action::ToggleBreathing) => renderer.toggle_breathing(),
+                    Some(Action::ToggleShadows) => renderer.toggle_shadows(),
+                    Some(Action::ToggleShake) => renderer.toggle_shake(),
+                    Some(Action::RotateView) => {
+                        let settings = renderer.settings();
+                        let settings = ViewSettings { view: settings.view + view_step, ..settings };
+                        renderer = Renderer::new(&texture_creator, font(scale), keymap, game, &theme, scale as i32, settings)?;
+                    },
+                    Some(Action::CycleHighlight) => renderer.cycle_critical_highlight(),
+                    Some(Action::ToggleThreats) => renderer.toggle_threats(),
+                    Some(Action::ToggleSlotTint) => renderer.toggle_slot_tint(),
+                    Some(Action::Help) => renderer.toggle_help(),
+                    Some(Action::Screenshot) => {
+                        screenshot = true;
+                        tinted_screenshot = keymod.intersects(SHIFT);
+                    },
+                    Some(Action::Place) if game.editing().is_some() => {
+                        if let Err(e) = game.finish_editing() {
+                            renderer.notify(format!("Can not start from this position: {}", e));
+                        }
+                    },
+                    Some(action) => game.keydown(renderer.game_action(action)),
+                    None => (),
+                },
+                Event::Window { win_event: WindowEvent::Leave, .. } => renderer.hover(None),
+                Event::MouseMotion { x, y, xrel, yrel, mousestate, .. } => {
+                    let pos = screen.to_drawing(x, y);
+                    if let Some(pos) = pos {
+                        mousepos = pos;
+                    }
+                    renderer.hover(pos);
+                    if mousestate.middle() {
+                        renderer.pan(xrel, yrel);
+                    }
+                    if holding {
+                        // The preview follows the mouse to another cell
+                        renderer.hold(pos.and_then(|pos| renderer.cell_at(game, pos)));
+                    } else if let Some((x, y)) = pos.filter(|_| mousestate.left()) {
+                        // Dragging on the mini-map keeps moving the board
+                        renderer.minimap_click(x, y);
+                    }
+                },
+                Event::MouseWheel { y, .. } => {
+                    // Over the list of moves, the wheel scrolls it instead of zooming the board
+                    let scrolled = renderer.scroll_history(game, mousepos.0, y);
+                    if !scrolled {
+                        renderer.zoom_at(mousepos.0, mousepos.1, y);
+                    }
+                },
+                Event::MouseButtonDown { mouse_btn: MouseButton::Middle, .. } => (),
+                Event::MouseButtonDown {x, y, mouse_btn: MouseButton::Right, .. } => {
+                    if let Some(p) = screen.to_drawing(x, y).and_then(|pos| renderer.cell_at(game, pos)) {
+                        if game.editing().is_some() {
+                            if let Err(e) = game.remove_marble(p) {
+                                renderer.notify(e);
+                            }
+                        }
+                    }
+                },
+                // Holding the button on a cell previews the move, which is only played once the
+                // button is released on a cell, so a quick click plays it right away. Releasing
+                // it outside of the board cancels the move.
+                Event::MouseButtonDown {x, y, mouse_btn: MouseButton::Left, .. } => {
+                    let pos = match screen.to_drawing(x, y) {
+                        Some(pos) => pos,
+                        None => continue,
+                    };
+                    // The mini-map is drawn over the board
+                    if renderer.minimap_click(pos.0, pos.1) {
+                        continue;
+                    }
+                    if let Some(p) = renderer.cell_at(game, pos) {
+                        holding = true;
+                        renderer.hold(Some(p));
+                    }
+                },
+                Event::MouseButtonUp {x, y, mouse_btn: MouseButton::Left, .. } if holding => {
+                    holding = false;
+                    renderer.hold(None);
+                    if let Some(p) = screen.to_drawing(x, y).and_then(|pos| renderer.cell_at(game, pos)) {
+                        if game.editing().is_none() {
+                            game.click(p);
+                        } else if let Err(e) = game.insert_marble(p) {
+                            renderer.notify(e);
+                        }
                     }
                 },
                 _ => {}
             }
         }
-        game.step();
-        renderer.update(&mut canvas, &game)?;
+        let now = Instant::now();
+        game.step(now - last_frame);
+        last_frame = now;
+        if let (Some(winner), false) = (game.winner(), recorded) {
+            recorded = true;
+            tally.record_win(game.player(winner));
+            standings = tally.standings();
+            if let Err(e) = tally.save() {
+                eprintln!("Can not save the tally: {}", e);
+                renderer.notify(format!("Can not save the tally: {}", e));
+            }
+            if let Err(e) = autosave.discard() {
+                eprintln!("{}", e);
+                renderer.notify(e);
+            }
+        }
+        if let Err(e) = autosave.update(game) {
+            eprintln!("{}", e);
+            renderer.notify(e);
+        }
+        // The debug tint is left out of the frame of a screenshot unless it was asked for
+        let hide_tint = screenshot && !tinted_screenshot && renderer.settings().slot_tint;
+        if hide_tint {
+            renderer.toggle_slot_tint();
+        }
+        renderer.look_ahead(game);
+        renderer.update(&mut canvas, game, Some(&standings))?;
+        if hide_tint {
+            renderer.toggle_slot_tint();
+        }
+        if screenshot {
+            screenshot = false;
+            // Failing to save is not a reason to end the game
+            match screenshot::save(&canvas) {
+                Ok(path) => {
+                    println!("Saved screenshot to {}", path.display());
+                    renderer.notify(format!("Saved {}", path.file_name().unwrap_or_default().to_string_lossy()));
+                },
+                Err(e) => {
+                    eprintln!("Can not save screenshot: {}", e);
+                    renderer.notify(format!("Can not save screenshot: {}", e));
+                },
+            }
+        }
         canvas.present();
         std::thread::sleep(Duration::new(0, 1_000_000_000u32 / 60));
     };
-    Ok(())
+    windows.game = Some(placement::of(canvas.window()));
+    if let Err(e) = windows.save() {
+        eprintln!("Can not save the window positions: {}", e);
+    }
+    // An unfinished game that was quit is kept for recovery, unless it was left for the menu. The
+    // window is closed by now, so failures are returned instead of shown.
+    if menu {
+        autosave.discard()?;
+    } else {
+        autosave.wait()?;
+    }
+    Ok(menu)
 }
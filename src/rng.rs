@@ -0,0 +1,32 @@
+/* Random numbers for everything that is left to chance: the moves of simulated games, the colors
+ * chosen in the menu and the sparks of the particles. Runs only depend on the seed, so they can
+ * be repeated.
+ */
+
+use std::time::SystemTime;
+
+/* SplitMix64, which is small, fast and good enough to pick random moves */
+pub struct Rng(u64);
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        Rng(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /* Random number below the given bound */
+    pub fn below(&mut self, bound: usize) -> usize {
+        (self.next_u64() % bound as u64) as usize
+    }
+}
+
+/* Seed that differs from launch to launch, for when none is given */
+pub fn time_seed() -> u64 {
+    SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_nanos() as u64)
+}
@@ -0,0 +1,65 @@
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use sdl2::pixels::PixelFormatEnum;
+use sdl2::render::Canvas;
+use sdl2::video::Window;
+use time::OffsetDateTime;
+
+// Bytes of each pixel in the image, which has no alpha channel
+const BYTES_PER_PIXEL: usize = 3;
+
+/* Save the frame that was drawn but not yet presented as PNG in the directory of screenshots and
+ * return the path of the file. The pixels are read at the resolution of the window, so on HiDPI
+ * displays the image is as sharp as the screen, and the bars left over by letterboxing are left
+ * out.
+ */
+pub fn save(canvas: &Canvas<Window>) -> Result<PathBuf, String> {
+    // The viewport is in logical pixels, but it is read in the pixels of the output
+    let viewport = canvas.viewport();
+    let (scale_x, scale_y) = canvas.scale();
+    let width = (viewport.width() as f32 * scale_x).round() as u32;
+    let height = (viewport.height() as f32 * scale_y).round() as u32;
+    let pixels = canvas.read_pixels(None, PixelFormatEnum::RGB24)?;
+    let dir = directory();
+    let name = file_name(SystemTime::now());
+    let mut path = dir.join(&name);
+    // Several screenshots in the same second get a number
+    let mut count = 1;
+    while path.exists() {
+        count += 1;
+        path = dir.join(name.replace(".png", &format!("-{}.png", count)));
+    }
+    write_png(&path, width, height, &pixels)?;
+    Ok(path)
+}
+
+/* Write an image given as rows of RGB bytes from the top as PNG */
+pub fn write_png(path: &Path, width: u32, height: u32, pixels: &[u8]) -> Result<(), String> {
+    if pixels.len() != width as usize * height as usize * BYTES_PER_PIXEL {
+        return Err(format!("Unexpected size of the screen contents for {}x{} pixels", width, height));
+    }
+    image::save_buffer(path, pixels, width, height, image::ExtendedColorType::Rgb8)
+        .map_err(|e| format!("Can not write {}: {}", path.display(), e))
+}
+
+/* Directory the screenshots are saved to. This is the pictures directory of the user if it is
+ * known and exists, and the current directory otherwise.
+ */
+pub fn directory() -> PathBuf {
+    match dirs::picture_dir() {
+        Some(dir) if dir.is_dir() => dir,
+        _ => PathBuf::from("."),
+    }
+}
+
+/* Name of a screenshot taken at the given time, like chainreaction-2024-03-05-142501.png. The
+ * time is in UTC, since the local time zone can not be found reliably while other threads run.
+ */
+pub fn file_name(time: SystemTime) -> String {
+    let time = OffsetDateTime::from(time);
+    format!(
+        "chainreaction-{:04}-{:02}-{:02}-{:02}{:02}{:02}.png",
+        time.year(), time.month() as u8, time.day(), time.hour(), time.minute(), time.second(),
+    )
+}
@@ -0,0 +1,61 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::grid::Point;
+
+/* Built-in shapes of the board */
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Shape {
+    Rectangle,
+    Plus,
+    Donut,
+    Diamond,
+}
+impl Shape {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Shape::Rectangle => "Rectangle",
+            Shape::Plus => "Plus",
+            Shape::Donut => "Donut",
+            Shape::Diamond => "Diamond",
+        }
+    }
+
+    pub fn next(&self) -> Shape {
+        match self {
+            Shape::Rectangle => Shape::Plus,
+            Shape::Plus => Shape::Donut,
+            Shape::Donut => Shape::Diamond,
+            Shape::Diamond => Shape::Rectangle,
+        }
+    }
+
+    /* Check if the given cell belongs to a board of this shape with the given dimension */
+    pub fn contains(&self, p: Point, dim: Point) -> bool {
+        // Whether the coordinate lies in the middle third of the given length
+        let middle = |x: i32, len: i32| x >= len/3 && x < len - len/3;
+        match self {
+            Shape::Rectangle => true,
+            Shape::Plus => middle(p.re, dim.re) || middle(p.im, dim.im),
+            Shape::Donut => !(middle(p.re, dim.re) && middle(p.im, dim.im)),
+            Shape::Diamond => {
+                // Distance from the center in doubled coordinates, scaled to the same range
+                let dx = (2*p.re + 1 - dim.re).abs() * dim.im;
+                let dy = (2*p.im + 1 - dim.im).abs() * dim.re;
+                dx + dy <= dim.re * dim.im
+            },
+        }
+    }
+
+    /* Mask for Grid::from_mask */
+    pub fn mask(&self, dim: Point) -> Vec<bool> {
+        let mut mask = Vec::with_capacity(dim.re as usize * dim.im as usize);
+        for y in 0..dim.im {
+            for x in 0..dim.re {
+                mask.push(self.contains(Point::new(x, y), dim));
+            }
+        }
+        mask
+    }
+}
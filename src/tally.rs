@@ -0,0 +1,92 @@
+/* Number of games each player won, kept across rematches and launches in a small file. Players
+ * are identified by their name, or their color if they have none, so they keep their wins when
+ * others join or leave.
+ */
+
+use crate::game::Player;
+use crate::keymap::{load_config, move_aside, save_config};
+
+// Environment variable with the path of the tally file, which is tally.conf in the configuration
+// directory otherwise
+pub const TALLY_VARIABLE: &str = "CHAINREACTION_TALLY";
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Tally {
+    // Identifier of each player that won at least once and their number of wins
+    wins: Vec<(String, u32)>,
+}
+impl Tally {
+    pub fn new() -> Tally {
+        Tally::default()
+    }
+
+    /* Identifier of the player in the tally, their name or their color like "#1E90FF" */
+    pub fn key(player: &Player) -> String {
+        if let Some(name) = player.name() {
+            return name.to_string();
+        }
+        let color = player.color();
+        format!("#{:02X}{:02X}{:02X}", color.r, color.g, color.b)
+    }
+
+    pub fn wins(&self, player: &Player) -> u32 {
+        let key = Tally::key(player);
+        self.wins.iter().find(|(k, _)| *k == key).map_or(0, |&(_, wins)| wins)
+    }
+
+    pub fn record_win(&mut self, player: &Player) {
+        let key = Tally::key(player);
+        match self.wins.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, wins)) => *wins += 1,
+            None => self.wins.push((key, 1)),
+        }
+    }
+
+    /* Identifiers and wins of all players that won at least once, the most wins first */
+    pub fn standings(&self) -> Vec<(String, u32)> {
+        let mut standings = self.wins.clone();
+        standings.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        standings
+    }
+
+    /* Parse a tally written by to_text(), one player per line in the form "3 #1E90FF". Empty
+     * lines are ignored.
+     */
+    pub fn parse(text: &str) -> Result<Tally, String> {
+        let mut tally = Tally::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let (wins, key) = line.split_once(' ')
+                .ok_or(format!("Line {}: expected wins and player", number+1))?;
+            let wins = wins.parse().map_err(|_| format!("Line {}: invalid number of wins {}", number+1, wins))?;
+            let key = key.trim().to_string();
+            if tally.wins.iter().any(|(k, _)| *k == key) {
+                return Err(format!("Line {}: player {} is listed twice", number+1, key));
+            }
+            tally.wins.push((key, wins));
+        }
+        Ok(tally)
+    }
+
+    pub fn to_text(&self) -> String {
+        self.standings().iter().map(|(key, wins)| format!("{} {}\n", wins, key)).collect()
+    }
+
+    /* Load the tally from its file, starting without any wins if there is none. A file that can
+     * not be read is renamed to tally.conf.broken, so the next win does not write over its wins.
+     */
+    pub fn load() -> Result<Tally, String> {
+        match load_config(TALLY_VARIABLE, "tally.conf", Tally::parse) {
+            Ok(tally) => Ok(tally.unwrap_or_default()),
+            Err(e) => Err(move_aside(TALLY_VARIABLE, "tally.conf", e)),
+        }
+    }
+
+    /* Write the tally to its file, creating the configuration directory if needed */
+    pub fn save(&self) -> Result<(), String> {
+        save_config(TALLY_VARIABLE, "tally.conf", &self.to_text())
+    }
+}
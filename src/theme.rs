@@ -0,0 +1,99 @@
+/* Colors of everything that is drawn around the marbles of the players. The renderer creates all
+ * of its textures from the theme of the game, so a different theme only needs a new renderer.
+ */
+
+use sdl2::pixels::Color;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Theme {
+    // Playable cells of the board
+    #[cfg_attr(feature = "serde", serde(with = "crate::game::rgb"))]
+    pub background: Color,
+    // Lines between the cells
+    #[cfg_attr(feature = "serde", serde(with = "crate::game::rgb"))]
+    pub lines: Color,
+    // Empty slots, which fade into the background towards their top
+    #[cfg_attr(feature = "serde", serde(with = "crate::game::rgb"))]
+    pub slot: Color,
+    // Area right of the board with the players and the history
+    #[cfg_attr(feature = "serde", serde(with = "crate::game::rgb"))]
+    pub sidebar: Color,
+    // Names, counts and markers in the sidebar
+    #[cfg_attr(feature = "serde", serde(with = "crate::game::rgb"))]
+    pub text: Color,
+    // Frame around the selected cell
+    #[cfg_attr(feature = "serde", serde(with = "crate::game::rgb"))]
+    pub selection: Color,
+    // Around the board and in cells outside of its shape
+    #[cfg_attr(feature = "serde", serde(with = "crate::game::rgb"))]
+    pub clear: Color,
+}
+
+impl Theme {
+    pub const CLASSIC: Theme = Theme {
+        background: Color::RGB(200, 200, 200),
+        lines: Color::RGB(0, 0, 0),
+        slot: Color::RGB(255, 255, 255),
+        sidebar: Color::RGB(200, 200, 200),
+        text: Color::RGB(0, 0, 0),
+        selection: Color::RGB(0, 0, 0),
+        clear: Color::RGB(90, 90, 90),
+    };
+
+    pub const DARK: Theme = Theme {
+        background: Color::RGB(58, 61, 68),
+        lines: Color::RGB(105, 110, 120),
+        slot: Color::RGB(135, 140, 150),
+        sidebar: Color::RGB(30, 32, 36),
+        text: Color::RGB(210, 210, 210),
+        selection: Color::RGB(230, 230, 230),
+        clear: Color::RGB(10, 10, 12),
+    };
+
+    // Used instead of the selected theme in the high contrast mode
+    pub const HIGH_CONTRAST: Theme = Theme {
+        background: Color::RGB(255, 255, 255),
+        lines: Color::RGB(0, 0, 0),
+        slot: Color::RGB(255, 255, 255),
+        sidebar: Color::RGB(255, 255, 255),
+        text: Color::RGB(0, 0, 0),
+        // Stands out against the thick black lines and the white cells
+        selection: Color::RGB(230, 90, 0),
+        clear: Color::RGB(90, 90, 90),
+    };
+
+    // Themes that can be selected in the menu, in the order they are cycled through
+    pub const ALL: [Theme; 2] = [Theme::CLASSIC, Theme::DARK];
+
+    /* Name of a built-in theme, or "custom" for one given in a configuration */
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Theme::CLASSIC => "classic",
+            Theme::DARK => "dark",
+            Theme::HIGH_CONTRAST => "high contrast",
+            _ => "custom",
+        }
+    }
+
+    /* Built-in theme with the given name */
+    pub fn by_name(name: &str) -> Option<Theme> {
+        Theme::ALL.into_iter().chain([Theme::HIGH_CONTRAST]).find(|theme| theme.name() == name)
+    }
+
+    /* The theme after this one in the menu, starting over after the last one */
+    pub fn next(&self) -> Theme {
+        match Theme::ALL.iter().position(|theme| theme == self) {
+            Some(idx) => Theme::ALL[(idx + 1) % Theme::ALL.len()],
+            None => Theme::ALL[0],
+        }
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Theme {
+        Theme::CLASSIC
+    }
+}
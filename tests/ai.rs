@@ -0,0 +1,90 @@
+use chain::ai::{score_moves, MoveScorer, WIN};
+use chain::bench;
+use chain::game::{Game, State};
+use chain::grid::Point;
+
+fn new_game(size: Point) -> Game {
+    Game::new(bench::config(2, size).unwrap()).unwrap()
+}
+
+fn score_of(scores: &[(Point, f32)], p: Point) -> f32 {
+    scores.iter().find(|&&(q, _)| q == p).unwrap().1
+}
+
+#[test]
+fn winning_moves_score_best() {
+    let mut game = new_game(Point::new(3, 3));
+    // Both players are one marble short of spreading, so whoever moves first takes the other
+    game.decode("3x3 a 2 a1b2.......").unwrap();
+    let scores = score_moves(&mut game);
+    assert_eq!(score_of(&scores, Point::new(0, 0)), WIN);
+    assert!(scores.iter().all(|&(p, score)| p == Point::new(0, 0) || score < 0.0));
+    // The second player would take the corner and then this cell as well
+    assert_eq!(score_of(&scores, Point::new(0, 1)), -WIN);
+}
+
+#[test]
+fn each_legal_move_is_scored_once() {
+    let mut game = new_game(Point::new(4, 3));
+    for p in [Point::new(0, 0), Point::new(3, 2), Point::new(1, 1), Point::new(2, 1)] {
+        game.click(p);
+        game.advance_until_stable();
+    }
+    let before = game.encode();
+    let mut scored: Vec<Point> = score_moves(&mut game).into_iter().map(|(p, _)| p).collect();
+    let mut legal = game.legal_moves();
+    scored.sort_by_key(|p| (p.im, p.re));
+    legal.sort_by_key(|p| (p.im, p.re));
+    assert_eq!(scored, legal);
+    // Looking ahead leaves the game alone
+    assert_eq!(game.encode(), before);
+}
+
+#[test]
+fn nothing_is_scored_without_a_move() {
+    let mut game = new_game(Point::new(3, 3));
+    // The first move scores without any rival
+    assert_eq!(score_moves(&mut game).len(), 9);
+    game.decode("3x3 a 2 a1b2.......").unwrap();
+    game.click(Point::new(0, 0));
+    assert!(matches!(game.state(), State::Animating(_)));
+    assert!(score_moves(&mut game).is_empty());
+    game.advance_until_stable();
+    assert!(game.winner().is_some());
+    assert!(score_moves(&mut game).is_empty());
+}
+
+#[test]
+fn large_boards_are_scored_quickly() {
+    let config = bench::config(2, Point::new(30, 20)).unwrap();
+    let mut game = Game::new(config).unwrap();
+    game.click(Point::new(5, 5));
+    game.advance_until_stable();
+    game.click(Point::new(20, 10));
+    game.advance_until_stable();
+    // Only the position after the move counts, so a single marble is all there is to gain
+    let scores = score_moves(&mut game);
+    assert_eq!(scores.len(), 599);
+    assert!(scores.iter().all(|&(_, score)| score == 0.0 || score == 1.0));
+}
+
+#[test]
+fn moves_can_be_scored_one_at_a_time() {
+    let mut game = new_game(Point::new(4, 4));
+    game.decode("4x4 a 2 a1b2..a2...b1.......").unwrap();
+    let before = game.encode();
+    let mut all = score_moves(&mut game);
+    let mut scorer = MoveScorer::new(&game);
+    assert_eq!(scorer.owner(), game.cur_player());
+    let mut steps = 0;
+    while !scorer.done() {
+        scorer.score_next(&mut game);
+        steps += 1;
+        assert_eq!(game.encode(), before);
+    }
+    assert_eq!(steps, game.legal_moves().len());
+    let mut scores = scorer.scores().to_vec();
+    all.sort_by_key(|(p, _)| (p.im, p.re));
+    scores.sort_by_key(|(p, _)| (p.im, p.re));
+    assert_eq!(scores, all);
+}
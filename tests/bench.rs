@@ -0,0 +1,13 @@
+use chain::bench;
+use chain::grid::Point;
+
+#[test]
+fn simulations_are_reproducible() {
+    let config = bench::config(3, Point::new(4, 3)).unwrap();
+    let stats = bench::run(&config, 50, 7).unwrap();
+    assert_eq!(stats.to_string(), bench::run(&config, 50, 7).unwrap().to_string());
+    assert_ne!(stats.to_string(), bench::run(&config, 50, 8).unwrap().to_string());
+    assert_eq!(stats.wins.iter().sum::<usize>() + stats.undecided, 50);
+    assert!(stats.max_depth > 0);
+    assert!(bench::config(7, Point::new(4, 3)).is_err());
+}
@@ -0,0 +1,90 @@
+use chain::bench;
+use chain::game::Game;
+use chain::grid::{Grid, Point};
+use chain::menu::Config;
+use chain::shape::Shape;
+
+mod common;
+use common::{owner, CELLSIZE};
+
+fn config() -> Config {
+    Config { bonus: vec![Point::new(1, 0)], ..bench::config(3, Point::new(3, 3)).unwrap() }
+}
+
+#[test]
+fn bonus_cells_count_when_they_change_owner() {
+    let mut grid = Grid::decode("3x3 a1b1.......", 2, CELLSIZE).unwrap();
+    grid.add_bonus(Point::new(1, 0)).unwrap();
+    grid.add_bonus(Point::new(0, 1)).unwrap();
+    assert!(grid.add_bonus(Point::new(3, 0)).is_err());
+    assert!(grid.cell(Point::new(1, 0)).bonus());
+
+    // Placing a marble on a bonus cell does not capture it
+    grid.cascade(Point::new(1, 0), owner(1), CELLSIZE).unwrap().for_each(drop);
+    assert!(grid.take_bonus_captures().is_empty());
+
+    // Taking the cell of the other player does, but spreading into the empty one does not
+    grid.cascade(Point::new(0, 0), owner(0), CELLSIZE).unwrap().for_each(drop);
+    assert_eq!(grid.take_bonus_captures(), [(Point::new(1, 0), owner(0))]);
+    assert!(grid.take_bonus_captures().is_empty());
+}
+
+#[test]
+fn bonus_cells_only_count_once_per_cascade() {
+    let mut grid = Grid::decode("2x2 a1b1b1.", 2, CELLSIZE).unwrap();
+    grid.add_bonus(Point::new(1, 0)).unwrap();
+    let waves = grid.cascade(Point::new(0, 0), owner(0), CELLSIZE).unwrap();
+    // The marbles keep going around the board, passing the bonus cell again and again
+    let mut arrivals = 0;
+    for wave in waves.take(12) {
+        arrivals += wave.unwrap().received.iter().filter(|&&p| p == Point::new(1, 0)).count();
+    }
+    assert!(arrivals > 2);
+    assert_eq!(grid.take_bonus_captures(), [(Point::new(1, 0), owner(0))]);
+}
+
+#[test]
+fn capturing_a_bonus_cell_grants_an_extra_placement() {
+    let mut game = Game::new(config()).unwrap();
+    for &(x, y) in &[(0, 0), (1, 0), (2, 2)] {
+        game.click(Point::new(x, y));
+        game.advance_until_stable();
+        assert_eq!(game.placements_left(), 1);
+    }
+    // The first player takes the bonus cell of the second one, who is out
+    game.click(Point::new(0, 0));
+    game.advance_until_stable();
+    assert_eq!((game.cur_player().idx(), game.placements_left()), (2, 1));
+    game.click(Point::new(2, 1));
+    game.advance_until_stable();
+    // Their next turn has two marbles, after which the turn passes as usual
+    assert_eq!((game.cur_player().idx(), game.placements_left()), (0, 2));
+    game.click(Point::new(0, 2));
+    game.advance_until_stable();
+    assert_eq!((game.cur_player().idx(), game.placements_left()), (0, 1));
+    game.click(Point::new(1, 1));
+    game.advance_until_stable();
+    assert_eq!((game.cur_player().idx(), game.placements_left()), (2, 1));
+
+    // A rematch starts without any bonus
+    let game = game.rematch();
+    assert_eq!(game.placements_left(), 1);
+    assert!(game.grid().cell(Point::new(1, 0)).bonus());
+}
+
+#[test]
+fn bonus_cells_must_be_on_the_board() {
+    let config = Config { bonus: vec![Point::new(3, 1)], ..config() };
+    assert!(config.validate().is_err());
+    // Cells outside of the shape of the board can not be bonus cells either
+    let mut config = Config { shape: Shape::Donut, size: Point::new(5, 5), ..config };
+    config.bonus = vec![Point::new(2, 2)];
+    let error = Game::new(config.clone()).err().unwrap();
+    assert!(error.contains("not part of the shape"), "{}", error);
+    config.bonus = vec![Point::new(0, 2)];
+    let mut game = Game::new(config).unwrap();
+    // Positions without the bonus cell are refused when pasted
+    assert!(game.decode("5x5 a 3 6.3#.4#2.3#6.").is_err());
+    game.decode("5x5 a 3 6.3#2.3#2.3#6.").unwrap();
+    assert!(game.grid().cell(Point::new(0, 2)).bonus());
+}
@@ -0,0 +1,29 @@
+use sdl2::rect::Rect;
+
+use chain::render::breathe;
+
+#[test]
+fn marbles_breathe_within_their_cell() {
+    let cell = Rect::new(100, 200, 40, 40);
+    // Marble in a slot one pixel away from the edge of the cell
+    let marble = Rect::new(101, 215, 11, 11);
+    let mut sizes = Vec::new();
+    for frame in 0..300 {
+        let rect = breathe(marble, cell, frame, 7, 2);
+        assert!(rect.left() >= cell.left() && rect.right() <= cell.right(), "{:?} leaves the cell in frame {}", rect, frame);
+        assert!(rect.top() >= cell.top() && rect.bottom() <= cell.bottom(), "{:?} leaves the cell in frame {}", rect, frame);
+        // The marble grows around its center
+        assert_eq!((rect.center(), rect.width()), (marble.center(), rect.height()));
+        assert!(rect.width() >= 11 && rect.width() <= 13);
+        sizes.push(rect.width());
+    }
+    assert!(sizes.iter().min() < sizes.iter().max());
+}
+
+#[test]
+fn marbles_breathe_out_of_phase() {
+    let cell = Rect::new(0, 0, 40, 40);
+    let marble = Rect::new(15, 15, 11, 11);
+    let sizes = |id| (0..150).map(|frame| breathe(marble, cell, frame, id, 2).width()).collect::<Vec<_>>();
+    assert_ne!(sizes(1), sizes(2));
+}
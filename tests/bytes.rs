@@ -0,0 +1,81 @@
+use chain::grid::{reserve_position, slot_position, Grid, Point, SlotKind};
+use chain::shape::Shape;
+
+mod common;
+use common::{random_position, Rng, CELLSIZE};
+
+fn round_trip(grid: &Grid, players: usize) -> Grid {
+    let bytes = grid.to_bytes();
+    assert_eq!(bytes.len(), 4 + 2 * (grid.dim().re * grid.dim().im) as usize);
+    let copy = Grid::from_bytes(grid.dim(), &bytes, players, CELLSIZE).unwrap();
+    assert_eq!(copy.to_bytes(), bytes);
+    copy
+}
+
+#[test]
+fn random_boards_survive_the_round_trip() {
+    let mut rng = Rng(0x6A09_E667_F3BC_C908);
+    for _ in 0..100 {
+        let dim = Point::new(2 + rng.next(10) as i32, 2 + rng.next(10) as i32);
+        let players = 2 + rng.next(4);
+        let neutral = rng.next(2) == 1;
+        let grid = random_position(&mut rng, dim, players, neutral);
+        let copy = round_trip(&grid, players);
+        assert_eq!(copy.encode(), grid.encode());
+        assert_eq!(copy.hash(), grid.hash());
+        assert_eq!(copy.marbles().count(), grid.marbles().count());
+    }
+}
+
+#[test]
+fn shaped_boards_survive_the_round_trip() {
+    let dim = Point::new(7, 5);
+    for shape in [Shape::Plus, Shape::Donut, Shape::Diamond] {
+        let grid = Grid::from_mask(dim, &shape.mask(dim));
+        let copy = round_trip(&grid, 2);
+        assert_eq!(copy.encode(), grid.encode(), "{}", shape.name());
+        assert_eq!(copy.playable_count(), grid.playable_count(), "{}", shape.name());
+    }
+}
+
+/* Only owners and counts are stored. The marbles of a loaded board rest in the slots add_marble()
+ * would have put them in, wherever they were before.
+ */
+#[test]
+fn loaded_marbles_rest_in_their_slots() {
+    let grid = Grid::decode("3x3 a1b2.a2*3b2.b1a1", 2, CELLSIZE).unwrap();
+    let copy = round_trip(&grid, 2);
+    assert_eq!(copy.in_flight().count(), 0);
+    for (coord, cell) in copy.cells() {
+        for (kind, direction, marble) in cell.marbles_by_slot() {
+            let target = match kind {
+                SlotKind::Reserve => reserve_position(coord, direction, CELLSIZE),
+                _ => slot_position(coord, direction, CELLSIZE),
+            };
+            assert_eq!(marble.get_pos(), target, "{} in {}", marble.id(), coord);
+        }
+    }
+}
+
+#[test]
+fn malformed_bytes_are_refused() {
+    let dim = Point::new(3, 2);
+    let bytes = Grid::decode("3x2 a1b2.a1.b1", 2, CELLSIZE).unwrap().to_bytes();
+    // Too short, too long and for a board of another size
+    assert!(Grid::from_bytes(dim, &bytes[..bytes.len() - 1], 2, CELLSIZE).is_err());
+    assert!(Grid::from_bytes(dim, &[bytes.as_slice(), &[0]].concat(), 2, CELLSIZE).is_err());
+    assert!(Grid::from_bytes(Point::new(2, 3), &bytes, 2, CELLSIZE).is_err());
+    // An owner beyond the players of the game
+    assert!(Grid::from_bytes(dim, &bytes, 1, CELLSIZE).is_err());
+    // A corner at capacity, an owner without marbles and marbles without an owner
+    for (offset, value) in [(5, 2), (5, 0), (4, 255)] {
+        let mut broken = bytes.clone();
+        broken[offset] = value;
+        assert!(Grid::from_bytes(dim, &broken, 2, CELLSIZE).is_err(), "{} = {}", offset, value);
+    }
+    // Boards without cells, even if the bytes match them
+    assert!(Grid::from_bytes(Point::new(0, 3), &[0, 0, 3, 0], 2, CELLSIZE).is_err());
+    assert!(Grid::from_bytes(Point::new(3, 0), &[3, 0, 0, 0], 2, CELLSIZE).is_err());
+    assert!(Grid::from_bytes(Point::new(-1, 3), &bytes, 2, CELLSIZE).is_err());
+    assert!(Grid::from_bytes(Point::new(-2, -3), &bytes, 2, CELLSIZE).is_err());
+}
@@ -0,0 +1,169 @@
+use chain::game::State;
+use chain::grid::{ease, reserve_position, slot_position, Grid, GridEvent, Point, SlotKind, MAX_WAVES};
+
+mod common;
+use common::{owner, random_game, Rng, CELLSIZE};
+
+// Positions where many cells are one marble short of spreading, so most moves set off cascades
+const LOADED: &[&str] = &[
+    "3x3 a1b2a1b2a3b2a1b2a1",
+    "5x4 a1b2b2a2a1b2a3b3a3a2a2b3a3b3b2a1a2b2b2a1",
+    "4x4 a12a2a1a22a32a22a3a2a12a2a1",
+];
+
+/* Cells that fire in the next spreading step if the whole board is scanned for cells at capacity,
+ * in the order they are stored, starting with the last one
+ */
+fn scan(grid: &Grid) -> Vec<Point> {
+    let mut full: Vec<Point> = grid.cells()
+        .filter(|(_, cell)| cell.playable() && cell.marbles().count() >= cell.threshold() as usize)
+        .map(|(p, _)| p)
+        .collect();
+    full.reverse();
+    full
+}
+
+fn critical_soon(grid: &Grid) -> Vec<Point> {
+    grid.cells().filter(|(_, cell)| cell.is_critical_soon()).map(|(p, _)| p).collect()
+}
+
+fn fired(events: &[GridEvent]) -> Vec<Point> {
+    events.iter().filter_map(|event| match event {
+        GridEvent::CellFired { at, .. } => Some(*at),
+        _ => None,
+    }).collect()
+}
+
+#[test]
+fn cascade_yields_one_wave_per_spreading_step() {
+    // The multi-wave cascade of the scripted games
+    let moves = [(0, 0), (2, 2), (0, 0), (2, 2), (1, 0), (2, 1), (1, 0)];
+    let mut grid = Grid::new(Point::new(3, 3));
+    for (turn, &(x, y)) in moves.iter().enumerate() {
+        for wave in grid.cascade(Point::new(x, y), owner(turn % 2), CELLSIZE).unwrap() {
+            wave.unwrap();
+        }
+    }
+    let waves: Vec<_> = grid.cascade(Point::new(2, 1), owner(1), CELLSIZE).unwrap()
+        .collect::<Result<_, _>>().unwrap();
+    assert_eq!(waves.len(), 2);
+    assert_eq!(waves[0].fired, [Point::new(2, 1)]);
+    assert_eq!(waves[0].received, [Point::new(2, 2), Point::new(1, 1), Point::new(2, 0)]);
+    assert_eq!(waves[1].fired, [Point::new(2, 0)]);
+    assert_eq!(waves[1].received, [Point::new(2, 1), Point::new(1, 0)]);
+    assert_eq!(grid.encode(), "3x3 a1b1.a1b2b1.2b1");
+    assert_eq!(grid.chain(), 2);
+}
+
+#[test]
+fn cascade_matches_animation() {
+    let moves = [(0, 0), (2, 2), (0, 0), (2, 2), (1, 0), (2, 1), (1, 0), (2, 1), (0, 1), (1, 2)];
+    let mut animated = Grid::new(Point::new(3, 3));
+    let mut resolved = Grid::new(Point::new(3, 3));
+    for (turn, &(x, y)) in moves.iter().enumerate() {
+        let p = Point::new(x, y);
+        let (mut state, _) = animated.add_marble(p, owner(turn % 2), CELLSIZE).unwrap();
+        let mut steps = 0;
+        while let State::Animating(_) = state {
+            state = animated.step(state, 0.25, CELLSIZE).0;
+            steps += 1;
+        }
+        let waves = resolved.cascade(p, owner(turn % 2), CELLSIZE).unwrap().count();
+        assert_eq!(resolved.encode(), animated.encode(), "after move {}", turn);
+        assert_eq!(waves as u32, resolved.chain());
+        assert_eq!(steps, 4 * waves);
+    }
+}
+
+#[test]
+fn worklist_fires_the_cells_a_scan_finds() {
+    let mut rng = Rng(0x5851_F42D_4C95_7F2D);
+    for position in LOADED {
+        let mut grid = Grid::decode(position, 2, CELLSIZE).unwrap();
+        // Cells one marble short of spreading when the board last settled, and the cells a scan
+        // finds for the next step
+        let mut soon = critical_soon(&grid);
+        let mut expected = Vec::new();
+        random_game(&mut grid, &mut rng, 2, 30, 1.0, |frame| {
+            if let Some(p) = frame.placed {
+                expected = if soon.contains(&p) { vec![p] } else { vec![] };
+            }
+            assert_eq!(fired(frame.events), expected, "{} move {}", position, frame.turn);
+            if frame.placed.is_none() {
+                assert_eq!(matches!(frame.state, State::AcceptingInput), expected.is_empty(), "{} move {}", position, frame.turn);
+            }
+            expected = scan(frame.grid);
+            if let State::AcceptingInput = frame.state {
+                soon = critical_soon(frame.grid);
+            }
+        });
+    }
+}
+
+#[test]
+fn placements_that_do_not_spread_yield_nothing() {
+    let mut grid = Grid::new(Point::new(3, 3));
+    assert_eq!(grid.cascade(Point::new(1, 1), owner(0), CELLSIZE).unwrap().count(), 0);
+    let error = grid.cascade(Point::new(1, 1), owner(1), CELLSIZE).err().unwrap();
+    assert!(error.contains("does not accept"), "{}", error);
+    assert!(grid.cascade(Point::new(3, 1), owner(0), CELLSIZE).is_err());
+    assert_eq!(grid.encode(), "3x3 4.a14.");
+}
+
+#[test]
+fn endless_cascades_are_stopped() {
+    // More marbles than the board can hold without spreading
+    let mut grid = Grid::decode("2x2 4a1", 2, CELLSIZE).unwrap();
+    let waves: Vec<_> = grid.cascade(Point::new(0, 0), owner(0), CELLSIZE).unwrap().collect();
+    assert_eq!(waves.len(), MAX_WAVES as usize + 1);
+    assert!(waves[..MAX_WAVES as usize].iter().all(|wave| wave.is_ok()));
+    assert!(waves[MAX_WAVES as usize].is_err());
+}
+
+#[test]
+fn marbles_come_to_rest_exactly_in_their_slots() {
+    // Uneven steps and cells that are not square, where rounding errors would show
+    let moves = [(0, 0), (2, 2), (0, 0), (2, 2), (1, 0), (2, 1), (1, 0), (2, 1), (0, 1), (1, 2), (0, 1)];
+    let cases = [
+        (0.3, Point::new(100, 100)),
+        (1.0 / 3.0, Point::new(77, 41)),
+        (0.7, Point::new(33, 90)),
+        (0.05, Point::new(7, 5)),
+    ];
+    for (delta, cellsize) in cases {
+        let mut grid = Grid::new(Point::new(3, 3));
+        for (turn, &(x, y)) in moves.iter().enumerate() {
+            let (mut state, _) = grid.add_marble(Point::new(x, y), owner(turn % 2), cellsize)
+                .unwrap_or_else(|e| panic!("Move {} was refused: {}", turn, e));
+            while let State::Animating(_) = state {
+                state = grid.step(state, delta, cellsize).0;
+            }
+            for (coord, cell) in grid.cells() {
+                for (kind, direction, marble) in cell.marbles_by_slot() {
+                    let target = match kind {
+                        SlotKind::Residing => slot_position(coord, direction, cellsize),
+                        SlotKind::Reserve => reserve_position(coord, direction, cellsize),
+                        _ => panic!("Marble {} is still {:?} in cell {} after move {}", marble.id(), kind, coord, turn),
+                    };
+                    assert_eq!(
+                        marble.get_pos(), target,
+                        "Marble {} in cell {} is not in its slot after move {}", marble.id(), coord, turn,
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn marbles_slow_down_and_settle_after_a_small_overshoot() {
+    assert!(ease(0.0).abs() < 1e-6);
+    assert_eq!(ease(1.0), 1.0);
+    let samples: Vec<f32> = (0..=100).map(|i| ease(i as f32 / 100.0)).collect();
+    let peak = samples.iter().copied().fold(0.0, f32::max);
+    assert!(peak > 1.02 && peak < 1.1, "peak {}", peak);
+    // The first tenth of the time covers more of the way than the last
+    assert!(samples[10] - samples[0] > (samples[100] - samples[90]).abs());
+    // The way out and back is smooth, without jumps
+    assert!(samples.windows(2).all(|w| (w[1] - w[0]).abs() < 0.05));
+}
@@ -0,0 +1,54 @@
+use chain::bench;
+use chain::game::{Game, OwnerChange, State};
+use chain::grid::Point;
+use chain::menu::Config;
+
+mod common;
+use common::owner;
+
+fn new_game(instant: bool) -> Game {
+    let config = bench::config(2, Point::new(3, 3)).unwrap();
+    Game::new(Config { instant, ..config }).unwrap()
+}
+
+#[test]
+fn placing_a_marble_takes_its_cell() {
+    let mut game = new_game(false);
+    game.click(Point::new(1, 1));
+    assert_eq!(game.last_owner_changes(), [OwnerChange { at: Point::new(1, 1), from: None, to: Some(owner(0)) }]);
+    // Refused moves do not change anything
+    game.click(Point::new(1, 1));
+    assert_eq!(game.last_owner_changes().len(), 1);
+}
+
+#[test]
+fn cascades_are_compared_once_they_settled() {
+    for instant in [false, true] {
+        let mut game = new_game(instant);
+        game.decode("3x3 a 2 a1b1.b1.....").unwrap();
+        let before = game.owners();
+        game.click(Point::new(0, 0));
+        if !instant {
+            assert!(matches!(game.state(), State::Animating(_)));
+            assert!(game.last_owner_changes().is_empty());
+            game.advance_until_stable();
+        }
+        let changes = game.last_owner_changes().to_vec();
+        assert_eq!(changes, game.owner_changes(&before));
+        // The corner spread and took both neighbors
+        assert!(changes.contains(&OwnerChange { at: Point::new(0, 0), from: Some(owner(0)), to: None }));
+        assert!(changes.contains(&OwnerChange { at: Point::new(1, 0), from: Some(owner(1)), to: Some(owner(0)) }));
+        assert!(changes.contains(&OwnerChange { at: Point::new(0, 1), from: Some(owner(1)), to: Some(owner(0)) }));
+        assert_eq!(changes.len(), 3);
+
+        // Starting over forgets them
+        game.decode("3x3 a 2 a1b1.b1.....").unwrap();
+        assert!(game.last_owner_changes().is_empty());
+    }
+}
+
+#[test]
+fn unchanged_positions_have_no_changes() {
+    let game = new_game(false);
+    assert!(game.owner_changes(&game.owners()).is_empty());
+}
@@ -0,0 +1,94 @@
+/* Helpers shared by the integration tests. Each test binary includes this module and uses only
+ * some of them.
+ */
+#![allow(dead_code)]
+
+use chain::game::State;
+use chain::grid::{Grid, GridEvent, Owner, Point};
+
+pub const CELLSIZE: Point = Point::new(100, 100);
+
+/* Owner with the given index in a game of two players */
+pub fn owner(idx: usize) -> Owner {
+    Owner::new(idx, 2).unwrap()
+}
+
+// Small xorshift generator, so the random positions are the same in each run
+pub struct Rng(pub u64);
+impl Rng {
+    pub fn next(&mut self, bound: usize) -> usize {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 % bound as u64) as usize
+    }
+}
+
+/* Random settled position of the given number of players, where each cell is empty, belongs to a
+ * player or, if asked for, is neutral
+ */
+pub fn random_position(rng: &mut Rng, dim: Point, players: usize, neutral: bool) -> Grid {
+    let mut text = format!("{}x{} ", dim.re, dim.im);
+    for y in 0..dim.im {
+        for x in 0..dim.re {
+            let neighbors = 4 - (x == 0) as usize - (y == 0) as usize
+                - (x == dim.re - 1) as usize - (y == dim.im - 1) as usize;
+            match rng.next(players + 1 + neutral as usize) {
+                0 => text.push('.'),
+                1 if neutral => text += &format!("*{}", 1 + rng.next(neighbors - 1)),
+                owner => {
+                    let owner = owner - neutral as usize;
+                    text += &format!("{}{}", (b'a' + owner as u8 - 1) as char, 1 + rng.next(neighbors - 1));
+                },
+            }
+        }
+    }
+    Grid::decode(&text, players, CELLSIZE).unwrap()
+}
+
+// Spreading steps after which random_game() gives up on a cascade
+pub const MAX_STEPS: usize = 500;
+
+/* Where a random game is, after a marble was placed or after a step of the animation */
+pub struct Frame<'a> {
+    pub grid: &'a Grid,
+    // Number of the move, starting with 0
+    pub turn: usize,
+    // Cell of the marble that was just placed, None after a step
+    pub placed: Option<Point>,
+    pub state: &'a State,
+    pub events: &'a [GridEvent],
+}
+
+/* Play up to the given number of moves, where the players take turns placing a marble in a random
+ * cell they may use, and animate each move with steps of the given size. The closure is called
+ * after each placement and each step. The game ends early once the player to move has no cell
+ * left, or once a cascade does not come to rest, which happens after one player took over the
+ * board. Returns the number of moves that were played.
+ */
+pub fn random_game<F>(grid: &mut Grid, rng: &mut Rng, players: usize, moves: usize, delta: f32, mut check: F) -> usize
+where
+    F: FnMut(Frame),
+{
+    for turn in 0..moves {
+        let owner = Owner::new(turn % players, players).unwrap();
+        let free: Vec<Point> = grid.cells().filter(|(_, cell)| cell.accepts(owner)).map(|(p, _)| p).collect();
+        if free.is_empty() {
+            return turn;
+        }
+        let p = free[rng.next(free.len())];
+        let (mut state, events) = grid.add_marble(p, owner, CELLSIZE).unwrap();
+        check(Frame { grid, turn, placed: Some(p), state: &state, events: &events });
+        let mut steps = 0;
+        while let State::Animating(_) = state {
+            if steps == MAX_STEPS {
+                return turn + 1;
+            }
+            let (next, events) = grid.step(state, delta, CELLSIZE);
+            state = next;
+            check(Frame { grid, turn, placed: None, state: &state, events: &events });
+            steps += 1;
+        }
+    }
+    moves
+}
@@ -0,0 +1,31 @@
+use chain::game::State;
+use chain::grid::{Grid, Point};
+
+mod common;
+use common::{random_game, Rng};
+
+const PLAYERS: usize = 3;
+
+/* Play random legal moves on random board sizes and count the marbles after every spreading step.
+ * Marbles are never destroyed, so there must always be exactly as many as were placed, and each
+ * settled cell has to hold fewer marbles than it takes to spread.
+ */
+#[test]
+fn marbles_are_never_lost_in_random_games() {
+    let mut rng = Rng(0x2545_F491_4F6C_DD1D);
+    let mut moves = 0;
+    for _ in 0..60 {
+        let dim = Point::new(2 + rng.next(8) as i32, 2 + rng.next(8) as i32);
+        let mut grid = Grid::new(dim);
+        moves += random_game(&mut grid, &mut rng, PLAYERS, 100, 1.0, |frame| {
+            let placed = frame.turn + 1;
+            assert_eq!(frame.grid.marbles().count(), placed, "{}x{} after {} moves", dim.re, dim.im, placed);
+            if let State::AcceptingInput = frame.state {
+                for (p, cell) in frame.grid.cells().filter(|(_, cell)| cell.playable()) {
+                    assert!(cell.marbles().count() < cell.threshold() as usize, "{}x{} at {}", dim.re, dim.im, p);
+                }
+            }
+        });
+    }
+    assert!(moves > 2000, "only {} moves were played", moves);
+}
@@ -0,0 +1,44 @@
+use sdl2::pixels::Color;
+
+use chain::render::{contrasting, greyed, luminance};
+
+const BLACK: Color = Color::RGB(0, 0, 0);
+const WHITE: Color = Color::RGB(255, 255, 255);
+
+#[test]
+fn labels_contrast_with_the_background() {
+    assert_eq!(contrasting(WHITE), BLACK);
+    assert_eq!(contrasting(Color::RGB(200, 200, 200)), BLACK);
+    assert_eq!(contrasting(BLACK), WHITE);
+    assert_eq!(contrasting(Color::RGB(40, 40, 60)), WHITE);
+    // Mid gray is closer to white in luminance than it looks, so black reads better on it
+    assert_eq!(contrasting(Color::RGB(128, 128, 128)), BLACK);
+    assert_eq!(contrasting(Color::RGB(100, 100, 100)), WHITE);
+    // Green is much brighter than blue at the same value
+    assert_eq!(contrasting(Color::RGB(0, 160, 0)), BLACK);
+    assert_eq!(contrasting(Color::RGB(0, 0, 160)), WHITE);
+}
+
+#[test]
+fn eliminated_players_turn_gray() {
+    let background = Color::RGB(200, 200, 200);
+    for color in [Color::RGB(255, 0, 0), Color::RGB(0, 160, 0), Color::RGB(30, 30, 200), WHITE, BLACK] {
+        let gray = greyed(color, background);
+        assert!(gray.r == gray.g && gray.g == gray.b, "{:?}", color);
+        // Halfway between a gray as bright as the color and the background
+        let v = (2 * gray.r as i32 - background.r as i32).clamp(0, 255) as u8;
+        assert!((luminance(Color::RGB(v, v, v)) - luminance(color)).abs() < 0.01, "{:?}", color);
+    }
+    // Bright and dark colors stay apart, but closer to the background
+    let (light, dark) = (greyed(Color::RGB(255, 255, 0), background), greyed(Color::RGB(0, 0, 160), background));
+    assert!(light.r > dark.r);
+    assert!(light.r <= 228 && dark.r >= 100);
+}
+
+#[test]
+fn luminance_spans_black_to_white() {
+    assert_eq!(luminance(BLACK), 0.0);
+    assert!((luminance(WHITE) - 1.0).abs() < 1e-4);
+    let grays: Vec<f32> = (0..=255).step_by(15).map(|v| luminance(Color::RGB(v, v, v))).collect();
+    assert!(grays.windows(2).all(|pair| pair[0] < pair[1]));
+}
@@ -0,0 +1,137 @@
+use chain::game::State;
+use chain::grid::{CellChange, Grid, Owner, Point};
+
+mod common;
+use common::{Rng, CELLSIZE};
+
+const PLAYERS: usize = 3;
+
+fn copy(grid: &Grid) -> Grid {
+    Grid::from_bytes(grid.dim(), &grid.to_bytes(), PLAYERS, CELLSIZE).unwrap()
+}
+
+/* Settled positions after each move of a random game, see common::random_game() */
+fn random_game(rng: &mut Rng, dim: Point, moves: usize) -> Vec<Grid> {
+    let mut grid = Grid::new(dim);
+    let mut positions = Vec::new();
+    common::random_game(&mut grid, rng, PLAYERS, moves, 1.0, |frame| {
+        if let State::AcceptingInput = frame.state {
+            positions.push(copy(frame.grid));
+        }
+    });
+    positions
+}
+
+/* Random settled position where each cell is empty, neutral or belongs to a player */
+fn random_position(rng: &mut Rng, dim: Point) -> Grid {
+    common::random_position(rng, dim, PLAYERS, true)
+}
+
+fn assert_round_trip(a: &Grid, b: &Grid) {
+    let mut result = copy(a);
+    let delta = a.diff(b).unwrap();
+    result.apply_delta(&delta, CELLSIZE).unwrap();
+    assert_eq!(result.encode(), b.encode());
+    assert_eq!(result.hash(), b.hash());
+    assert_eq!(result.marbles().count(), b.marbles().count());
+    assert!(result.diff(b).unwrap().is_empty());
+}
+
+#[test]
+fn deltas_between_positions_of_random_games() {
+    let mut rng = Rng(0x9E37_79B9_7F4A_7C15);
+    for _ in 0..20 {
+        let positions = random_game(&mut rng, Point::new(6, 5), 60);
+        for (a, b) in positions.iter().zip(positions.iter().skip(1)) {
+            assert_round_trip(a, b);
+        }
+        if let (Some(first), Some(last)) = (positions.first(), positions.last()) {
+            assert_round_trip(first, last);
+            assert_round_trip(last, first);
+        }
+    }
+}
+
+#[test]
+fn deltas_between_completely_different_positions() {
+    let mut rng = Rng(0x2545_F491_4F6C_DD1D);
+    for _ in 0..100 {
+        let a = random_position(&mut rng, Point::new(7, 4));
+        let b = random_position(&mut rng, Point::new(7, 4));
+        assert_round_trip(&a, &b);
+        assert_round_trip(&a, &Grid::new(Point::new(7, 4)));
+        assert_round_trip(&Grid::new(Point::new(7, 4)), &a);
+    }
+}
+
+#[test]
+fn deltas_of_single_cells() {
+    let mut rng = Rng(0xDEAD_BEEF);
+    let owner = Owner::new(1, PLAYERS).unwrap();
+    for _ in 0..50 {
+        let a = random_position(&mut rng, Point::new(5, 5));
+        let p = Point::new(rng.next(5) as i32, rng.next(5) as i32);
+        let mut b = copy(&a);
+        if b.insert_marble(p, owner, CELLSIZE).is_err() || b.check_settled().is_err() {
+            b = copy(&a);
+            b.remove_marble(p).unwrap();
+        }
+        let delta = a.diff(&b).unwrap();
+        assert_eq!(delta.changes.len(), 1);
+        assert_eq!(delta.changes[0].at, p);
+        assert_round_trip(&a, &b);
+        assert_round_trip(&b, &a);
+    }
+}
+
+#[test]
+fn invalid_deltas_are_refused() {
+    let mut rng = Rng(0x1234_5678);
+    let a = random_position(&mut rng, Point::new(4, 4));
+    let b = random_position(&mut rng, Point::new(4, 4));
+    let delta = a.diff(&b).unwrap();
+
+    // Applying to any other position
+    let mut other = copy(&b);
+    assert!(other.apply_delta(&delta, CELLSIZE).is_err());
+    assert_eq!(other.encode(), b.encode());
+
+    let owner = Some(Owner::new(0, PLAYERS).unwrap());
+    let corner = CellChange { at: Point::new(0, 0), owner, count: 1 };
+    let refused = [
+        // Cell at capacity
+        CellChange { count: 2, ..corner },
+        // Owner without marbles and the other way around
+        CellChange { count: 0, ..corner },
+        CellChange { owner: None, ..corner },
+        // Outside of the board
+        CellChange { at: Point::new(4, 0), ..corner },
+    ];
+    for change in refused {
+        let mut changed = delta.clone();
+        changed.changes = vec![change];
+        let mut grid = copy(&a);
+        assert!(grid.apply_delta(&changed, CELLSIZE).is_err(), "{:?} was accepted", change);
+        assert_eq!(grid.encode(), a.encode());
+    }
+
+    // The same cell twice
+    let mut changed = a.diff(&Grid::new(Point::new(4, 4))).unwrap();
+    let first = changed.changes[0];
+    changed.changes.push(first);
+    let mut grid = copy(&a);
+    assert!(grid.apply_delta(&changed, CELLSIZE).is_err());
+    assert_eq!(grid.encode(), a.encode());
+}
+
+#[test]
+fn boards_that_do_not_match_are_refused() {
+    let mut rng = Rng(0x0BAD_CAFE);
+    let a = random_position(&mut rng, Point::new(4, 4));
+    let error = a.diff(&Grid::new(Point::new(4, 5))).unwrap_err();
+    assert!(error.contains("4x4 and 4x5"), "{}", error);
+    let mut mask = vec![true; 16];
+    mask[6] = false;
+    let error = a.diff(&Grid::from_mask(Point::new(4, 4), &mask)).unwrap_err();
+    assert!(error.contains("shape at C2"), "{}", error);
+}
@@ -0,0 +1,37 @@
+use std::time::Duration;
+
+use chain::demo::{Demo, Settings};
+use chain::grid::Point;
+
+#[test]
+fn demo_plays_games_one_after_another() {
+    let settings = Settings { size: Point::new(3, 3), speed: 4.0, ..Settings::default() };
+    let mut demo = Demo::new(&settings, 7).unwrap();
+    let (mut won, mut started) = (0, 0);
+    let mut first = demo.game().first_player();
+    let mut placed = 0;
+    for _ in 0..20_000 {
+        let had_winner = demo.game().winner().is_some();
+        demo.advance(Duration::from_millis(20));
+        assert!(demo.game().marbles_placed() >= placed || demo.game().marbles_placed() == 0);
+        placed = demo.game().marbles_placed();
+        if demo.game().winner().is_some() && !had_winner {
+            won += 1;
+        }
+        if demo.game().first_player() != first {
+            first = demo.game().first_player();
+            started += 1;
+        }
+    }
+    assert!(won >= 3, "only {} games were won", won);
+    assert!(started >= won - 1);
+}
+
+#[test]
+fn invalid_demo_settings_are_refused() {
+    assert!(Settings::default().validate().is_ok());
+    assert!(Settings { speed: 0.0, ..Settings::default() }.validate().is_err());
+    assert!(Settings { speed: f32::NAN, ..Settings::default() }.validate().is_err());
+    assert!(Settings { size: Point::new(1, 4), ..Settings::default() }.validate().is_err());
+    assert!(Demo::new(&Settings { speed: -1.0, ..Settings::default() }, 0).is_err());
+}
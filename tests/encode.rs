@@ -0,0 +1,52 @@
+use chain::bench;
+use chain::game::Game;
+use chain::grid::{Grid, Point};
+
+mod common;
+use common::{random_position, Rng, CELLSIZE};
+
+#[test]
+fn random_positions_survive_the_round_trip() {
+    let mut rng = Rng(0x3C6E_F372_FE94_F82B);
+    for _ in 0..200 {
+        let dim = Point::new(2 + rng.next(12) as i32, 2 + rng.next(12) as i32);
+        let players = 2 + rng.next(5);
+        let neutral = rng.next(2) == 1;
+        let grid = random_position(&mut rng, dim, players, neutral);
+        let text = grid.encode();
+        assert!(!text.contains('\n'));
+        let copy = Grid::decode(&text, players, CELLSIZE).unwrap();
+        assert_eq!(copy.encode(), text);
+        assert_eq!(copy.to_bytes(), grid.to_bytes());
+    }
+}
+
+#[test]
+fn games_keep_the_player_to_move() {
+    let mut game = Game::new(bench::config(3, Point::new(3, 3)).unwrap()).unwrap();
+    game.decode("3x3 c 3 a1b1.c25.").unwrap();
+    assert_eq!(game.encode().as_deref(), Some("3x3 c 3 a1b1.c25."));
+    assert_eq!(game.cur_player().idx(), 2);
+    // Cells that are not combined into runs and a trailing newline, like a pasted line
+    game.decode("3x3 b 3 .........\n").unwrap();
+    assert_eq!(game.encode().as_deref(), Some("3x3 b 3 9."));
+}
+
+#[test]
+fn malformed_positions_are_refused() {
+    for text in [
+        "", "3x3", "3 9.", "3x 9.", "x3 9.", "0x3 .", "3x3 8.", "3x3 10.", "3x3 0a1", "3x3 4.a", "3x3 4.a0",
+        "3x3 4.A1", "3x3 4.?1", "3x3 4.c1", "3x3 a2", "3x3 8.a2", "3x3 4.a4", "3x3 4", "3x3 b1.*0",
+    ] {
+        assert!(Grid::decode(text, 2, CELLSIZE).is_err(), "{:?} was accepted", text);
+    }
+    let mut game = Game::new(bench::config(2, Point::new(3, 3)).unwrap()).unwrap();
+    let before = game.encode();
+    for text in [
+        "3x3 9.", "3x3 a 9.", "3x3 a 2 9. .", "3x3 a 3 9.", "3x3 ab 2 9.", "3x3 c 2 9.", "2x2 a 2 4.",
+        "3x3 a x 9.",
+    ] {
+        assert!(game.decode(text).is_err(), "{:?} was accepted", text);
+    }
+    assert_eq!(game.encode(), before);
+}
@@ -0,0 +1,92 @@
+use chain::bench;
+use chain::game::{Game, State, SPREAD_DURATION};
+use chain::grid::{Grid, GridEvent, Point};
+
+mod common;
+use common::{owner, CELLSIZE};
+
+// Moves of the scripted multi-wave cascade, up to the one that sets it off
+const MOVES: [(i32, i32); 7] = [(0, 0), (2, 2), (0, 0), (2, 2), (1, 0), (2, 1), (1, 0)];
+
+fn before_cascade() -> Grid {
+    let mut grid = Grid::new(Point::new(3, 3));
+    for (turn, &(x, y)) in MOVES.iter().enumerate() {
+        let _ = grid.cascade(Point::new(x, y), owner(turn % 2), CELLSIZE).unwrap().count();
+    }
+    grid
+}
+
+/* Events of each spreading step, animated with the given step size */
+fn waves(grid: &mut Grid, p: Point, delta: f32) -> Vec<Vec<GridEvent>> {
+    let (mut state, events) = grid.add_marble(p, owner(MOVES.len() % 2), CELLSIZE).unwrap();
+    let mut waves = vec![events];
+    while let State::Animating(_) = state {
+        let (next, events) = grid.step(state, delta, CELLSIZE);
+        if !events.is_empty() {
+            waves.push(events);
+        }
+        state = next;
+    }
+    waves
+}
+
+#[test]
+fn events_of_the_scripted_cascade() {
+    let p = |x, y| Point::new(x, y);
+    let waves = waves(&mut before_cascade(), p(2, 1), 0.25);
+    // Directions are indices into DIRECTIONS: 0 is from the right, 1 from below, 3 from above
+    assert_eq!(waves, vec![
+        vec![
+            GridEvent::CellFired { at: p(2, 1), owner: owner(1) },
+            GridEvent::MarbleArrived { at: p(2, 2), from_dir: 3, owner: owner(1) },
+            GridEvent::MarbleArrived { at: p(1, 1), from_dir: 0, owner: owner(1) },
+            GridEvent::CellCaptured { at: p(1, 1), from: owner(0), to: owner(1) },
+            GridEvent::MarbleArrived { at: p(2, 0), from_dir: 1, owner: owner(1) },
+            GridEvent::CellCaptured { at: p(2, 0), from: owner(0), to: owner(1) },
+        ],
+        vec![
+            GridEvent::CellFired { at: p(2, 0), owner: owner(1) },
+            GridEvent::MarbleArrived { at: p(2, 1), from_dir: 3, owner: owner(1) },
+            GridEvent::MarbleArrived { at: p(1, 0), from_dir: 0, owner: owner(1) },
+        ],
+    ]);
+}
+
+#[test]
+fn events_do_not_depend_on_the_pace() {
+    let reference = waves(&mut before_cascade(), Point::new(2, 1), 1.0);
+    for delta in [0.5, 0.3, 0.07] {
+        assert_eq!(waves(&mut before_cascade(), Point::new(2, 1), delta), reference, "{}", delta);
+    }
+    // The cascade reports the same events, one wave at a time
+    let mut grid = before_cascade();
+    let cascade: Vec<Vec<GridEvent>> = grid.cascade(Point::new(2, 1), owner(1), CELLSIZE).unwrap()
+        .map(|wave| wave.unwrap().events)
+        .collect();
+    assert_eq!(cascade, reference);
+}
+
+#[test]
+fn games_report_the_events_of_the_last_frame() {
+    let mut game = Game::new(bench::config(2, Point::new(3, 3)).unwrap()).unwrap();
+    for (x, y) in MOVES {
+        game.click(Point::new(x, y));
+        game.advance_until_stable();
+    }
+    // Hand out what happened while setting up the position
+    game.step(SPREAD_DURATION / 4);
+    assert!(!game.events().is_empty());
+    game.click(Point::new(2, 1));
+    // What happened on the click is reported with the next frame, instead of the earlier events
+    game.step(SPREAD_DURATION / 4);
+    assert_eq!(game.events().len(), 6);
+    assert!(matches!(game.events()[0], GridEvent::CellFired { .. }));
+    game.step(SPREAD_DURATION / 4);
+    assert!(game.events().is_empty());
+    // The second wave starts once the first one arrived, in a frame of its own
+    while game.events().is_empty() {
+        game.step(SPREAD_DURATION / 4);
+    }
+    assert_eq!(game.events().len(), 3);
+    assert_eq!(game.events()[0], GridEvent::CellFired { at: Point::new(2, 0), owner: owner(1) });
+}
@@ -0,0 +1,44 @@
+use std::path::PathBuf;
+
+use chain::font::{first_working, sources, FontSource, SYSTEM_FONTS};
+
+#[test]
+fn override_comes_before_the_built_in_font() {
+    let system: Vec<FontSource> = SYSTEM_FONTS.iter().map(|path| FontSource::Path(PathBuf::from(path))).collect();
+
+    let found = sources(None);
+    assert_eq!(found[0], FontSource::Embedded);
+    assert_eq!(found[1..], system[..]);
+
+    let custom = PathBuf::from("/home/someone/fonts/Custom.ttf");
+    let found = sources(Some(custom.clone()));
+    assert_eq!(found[..2], [FontSource::Path(custom), FontSource::Embedded]);
+    assert_eq!(found[2..], system[..]);
+}
+
+#[test]
+fn missing_or_broken_fonts_are_skipped() {
+    let missing = PathBuf::from("/nonexistent/Missing.ttf");
+    let broken = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("Cargo.toml");
+    let open = |source: &FontSource| match source {
+        FontSource::Path(path) if path == &broken => Err("not a font".to_string()),
+        FontSource::Path(path) => Ok(path.clone()),
+        FontSource::Embedded => Err("damaged".to_string()),
+    };
+
+    // Each source that fails is reported
+    let (font, errors) = first_working(vec![FontSource::Path(missing.clone()), FontSource::Path(broken.clone())], open);
+    assert_eq!(font, None);
+    assert_eq!(errors.len(), 2);
+    assert!(errors[0].contains("does not exist"));
+    assert!(errors[1].contains("not a font"));
+
+    // A missing or broken source does not keep the next one from being used
+    let readable = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("src/lib.rs");
+    let (font, errors) = first_working(
+        vec![FontSource::Path(missing), FontSource::Embedded, FontSource::Path(readable.clone())],
+        open,
+    );
+    assert_eq!(font, Some(readable));
+    assert_eq!(errors.len(), 2);
+}
@@ -0,0 +1,56 @@
+use std::collections::HashMap;
+
+use chain::grid::{Grid, Point};
+
+mod common;
+use common::{owner, random_position, Rng, CELLSIZE};
+
+fn play(moves: &[(i32, i32, usize)]) -> Grid {
+    let mut grid = Grid::new(Point::new(4, 4));
+    for &(x, y, player) in moves {
+        let _ = grid.cascade(Point::new(x, y), owner(player), CELLSIZE).unwrap().count();
+    }
+    grid
+}
+
+#[test]
+fn move_orders_reaching_the_same_position_hash_equal() {
+    let a = play(&[(1, 1, 0), (2, 2, 1), (1, 2, 0), (3, 1, 1), (1, 1, 0)]);
+    let b = play(&[(1, 2, 0), (3, 1, 1), (1, 1, 0), (2, 2, 1), (1, 1, 0)]);
+    assert_eq!(a.encode(), b.encode());
+    assert_eq!(a.hash(), b.hash());
+
+    // Also when cascades lead there, compared to the position set up directly
+    let c = play(&[(0, 0, 0), (3, 3, 1), (0, 0, 0), (3, 3, 1)]);
+    let d = Grid::decode(&c.encode(), 2, CELLSIZE).unwrap();
+    assert_eq!(c.hash(), d.hash());
+    assert_ne!(c.hash(), Grid::new(Point::new(4, 4)).hash());
+}
+
+#[test]
+fn different_positions_hash_differently() {
+    let mut rng = Rng(0xBB67_AE85_84CA_A73B);
+    let mut seen: HashMap<u64, String> = HashMap::new();
+    for _ in 0..2000 {
+        let grid = random_position(&mut rng, Point::new(5, 4), 3, true);
+        let position = grid.encode();
+        if let Some(other) = seen.insert(grid.hash(), position.clone()) {
+            assert_eq!(other, position, "Both positions have the hash {:x}", grid.hash());
+        }
+    }
+    assert!(seen.len() > 1900);
+}
+
+#[test]
+fn one_marble_more_changes_the_hash() {
+    let mut grid = Grid::new(Point::new(3, 3));
+    let mut hashes = vec![grid.hash()];
+    for (x, y, player) in [(1, 1, 0), (1, 1, 0), (0, 1, 1), (1, 1, 0)] {
+        grid.add_marble(Point::new(x, y), owner(player), CELLSIZE).unwrap();
+        hashes.push(grid.hash());
+    }
+    let count = hashes.len();
+    hashes.sort_unstable();
+    hashes.dedup();
+    assert_eq!(hashes.len(), count);
+}
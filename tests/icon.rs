@@ -0,0 +1,23 @@
+use chain::icon::EMBEDDED;
+
+fn u32_at(offset: usize) -> u32 {
+    u32::from_le_bytes(EMBEDDED[offset..offset + 4].try_into().unwrap())
+}
+
+#[test]
+fn icon_is_a_bmp_with_alpha() {
+    assert_eq!(&EMBEDDED[..2], b"BM");
+    assert_eq!(u32_at(2) as usize, EMBEDDED.len());
+    let (width, height) = (u32_at(18), u32_at(22));
+    assert_eq!((width, height), (32, 32));
+    // 32 bits per pixel with bit fields, the last of which is the alpha channel
+    assert_eq!(u16::from_le_bytes([EMBEDDED[28], EMBEDDED[29]]), 32);
+    assert_eq!(u32_at(30), 3);
+    assert_eq!(u32_at(66), 0xFF000000);
+    let pixels = u32_at(10) as usize;
+    assert_eq!(EMBEDDED.len() - pixels, (width * height * 4) as usize);
+    // Transparent in the corners and opaque in the middle
+    let alpha = |x: u32, y: u32| EMBEDDED[pixels + ((y * width + x) * 4 + 3) as usize];
+    assert_eq!(alpha(0, 0), 0);
+    assert_eq!(alpha(16, 16), 255);
+}
@@ -0,0 +1,58 @@
+use std::collections::{HashMap, HashSet};
+
+use chain::game::State;
+use chain::grid::{Grid, Point};
+
+mod common;
+use common::{owner, random_game, Rng, CELLSIZE};
+
+fn ids(grid: &Grid) -> Vec<u32> {
+    let mut ids: Vec<u32> = grid.marbles().map(|marble| marble.id()).collect();
+    ids.sort_unstable();
+    ids
+}
+
+#[test]
+fn ids_stay_unique_in_long_random_games() {
+    let mut rng = Rng(0xA54F_F53A_5F1D_36F1);
+    for _ in 0..10 {
+        let mut grid = Grid::new(Point::new(7, 6));
+        let placed = random_game(&mut grid, &mut rng, 4, 200, 0.5, |frame| {
+            // Each marble ever placed is there exactly once
+            let placed = frame.turn as u32 + 1;
+            assert_eq!(ids(frame.grid), (0..placed).collect::<Vec<u32>>(), "after move {}", frame.turn);
+        });
+        assert!(placed > 50);
+    }
+}
+
+/* Follow each marble through a cascade by its id. It keeps the id the whole way, so from one frame
+ * to the next it only moves a small step.
+ */
+#[test]
+fn ids_follow_their_marbles_through_a_cascade() {
+    // The multi-wave cascade of the scripted games
+    let moves = [(0, 0), (2, 2), (0, 0), (2, 2), (1, 0), (2, 1), (1, 0)];
+    let mut grid = Grid::new(Point::new(3, 3));
+    for (turn, &(x, y)) in moves.iter().enumerate() {
+        let _ = grid.cascade(Point::new(x, y), owner(turn % 2), CELLSIZE).unwrap().count();
+    }
+    let before = ids(&grid);
+    let (mut state, _) = grid.add_marble(Point::new(2, 1), owner(1), CELLSIZE).unwrap();
+    let new = grid.last_placed().unwrap();
+    assert!(!before.contains(&new));
+    let mut positions: HashMap<u32, Point> = grid.marbles().map(|marble| (marble.id(), marble.get_pos())).collect();
+    let mut frames = 0;
+    while let State::Animating(_) = state {
+        state = grid.step(state, 0.1, CELLSIZE).0;
+        for marble in grid.marbles() {
+            let step = marble.get_pos() - positions[&marble.id()];
+            assert!(step.re.abs() + step.im.abs() < CELLSIZE.re / 3, "Marble {} jumped by {}", marble.id(), step);
+            positions.insert(marble.id(), marble.get_pos());
+        }
+        frames += 1;
+    }
+    assert_eq!(grid.chain(), 2);
+    assert_eq!(frames, 20);
+    assert_eq!(ids(&grid).into_iter().collect::<HashSet<_>>(), before.into_iter().chain([new]).collect());
+}
@@ -0,0 +1,110 @@
+use chain::game::State;
+use chain::grid::{reserve_position, rotate_point, slot_position, Grid, InFlight, Point, SlotKind};
+
+mod common;
+use common::{owner, random_game, Rng, CELLSIZE};
+
+fn in_flight(grid: &Grid) -> Vec<InFlight> {
+    grid.in_flight().collect()
+}
+
+#[test]
+fn marbles_are_in_flight_exactly_during_waves() {
+    let moves = [(0, 0), (2, 2), (0, 0), (2, 2), (1, 0), (2, 1), (1, 0), (2, 1), (0, 1), (1, 2)];
+    let mut grid = Grid::new(Point::new(3, 3));
+    let mut waves = 0;
+    for (turn, &(x, y)) in moves.iter().enumerate() {
+        assert!(in_flight(&grid).is_empty());
+        let (mut state, _) = grid.add_marble(Point::new(x, y), owner(turn % 2), CELLSIZE).unwrap();
+        while let State::Animating(progress) = state {
+            let marbles = in_flight(&grid);
+            assert!(!marbles.is_empty(), "no marble in flight after move {}", turn);
+            if progress == 0.0 {
+                waves += 1;
+            }
+            for marble in marbles {
+                assert_eq!((marble.from - marble.to).norm_sqr(), 1);
+                assert!((marble.remaining - (1.0 - progress)).abs() < 0.05);
+                let resting = grid.marbles().find(|m| m.id() == marble.id).unwrap();
+                assert_eq!(resting.get_pos(), marble.pos);
+                assert_eq!(resting.get_owner(), marble.owner);
+            }
+            state = grid.step(state, 0.25, CELLSIZE).0;
+        }
+    }
+    assert!(in_flight(&grid).is_empty());
+    assert!(waves > 3);
+}
+
+/* Only the cells of the current spreading step, which send or receive marbles, are animated.
+ * Throughout a long random game, every marble outside of these cells has to rest exactly in its
+ * slot, or the list of animated cells lost track of a cell whose marbles are still moving.
+ */
+#[test]
+fn only_marbles_in_flight_are_away_from_their_slots() {
+    let mut rng = Rng(0xD1B5_4A32_D192_ED03);
+    let mut grid = Grid::new(Point::new(8, 6));
+    let mut frames = 0;
+    random_game(&mut grid, &mut rng, 3, 300, 0.3, |frame| {
+        let animated: Vec<Point> = frame.grid.in_flight().flat_map(|marble| [marble.from, marble.to]).collect();
+        for (coord, cell) in frame.grid.cells().filter(|(p, _)| !animated.contains(p)) {
+            for (kind, direction, marble) in cell.marbles_by_slot() {
+                let target = match kind {
+                    SlotKind::Residing | SlotKind::Outgoing => slot_position(coord, direction, CELLSIZE),
+                    SlotKind::Reserve => reserve_position(coord, direction, CELLSIZE),
+                    SlotKind::Incoming => panic!("Marble {} arrived in cell {}, which is not animated", marble.id(), coord),
+                };
+                assert_eq!(marble.get_pos(), target, "Marble {} in cell {} after move {}", marble.id(), coord, frame.turn);
+            }
+        }
+        if let State::AcceptingInput = frame.state {
+            assert!(animated.is_empty());
+        }
+        if frame.placed.is_none() {
+            frames += 1;
+        }
+    });
+    assert!(frames > 200, "only {} frames were animated", frames);
+}
+
+#[test]
+fn marbles_travel_from_the_firing_cell_to_its_neighbors() {
+    let mut grid = Grid::decode("2x2 a1b1..", 2, CELLSIZE).unwrap();
+    let (state, _) = grid.add_marble(Point::new(1, 0), owner(1), CELLSIZE).unwrap();
+    let mut marbles = in_flight(&grid);
+    marbles.sort_by_key(|marble| marble.to.re);
+    assert_eq!(marbles.len(), 2);
+    assert_eq!(marbles.iter().map(|m| (m.from, m.to)).collect::<Vec<_>>(), [
+        (Point::new(1, 0), Point::new(0, 0)),
+        (Point::new(1, 0), Point::new(1, 1)),
+    ]);
+    assert!(marbles.iter().all(|m| m.owner == owner(1) && m.remaining == 1.0));
+
+    // Half way, the marbles are between both cells
+    grid.step(state, 0.5, CELLSIZE);
+    for marble in in_flight(&grid) {
+        assert!((marble.remaining - 0.5).abs() < 0.05);
+        let (a, b) = (marble.from * 100, marble.to * 100);
+        let lower = Point::new(a.re.min(b.re), a.im.min(b.im));
+        let upper = Point::new(a.re.max(b.re), a.im.max(b.im)) + CELLSIZE;
+        assert!(marble.pos.re >= lower.re && marble.pos.re < upper.re);
+        assert!(marble.pos.im >= lower.im && marble.pos.im < upper.im);
+    }
+}
+
+#[test]
+fn turned_grids_keep_the_marbles_in_flight() {
+    let mut grid = Grid::decode("3x3 a1b2.b1.....", 2, CELLSIZE).unwrap();
+    let (state, _) = grid.add_marble(Point::new(1, 0), owner(1), CELLSIZE).unwrap();
+    grid.step(state, 0.25, CELLSIZE);
+    let dim = grid.dim();
+    let turned = grid.rotated(1, CELLSIZE);
+    let mut expected: Vec<_> = in_flight(&grid).iter()
+        .map(|m| (m.id, rotate_point(m.from, dim, 1), rotate_point(m.to, dim, 1)))
+        .collect();
+    let mut marbles: Vec<_> = in_flight(&turned).iter().map(|m| (m.id, m.from, m.to)).collect();
+    expected.sort_by_key(|&(id, _, _)| id);
+    marbles.sort_by_key(|&(id, _, _)| id);
+    assert!(!marbles.is_empty());
+    assert_eq!(marbles, expected);
+}
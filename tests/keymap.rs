@@ -0,0 +1,53 @@
+use sdl2::keyboard::Keycode;
+
+use chain::keymap::{Action, KeyMap};
+
+#[test]
+fn defaults_keep_the_usual_keys() {
+    let keymap = KeyMap::default();
+    assert_eq!(keymap.action(Keycode::Up), Some(Action::MoveUp));
+    assert_eq!(keymap.action(Keycode::Return), Some(Action::Place));
+    assert_eq!(keymap.action(Keycode::Escape), Some(Action::Quit));
+    assert_eq!(keymap.action(Keycode::F1), Some(Action::Help));
+    assert_eq!(keymap.action(Keycode::Z), None);
+    // Every action has a key of its own
+    for action in Action::ALL {
+        assert_eq!(keymap.action(keymap.key(action)), Some(action), "{}", action.name());
+    }
+    assert_eq!(KeyMap::parse("").unwrap().help_lines(), keymap.help_lines());
+}
+
+#[test]
+fn bindings_replace_the_defaults() {
+    let text = "# WASD\n\nmove_up = W\nmove_left=A\n  move_down = S  \nmove_right = D\nslow_motion = F2\ntoggle_shadows = F10\n";
+    let keymap = KeyMap::parse(text).unwrap();
+    assert_eq!(keymap.action(Keycode::W), Some(Action::MoveUp));
+    assert_eq!(keymap.action(Keycode::A), Some(Action::MoveLeft));
+    assert_eq!(keymap.action(Keycode::S), Some(Action::MoveDown));
+    assert_eq!(keymap.action(Keycode::D), Some(Action::MoveRight));
+    // The old keys are free, the others are unchanged
+    assert_eq!(keymap.action(Keycode::Up), None);
+    assert_eq!(keymap.key(Action::Place), Keycode::Return);
+    // The help lists the keys that are bound
+    let help = keymap.help_lines();
+    assert_eq!(help.len(), Action::ALL.len());
+    assert!(help[0].starts_with("W ") && help[0].ends_with(Action::MoveUp.description()), "{}", help[0]);
+    assert!(help.iter().all(|line| !line.starts_with("Up ")));
+}
+
+#[test]
+fn conflicting_bindings_are_refused() {
+    // A key bound twice, or bound to an action while it still triggers another by default
+    let error = KeyMap::parse("place = Space\nfast_forward = Space").err().unwrap();
+    assert!(error.contains("place") && error.contains("fast_forward"), "{}", error);
+    let error = KeyMap::parse("move_up = W\nmove_down = Return").err().unwrap();
+    assert!(error.contains("Return") && error.contains("move_down") && error.contains("place"), "{}", error);
+
+    for (text, expected) in [
+        ("place Space", "Line 1: expected action = key"),
+        ("# comment\njump = Space", "Line 2: unknown action jump"),
+        ("place = NoSuchKey", "Line 1: unknown key NoSuchKey"),
+    ] {
+        assert_eq!(KeyMap::parse(text).err().as_deref(), Some(expected));
+    }
+}
@@ -0,0 +1,24 @@
+use chain::render::line_span;
+
+// Width of a board of eight cells of 100 pixels
+const BOARD: i32 = 800;
+
+#[test]
+fn lines_are_centered_between_cells() {
+    assert_eq!(line_span(300, 1, BOARD), (300, 300));
+    assert_eq!(line_span(300, 2, BOARD), (299, 300));
+    assert_eq!(line_span(300, 4, BOARD), (298, 301));
+    assert_eq!(line_span(300, 3, BOARD), (299, 301));
+}
+
+#[test]
+fn lines_around_the_board_are_inside() {
+    for width in 1..=12 {
+        let (left, right) = (line_span(0, width, BOARD), line_span(BOARD, width, BOARD));
+        // As wide as the lines between the cells, with no pixel outside
+        assert_eq!(left, (0, width - 1));
+        assert_eq!(right, (BOARD - width, BOARD - 1));
+    }
+    // Lines wider than the board start at its edge
+    assert_eq!(line_span(10, 30, 20).0, 0);
+}
@@ -0,0 +1,73 @@
+use chain::bench;
+use chain::grid::{Grid, Point, MAX_CELLS};
+use chain::game::{Game, Player};
+use chain::menu::{parse_hex_color, spread_colors, Config};
+use sdl2::pixels::Color;
+
+#[test]
+fn hex_colors_cover_all_of_rgb() {
+    assert_eq!(parse_hex_color("#1E90FF"), Ok(Color::RGB(0x1E, 0x90, 0xFF)));
+    assert_eq!(parse_hex_color("ffffff"), Ok(Color::RGB(255, 255, 255)));
+    assert_eq!(parse_hex_color("#fadadd"), Ok(Color::RGB(0xFA, 0xDA, 0xDD)));
+    for malformed in ["", "#", "#1E90F", "#1E90FF0", "#1E90FG", "##1E90F", "#+E90FF", "#1E 0FF"] {
+        assert!(parse_hex_color(malformed).is_err(), "{} was accepted", malformed);
+    }
+}
+
+#[test]
+fn oversized_boards_are_refused_before_allocating() {
+    let config = bench::config(2, Point::new(8, 6)).unwrap();
+    assert!(Config { size: Point::new(64, 64), ..config.clone() }.validate().is_ok());
+    for size in [Point::new(65, 64), Point::new(5000, 5000), Point::new(MAX_CELLS / 2 + 1, 2), Point::new(i32::MAX, 2)] {
+        let error = Config { size, ..config.clone() }.validate().unwrap_err();
+        assert!(error.contains(&MAX_CELLS.to_string()), "{}", error);
+    }
+    // Pasted positions are checked before the cells are read
+    let error = Grid::decode("60000x60000 .", 2, Point::new(100, 100)).err().unwrap();
+    assert!(error.contains(&MAX_CELLS.to_string()), "{}", error);
+}
+
+#[test]
+fn invalid_settings_are_explained() {
+    let config = bench::config(2, Point::new(4, 3)).unwrap();
+    let error = |config: Config| config.validate().unwrap_err();
+    // Empty and negative boards are refused before a grid is created
+    for size in [Point::new(0, 5), Point::new(5, 0), Point::new(-3, 5), Point::new(1, 1)] {
+        assert!(error(Config { size, ..config.clone() }).contains("at least 2x2"), "{}", size);
+        assert!(Game::new(Config { size, ..config.clone() }).is_err());
+    }
+    for cellsize in [Point::new(0, 100), Point::new(100, -1)] {
+        assert!(error(Config { cellsize, ..config.clone() }).contains("cell size"));
+    }
+    assert!(error(Config { players: config.players[..1].to_vec(), ..config.clone() }).contains("two players"));
+    let twins = vec![Player::new(Color::RGB(200, 0, 0)), Player::new(Color::RGB(205, 5, 0))];
+    assert!(error(Config { players: twins, ..config.clone() }).contains("too similar"));
+}
+
+#[test]
+fn games_are_only_started_with_valid_settings() {
+    let config = bench::config(2, Point::new(4, 3)).unwrap();
+    assert!(Game::new(config.clone()).is_ok());
+    assert!(Game::new(Config { players: Vec::new(), ..config.clone() }).is_err());
+    assert!(Game::new(Config { size: Point::new(1, 3), ..config.clone() }).is_err());
+    assert!(Game::new(Config::default()).is_err());
+}
+
+#[test]
+fn spread_colors_are_bright_and_far_apart() {
+    assert_eq!(spread_colors(3, 0.0), [Color::RGB(242, 36, 36), Color::RGB(36, 242, 36), Color::RGB(36, 36, 242)]);
+    for count in 2..=12 {
+        for offset in [0.0, 17.0, 90.0, 359.0] {
+            let colors = spread_colors(count, offset);
+            assert_eq!(colors.len(), count);
+            // Every pair is accepted as distinct when starting a game
+            let config = Config {
+                players: colors.iter().map(|&color| Player::new(color)).collect(),
+                ..bench::config(2, Point::new(8, 6)).unwrap()
+            };
+            assert!(config.validate().is_ok(), "{} colors from {}: {:?}", count, offset, config.validate());
+            // None is dark enough to vanish in the gradient of the marbles
+            assert!(colors.iter().all(|c| c.r.max(c.g).max(c.b) >= 240));
+        }
+    }
+}
@@ -0,0 +1,76 @@
+use std::time::Duration;
+
+use chain::bench;
+use chain::game::{Game, PlayerKind};
+use chain::grid::Point;
+use chain::menu::Config;
+
+mod common;
+use common::owner;
+
+fn mirrored(w: i32, h: i32) -> Config {
+    let mut config = bench::config(2, Point::new(w, h)).unwrap();
+    config.players[1].kind = PlayerKind::Mirror(owner(0));
+    config
+}
+
+/* Play a move of the first player and let the mirror answer */
+fn play(game: &mut Game, x: i32, y: i32) -> Point {
+    game.click(Point::new(x, y));
+    game.advance_until_stable();
+    game.step(Duration::ZERO);
+    game.advance_until_stable();
+    game.history().last().unwrap().1
+}
+
+#[test]
+fn mirror_answers_through_the_center() {
+    let mut game = Game::new(mirrored(4, 3)).unwrap();
+    assert_eq!(play(&mut game, 0, 0), Point::new(3, 2));
+    assert_eq!(play(&mut game, 1, 0), Point::new(2, 2));
+    assert_eq!(game.history(), [
+        (owner(0), Point::new(0, 0)),
+        (owner(1), Point::new(3, 2)),
+        (owner(0), Point::new(1, 0)),
+        (owner(1), Point::new(2, 2)),
+    ]);
+    assert_eq!(game.cur_player(), owner(0));
+
+    // Clicks do not play for the mirror
+    game.click(Point::new(3, 0));
+    assert_eq!(game.cur_player(), owner(1));
+    assert_eq!(game.history().len(), 5);
+    game.click(Point::new(3, 1));
+    assert_eq!(game.history().len(), 5);
+}
+
+#[test]
+fn mirror_picks_the_closest_legal_cell() {
+    // The center of a board with odd sides mirrors to itself, which is taken
+    let mut game = Game::new(mirrored(3, 3)).unwrap();
+    let answer = play(&mut game, 1, 1);
+    assert_eq!((answer - Point::new(1, 1)).norm_sqr(), 1);
+
+    // The mirror image of (0, 1) belongs to the first player
+    let mut game = Game::new(mirrored(3, 3)).unwrap();
+    game.decode("3x3 a 2 .b1.a1.a13.").unwrap();
+    let answer = play(&mut game, 0, 1);
+    assert_eq!(game.grid().get(Point::new(2, 1)).unwrap().owner(), Some(owner(0)));
+    assert_eq!((answer - Point::new(2, 1)).norm_sqr(), 1);
+}
+
+#[test]
+fn mirror_starts_close_to_the_center() {
+    let mut game = Game::new(mirrored(4, 4)).unwrap().rematch();
+    assert_eq!(game.cur_player(), owner(1));
+    assert_eq!(game.mirror_move(owner(0)), Some(Point::new(1, 1)));
+    game.step(Duration::ZERO);
+    assert_eq!(game.history(), [(owner(1), Point::new(1, 1))]);
+}
+
+#[test]
+fn players_can_not_mirror_themselves() {
+    let mut config = bench::config(2, Point::new(3, 3)).unwrap();
+    config.players[0].kind = PlayerKind::Mirror(owner(0));
+    assert!(config.validate().is_err());
+}
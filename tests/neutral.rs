@@ -0,0 +1,77 @@
+use chain::bench;
+use chain::game::Game;
+use chain::grid::{Grid, Point, NEUTRAL};
+use chain::menu::Config;
+use chain::shape::Shape;
+
+mod common;
+use common::{owner, CELLSIZE};
+
+#[test]
+fn any_player_takes_over_neutral_cells() {
+    for player in [owner(0), owner(1)] {
+        let mut grid = Grid::decode("3x3 .*17.", 2, CELLSIZE).unwrap();
+        assert!(grid.cell(Point::new(1, 0)).accepts(player));
+        assert_eq!(grid.cascade(Point::new(1, 0), player, CELLSIZE).unwrap().count(), 0);
+        // The neutral marble belongs to the player together with the cell
+        assert!(grid.cell(Point::new(1, 0)).marbles().all(|marble| marble.get_owner() == player));
+        assert_eq!(grid.cell(Point::new(1, 0)).owner(), Some(player));
+    }
+    // Neutral marbles are not played
+    let grid = Grid::decode("3x3 .*17.", 2, CELLSIZE).unwrap();
+    assert!(grid.check_move(Point::new(0, 0), NEUTRAL).is_err());
+}
+
+#[test]
+fn spreading_captures_neutral_cells_with_their_marbles() {
+    let mut grid = Grid::decode("3x3 a1*1.*15.", 2, CELLSIZE).unwrap();
+    let waves = grid.cascade(Point::new(0, 0), owner(0), CELLSIZE).unwrap().count();
+    assert!(waves > 0);
+    // Neither the marble that arrived nor the neutral ones that were there stay neutral
+    assert!(grid.marbles().all(|marble| marble.get_owner() == owner(0)));
+    assert_eq!(grid.single_owner(), Some(owner(0)));
+}
+
+#[test]
+fn neutral_cells_do_not_keep_players_alive() {
+    let config = bench::config(2, Point::new(3, 3)).unwrap();
+    let mut game = Game::new(config).unwrap();
+    game.decode("3x3 a 2 a1*1.b1.....").unwrap();
+    game.resign();
+    // The resigned player's cell became neutral and the other player wins
+    assert_eq!(game.winner(), Some(owner(1)));
+    assert_eq!(game.encode().as_deref(), Some("3x3 b 2 2*1.b15."));
+}
+
+#[test]
+fn games_start_with_the_neutral_cells_of_the_settings() {
+    let config = Config { neutral: vec![Point::new(1, 1), Point::new(1, 0)], ..bench::config(2, Point::new(3, 3)).unwrap() };
+    let game = Game::new(config).unwrap();
+    assert_eq!(game.encode().as_deref(), Some("3x3 a 2 .*12.*14."));
+    // The neutral cells do not decide the game
+    assert!(game.winner().is_none());
+}
+
+#[test]
+fn neutral_cells_must_be_able_to_hold_their_marble() {
+    let base = bench::config(2, Point::new(4, 4)).unwrap();
+    let refused = |neutral: Vec<Point>, shape: Shape, factor: f32| {
+        Config { neutral, shape, threshold_factor: factor, ..base.clone() }.validate().is_err()
+    };
+    assert!(!refused(vec![Point::new(0, 0), Point::new(1, 2)], Shape::Rectangle, 1.0));
+    assert!(refused(vec![Point::new(4, 0)], Shape::Rectangle, 1.0));
+    assert!(refused(vec![Point::new(1, 1), Point::new(1, 1)], Shape::Rectangle, 1.0));
+    // The center of the donut is not part of the board
+    assert!(refused(vec![Point::new(1, 1)], Shape::Donut, 1.0));
+    // Corners spread with a single marble at half the threshold, edges do not
+    assert!(refused(vec![Point::new(0, 0)], Shape::Rectangle, 0.5));
+    assert!(!refused(vec![Point::new(1, 0)], Shape::Rectangle, 0.5));
+
+    let mut grid = Grid::new(Point::new(3, 3));
+    grid.add_neutral(Point::new(0, 0), CELLSIZE).unwrap();
+    let error = grid.add_neutral(Point::new(0, 0), CELLSIZE).unwrap_err();
+    assert!(error.contains("without spreading"), "{}", error);
+    assert!(grid.add_neutral(Point::new(3, 0), CELLSIZE).is_err());
+    grid.cascade(Point::new(1, 1), owner(0), CELLSIZE).unwrap().for_each(drop);
+    assert!(grid.add_neutral(Point::new(1, 1), CELLSIZE).is_err());
+}
@@ -0,0 +1,56 @@
+use chain::grid::{Grid, Owner, Point, NEUTRAL};
+
+mod common;
+use common::owner;
+
+#[test]
+fn owners_are_checked_against_the_number_of_players() {
+    assert_eq!(Owner::new(1, 2).map(|owner| owner.idx()), Ok(1));
+    assert!(Owner::new(2, 2).is_err());
+    assert!(Owner::from_letter('c', 2).is_err());
+    assert!(Owner::from_letter('A', 2).is_err());
+    assert_eq!(Owner::all(3).map(|owner| owner.to_string()).collect::<Vec<_>>(), ["a", "b", "c"]);
+    assert_eq!(NEUTRAL.to_string(), "*");
+}
+
+#[test]
+fn positions_of_unknown_players_are_refused() {
+    assert!(Grid::decode("2x2 b1.2*1", 2, Point::new(100, 100)).is_ok());
+    assert!(Grid::decode("2x2 c1.2*1", 2, Point::new(100, 100)).is_err());
+
+    let mut bytes = Grid::new(Point::new(2, 2)).to_bytes();
+    bytes[4..6].copy_from_slice(&[1, 1]);
+    assert!(Grid::from_bytes(Point::new(2, 2), &bytes, 2, Point::new(100, 100)).is_ok());
+    bytes[4..6].copy_from_slice(&[2, 1]);
+    assert!(Grid::from_bytes(Point::new(2, 2), &bytes, 2, Point::new(100, 100)).is_err());
+}
+
+#[test]
+fn cells_and_marbles_are_counted_per_owner() {
+    let grid = Grid::decode("3x3 a1b1.a2.*1...", 2, Point::new(100, 100)).unwrap();
+    assert_eq!(grid.count_per_owner(2), [(2, 3), (1, 1)]);
+    assert_eq!((grid.cells_of(owner(0)), grid.cells_of(owner(1))), (2, 1));
+    assert_eq!(Grid::new(Point::new(2, 2)).count_per_owner(3), [(0, 0); 3]);
+}
+
+#[test]
+fn marbles_are_listed_per_owner() {
+    let cellsize = Point::new(100, 100);
+    let mut grid = Grid::decode("4x3 a1.b2..a3*1.b1.a1*1", 2, cellsize).unwrap();
+    let owners = [owner(0), owner(1)];
+    let moves = [(1, 0), (2, 2), (1, 0), (3, 1), (0, 0), (2, 1), (1, 1), (3, 2)];
+    for (turn, (x, y)) in moves.into_iter().enumerate() {
+        let owner = owners[turn % 2];
+        let p = Point::new(x, y);
+        if grid.check_move(p, owner).is_err() {
+            continue;
+        }
+        let _ = grid.cascade(p, owner, cellsize).unwrap().count();
+        let counts = grid.count_per_owner(2);
+        for owner in owners {
+            assert_eq!(grid.marbles_of(owner).count(), counts[owner.idx()].1, "{}", grid.encode());
+            assert!(grid.marbles_of(owner).all(|marble| marble.get_owner() == owner));
+        }
+        assert_eq!(grid.marbles_of(NEUTRAL).count() + counts[0].1 + counts[1].1, grid.marbles().count());
+    }
+}
@@ -0,0 +1,80 @@
+use std::time::Duration;
+
+use num_complex::Complex;
+use sdl2::pixels::Color;
+
+use chain::grid::Point;
+use chain::particles::{Particles, Shake, Trails, MAX_PARTICLES, TRAIL_LENGTH};
+
+mod common;
+use common::owner;
+
+#[test]
+fn bursts_fade_out_and_are_capped() {
+    let mut particles = Particles::new(7);
+    let center = Complex::new(50.0, 50.0);
+    particles.burst(center, Color::RGB(255, 0, 0), 200.0);
+    assert!((8..=16).contains(&particles.len()));
+    assert!(particles.iter().all(|p| p.pos == center && p.alpha() > 0.0 && p.alpha() <= 1.0));
+
+    // Particles fly apart and slow down
+    let before: Vec<f32> = particles.iter().map(|p| p.vel.norm()).collect();
+    particles.advance(Duration::from_millis(100));
+    assert!(particles.iter().all(|p| (p.pos - center).norm() > 0.0));
+    assert!(particles.iter().zip(before).all(|(p, speed)| p.vel.norm() < speed));
+
+    particles.advance(Duration::from_secs(1));
+    assert!(particles.is_empty());
+
+    // A large wave does not exceed the limit
+    for _ in 0..100 {
+        particles.burst(center, Color::RGB(0, 0, 255), 200.0);
+    }
+    assert_eq!(particles.len(), MAX_PARTICLES);
+}
+
+#[test]
+fn trails_follow_travelling_marbles_and_fade() {
+    let player = owner(0);
+    let mut trails = Trails::new();
+    for step in 0..10 {
+        trails.record([(1, player, Point::new(step, 0)), (2, player, Point::new(0, step))].into_iter());
+    }
+    assert_eq!(trails.len(), 2);
+    let mut dots: Vec<_> = trails.dots().filter(|&(_, pos, _)| pos.im == 0 && pos.re > 0).collect();
+    assert_eq!(dots.len(), TRAIL_LENGTH);
+    dots.sort_by_key(|&(_, pos, _)| pos.re);
+    // The newest position is the largest dot and the older ones shrink
+    assert_eq!(dots.last().unwrap().1, Point::new(9, 0));
+    assert!(dots.windows(2).all(|pair| pair[0].2 < pair[1].2));
+    assert!(dots.iter().all(|&(o, _, share)| o == player && share > 0.0 && share < 1.0));
+
+    // Marbles that arrived lose their trail
+    trails.record([(2, player, Point::new(0, 10))].into_iter());
+    assert_eq!(trails.len(), 1);
+    assert!(trails.dots().all(|(_, pos, _)| pos.re == 0));
+    trails.record(std::iter::empty());
+    assert!(trails.is_empty());
+}
+
+#[test]
+fn shake_grows_with_the_wave_and_settles() {
+    let mut shake = Shake::new(3);
+    assert_eq!(shake.next_offset(8), (0, 0));
+    shake.add(2);
+    let small = shake.energy();
+    shake.clear();
+    shake.add(12);
+    assert!(shake.energy() > small);
+    shake.add(1000);
+    assert_eq!(shake.energy(), 1.0);
+
+    let mut frames = 0;
+    while shake.energy() > 0.0 {
+        let (x, y) = shake.next_offset(8);
+        assert!(x.abs() <= 8 && y.abs() <= 8);
+        frames += 1;
+        assert!(frames < 100);
+    }
+    assert_eq!(shake.next_offset(8), (0, 0));
+}
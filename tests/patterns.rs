@@ -0,0 +1,39 @@
+use chain::render::{pattern_mask, MIN_CELLSIZE};
+
+// Radius of the marbles in the smallest cells that are chosen automatically
+const SMALLEST: i32 = MIN_CELLSIZE * 3 / 20;
+
+#[test]
+fn patterns_tell_players_apart() {
+    for radius in [SMALLEST, 15, 30] {
+        let masks: Vec<Vec<(i32, i32)>> = (0..8).map(|idx| pattern_mask(idx, radius)).collect();
+        assert!(masks[0].is_empty());
+        for (i, a) in masks.iter().enumerate().skip(1) {
+            // Enough pixels to be seen, and no two alike
+            assert!(a.len() >= 3, "pattern {} at radius {}", i, radius);
+            for (j, b) in masks.iter().enumerate().skip(i + 1) {
+                assert_ne!(a, b, "patterns {} and {} at radius {}", i, j, radius);
+            }
+        }
+    }
+}
+
+#[test]
+fn patterns_fit_on_the_marble() {
+    for radius in [SMALLEST, 10, 15, 30] {
+        for idx in 0..8 {
+            // Including the outline around the pattern
+            for (x, y) in pattern_mask(idx, radius) {
+                let (x, y) = (x.abs() + 1, y.abs() + 1);
+                assert!(x * x + y * y <= radius * radius, "pattern {} at radius {}", idx, radius);
+            }
+        }
+    }
+}
+
+#[test]
+fn patterns_repeat_for_many_players() {
+    for idx in 0..8 {
+        assert_eq!(pattern_mask(idx + 8, 15), pattern_mask(idx, 15));
+    }
+}
@@ -0,0 +1,58 @@
+use sdl2::rect::Rect;
+
+use chain::placement::{choose, Placement, Placements};
+
+fn placement(x: i32, y: i32, width: u32, height: u32) -> Placement {
+    Placement { x, y, width, height }
+}
+
+// A laptop screen with a larger monitor to its right
+fn displays() -> Vec<Rect> {
+    vec![Rect::new(0, 0, 1920, 1080), Rect::new(1920, -200, 2560, 1440)]
+}
+
+#[test]
+fn placements_are_written_and_read_back() {
+    let placements = Placements { menu: Some(placement(-40, 20, 800, 600)), game: Some(placement(2000, 0, 1200, 900)) };
+    let text = placements.to_text();
+    assert_eq!(text, "menu -40 20 800 600\ngame 2000 0 1200 900\n");
+    assert_eq!(Placements::parse(&text), Ok(placements));
+    assert_eq!(Placements::parse("\n  game 1 2 3 4  \n").unwrap().menu, None);
+    assert!(Placements::parse("game 1 2 3").is_err());
+    assert!(Placements::parse("game 1 2 3 -4").is_err());
+    assert!(Placements::parse("editor 1 2 3 4").is_err());
+}
+
+#[test]
+fn windows_on_a_display_are_visible() {
+    assert!(placement(100, 100, 800, 600).visible(&displays()));
+    // On the second display, above the first one
+    assert!(placement(2500, -150, 800, 600).visible(&displays()));
+    // Partly off the right edge of the second display
+    assert!(placement(4350, 100, 800, 600).visible(&displays()));
+    assert!(!placement(4450, 100, 800, 600).visible(&displays()));
+    // The title bar would be out of reach above the first display
+    assert!(!placement(100, -50, 800, 600).visible(&displays()));
+    // Where a third display was
+    assert!(!placement(-1500, 100, 800, 600).visible(&displays()));
+    assert!(!placement(100, 100, 800, 600).visible(&[]));
+}
+
+#[test]
+fn saved_windows_open_where_they_were() {
+    let saved = placement(2500, 100, 1000, 700);
+    assert_eq!(choose(Some(saved), &displays(), None, (800, 600)), saved);
+    // Without that display, the window is centered on the first one
+    assert_eq!(choose(Some(saved), &displays()[..1], None, (800, 600)), placement(560, 240, 800, 600));
+    assert_eq!(choose(None, &displays(), None, (800, 600)), placement(560, 240, 800, 600));
+}
+
+#[test]
+fn given_displays_override_the_saved_position() {
+    let saved = placement(100, 100, 1000, 700);
+    assert_eq!(choose(Some(saved), &displays(), Some(1), (800, 600)), placement(2800, 220, 800, 600));
+    assert_eq!(choose(Some(saved), &displays(), Some(0), (800, 600)), placement(560, 240, 800, 600));
+    // Displays that are gone fall back to the first one
+    assert_eq!(choose(None, &displays(), Some(5), (800, 600)), placement(560, 240, 800, 600));
+    assert_eq!(choose(None, &[], None, (800, 600)), placement(0, 0, 800, 600));
+}
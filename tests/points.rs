@@ -0,0 +1,94 @@
+use std::collections::HashSet;
+
+use chain::bench;
+use chain::game::Game;
+use chain::grid::{Grid, Point};
+
+// Boards that are wide, tall, square and a single cell
+const DIMS: [(i32, i32); 5] = [(1, 1), (5, 3), (2, 7), (6, 6), (64, 1)];
+
+#[test]
+fn points_visit_each_cell_once_row_by_row() {
+    for (w, h) in DIMS {
+        let grid = Grid::new(Point::new(w, h));
+        let points: Vec<Point> = grid.points().collect();
+        assert_eq!(grid.points().len(), (w * h) as usize);
+        assert_eq!(points.len(), (w * h) as usize);
+        assert_eq!(points.iter().collect::<HashSet<_>>().len(), points.len());
+        assert!(points.windows(2).all(|pair| (pair[0].im, pair[0].re) < (pair[1].im, pair[1].re)));
+        assert!(points.iter().all(|&p| grid.get(p).is_some()));
+
+        let mut reversed: Vec<Point> = grid.points().rev().collect();
+        reversed.reverse();
+        assert_eq!(reversed, points);
+    }
+}
+
+#[test]
+fn points_can_be_taken_from_both_ends() {
+    for (w, h) in DIMS {
+        let grid = Grid::new(Point::new(w, h));
+        let mut points = grid.points();
+        let mut seen = HashSet::new();
+        let mut from_back = false;
+        while let Some(p) = if from_back { points.next_back() } else { points.next() } {
+            assert!(seen.insert(p), "{} visited twice", p);
+            assert_eq!(points.len(), (w * h) as usize - seen.len());
+            from_back = !from_back;
+        }
+        assert_eq!(seen.len(), (w * h) as usize);
+    }
+}
+
+#[test]
+fn rectangles_are_clipped_to_the_board() {
+    let grid = Grid::new(Point::new(5, 3));
+    let inside: Vec<Point> = grid.points_in(Point::new(1, 1), Point::new(3, 2)).collect();
+    assert_eq!(inside, vec![
+        Point::new(1, 1), Point::new(2, 1), Point::new(3, 1),
+        Point::new(1, 2), Point::new(2, 2), Point::new(3, 2),
+    ]);
+    for (corner, size) in [((-2, -1), (4, 3)), ((3, 2), (10, 10)), ((-5, -5), (20, 20)), ((6, 0), (2, 2)), ((1, 1), (0, 2))] {
+        let (corner, size) = (Point::new(corner.0, corner.1), Point::new(size.0, size.1));
+        let points: Vec<Point> = grid.points_in(corner, size).collect();
+        let expected: Vec<Point> = grid.points()
+            .filter(|p| p.re >= corner.re && p.im >= corner.im && p.re < corner.re + size.re && p.im < corner.im + size.im)
+            .collect();
+        assert_eq!(points, expected, "{} {}", corner, size);
+        assert_eq!(grid.points_in(corner, size).len(), expected.len());
+    }
+}
+
+#[test]
+fn cells_yield_their_own_coordinates() {
+    for (w, h) in DIMS {
+        let grid = Grid::new(Point::new(w, h));
+        let coords: Vec<Point> = grid.cells().map(|(p, cell)| {
+            assert_eq!(p, cell.coord());
+            assert!(std::ptr::eq(cell, grid.cell(p)), "{} in {}x{}", p, w, h);
+            p
+        }).collect();
+        assert_eq!(coords.len(), (w * h) as usize);
+        assert_eq!(coords.iter().collect::<HashSet<_>>(), grid.points().collect::<Vec<_>>().iter().collect());
+    }
+}
+
+#[test]
+fn points_off_the_board_have_no_cell() {
+    let outside = [(-1, 0), (0, -1), (5, 0), (0, 3), (5, 3), (i32::MIN, i32::MAX), (-5, 1)];
+    let mut grid = Grid::new(Point::new(5, 3));
+    for (x, y) in outside {
+        let p = Point::new(x, y);
+        assert!(grid.get(p).is_none(), "{}", p);
+        assert!(grid.get_mut(p).is_none(), "{}", p);
+    }
+    assert_eq!(grid.get(Point::new(4, 2)).map(|cell| cell.coord()), Some(Point::new(4, 2)));
+
+    // Clicks next to the board are ignored
+    let mut game = Game::new(bench::config(2, Point::new(5, 3)).unwrap()).unwrap();
+    let before = game.encode();
+    for (x, y) in outside {
+        game.click(Point::new(x, y));
+    }
+    assert_eq!(game.encode(), before);
+}
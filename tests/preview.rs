@@ -0,0 +1,75 @@
+use chain::bench;
+use chain::game::{Game, OwnerChange, State};
+use chain::grid::Point;
+use chain::render::{chain_preview, preview, Preview};
+
+mod common;
+use common::owner;
+
+fn new_game() -> Game {
+    Game::new(bench::config(2, Point::new(3, 3)).unwrap()).unwrap()
+}
+
+#[test]
+fn preview_shows_the_current_player() {
+    let mut game = new_game();
+    assert_eq!(preview(&game, Point::new(0, 0)), Some(Preview::Marble(owner(0))));
+    game.click(Point::new(0, 0));
+    game.advance_until_stable();
+    // The cell of the first player is refused for the second one
+    assert_eq!(preview(&game, Point::new(0, 0)), Some(Preview::Refused));
+    assert_eq!(preview(&game, Point::new(1, 0)), Some(Preview::Marble(owner(1))));
+    game.click(Point::new(2, 2));
+    game.advance_until_stable();
+    assert_eq!(preview(&game, Point::new(0, 0)), Some(Preview::Marble(owner(0))));
+}
+
+#[test]
+fn preview_is_hidden_unless_a_marble_can_be_placed() {
+    let mut game = new_game();
+    for &(x, y) in &[(0, 0), (1, 0)] {
+        game.click(Point::new(x, y));
+        game.advance_until_stable();
+    }
+    // While the marbles move
+    game.click(Point::new(0, 0));
+    assert!(matches!(game.state(), State::Animating(_)));
+    assert_eq!(preview(&game, Point::new(2, 2)), None);
+    // Once the game is won
+    game.advance_until_stable();
+    assert!(game.winner().is_some());
+    assert_eq!(preview(&game, Point::new(2, 2)), None);
+    // In the board editor
+    let mut game = new_game();
+    game.start_editing();
+    assert_eq!(preview(&game, Point::new(2, 2)), None);
+}
+
+#[test]
+fn chain_preview_matches_the_move() {
+    let mut game = new_game();
+    game.decode("3x3 a 2 a1b1.b1.....").unwrap();
+    assert_eq!(chain_preview(&mut game, Point::new(1, 1)), Some(vec![
+        OwnerChange { at: Point::new(1, 1), from: None, to: Some(owner(0)) },
+    ]));
+    // Cells of the other player are refused
+    assert_eq!(chain_preview(&mut game, Point::new(1, 0)), None);
+
+    let preview = chain_preview(&mut game, Point::new(0, 0)).unwrap();
+    let position = game.encode();
+    // The game is left as it was
+    assert_eq!(game.encode(), position);
+    game.click(Point::new(0, 0));
+    game.advance_until_stable();
+    assert_eq!(game.last_owner_changes(), preview);
+
+    // Taking the last cell of the other player wins, and nothing is previewed after that
+    assert_eq!(game.winner(), Some(owner(0)));
+    assert_eq!(chain_preview(&mut game, Point::new(2, 2)), None);
+
+    // Nor while the marbles move
+    game.decode("3x3 a 2 a1b1.b1.....").unwrap();
+    game.click(Point::new(0, 0));
+    assert!(matches!(game.state(), State::Animating(_)));
+    assert_eq!(chain_preview(&mut game, Point::new(2, 2)), None);
+}
@@ -0,0 +1,86 @@
+#![cfg(feature = "serde")]
+
+use chain::bench;
+use chain::game::Game;
+use chain::grid::Point;
+use chain::recovery::{Autosave, Recovery, RECOVERY_VARIABLE};
+
+mod common;
+use common::CELLSIZE;
+
+fn play(game: &mut Game, moves: &[(i32, i32)]) {
+    for &(x, y) in moves {
+        game.click(Point::new(x, y));
+        game.advance_until_stable();
+    }
+}
+
+#[test]
+fn games_are_resumed_where_they_were_left() {
+    let game = Game::new(bench::config(2, Point::new(4, 3)).unwrap()).unwrap();
+    // The second player begins the rematch
+    let mut game = game.rematch();
+    play(&mut game, &[(0, 0), (3, 2), (0, 0), (3, 2), (1, 0)]);
+
+    let recovery = Recovery::of(&game).unwrap();
+    let recovery = Recovery::parse(&recovery.to_text().unwrap()).unwrap();
+    // Resumed on a smaller screen
+    let resumed = recovery.resume(CELLSIZE / 2).unwrap();
+    assert_eq!(resumed.encode(), game.encode());
+    assert_eq!(resumed.history(), game.history());
+    assert_eq!(resumed.first_player(), game.first_player());
+    assert_eq!(resumed.cellsize(), CELLSIZE / 2);
+
+    // A loaded position has no moves that lead to it, so it is restored without them
+    let mut loaded = Game::new(bench::config(2, Point::new(4, 3)).unwrap()).unwrap();
+    loaded.decode("4x3 b 2 a13.b16.a1").unwrap();
+    let resumed = Recovery::of(&loaded).unwrap().resume(CELLSIZE).unwrap();
+    assert_eq!(resumed.encode(), loaded.encode());
+    assert!(resumed.history().is_empty());
+
+    // Nothing is left to recover once the game is won
+    let mut moves = 0;
+    while game.winner().is_none() && moves < 100 {
+        let legal = game.legal_moves();
+        game.click(legal[moves % legal.len()]);
+        game.advance_until_stable();
+        moves += 1;
+    }
+    assert!(game.winner().is_some());
+    assert!(Recovery::of(&game).is_none());
+}
+
+#[test]
+fn turns_are_saved_and_the_file_is_removed_when_the_game_ends() {
+    let path = std::env::temp_dir().join(format!("chainreaction-recovery-{}.json", std::process::id()));
+    std::env::set_var(RECOVERY_VARIABLE, &path);
+    let mut game = Game::new(bench::config(2, Point::new(3, 3)).unwrap()).unwrap();
+    let mut autosave = Autosave::new(&game);
+    autosave.update(&game).unwrap();
+    autosave.wait().unwrap();
+    assert!(!path.exists());
+
+    play(&mut game, &[(0, 0)]);
+    autosave.update(&game).unwrap();
+    autosave.wait().unwrap();
+    let saved = Recovery::load().unwrap().unwrap();
+    assert_eq!(saved.position, game.encode().unwrap());
+
+    play(&mut game, &[(2, 2)]);
+    autosave.update(&game).unwrap();
+    autosave.wait().unwrap();
+    assert_eq!(Recovery::load().unwrap().unwrap().moves.len(), 2);
+
+    autosave.discard().unwrap();
+    assert!(!path.exists());
+    assert!(Recovery::load().unwrap().is_none());
+
+    // A broken file is reported once and then moved out of the way
+    std::fs::write(&path, "{\"config\":").unwrap();
+    let error = Recovery::load().err().unwrap();
+    let broken = path.with_extension("json.broken");
+    assert!(error.contains(&broken.display().to_string()), "{}", error);
+    assert!(!path.exists());
+    assert!(Recovery::load().unwrap().is_none());
+    std::fs::remove_file(&broken).unwrap();
+}
@@ -0,0 +1,90 @@
+use chain::bench;
+use chain::game::Game;
+use chain::grid::{Grid, Point};
+use chain::keymap::Action;
+use chain::menu::Config;
+
+mod common;
+use common::{owner, CELLSIZE};
+
+// Board split in two by a wall of cells outside of its shape, with a marble of each player
+const SPLIT: &str = "5x3 a1.#.b1..#....#..";
+
+fn contested(text: &str) -> bool {
+    Grid::decode(text, 2, CELLSIZE).unwrap().contested()
+}
+
+#[test]
+fn walls_separate_the_players() {
+    assert!(!contested(SPLIT));
+    // A gap in the wall joins the regions
+    assert!(contested("5x3 a1.#.b1.......#.."));
+    // Both players on the same side
+    assert!(contested("5x3 a1b1#....#....#.."));
+    // Several marbles of one player and neutral ones next to the other player
+    assert!(!contested("5x3 a1.#..a1.#*1...#.b1"));
+    assert!(!contested("5x3 ..#....#....#.."));
+}
+
+#[test]
+fn captured_regions_are_no_longer_contested() {
+    let mut grid = Grid::decode("5x3 a1b1#....#....#..", 2, CELLSIZE).unwrap();
+    assert!(grid.contested());
+    // Spreading the corner takes the cell of the other player
+    let _ = grid.cascade(Point::new(0, 0), owner(0), CELLSIZE).unwrap().count();
+    assert_eq!(grid.count_per_owner(2)[1], (0, 0));
+    assert!(!grid.contested());
+}
+
+fn split_game(end_when_separated: bool) -> Game {
+    let config = bench::config(2, Point::new(5, 3)).unwrap();
+    let mut game = Game::new(Config { end_when_separated, ..config }).unwrap();
+    game.decode(&format!("{} a 2 {}", "5x3", &SPLIT[4..])).unwrap();
+    game
+}
+
+#[test]
+fn separated_games_can_be_ended() {
+    let mut game = split_game(true);
+    assert!(!game.separated());
+    // Ending is only offered once the position settled after a move
+    game.keydown(Action::EndSeparated);
+    assert!(game.winner().is_none());
+    game.click(Point::new(0, 1));
+    game.advance_until_stable();
+    assert!(game.separated());
+    assert_eq!(game.leader().map(|owner| owner.idx()), Some(0));
+    game.keydown(Action::EndSeparated);
+    assert_eq!(game.winner().map(|owner| owner.idx()), Some(0));
+    assert!(!game.separated());
+    assert!(game.legal_moves().is_empty());
+}
+
+#[test]
+fn ending_is_opt_in_and_needs_a_leader() {
+    let mut game = split_game(false);
+    game.click(Point::new(0, 1));
+    game.advance_until_stable();
+    assert!(!game.separated());
+    game.keydown(Action::EndSeparated);
+    assert!(game.winner().is_none());
+
+    // Equal numbers of marbles
+    let mut game = split_game(true);
+    game.decode("5x3 b 2 a1.#.b1..#....#..").unwrap();
+    game.click(Point::new(4, 1));
+    game.advance_until_stable();
+    game.click(Point::new(0, 1));
+    game.advance_until_stable();
+    assert_eq!(game.leader(), None);
+    assert!(!game.separated());
+
+    // A player that places a marble in the region of the other contests it again
+    let mut game = split_game(true);
+    game.click(Point::new(0, 1));
+    game.advance_until_stable();
+    assert!(game.separated());
+    game.click(Point::new(1, 2));
+    game.advance_until_stable();
+    assert!(!game.separated());
+}
@@ -0,0 +1,100 @@
+use chain::bench;
+use chain::game::Game;
+use chain::grid::{Grid, Owner, Point, NEUTRAL};
+
+mod common;
+use common::{owner, CELLSIZE};
+
+// A game between three players on a 4x4 board, given as column, row and player
+const RECORDED: &[(i32, i32, usize)] = &[
+    (2, 2, 0), (1, 0, 1), (2, 3, 2), (2, 2, 0), (0, 3, 1), (1, 1, 2), (3, 1, 0), (0, 3, 1),
+    (1, 2, 2), (2, 2, 0), (1, 0, 1), (2, 0, 2), (0, 0, 0), (0, 3, 1), (1, 2, 2), (3, 2, 0),
+    (1, 0, 1), (2, 3, 2), (3, 1, 0), (1, 3, 1), (3, 3, 2), (3, 2, 0), (1, 0, 1), (2, 3, 2),
+    (1, 0, 1),
+];
+
+fn recorded(players: usize) -> Vec<(Point, Owner)> {
+    RECORDED.iter().map(|&(x, y, idx)| (Point::new(x, y), Owner::new(idx, players).unwrap())).collect()
+}
+
+fn new_game() -> Game {
+    Game::new(bench::config(3, Point::new(4, 4)).unwrap()).unwrap()
+}
+
+#[test]
+fn recorded_game_is_replayed() {
+    let moves = recorded(3);
+    let mut game = new_game();
+    let summary = game.apply_moves(&moves).unwrap();
+    assert_eq!(summary.moves, moves.len());
+    assert_eq!((summary.waves, summary.longest_chain), (19, 8));
+    assert_eq!(game.marbles_placed() as usize, moves.len());
+    assert_eq!(game.winner().map(|owner| owner.idx()), Some(1));
+    let alive: Vec<bool> = game.players().map(|player| player.alive).collect();
+    assert_eq!(alive, [false, true, false]);
+    let cells: Vec<usize> = (0..3).map(|idx| game.grid().cells_of(Owner::new(idx, 3).unwrap())).collect();
+    assert_eq!(cells, [0, 11, 0]);
+
+    // The first player is eliminated before the third one
+    let mut eliminated = Vec::new();
+    let mut step = new_game();
+    for &m in moves.iter() {
+        step.apply_moves(&[m]).unwrap();
+        for (idx, player) in step.players().enumerate() {
+            if !player.alive && !eliminated.contains(&idx) {
+                eliminated.push(idx);
+            }
+        }
+    }
+    assert_eq!(eliminated, [0, 2]);
+    assert_eq!(step.grid().encode(), game.grid().encode());
+}
+
+#[test]
+fn refused_moves_are_reported_by_index() {
+    let moves = recorded(3);
+    let mut game = new_game();
+    let error = game.apply_moves(&[moves[0], moves[2]]).unwrap_err();
+    assert_eq!(error.index, 1);
+    assert!(error.reason.contains("is to move"), "{}", error);
+    // The moves before are kept
+    assert_eq!(game.marbles_placed(), 1);
+
+    // Cell of another player
+    let error = game.apply_moves(&[(Point::new(2, 2), moves[1].1)]).unwrap_err();
+    assert_eq!(error.index, 0);
+    assert!(error.reason.contains("belongs to player a"), "{}", error);
+
+    let mut game = new_game();
+    let mut after_the_end = moves.clone();
+    after_the_end.push((Point::new(0, 0), moves[1].1));
+    let error = game.apply_moves(&after_the_end).unwrap_err();
+    assert_eq!(error.index, moves.len());
+    assert!(error.reason.contains("already won"), "{}", error);
+}
+
+#[test]
+fn grid_replays_without_turn_order() {
+    let mut grid = Grid::new(Point::new(3, 3));
+    let (a, b) = (owner(0), owner(1));
+    let moves = [(Point::new(0, 0), a), (Point::new(0, 0), a), (Point::new(2, 0), b), (Point::new(2, 0), b)];
+    let summary = grid.apply_moves(&moves, CELLSIZE).unwrap();
+    assert_eq!((summary.moves, summary.waves, summary.longest_chain), (4, 2, 1));
+    assert_eq!(grid.encode(), "3x3 .b2.a1.b13.");
+
+    let refused = [
+        ((Point::new(3, 0), a), "outside of the board"),
+        ((Point::new(1, 0), a), "belongs to player b"),
+        ((Point::new(1, 1), NEUTRAL), "Neutral"),
+    ];
+    for ((p, owner), reason) in refused {
+        let error = grid.apply_moves(&[(Point::new(2, 2), a), (p, owner)], CELLSIZE).unwrap_err();
+        assert_eq!(error.index, 1);
+        assert!(error.reason.contains(reason), "{}", error);
+    }
+
+    // Endless cascades are stopped
+    let mut grid = Grid::decode("2x2 4a1", 2, CELLSIZE).unwrap();
+    let error = grid.apply_moves(&[(Point::new(0, 0), a)], CELLSIZE).unwrap_err();
+    assert_eq!(error.index, 0);
+}
@@ -0,0 +1,90 @@
+use chain::bench;
+use chain::game::Game;
+use chain::grid::{Grid, Point, SpreadBias, Wave};
+use chain::menu::Config;
+use chain::rng::Rng;
+use chain::shape::Shape;
+
+mod common;
+use common::{owner, CELLSIZE};
+
+fn fresh_grid() -> Grid {
+    let dim = Point::new(7, 7);
+    let mut grid = Grid::from_mask(dim, &Shape::Donut.mask(dim));
+    grid.set_spread_bias(SpreadBias::Clockwise);
+    grid.set_threshold_factor(1.5).unwrap();
+    grid
+}
+
+/* Play random moves of two players and record every spreading step */
+fn play(grid: &mut Grid, seed: u64) -> Vec<Result<Wave, String>> {
+    let mut rng = Rng::new(seed);
+    let mut waves = Vec::new();
+    for turn in 0..60 {
+        let player = owner(turn % 2);
+        let moves: Vec<Point> = grid.points().filter(|&p| grid.check_move(p, player).is_ok()).collect();
+        if moves.is_empty() || grid.single_owner().is_some() {
+            break;
+        }
+        waves.extend(grid.cascade(moves[rng.below(moves.len())], player, CELLSIZE).unwrap());
+    }
+    waves
+}
+
+#[test]
+fn reset_grid_plays_like_a_fresh_one() {
+    let mut grid = fresh_grid();
+    grid.add_bonus(Point::new(0, 0)).unwrap();
+    grid.add_neutral(Point::new(3, 0), CELLSIZE).unwrap();
+    play(&mut grid, 5);
+    assert!(!grid.is_empty());
+
+    grid.reset();
+    let mut fresh = fresh_grid();
+    assert!(grid.is_empty());
+    assert_eq!(grid.encode(), fresh.encode());
+    assert_eq!(grid.hash(), fresh.hash());
+    assert_eq!(grid.last_placed(), None);
+    assert!(grid.points().all(|p| !grid.cell(p).bonus() && grid.cell(p).playable() == fresh.cell(p).playable()));
+
+    for seed in [5, 6] {
+        let waves = play(&mut grid, seed);
+        assert!(!waves.is_empty());
+        assert_eq!(waves, play(&mut fresh, seed));
+        assert_eq!(grid.encode(), fresh.encode());
+        assert_eq!(grid.hash(), fresh.hash());
+        assert_eq!(grid.single_owner(), fresh.single_owner());
+        let ids = |grid: &Grid| grid.marbles().map(|marble| marble.id()).collect::<Vec<_>>();
+        assert_eq!(ids(&grid), ids(&fresh));
+        grid.reset();
+        fresh = fresh_grid();
+    }
+}
+
+#[test]
+fn restart_is_a_rematch_on_the_same_board() {
+    let config = Config {
+        bonus: vec![Point::new(1, 1)],
+        neutral: vec![Point::new(2, 2)],
+        ..bench::config(3, Point::new(5, 4)).unwrap()
+    };
+    let mut game = Game::new(config).unwrap();
+    let mut rng = Rng::new(9);
+    for _ in 0..12 {
+        let moves = game.legal_moves();
+        game.click(moves[rng.below(moves.len())]);
+        game.advance_until_stable();
+    }
+    assert!(!game.history().is_empty());
+    game.toggle_slow_motion();
+
+    let rematch = game.rematch();
+    game.restart();
+    assert_eq!(game.encode(), rematch.encode());
+    assert_eq!(game.first_player(), rematch.first_player());
+    assert_eq!(game.cur_player(), rematch.cur_player());
+    assert!(game.history().is_empty());
+    assert_eq!(game.marbles_placed(), 0);
+    assert!(game.slow_motion() && rematch.slow_motion());
+    assert!(game.grid().cell(Point::new(1, 1)).bonus());
+}
@@ -0,0 +1,14 @@
+use chain::rng::Rng;
+
+#[test]
+fn the_same_seed_repeats_the_numbers() {
+    let numbers = |seed| {
+        let mut rng = Rng::new(seed);
+        (0..100).map(|_| rng.below(6)).collect::<Vec<usize>>()
+    };
+    assert_eq!(numbers(42), numbers(42));
+    assert_ne!(numbers(42), numbers(43));
+    // Every number below the bound comes up
+    assert!((0..6).all(|n| numbers(42).contains(&n)));
+    assert!(numbers(7).iter().all(|&n| n < 6));
+}
@@ -0,0 +1,45 @@
+use chain::bench;
+use chain::game::Game;
+use chain::grid::Point;
+use chain::menu::Config;
+
+fn sandbox(players: usize) -> Game {
+    let config = Config { sandbox: true, ..bench::config(players, Point::new(3, 3)).unwrap() };
+    Game::new(config).unwrap()
+}
+
+fn play(game: &mut Game, moves: &[(i32, i32)]) {
+    for &(x, y) in moves {
+        game.click(Point::new(x, y));
+        game.advance_until_stable();
+    }
+}
+
+#[test]
+fn players_without_cells_re_enter_on_an_empty_cell() {
+    // The second player loses the only cell, which would end a normal game
+    let mut game = sandbox(2);
+    play(&mut game, &[(0, 0), (1, 0), (0, 0)]);
+    assert_eq!(game.encode().as_deref(), Some("3x3 b 2 .a2.a15."));
+    assert_eq!(game.winner(), None);
+    assert_eq!(game.cur_player().idx(), 1);
+
+    // Cells of the other player are still refused, any empty cell is fine
+    play(&mut game, &[(1, 0)]);
+    assert_eq!(game.cur_player().idx(), 1);
+    play(&mut game, &[(2, 2)]);
+    assert_eq!(game.encode().as_deref(), Some("3x3 a 2 .a2.a14.b1"));
+    assert_eq!(game.winner(), None);
+}
+
+#[test]
+fn players_without_any_cell_to_play_are_skipped() {
+    // All cells belong to the first two players, so the third one has nowhere to go
+    let mut game = sandbox(3);
+    game.decode("3x3 a 3 a1b1a1b1a1b1a1b1a1").unwrap();
+    play(&mut game, &[(1, 1)]);
+    assert_eq!(game.cur_player().idx(), 1);
+    play(&mut game, &[(1, 0)]);
+    assert_eq!(game.cur_player().idx(), 0);
+    assert_eq!(game.winner(), None);
+}
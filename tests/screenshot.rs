@@ -0,0 +1,34 @@
+use std::time::{Duration, UNIX_EPOCH};
+
+use chain::screenshot::{file_name, write_png};
+
+#[test]
+fn images_survive_saving() {
+    let (width, height) = (37, 11);
+    let mut pixels = Vec::new();
+    for y in 0..height {
+        for x in 0..width {
+            // Flat areas, gradients and noise
+            pixels.extend([(x * 7) as u8, if x < 20 { 90 } else { (x * y * 31 % 251) as u8 }, (y * 20) as u8]);
+        }
+    }
+    let path = std::env::temp_dir().join(format!("chainreaction-screenshot-{}.png", std::process::id()));
+    write_png(&path, width, height, &pixels).unwrap();
+    let image = image::open(&path).unwrap().into_rgb8();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(image.dimensions(), (width, height));
+    assert_eq!(image.into_raw(), pixels);
+
+    // Pixels that do not fit the size are refused before anything is written
+    assert!(write_png(&path, width, height + 1, &pixels).is_err());
+    assert!(!path.exists());
+}
+
+#[test]
+fn file_names_tell_the_time() {
+    let at = |secs| file_name(UNIX_EPOCH + Duration::from_secs(secs));
+    assert_eq!(at(0), "chainreaction-1970-01-01-000000.png");
+    assert_eq!(at(951_782_400), "chainreaction-2000-02-29-000000.png");
+    assert_eq!(at(1_709_648_701), "chainreaction-2024-03-05-142501.png");
+    assert_eq!(at(4_102_444_799), "chainreaction-2099-12-31-235959.png");
+}
@@ -0,0 +1,549 @@
+use sdl2::pixels::Color;
+
+use std::time::Duration;
+
+use chain::bench;
+use chain::game::{Game, Player, State, WinCondition, FRAME, SLOW_MOTION, SPREAD_DURATION};
+use chain::keymap::Action;
+use chain::grid::{Grid, Owner, Point};
+use chain::menu::Config;
+
+/* A game on a 3x3 board with two players, given by the cells that are clicked in turn, and the
+ * expected outcome. Positions use the format of Game::encode(). If the rules change on purpose,
+ * update the expected positions here.
+ */
+struct Script {
+    name: &'static str,
+    moves: &'static [(i32, i32)],
+    // Position after the last move, once all marbles came to rest
+    position: &'static str,
+    // Number of spreading waves caused by the last move
+    chain: u32,
+    // Index of the winning player
+    winner: Option<usize>,
+}
+
+const SCRIPTS: &[Script] = &[
+    Script {
+        name: "single spread",
+        moves: &[(0, 0), (2, 2), (0, 0)],
+        position: "3x3 b 2 .a1.a14.b1",
+        chain: 1,
+        winner: None,
+    },
+    Script {
+        name: "multi-wave cascade",
+        moves: &[(0, 0), (2, 2), (0, 0), (2, 2), (1, 0), (2, 1), (1, 0), (2, 1)],
+        position: "3x3 a 2 a1b1.a1b2b1.2b1",
+        chain: 2,
+        winner: None,
+    },
+    Script {
+        name: "elimination",
+        moves: &[(0, 0), (1, 0), (0, 0)],
+        position: "3x3 a 2 .a2.a15.",
+        chain: 1,
+        winner: Some(0),
+    },
+];
+
+fn config() -> Config {
+    bench::config(2, Point::new(3, 3)).unwrap()
+}
+
+fn new_game() -> Game {
+    Game::new(config()).unwrap()
+}
+
+fn new_game_with(win_condition: WinCondition) -> Game {
+    Game::new(Config { win_condition, ..config() }).unwrap()
+}
+
+#[test]
+fn scripted_games() {
+    for script in SCRIPTS {
+        let mut game = new_game();
+        for &(x, y) in script.moves {
+            assert!(game.winner().is_none(), "{}: game ended before move {:?}", script.name, (x, y));
+            game.click(Point::new(x, y));
+            game.advance_until_stable();
+        }
+        assert_eq!(game.encode().as_deref(), Some(script.position), "{}", script.name);
+        assert_eq!(game.chain_depth(), script.chain, "{}", script.name);
+        assert_eq!(game.winner().map(|owner| owner.idx()), script.winner, "{}", script.name);
+    }
+}
+
+#[test]
+fn fast_forward_ends_on_the_animated_board() {
+    let play = |game: &mut Game, p: Point, fast: bool| {
+        game.click(p);
+        if fast {
+            game.advance_until_stable();
+        }
+        for _ in 0..1000 {
+            if fast || matches!(game.state(), State::AcceptingInput) {
+                break;
+            }
+            game.step(FRAME);
+        }
+        assert!(game.encode().is_some(), "The marbles did not come to rest");
+    };
+    for script in SCRIPTS {
+        let (mut fast, mut animated) = (new_game(), new_game());
+        for &(x, y) in script.moves {
+            play(&mut fast, Point::new(x, y), true);
+            play(&mut animated, Point::new(x, y), false);
+            assert_eq!(fast.encode(), animated.encode(), "{}", script.name);
+        }
+    }
+    // The first step takes the last cell of b, and the cascade goes on after the game is won
+    let (mut fast, mut animated) = (new_game(), new_game());
+    for game in [&mut fast, &mut animated] {
+        game.decode("3x3 a 2 a1b1.a25.").unwrap();
+    }
+    play(&mut fast, Point::new(0, 0), true);
+    assert!(fast.winner().is_some());
+    play(&mut animated, Point::new(0, 0), false);
+    assert_eq!(fast.encode(), animated.encode());
+    assert_eq!(fast.chain_depth(), 2);
+    // Instant moves are resolved the same way
+    let mut instant = Game::new(Config { instant: true, ..config() }).unwrap();
+    instant.decode("3x3 a 2 a1b1.a25.").unwrap();
+    instant.click(Point::new(0, 0));
+    assert_eq!(instant.encode(), animated.encode());
+}
+
+#[test]
+fn chain_depth_counts_the_waves_of_each_move() {
+    let script = &SCRIPTS[1];
+    let mut game = new_game();
+    let mut depths = Vec::new();
+    for &(x, y) in script.moves {
+        game.click(Point::new(x, y));
+        depths.clear();
+        while let State::Animating(_) = game.state() {
+            if depths.last() != Some(&game.chain_depth()) {
+                depths.push(game.chain_depth());
+            }
+            game.step(FRAME);
+        }
+    }
+    // Each wave adds one, up to the number of waves of the last move
+    assert_eq!(depths, (1..=script.chain).collect::<Vec<u32>>());
+    assert_eq!(game.chain_depth(), script.chain);
+    // A move that does not spread starts again from zero
+    game.click(Point::new(2, 0));
+    game.advance_until_stable();
+    assert_eq!(game.chain_depth(), 0);
+}
+
+#[test]
+fn instant_games_end_like_animated_ones() {
+    for script in SCRIPTS {
+        let mut game = Game::new(Config { instant: true, ..config() }).unwrap();
+        for (turn, &(x, y)) in script.moves.iter().enumerate() {
+            assert!(game.winner().is_none(), "{}: game ended before move {:?}", script.name, (x, y));
+            game.click(Point::new(x, y));
+            // Each move is resolved at once and passes the turn exactly once
+            assert!(matches!(game.state(), State::AcceptingInput), "{}", script.name);
+            if game.winner().is_none() {
+                assert_eq!(game.cur_player().idx(), (turn + 1) % 2, "{}", script.name);
+            }
+        }
+        assert_eq!(game.encode().as_deref(), Some(script.position), "{}", script.name);
+        assert_eq!(game.chain_depth(), script.chain, "{}", script.name);
+        assert_eq!(game.winner().map(|owner| owner.idx()), script.winner, "{}", script.name);
+        assert_eq!(game.marbles_placed(), script.moves.len() as u32);
+    }
+}
+
+#[test]
+fn games_are_over_once_the_last_move_played_out() {
+    let mut game = new_game();
+    for &(x, y) in &SCRIPTS[2].moves[..2] {
+        game.click(Point::new(x, y));
+        game.advance_until_stable();
+    }
+    game.click(Point::new(0, 0));
+    while let State::Animating(_) = game.state() {
+        assert!(!game.is_over());
+        game.step(SPREAD_DURATION / 4);
+    }
+    assert!(game.winner().is_some());
+    assert!(game.is_over());
+
+    // Marbles that can never come to rest do not hold up the end of the game
+    let mut game = Game::new(Config { size: Point::new(2, 2), ..config() }).unwrap();
+    game.decode("2x2 a 2 a1a1a1b1").unwrap();
+    assert!(!game.is_over());
+    game.click(Point::new(0, 0));
+    game.step(SPREAD_DURATION * 3);
+    assert!(matches!(game.state(), State::Animating(_)));
+    assert_eq!(game.winner().map(|owner| owner.idx()), Some(0));
+    assert!(game.is_over());
+}
+
+#[test]
+fn clicks_during_the_animation_are_played_afterwards() {
+    for (clicked, position) in [((2, 1), Some("3x3 a 2 .a1.a1.b12.b1")), ((1, 0), None)] {
+        let mut game = Game::new(Config { buffer_input: true, ..config() }).unwrap();
+        for &(x, y) in SCRIPTS[0].moves {
+            game.click(Point::new(x, y));
+        }
+        assert!(matches!(game.state(), State::Animating(_)));
+        game.click(Point::new(clicked.0, clicked.1));
+        assert_eq!(game.pending_move(), Some(Point::new(clicked.0, clicked.1)));
+        game.advance_until_stable();
+        assert_eq!(game.pending_move(), None);
+        // The cell taken by the cascade is no longer a legal move, so the click is dropped
+        assert_eq!(game.encode().as_deref(), Some(position.unwrap_or(SCRIPTS[0].position)));
+    }
+
+    // Without buffering, clicks during the animation are ignored
+    let mut game = new_game();
+    for &(x, y) in SCRIPTS[0].moves {
+        game.click(Point::new(x, y));
+    }
+    game.click(Point::new(2, 1));
+    assert_eq!(game.pending_move(), None);
+    game.advance_until_stable();
+    assert_eq!(game.encode().as_deref(), Some(SCRIPTS[0].position));
+}
+
+/* Play the moves of a script, stepping the animation by the given time per frame, and return the
+ * number of frames it took
+ */
+fn play_animated(game: &mut Game, script: &Script, frame: Duration) -> usize {
+    let mut frames = 0;
+    for &(x, y) in script.moves {
+        game.click(Point::new(x, y));
+        while let State::Animating(_) = game.state() {
+            game.step(frame);
+            frames += 1;
+        }
+    }
+    frames
+}
+
+#[test]
+fn slow_motion_only_changes_the_pace() {
+    for script in SCRIPTS {
+        let mut normal = new_game();
+        let mut slow = new_game();
+        slow.keydown(Action::SlowMotion);
+        assert!(slow.slow_motion());
+        let frames = play_animated(&mut normal, script, SPREAD_DURATION / 8);
+        let slow_frames = play_animated(&mut slow, script, SPREAD_DURATION / 8);
+        // Placing a marble without a cascade takes a frame either way
+        assert!(slow_frames > frames * (SLOW_MOTION as usize - 1), "{}: {} frames", script.name, slow_frames);
+        assert_eq!(slow.encode(), normal.encode(), "{}", script.name);
+        assert_eq!(slow.cur_player(), normal.cur_player(), "{}", script.name);
+        assert_eq!(slow.winner(), normal.winner(), "{}", script.name);
+    }
+}
+
+#[test]
+fn slow_motion_continues_where_the_marbles_are() {
+    let mut game = new_game();
+    for &(x, y) in SCRIPTS[1].moves {
+        game.advance_until_stable();
+        game.click(Point::new(x, y));
+    }
+    game.step(SPREAD_DURATION / 2);
+    assert!(matches!(game.state(), State::Animating(_)));
+    let positions = |game: &Game| {
+        let mut marbles: Vec<(u32, Point)> = game.grid().marbles().map(|m| (m.id(), m.get_pos())).collect();
+        marbles.sort_by_key(|&(id, _)| id);
+        marbles
+    };
+    let before = positions(&game);
+    game.toggle_slow_motion();
+    game.step(Duration::from_millis(10));
+    // Marbles move on by a few pixels, at most as far as in a single normal frame
+    for ((id, a), (_, b)) in before.iter().zip(positions(&game)) {
+        let moved = (b - a).re.abs() + (b - a).im.abs();
+        assert!(moved <= 10, "marble {} moved from {} to {}", id, a, b);
+    }
+    game.toggle_slow_motion();
+    assert!(!game.slow_motion());
+}
+
+#[test]
+fn clicks_on_foreign_cells_are_ignored() {
+    let mut game = new_game();
+    game.click(Point::new(0, 0));
+    game.advance_until_stable();
+    game.click(Point::new(0, 0));
+    game.advance_until_stable();
+    assert_eq!(game.encode().as_deref(), Some("3x3 b 2 a18."));
+}
+
+#[test]
+fn last_move_is_the_last_placed_marble() {
+    let mut game = new_game();
+    assert!(game.last_move().is_none());
+    game.click(Point::new(0, 0));
+    game.advance_until_stable();
+    let (owner, p) = game.last_move().unwrap();
+    assert_eq!((owner.idx(), p), (0, Point::new(0, 0)));
+
+    // Refused clicks keep the last move
+    game.click(Point::new(0, 0));
+    assert_eq!(game.last_move().map(|(owner, p)| (owner.idx(), p)), Some((0, Point::new(0, 0))));
+    game.click(Point::new(2, 1));
+    assert_eq!(game.last_move().map(|(owner, p)| (owner.idx(), p)), Some((1, Point::new(2, 1))));
+    let history: Vec<_> = game.history().iter().map(|&(owner, p)| (owner.idx(), Grid::cell_label(p))).collect();
+    assert_eq!(history, [(0, "A1".to_string()), (1, "C2".to_string())]);
+
+    // The cell stays marked after it spread
+    game.advance_until_stable();
+    game.click(Point::new(0, 0));
+    game.advance_until_stable();
+    assert_eq!(game.grid().cell(Point::new(0, 0)).owner(), None);
+    assert_eq!(game.last_move().map(|(owner, p)| (owner.idx(), p)), Some((0, Point::new(0, 0))));
+
+    game.decode("3x3 b 2 a18.").unwrap();
+    assert!(game.last_move().is_none());
+    assert!(game.history().is_empty());
+}
+
+#[test]
+fn players_without_names_are_numbered() {
+    let mut config = config();
+    config.players[1].set_name("  Ada ");
+    let mut game = Game::new(config).unwrap();
+    let names: Vec<_> = game.players().map(|player| player.name()).collect();
+    assert_eq!(names, [None, Some("Ada")]);
+    let owners: Vec<_> = Owner::all(2).collect();
+    assert_eq!((game.player_name(owners[0]), game.player_name(owners[1])), ("Player 1".to_string(), "Ada".to_string()));
+    // Names stay when a position is loaded and in the rematch
+    game.decode("3x3 b 2 a18.").unwrap();
+    assert_eq!(game.player_name(owners[1]), "Ada");
+    assert_eq!(game.rematch().player_name(owners[1]), "Ada");
+    let mut player = Player::new(Color::RGB(0, 0, 0));
+    player.set_name(" ");
+    assert_eq!(player.name(), None);
+}
+
+#[test]
+fn cell_labels_continue_after_z() {
+    assert_eq!(Grid::cell_label(Point::new(1, 3)), "B4");
+    assert_eq!(Grid::cell_label(Point::new(25, 9)), "Z10");
+    assert_eq!(Grid::cell_label(Point::new(27, 11)), "AB12");
+    assert_eq!(Grid::cell_label(Point::new(26 * 27, 0)), "AAA1");
+}
+
+#[test]
+fn labels_of_a_12x12_board() {
+    let grid = Grid::new(Point::new(12, 12));
+    let columns: Vec<String> = (0..12).map(Grid::column_label).collect();
+    assert_eq!(columns.concat(), "ABCDEFGHIJKL");
+    let rows: Vec<String> = (0..12).map(Grid::row_label).collect();
+    assert_eq!(rows.join(" "), "1 2 3 4 5 6 7 8 9 10 11 12");
+    let mut labels: Vec<String> = grid.points().map(Grid::cell_label).collect();
+    assert_eq!((labels.first().unwrap().as_str(), labels.last().unwrap().as_str()), ("A1", "L12"));
+    labels.sort();
+    labels.dedup();
+    assert_eq!(labels.len(), 144);
+}
+
+#[test]
+fn positions_of_unknown_players_are_refused() {
+    let mut game = new_game();
+    assert!(game.decode("3x3 a 2 c18.").is_err());
+    assert!(game.decode("3x3 c 2 a18.").is_err());
+    assert!(game.decode("3x3 b 2 a18.").is_ok());
+    assert_eq!(game.encode().as_deref(), Some("3x3 b 2 a18."));
+}
+
+#[test]
+fn legal_moves_exclude_cells_of_other_players() {
+    let mut game = new_game();
+    game.decode("3x3 b 2 a1b1.a1.*1b12.").unwrap();
+    let mut moves = game.legal_moves();
+    moves.sort_by_key(|p| (p.im, p.re));
+    let expected: Vec<Point> = [(1, 0), (2, 0), (1, 1), (2, 1), (0, 2), (1, 2), (2, 2)].iter()
+        .map(|&(x, y)| Point::new(x, y))
+        .collect();
+    assert_eq!(moves, expected);
+    // Listing the moves does not change the position
+    assert_eq!(game.encode().as_deref(), Some("3x3 b 2 a1b1.a1.*1b12."));
+
+    // No moves while marbles are moving
+    game.click(Point::new(0, 2));
+    assert!(matches!(game.state(), State::Animating(_)));
+    assert!(game.legal_moves().is_empty());
+}
+
+#[test]
+fn multi_placement_advances_once_after_the_last_marble() {
+    let mut config = config();
+    config.players.push(Player::new(Color::RGB(0, 160, 0)));
+    let mut game = Game::new(config).unwrap();
+    game.set_auto_advance(false);
+    for &(x, y) in &[(0, 0), (2, 2)] {
+        game.click(Point::new(x, y));
+        game.advance_until_stable();
+        assert_eq!(game.cur_player().idx(), 0);
+    }
+    // The turn ends with a spreading marble, so it passes once the marbles came to rest
+    game.click(Point::new(0, 0));
+    game.end_turn();
+    assert!(matches!(game.state(), State::Animating(_)));
+    assert_eq!(game.cur_player().idx(), 0);
+    game.advance_until_stable();
+    assert_eq!(game.cur_player().idx(), 1);
+    assert_eq!(game.encode().as_deref(), Some("3x3 b 3 .a1.a14.a1"));
+    // All in one turn of the first player
+    assert_eq!(game.marbles_placed(), 3);
+
+    game.click(Point::new(1, 1));
+    game.click(Point::new(1, 1));
+    game.end_turn();
+    assert_eq!(game.cur_player().idx(), 2);
+    // Ending a turn without placing a marble passes
+    game.end_turn();
+    assert_eq!(game.cur_player().idx(), 0);
+}
+
+// Moves after which the first player briefly controls 5 of 9 cells in the middle of the last
+// chain reaction, but only 4 once it settled
+const DOMINATION_MOVES: &[(i32, i32)] = &[
+    (0, 1), (0, 2), (1, 0), (2, 2), (1, 2), (2, 1), (0, 1), (0, 0), (0, 1),
+];
+
+#[test]
+fn domination_only_counts_settled_positions() {
+    let mut game = new_game_with(WinCondition::Domination(0.5));
+    let (last, moves) = DOMINATION_MOVES.split_last().unwrap();
+    for &(x, y) in moves {
+        game.click(Point::new(x, y));
+        game.advance_until_stable();
+    }
+    assert!(game.winner().is_none());
+    let first = game.cur_player();
+    game.click(Point::new(last.0, last.1));
+    let mut peak = 0;
+    while let State::Animating(_) = game.state() {
+        peak = peak.max(game.grid().cells_of(first));
+        game.step(SPREAD_DURATION);
+    }
+    assert_eq!(peak, 5);
+    assert_eq!(game.grid().cells_of(first), 4);
+    assert!(!game.player(first).dominating);
+    game.click(Point::new(2, 0));
+    assert_eq!(game.cur_player(), first);
+    assert!(game.winner().is_none());
+}
+
+#[test]
+fn domination_wins_after_a_full_round() {
+    let mut game = new_game_with(WinCondition::Domination(0.4));
+    for &(x, y) in DOMINATION_MOVES {
+        game.click(Point::new(x, y));
+        game.advance_until_stable();
+        assert!(game.winner().is_none());
+    }
+    let first = game.cur_player().next(2);
+    assert!(game.player(first).dominating);
+    game.click(Point::new(2, 0));
+    assert_eq!(game.winner(), Some(first));
+    // The game is over
+    game.click(Point::new(2, 0));
+    assert_eq!(game.encode().as_deref(), Some("3x3 a 2 .a2b1a2a1b1.a2b1"));
+}
+
+#[test]
+fn decoded_positions_are_not_won_yet() {
+    // A pasted position continues from scratch, even after a domination win
+    let mut game = new_game_with(WinCondition::Domination(0.4));
+    for &(x, y) in DOMINATION_MOVES.iter().chain(&[(2, 0)]) {
+        game.click(Point::new(x, y));
+        game.advance_until_stable();
+    }
+    assert!(game.winner().is_some());
+    game.decode("3x3 b 2 a12.b15.").unwrap();
+    assert_eq!(game.winner(), None);
+    assert!(game.events().is_empty());
+    game.click(Point::new(2, 2));
+    game.advance_until_stable();
+    assert_eq!(game.encode().as_deref(), Some("3x3 a 2 a12.b14.b1"));
+
+    // Or after a game that was ended once the players were separated
+    let config = Config { end_when_separated: true, ..bench::config(2, Point::new(5, 3)).unwrap() };
+    let mut game = Game::new(config).unwrap();
+    game.decode("5x3 a 2 a1.#.b1..#....#..").unwrap();
+    game.click(Point::new(0, 1));
+    game.advance_until_stable();
+    game.keydown(Action::EndSeparated);
+    assert!(game.winner().is_some());
+    game.decode("5x3 a 2 a1.#.b1..#....#..").unwrap();
+    assert_eq!(game.winner(), None);
+}
+
+#[test]
+fn resigning_hands_the_win_to_the_last_player() {
+    let mut game = new_game();
+    game.click(Point::new(0, 0));
+    game.click(Point::new(2, 2));
+    game.resign();
+    assert_eq!(game.winner().map(|owner| owner.idx()), Some(1));
+    assert_eq!(game.encode().as_deref(), Some("3x3 b 2 *17.b1"));
+}
+
+#[test]
+fn resigned_players_are_skipped_and_leave_neutral_cells() {
+    let mut players = config().players;
+    players.push(Player::new(Color::RGB(0, 255, 0)));
+    let mut game = Game::new(Config { players, ..config() }).unwrap();
+    game.click(Point::new(0, 0));
+    game.click(Point::new(1, 1));
+    // The third player resigns before placing a marble
+    game.resign();
+    assert!(game.winner().is_none());
+    game.click(Point::new(0, 0));
+    game.advance_until_stable();
+    game.resign();
+    assert_eq!(game.winner().map(|owner| owner.idx()), Some(0));
+    // The marble of the second player stays as a neutral marble
+    assert_eq!(game.encode().as_deref(), Some("3x3 a 3 .a1.a1*14."));
+}
+
+#[test]
+fn editor_sets_up_positions() {
+    let mut game = new_game();
+    game.start_editing();
+    game.edit_for(1);
+    game.insert_marble(Point::new(1, 1)).unwrap();
+    game.insert_marble(Point::new(1, 1)).unwrap();
+    game.insert_marble(Point::new(1, 1)).unwrap();
+    game.edit_for(0);
+    // A corner is at capacity with two marbles, which only the editor can set up
+    game.insert_marble(Point::new(0, 0)).unwrap();
+    game.insert_marble(Point::new(0, 0)).unwrap();
+    assert!(game.insert_marble(Point::new(0, 0)).is_err());
+    assert!(game.finish_editing().is_err(), "A1 is at capacity");
+    game.remove_marble(Point::new(0, 0)).unwrap();
+    game.insert_marble(Point::new(1, 0)).unwrap();
+    // Taking over a cell keeps its marbles
+    game.edit_for(1);
+    game.insert_marble(Point::new(1, 0)).unwrap();
+    assert!(game.remove_marble(Point::new(2, 2)).is_err(), "C3 is empty");
+    assert!(game.finish_editing().is_ok());
+    assert_eq!(game.encode().as_deref(), Some("3x3 b 2 a1b22.b34."));
+
+    // The second player sets off a chain reaction that captures the only cell of the first player
+    assert!(game.insert_marble(Point::new(1, 1)).is_err());
+    game.click(Point::new(1, 1));
+    game.advance_until_stable();
+    assert_eq!(game.winner().map(|owner| owner.idx()), Some(1));
+}
+
+#[test]
+fn editor_is_only_available_before_the_first_move() {
+    let mut game = new_game();
+    game.click(Point::new(0, 0));
+    game.start_editing();
+    assert!(game.editing().is_none());
+}
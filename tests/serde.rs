@@ -0,0 +1,171 @@
+#![cfg(feature = "serde")]
+
+use chain::bench;
+use chain::game::State;
+use chain::grid::{Grid, Owner, Point};
+use chain::menu::Config;
+use chain::rng::Rng;
+use chain::theme::Theme;
+
+mod common;
+use common::{owner, CELLSIZE};
+
+fn positions(grid: &Grid) -> Vec<(u32, Point, Owner)> {
+    let mut marbles: Vec<_> = grid.marbles().map(|m| (m.id(), m.get_pos(), m.get_owner())).collect();
+    marbles.sort_by_key(|&(id, _, _)| id);
+    marbles
+}
+
+#[test]
+fn grid_survives_a_round_trip_during_a_cascade() {
+    let mut grid = Grid::decode("3x3 a1b2.b1a1b2.a1.", 2, CELLSIZE).unwrap();
+    let (mut state, _) = grid.add_marble(Point::new(1, 0), owner(1), CELLSIZE).unwrap();
+    state = grid.step(state, 0.4, CELLSIZE).0;
+    assert!(matches!(state, State::Animating(_)));
+
+    let text = serde_json::to_string(&grid).unwrap();
+    let mut copy: Grid = serde_json::from_str(&text).unwrap();
+    assert_eq!(copy.hash(), grid.hash());
+    assert_eq!(copy.chain(), grid.chain());
+    assert_eq!(positions(&copy), positions(&grid));
+
+    // Both continue the cascade the same way
+    let state: State = serde_json::from_str(&serde_json::to_string(&state).unwrap()).unwrap();
+    let (mut a, mut b) = (state, state);
+    for _ in 0..100 {
+        a = grid.step(a, 0.3, CELLSIZE).0;
+        b = copy.step(b, 0.3, CELLSIZE).0;
+    }
+    assert!(matches!((a, b), (State::AcceptingInput, State::AcceptingInput)));
+    assert_eq!(copy.encode(), grid.encode());
+    assert_eq!(copy.hash(), grid.hash());
+    assert_eq!(copy.single_owner(), grid.single_owner());
+    assert_eq!(positions(&copy), positions(&grid));
+
+    // Lost marbles are noticed
+    let broken = text.replacen("\"placed\":", "\"placed\":1", 1);
+    assert!(serde_json::from_str::<Grid>(&broken).is_err());
+}
+
+#[test]
+fn config_survives_a_round_trip() {
+    let config = Config { theme: Theme::DARK, ..bench::config(3, Point::new(6, 5)).unwrap() };
+    let text = serde_json::to_string(&config).unwrap();
+    // Colors are stored without their alpha channel
+    assert!(text.contains("\"color\":[255,0,0]"));
+    let copy: Config = serde_json::from_str(&text).unwrap();
+    assert_eq!(copy.size, config.size);
+    assert_eq!(copy.shape, config.shape);
+    assert_eq!(copy.win_condition, config.win_condition);
+    assert_eq!(copy.theme, Theme::DARK);
+    let colors = |config: &Config| config.players.iter().map(|p| p.color()).collect::<Vec<_>>();
+    assert_eq!(colors(&copy), colors(&config));
+}
+
+/* The grid in the middle of the cascade of grid_survives_a_round_trip_during_a_cascade(), as JSON
+ * that can be changed before reading it back
+ */
+fn cascading() -> serde_json::Value {
+    let mut grid = Grid::decode("3x3 a1b2.b1a1b2.a1.", 2, CELLSIZE).unwrap();
+    let (state, _) = grid.add_marble(Point::new(1, 0), owner(1), CELLSIZE).unwrap();
+    grid.step(state, 0.4, CELLSIZE);
+    serde_json::to_value(&grid).unwrap()
+}
+
+/* Index of the cell at p in the serialized cells of a 3x3 grid, which are stored column by column */
+fn cell(p: Point) -> usize {
+    (p.re * 3 + p.im) as usize
+}
+
+#[test]
+fn inconsistent_cells_are_refused() {
+    assert!(serde_json::from_value::<Grid>(cascading()).is_ok());
+    let refused = |change: &dyn Fn(&mut serde_json::Value)| {
+        let mut value = cascading();
+        change(&mut value);
+        serde_json::from_value::<Grid>(value).is_err()
+    };
+    // A cell that holds marbles, one that does not and one that is about to spread
+    let (taken, empty, spreading) = (cell(Point::new(1, 2)), cell(Point::new(2, 2)), cell(Point::new(1, 0)));
+
+    // Owners of players that can not exist
+    assert!(refused(&|v| v["cells"][taken]["owner"] = 30.into()));
+    assert!(refused(&|v| {
+        v["cells"][taken]["owner"] = 30.into();
+        for slots in v["cells"][taken]["slots"].as_array_mut().unwrap() {
+            for marble in slots["marbles"].as_array_mut().unwrap().iter_mut().filter(|m| !m.is_null()) {
+                marble["owner"] = 30.into();
+            }
+        }
+    }));
+    // Counts that do not match the marbles in the slots, or cells without marbles that have an owner
+    assert!(refused(&|v| v["cells"][taken]["count"] = 2.into()));
+    assert!(refused(&|v| v["cells"][empty]["owner"] = 0.into()));
+    // Neighbors that do not fit the shape of the board
+    assert!(refused(&|v| v["cells"][empty]["has_neighbor"][2] = false.into()));
+    assert!(refused(&|v| v["cells"][empty]["neighbors"] = 3.into()));
+    assert!(refused(&|v| v["cells"][empty]["playable"] = false.into()));
+    // Cells at capacity that are not about to spread, and the other way around
+    assert!(refused(&|v| v["critical"] = serde_json::json!([])));
+    assert!(refused(&|v| v["critical"].as_array_mut().unwrap().push(empty.into())));
+    assert!(refused(&|v| v["critical"] = serde_json::json!([9])));
+    // Marbles moving towards a cell that is not part of the current spreading step
+    assert!(refused(&|v| v["wave"] = serde_json::json!([])));
+    assert!(refused(&|v| v["wave"] = serde_json::json!([spreading])));
+}
+
+#[test]
+fn grids_survive_a_round_trip_at_every_frame() {
+    let mut rng = Rng::new(340);
+    let mut grid = Grid::new(Point::new(4, 3));
+    let mut frames = 0;
+    for turn in 0..30 {
+        let player = owner(turn % 2);
+        let moves: Vec<Point> = grid.points().filter(|&p| grid.check_move(p, player).is_ok()).collect();
+        if moves.is_empty() || grid.single_owner().is_some() {
+            break;
+        }
+        let (mut state, _) = grid.add_marble(moves[rng.below(moves.len())], player, CELLSIZE).unwrap();
+        while matches!(state, State::Animating(_)) && grid.chain() < 200 {
+            let copy: Grid = serde_json::from_str(&serde_json::to_string(&grid).unwrap()).unwrap();
+            assert_eq!(copy.hash(), grid.hash());
+            assert_eq!(positions(&copy), positions(&grid));
+            state = grid.step(state, 0.3, CELLSIZE).0;
+            frames += 1;
+        }
+    }
+    assert!(frames > 50, "only {} frames", frames);
+}
+
+#[test]
+fn grids_saved_before_the_reserve_slots_are_read() {
+    // Saved without the Reserve slots, the threshold factor, the spread bias and the sub-pixel
+    // progress of the marbles
+    let empty = r#"{"marbles":[null,null,null,null]}"#;
+    let cell = |coord: &str, owner: &str, count: u8, has_neighbor: &str, residing: &str| format!(
+        r#"{{"coord":{},"playable":true,"owner":{},"neighbors":2,"count":{},"has_neighbor":{},"slots":[{{"marbles":{}}},{},{}]}}"#,
+        coord, owner, count, has_neighbor, residing, empty, empty,
+    );
+    let text = format!(
+        r#"{{"dim":[2,2],"cells":[{},{},{},{}],"placed":2,"removed":0,"critical":[],"wave":[],"chain":0,"min_marbles":2}}"#,
+        cell("[0,0]", "0", 1, "[true,true,false,false]",
+            r#"[{"pos":[75,50],"start":[75,50],"owner":0,"id":0,"from_dir":null},null,null,null]"#),
+        cell("[0,1]", "null", 0, "[true,false,false,true]", "[null,null,null,null]"),
+        cell("[1,0]", "null", 0, "[false,true,true,false]", "[null,null,null,null]"),
+        cell("[1,1]", "1", 1, "[false,false,true,true]",
+            r#"[null,null,{"pos":[125,150],"start":[125,150],"owner":1,"id":1,"from_dir":null},null]"#),
+    );
+    let grid: Grid = serde_json::from_str(&text).unwrap();
+    assert_eq!(grid.encode(), "2x2 a12.b1");
+    assert_eq!(grid.threshold_factor(), 1.0);
+    assert_eq!(grid.hash(), Grid::decode("2x2 a12.b1", 2, CELLSIZE).unwrap().hash());
+    // The saved grid continues like a new one
+    let mut grid = grid;
+    let waves = grid.cascade(Point::new(0, 0), owner(0), CELLSIZE).unwrap().count();
+    assert!(waves > 0);
+    assert_eq!(grid.marbles().count(), 3);
+
+    // More sets of slots are refused
+    let five = text.replacen(&format!("{},{}]", empty, empty), &format!("{},{},{},{}]", empty, empty, empty, empty), 1);
+    assert!(serde_json::from_str::<Grid>(&five).is_err());
+}
@@ -0,0 +1,34 @@
+use sdl2::rect::Rect;
+
+use chain::render::{shadow_alpha, shadow_lift, shadow_rect};
+
+#[test]
+fn shadows_fade_towards_their_edge() {
+    let alphas: Vec<u8> = (0..=10).map(|i| shadow_alpha(i as f32 / 10.0)).collect();
+    assert!(alphas[0] > 0);
+    assert!(alphas.windows(2).all(|pair| pair[0] >= pair[1]));
+    assert_eq!(alphas[10], 0);
+    assert_eq!(shadow_alpha(1.5), 0);
+}
+
+#[test]
+fn lifted_marbles_cast_flatter_shadows_further_away() {
+    let marble = Rect::new(100, 100, 41, 41);
+    let resting = shadow_rect(marble, 0.0);
+    assert!(resting.center().x() > marble.center().x() && resting.center().y() > marble.center().y());
+    assert_eq!(resting.width(), marble.width());
+    assert!(resting.height() < marble.height());
+
+    let lifted = shadow_rect(marble, 1.0);
+    assert!(lifted.center().x() > resting.center().x() && lifted.center().y() > resting.center().y());
+    assert!(lifted.height() < resting.height());
+}
+
+#[test]
+fn travelling_marbles_rise_and_land_again() {
+    assert!(shadow_lift(1.0).abs() < 1e-6);
+    assert!((shadow_lift(0.5) - 1.0).abs() < 1e-6);
+    assert!(shadow_lift(0.0).abs() < 1e-6);
+    let lifts: Vec<f32> = (0..=10).map(|i| shadow_lift(1.0 - i as f32 / 20.0)).collect();
+    assert!(lifts.windows(2).all(|pair| pair[0] < pair[1]));
+}
@@ -0,0 +1,64 @@
+use chain::grid::{Grid, Point, DIRECTIONS};
+use chain::shape::Shape;
+
+mod common;
+use common::{owner, CELLSIZE};
+
+const SHAPES: [Shape; 4] = [Shape::Rectangle, Shape::Plus, Shape::Donut, Shape::Diamond];
+
+#[test]
+fn cells_only_count_neighbors_on_the_board() {
+    for shape in SHAPES {
+        for dim in [Point::new(3, 3), Point::new(6, 6), Point::new(7, 5)] {
+            let grid = Grid::from_mask(dim, &shape.mask(dim));
+            for (p, cell) in grid.cells() {
+                assert_eq!(cell.playable(), shape.contains(p, dim));
+                let neighbors = DIRECTIONS.iter()
+                    .map(|&d| p + d)
+                    .filter(|&q| q.re >= 0 && q.im >= 0 && q.re < dim.re && q.im < dim.im && shape.contains(q, dim))
+                    .count();
+                let expected = if cell.playable() { neighbors as u8 } else { 0 };
+                assert_eq!(cell.threshold(), expected, "{} {}x{} at {}", shape.name(), dim.re, dim.im, p);
+            }
+        }
+    }
+}
+
+#[test]
+fn concave_edges_of_a_plus() {
+    // The arms of a plus on 6x6 are the rows and columns 2 and 3
+    let dim = Point::new(6, 6);
+    let grid = Grid::from_mask(dim, &Shape::Plus.mask(dim));
+    assert!(!grid.cell(Point::new(1, 1)).playable());
+    // Tip of an arm, the side of an arm and the inner corner next to the cut off corner
+    assert_eq!(grid.cell(Point::new(2, 0)).threshold(), 2);
+    assert_eq!(grid.cell(Point::new(2, 1)).threshold(), 3);
+    assert_eq!(grid.cell(Point::new(2, 2)).threshold(), 4);
+    assert_eq!(grid.cell(Point::new(1, 2)).threshold(), 3);
+}
+
+#[test]
+fn marbles_never_spread_off_the_board() {
+    for shape in [Shape::Plus, Shape::Donut, Shape::Diamond] {
+        let dim = Point::new(6, 6);
+        let mut grid = Grid::from_mask(dim, &shape.mask(dim));
+        let mut played = 0;
+        for turn in 0..60 {
+            let player = owner(turn % 2);
+            let free: Vec<Point> = grid.cells().filter(|(_, cell)| cell.accepts(player)).map(|(p, _)| p).collect();
+            let p = match free.get(turn * 7 % free.len().max(1)) {
+                Some(&p) => p,
+                None => break,
+            };
+            let _ = grid.cascade(p, player, CELLSIZE).unwrap().count();
+            played += 1;
+            for (p, cell) in grid.cells().filter(|(_, cell)| !cell.playable()) {
+                assert_eq!((cell.owner(), cell.marbles().count()), (None, 0), "{} at {}", shape.name(), p);
+            }
+            if grid.single_owner().is_some() {
+                break;
+            }
+        }
+        assert!(played > 10, "{}", shape.name());
+    }
+}
@@ -0,0 +1,91 @@
+use chain::grid::{Grid, Owner, Point};
+
+mod common;
+use common::{owner, Rng, CELLSIZE};
+
+const PLAYERS: usize = 2;
+
+/* Random settled position where each cell is empty or belongs to a player */
+fn random_position(rng: &mut Rng, dim: Point) -> Grid {
+    common::random_position(rng, dim, PLAYERS, false)
+}
+
+type Snapshot = (String, u64, Vec<u8>, u32, Vec<(u32, Point, Owner)>);
+
+// Everything about a grid that a simulation could change, including where each marble rests
+fn snapshot(grid: &Grid) -> Snapshot {
+    let mut marbles: Vec<_> = grid.marbles().map(|m| (m.id(), m.get_pos(), m.get_owner())).collect();
+    marbles.sort_by_key(|&(id, _, _)| id);
+    (grid.encode(), grid.hash(), grid.to_bytes(), grid.chain(), marbles)
+}
+
+/* Position after the move, played on a copy of the grid, or None if one player took over the
+ * board and the cascade need not end */
+fn played(grid: &Grid, p: Point, owner: Owner) -> Option<String> {
+    let mut copy = grid.clone();
+    let waves: Result<Vec<_>, _> = copy.cascade(p, owner, CELLSIZE).unwrap().collect();
+    match (waves, copy.single_owner()) {
+        (Ok(_), None) => Some(copy.encode()),
+        _ => None,
+    }
+}
+
+#[test]
+fn simulated_moves_are_undone() {
+    let mut rng = Rng(0x51_3A7E);
+    let mut simulated = 0;
+    for _ in 0..200 {
+        let mut grid = random_position(&mut rng, Point::new(5, 4));
+        let p = Point::new(rng.next(5) as i32, rng.next(4) as i32);
+        let owner = Owner::new(rng.next(PLAYERS), PLAYERS).unwrap();
+        let before = snapshot(&grid);
+        #[cfg(feature = "serde")]
+        let json = serde_json::to_string(&grid).unwrap();
+        let expected = grid.check_move(p, owner).map(|()| played(&grid, p, owner));
+        let seen = grid.with_simulated_move(p, owner, CELLSIZE, |grid| grid.encode());
+        // The closure sees the same position as playing the move on a copy, unless the cascade
+        // never ends
+        if let (Ok(seen), Ok(Some(expected))) = (&seen, &expected) {
+            assert_eq!(seen, expected);
+            simulated += 1;
+        }
+        assert_eq!(seen.is_ok(), expected.is_ok());
+        assert_eq!(snapshot(&grid), before);
+        #[cfg(feature = "serde")]
+        assert_eq!(serde_json::to_string(&grid).unwrap(), json);
+    }
+    assert!(simulated > 50);
+}
+
+#[test]
+fn simulations_can_be_nested() {
+    let mut rng = Rng(0xDEE9);
+    for _ in 0..10 {
+        let mut grid = random_position(&mut rng, Point::new(4, 3));
+        let before = snapshot(&grid);
+        let (a, b) = (owner(0), owner(1));
+        let first: Vec<Point> = grid.points().filter(|&p| grid.check_move(p, a).is_ok()).collect();
+        for &p in first.iter() {
+            let inner = grid.with_simulated_move(p, a, CELLSIZE, |grid| {
+                let after_first = snapshot(grid);
+                let replies: Vec<Point> = grid.points().filter(|&q| grid.check_move(q, b).is_ok()).collect();
+                for q in replies {
+                    grid.with_simulated_move(q, b, CELLSIZE, |_| ()).unwrap();
+                    assert_eq!(snapshot(grid), after_first);
+                }
+            });
+            assert!(inner.is_ok());
+            assert_eq!(snapshot(&grid), before);
+        }
+    }
+}
+
+#[test]
+fn refused_moves_leave_the_grid_alone() {
+    let mut grid = Grid::decode("3x3 a1b1.......", PLAYERS, CELLSIZE).unwrap();
+    let before = snapshot(&grid);
+    let a = owner(0);
+    assert!(grid.with_simulated_move(Point::new(1, 0), a, CELLSIZE, |_| ()).is_err());
+    assert!(grid.with_simulated_move(Point::new(5, 0), a, CELLSIZE, |_| ()).is_err());
+    assert_eq!(snapshot(&grid), before);
+}
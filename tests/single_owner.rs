@@ -0,0 +1,63 @@
+use chain::game::State;
+use chain::grid::{Grid, Point};
+
+mod common;
+use common::{owner, CELLSIZE};
+
+#[test]
+fn first_move_does_not_decide_the_game() {
+    let mut grid = Grid::new(Point::new(3, 3));
+    assert!(grid.is_empty());
+    assert_eq!(grid.single_owner(), None);
+
+    grid.add_marble(Point::new(1, 1), owner(0), CELLSIZE).unwrap();
+    assert!(!grid.is_empty());
+    assert_eq!(grid.single_owner(), None);
+    grid.set_min_marbles(1);
+    assert_eq!(grid.single_owner(), Some(owner(0)));
+
+    grid.add_marble(Point::new(0, 1), owner(1), CELLSIZE).unwrap();
+    assert_eq!(grid.single_owner(), None);
+}
+
+#[test]
+fn single_owner_follows_captures_during_the_cascade() {
+    let mut grid = Grid::decode("2x2 a1b1..", 2, CELLSIZE).unwrap();
+    assert_eq!(grid.single_owner(), None);
+    let (mut state, _) = grid.add_marble(Point::new(1, 0), owner(1), CELLSIZE).unwrap();
+    // The only cell of the first player is captured by the first wave, while the second wave is
+    // still to come
+    assert!(matches!(state, State::Animating(_)));
+    assert_eq!(grid.single_owner(), Some(owner(1)));
+    while let State::Animating(_) = state {
+        state = grid.step(state, 0.5, CELLSIZE).0;
+        assert_eq!(grid.single_owner(), Some(owner(1)));
+    }
+    assert_eq!(grid.encode(), "2x2 .3b1");
+    assert_eq!(grid.chain(), 2);
+}
+
+#[test]
+fn neutral_cells_do_not_prevent_a_decision() {
+    let grid = Grid::decode("3x3 a1*1.b2.4a1", 2, CELLSIZE).unwrap();
+    assert_eq!(grid.single_owner(), None);
+    let grid = Grid::decode("3x3 a1*1.a2.4a1", 2, CELLSIZE).unwrap();
+    assert_eq!(grid.single_owner(), Some(owner(0)));
+    let grid = Grid::decode("3x3 *1*17.", 2, CELLSIZE).unwrap();
+    assert!(!grid.is_empty());
+    assert_eq!(grid.single_owner(), None);
+}
+
+#[test]
+fn editing_keeps_the_owner_up_to_date() {
+    let mut grid = Grid::decode("3x3 a1a17.", 2, CELLSIZE).unwrap();
+    assert_eq!(grid.single_owner(), Some(owner(0)));
+    grid.set_owner(Point::new(1, 0), owner(1)).unwrap();
+    assert_eq!(grid.single_owner(), None);
+    grid.remove_marble(Point::new(0, 0)).unwrap();
+    assert_eq!(grid.single_owner(), None);
+    grid.insert_marble(Point::new(2, 2), owner(1), CELLSIZE).unwrap();
+    assert_eq!(grid.single_owner(), Some(owner(1)));
+    grid.neutralize(owner(1));
+    assert_eq!(grid.single_owner(), None);
+}
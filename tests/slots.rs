@@ -0,0 +1,42 @@
+use chain::game::State;
+use chain::grid::{Grid, Point, SlotKind};
+use chain::render::slot_tint;
+
+mod common;
+use common::{owner, CELLSIZE};
+
+fn kinds(grid: &Grid) -> Vec<SlotKind> {
+    grid.cells().flat_map(|(_, cell)| cell.marbles_by_slot().map(|(kind, _, _)| kind)).collect()
+}
+
+#[test]
+fn marbles_are_listed_with_their_slots() {
+    let mut grid = Grid::decode("3x3 a1b2.b1.....", 2, CELLSIZE).unwrap();
+    assert!(kinds(&grid).iter().all(|&kind| kind == SlotKind::Residing));
+
+    let (mut state, _) = grid.add_marble(Point::new(1, 0), owner(1), CELLSIZE).unwrap();
+    let mut seen = Vec::new();
+    while let State::Animating(_) = state {
+        for (_, cell) in grid.cells() {
+            // The same marbles in the same order as without the slots
+            let ids: Vec<u32> = cell.marbles_by_slot().map(|(_, _, marble)| marble.id()).collect();
+            assert_eq!(ids, cell.marbles().map(|marble| marble.id()).collect::<Vec<_>>());
+        }
+        seen.extend(kinds(&grid));
+        state = grid.step(state, 0.25, CELLSIZE).0;
+    }
+    assert!(seen.iter().any(|&kind| kind != SlotKind::Residing));
+
+    // Once settled, marbles rest in their cells
+    let settled = kinds(&grid);
+    assert_eq!(settled.len(), grid.marbles().count());
+    assert!(settled.iter().all(|&kind| kind == SlotKind::Residing || kind == SlotKind::Reserve));
+}
+
+#[test]
+fn each_slot_has_its_own_tint() {
+    for (idx, &kind) in SlotKind::ALL.iter().enumerate() {
+        assert!(SlotKind::ALL[idx + 1..].iter().all(|&other| slot_tint(other) != slot_tint(kind)));
+        assert!(slot_tint(kind).a > 0);
+    }
+}
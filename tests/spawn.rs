@@ -0,0 +1,36 @@
+use sdl2::rect::Rect;
+
+use chain::grid::{Grid, Point};
+use chain::render::{scaled_rect, spawn_scale};
+
+mod common;
+use common::{owner, CELLSIZE};
+
+#[test]
+fn placed_marbles_grow_into_their_slot() {
+    assert!((spawn_scale(0) - 0.3).abs() < 1e-6);
+    let scales: Vec<f32> = (0..=10).map(spawn_scale).collect();
+    // They grow slightly past their size and settle back on it
+    assert!(scales.iter().any(|&scale| scale > 1.0));
+    assert!(scales.iter().all(|&scale| scale < 1.1));
+    assert_eq!(spawn_scale(10), 1.0);
+    assert_eq!(spawn_scale(1000), 1.0);
+
+    let rect = Rect::new(10, 20, 41, 41);
+    let small = scaled_rect(rect, 0.3);
+    assert_eq!(small.center(), rect.center());
+    assert_eq!((small.width(), small.height()), (12, 12));
+    assert_eq!(scaled_rect(rect, 1.0), rect);
+}
+
+#[test]
+fn the_last_placed_marble_is_known() {
+    let mut grid = Grid::new(Point::new(3, 3));
+    assert_eq!(grid.last_placed(), None);
+    let player = owner(0);
+    grid.add_marble(Point::new(1, 1), player, CELLSIZE).unwrap();
+    assert_eq!(grid.last_placed(), Some(0));
+    grid.add_marble(Point::new(1, 1), player, CELLSIZE).unwrap();
+    let newest = grid.marbles().map(|marble| marble.id()).max();
+    assert_eq!(grid.last_placed(), newest);
+}
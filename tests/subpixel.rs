@@ -0,0 +1,46 @@
+use sdl2::rect::Rect;
+
+use chain::game::State;
+use chain::grid::{slot_position, subpixel, Grid, Point};
+use chain::render::marble_rect;
+
+mod common;
+use common::owner;
+
+// Cells much smaller than common::CELLSIZE, where a marble moves less than a pixel in many frames
+const SMALL_CELLSIZE: Point = Point::new(10, 10);
+
+#[test]
+fn marbles_move_in_every_frame_and_settle_on_their_slots() {
+    let mut grid = Grid::decode("2x2 a1b1..", 2, SMALL_CELLSIZE).unwrap();
+    let (mut state, _) = grid.add_marble(Point::new(1, 0), owner(1), SMALL_CELLSIZE).unwrap();
+    let ids: Vec<u32> = grid.in_flight().map(|marble| marble.id).collect();
+    assert_eq!(ids.len(), 2);
+    let position = |grid: &Grid, id: u32| grid.marbles().find(|m| m.id() == id).unwrap().subpixel_pos();
+
+    // Until they arrive in the neighbors, in steps that add up to exactly one
+    for frame in 0..32 {
+        let before: Vec<_> = ids.iter().map(|&id| position(&grid, id)).collect();
+        state = grid.step(state, 1.0 / 32.0, SMALL_CELLSIZE).0;
+        for (&id, &old) in ids.iter().zip(before.iter()) {
+            assert_ne!(position(&grid, id), old, "Marble {} stood still in frame {}", id, frame);
+        }
+    }
+    while let State::Animating(_) = state {
+        state = grid.step(state, 1.0 / 32.0, SMALL_CELLSIZE).0;
+    }
+
+    let (scale, radius) = (3, 4);
+    for (coord, cell) in grid.cells() {
+        let slots: Vec<Point> = (0..4).filter(|&d| cell.has_neighbor(d))
+            .map(|d| slot_position(coord, d, SMALL_CELLSIZE)).collect();
+        for marble in cell.marbles() {
+            let slot = *slots.iter().find(|&&slot| slot == marble.get_pos()).unwrap();
+            assert!((marble.subpixel_pos() - subpixel(slot)).norm() < 1e-4);
+            // The same rectangle as when positions were whole pixels
+            let pos = slot * scale;
+            let size = 2*radius as u32 + 1;
+            assert_eq!(marble_rect(marble.subpixel_pos(), scale, radius), Rect::new(pos.re - radius, pos.im - radius, size, size));
+        }
+    }
+}
@@ -0,0 +1,66 @@
+use sdl2::pixels::Color;
+
+use chain::game::Player;
+use chain::tally::{Tally, TALLY_VARIABLE};
+
+#[test]
+fn wins_are_kept_per_color() {
+    let (red, blue, green) = (
+        Player::new(Color::RGB(255, 0, 0)),
+        Player::new(Color::RGB(0, 0, 255)),
+        Player::new(Color::RGB(0, 160, 0)),
+    );
+    assert_eq!(Tally::key(&red), "#FF0000");
+    // Named players keep their wins when they pick another color
+    let mut named = Player::new(Color::RGB(1, 2, 3));
+    named.set_name("Ada Lovelace");
+    assert_eq!(Tally::key(&named), "Ada Lovelace");
+    let mut tally = Tally::new();
+    tally.record_win(&blue);
+    tally.record_win(&red);
+    tally.record_win(&red);
+    tally.record_win(&green);
+    assert_eq!((tally.wins(&red), tally.wins(&blue)), (2, 1));
+    // A player that joins later starts without wins and the colors keep theirs
+    assert_eq!(tally.wins(&Player::new(Color::RGB(255, 255, 0))), 0);
+    assert_eq!(tally.standings(), [
+        ("#FF0000".to_string(), 2),
+        ("#0000FF".to_string(), 1),
+        ("#00A000".to_string(), 1),
+    ]);
+}
+
+#[test]
+fn tally_is_written_and_read_back() {
+    let mut tally = Tally::new();
+    tally.record_win(&Player::new(Color::RGB(0x1E, 0x90, 0xFF)));
+    assert_eq!(tally.to_text(), "1 #1E90FF\n");
+    assert_eq!(Tally::parse(&tally.to_text()), Ok(tally));
+    assert_eq!(Tally::parse("\n3 #FF0000\n"), Ok(Tally::parse("3 #FF0000").unwrap()));
+    let named = Tally::parse("2 Ada Lovelace\n").unwrap();
+    assert_eq!(named.standings(), [("Ada Lovelace".to_string(), 2)]);
+    for malformed in ["#FF0000", "x #FF0000", "-1 #FF0000", "1 #FF0000\n2 #FF0000"] {
+        assert!(Tally::parse(malformed).is_err(), "{} was accepted", malformed);
+    }
+}
+
+#[test]
+fn broken_tally_files_are_kept() {
+    let path = std::env::temp_dir().join(format!("chainreaction-tally-{}.conf", std::process::id()));
+    std::env::set_var(TALLY_VARIABLE, &path);
+    let mut tally = Tally::new();
+    tally.record_win(&Player::new(Color::RGB(255, 0, 0)));
+    tally.save().unwrap();
+    assert_eq!(Tally::load(), Ok(tally));
+
+    // A file that can not be read is moved out of the way, so saving does not lose its wins
+    std::fs::write(&path, "3 #FF0000\nbroken").unwrap();
+    let error = Tally::load().err().unwrap();
+    let broken = path.with_extension("conf.broken");
+    assert!(error.contains(&broken.display().to_string()), "{}", error);
+    assert_eq!(Tally::load(), Ok(Tally::new()));
+    Tally::new().save().unwrap();
+    assert_eq!(std::fs::read_to_string(&broken).unwrap(), "3 #FF0000\nbroken");
+    std::fs::remove_file(&path).unwrap();
+    std::fs::remove_file(&broken).unwrap();
+}
@@ -0,0 +1,47 @@
+use sdl2::pixels::Color;
+
+use chain::render::luminance;
+use chain::theme::Theme;
+
+// Ratio between the luminance of the lighter and the darker color as defined by WCAG
+fn contrast(a: Color, b: Color) -> f32 {
+    let (a, b) = (luminance(a) + 0.05, luminance(b) + 0.05);
+    a.max(b) / a.min(b)
+}
+
+#[test]
+fn themes_are_cycled_through_by_name() {
+    assert_eq!(Theme::default(), Theme::CLASSIC);
+    assert_eq!(Theme::CLASSIC.next(), Theme::DARK);
+    assert_eq!(Theme::DARK.next(), Theme::CLASSIC);
+    // The high contrast theme is not selected in the menu
+    assert_eq!(Theme::HIGH_CONTRAST.next(), Theme::CLASSIC);
+    for theme in Theme::ALL.into_iter().chain([Theme::HIGH_CONTRAST]) {
+        assert_eq!(Theme::by_name(theme.name()), Some(theme));
+    }
+    let custom = Theme { lines: Color::RGB(1, 2, 3), ..Theme::DARK };
+    assert_eq!(custom.name(), "custom");
+    assert_eq!(Theme::by_name("custom"), None);
+}
+
+#[test]
+fn themes_can_be_read() {
+    for theme in Theme::ALL.into_iter().chain([Theme::HIGH_CONTRAST]) {
+        let name = theme.name();
+        assert!(contrast(theme.text, theme.sidebar) >= 4.5, "text of {}", name);
+        assert!(contrast(theme.lines, theme.background) >= 1.5, "lines of {}", name);
+        assert!(contrast(theme.selection, theme.background) >= 3.0, "selection of {}", name);
+        assert!(contrast(theme.clear, theme.background) >= 1.5, "cells outside of {}", name);
+    }
+    // The selection stands out against the thick lines of the high contrast mode
+    assert!(contrast(Theme::HIGH_CONTRAST.selection, Theme::HIGH_CONTRAST.lines) >= 3.0);
+    // The dark theme is darker everywhere
+    for (dark, classic) in [
+        (Theme::DARK.background, Theme::CLASSIC.background),
+        (Theme::DARK.sidebar, Theme::CLASSIC.sidebar),
+        (Theme::DARK.clear, Theme::CLASSIC.clear),
+    ] {
+        assert!(luminance(dark) < luminance(classic));
+    }
+}
+
@@ -0,0 +1,90 @@
+use chain::bench;
+use chain::grid::{threshold, Grid, Point};
+
+mod common;
+use common::{owner, CELLSIZE};
+
+// Corner, edge and center of a 3x3 board
+const CELLS: [Point; 3] = [Point::new(0, 0), Point::new(1, 0), Point::new(1, 1)];
+
+fn grid(factor: f32) -> Grid {
+    let mut grid = Grid::new(Point::new(3, 3));
+    grid.set_threshold_factor(factor).unwrap();
+    grid
+}
+
+#[test]
+fn thresholds_scale_with_the_neighbors() {
+    for (factor, expected, capacity) in [(0.5, [1, 2, 2], 5), (1.0, [2, 3, 4], 15), (2.0, [4, 6, 8], 39)] {
+        let grid = grid(factor);
+        assert_eq!(CELLS.map(|p| grid.cell(p).threshold()), expected, "factor {}", factor);
+        assert_eq!(grid.capacity(), capacity, "factor {}", factor);
+    }
+    // Cells with neighbors spread at one marble or more, and the factor is capped
+    assert_eq!(threshold(2, 0.1), 1);
+    assert_eq!(threshold(4, 5.0), 8);
+    assert_eq!(threshold(0, 1.0), 0);
+}
+
+#[test]
+fn cells_spread_at_their_threshold() {
+    for factor in [0.5, 1.0, 2.0] {
+        for p in CELLS {
+            let mut grid = grid(factor);
+            let limit = grid.cell(p).threshold() as usize;
+            for count in 1..limit {
+                assert_eq!(grid.cascade(p, owner(0), CELLSIZE).unwrap().count(), 0);
+                assert_eq!(grid.cell(p).marbles().count(), count);
+            }
+            let waves = grid.cascade(p, owner(0), CELLSIZE).unwrap();
+            assert!(!waves.collect::<Result<Vec<_>, _>>().unwrap().is_empty(), "factor {} at {}", factor, p);
+            assert_eq!(grid.marbles().count(), limit);
+            assert!(grid.cell(p).marbles().count() < limit);
+        }
+    }
+}
+
+#[test]
+fn positions_keep_counts_above_the_neighbors() {
+    let text = "3x3 a3b5a26.";
+    assert!(Grid::decode(text, 2, CELLSIZE).is_err());
+    let grid = Grid::decode_with_threshold(text, 2, CELLSIZE, 2.0).unwrap();
+    assert_eq!(grid.encode(), text);
+    assert_eq!(grid.cell(Point::new(1, 0)).marbles().count(), 5);
+    // Turning keeps the marbles beyond one per neighbor
+    assert_eq!(grid.rotated(1, CELLSIZE).rotated(3, CELLSIZE).encode(), text);
+    assert_eq!(grid.mirrored(true, CELLSIZE).mirrored(true, CELLSIZE).encode(), text);
+}
+
+#[test]
+fn games_end_with_any_factor() {
+    for factor in [0.5, 2.0] {
+        let mut config = bench::config(2, Point::new(4, 3)).unwrap();
+        config.threshold_factor = factor;
+        let stats = bench::run(&config, 10, 3).unwrap();
+        assert_eq!(stats.wins.iter().sum::<usize>() + stats.undecided, 10);
+        assert!(stats.max_depth > 0);
+    }
+}
+
+#[test]
+fn factors_only_change_on_empty_boards() {
+    let mut grid = grid(1.0);
+    grid.cascade(Point::new(1, 1), owner(0), CELLSIZE).unwrap().for_each(drop);
+    assert!(grid.set_threshold_factor(2.0).is_err());
+    assert_eq!(grid.cell(Point::new(1, 1)).threshold(), 4);
+    grid.reset();
+    grid.set_threshold_factor(2.0).unwrap();
+    assert_eq!(grid.cell(Point::new(1, 1)).threshold(), 8);
+}
+
+#[test]
+fn factors_are_validated() {
+    let mut config = bench::config(2, Point::new(4, 3)).unwrap();
+    for factor in [0.0, -1.0, 2.5, f32::NAN] {
+        config.threshold_factor = factor;
+        assert!(config.validate().is_err(), "factor {}", factor);
+    }
+    config.threshold_factor = 1.5;
+    assert!(config.validate().is_ok());
+}
@@ -0,0 +1,26 @@
+use sdl2::rect::Rect;
+
+use chain::render::corner_ticks;
+
+#[test]
+fn ticks_are_inside_the_cell_away_from_its_edges() {
+    for (w, h) in [(100, 100), (40, 40), (120, 80)] {
+        let cell = Rect::new(200, 300, w, h);
+        let ticks = corner_ticks(cell);
+        assert_eq!(ticks.len(), 8);
+        let margin = w.min(h) as i32 / 10;
+        for tick in ticks.iter() {
+            assert!(tick.left() >= cell.left() + margin && tick.right() <= cell.right() - margin, "{:?}", tick);
+            assert!(tick.top() >= cell.top() + margin && tick.bottom() <= cell.bottom() - margin, "{:?}", tick);
+        }
+        // Each corner is marked
+        let inner = Rect::new(cell.x() + margin, cell.y() + margin, w - 2 * margin as u32, h - 2 * margin as u32);
+        for corner in [inner.top_left(), inner.top_right(), inner.bottom_left(), inner.bottom_right()] {
+            let x = if corner.x() == inner.right() { corner.x() - 1 } else { corner.x() };
+            let y = if corner.y() == inner.bottom() { corner.y() - 1 } else { corner.y() };
+            assert_eq!(ticks.iter().filter(|tick| tick.contains_point((x, y))).count(), 2, "{:?}", corner);
+        }
+        // The middle of the cell with the marbles stays free
+        assert!(!ticks.iter().any(|tick| tick.contains_point(cell.center())));
+    }
+}
@@ -0,0 +1,164 @@
+use chain::game::State;
+use chain::grid::{mirror_point, rotate_point, Grid, Owner, Point, SpreadBias, Subpixel};
+use chain::render::rotate_subpixel;
+
+mod common;
+use common::{Rng, CELLSIZE};
+
+const PLAYERS: usize = 2;
+
+/* Random settled position where each cell is empty or belongs to a player */
+fn random_position(rng: &mut Rng, dim: Point) -> Grid {
+    common::random_position(rng, dim, PLAYERS, false)
+}
+
+// Copy of a grid and the mapping of coordinates from the original
+type Transform = (Grid, Box<dyn Fn(Point) -> Point>);
+
+// All transformations of the grid
+fn transforms(grid: &Grid) -> Vec<Transform> {
+    let dim = grid.dim();
+    let mut result: Vec<Transform> = Vec::new();
+    for turns in 0..4 {
+        result.push((grid.rotated(turns, CELLSIZE), Box::new(move |p| rotate_point(p, dim, turns))));
+    }
+    for horizontal in [true, false] {
+        result.push((grid.mirrored(horizontal, CELLSIZE), Box::new(move |p| mirror_point(p, dim, horizontal))));
+    }
+    result
+}
+
+/* Play a move and let all marbles come to rest. Returns false if the cascade does not end. */
+fn play(grid: &mut Grid, p: Point, owner: Owner) -> bool {
+    match grid.cascade(p, owner, CELLSIZE) {
+        Ok(mut waves) => waves.all(|wave| wave.is_ok()),
+        Err(_) => true,
+    }
+}
+
+#[test]
+fn playing_commutes_with_transforming() {
+    let mut rng = Rng(0x5DEE_CE66_D1CE_4E5B);
+    for _ in 0..200 {
+        let mut grid = random_position(&mut rng, Point::new(5, 4));
+        let p = Point::new(rng.next(5) as i32, rng.next(4) as i32);
+        let owner = Owner::new(rng.next(PLAYERS), PLAYERS).unwrap();
+        let before = transforms(&grid);
+        if !play(&mut grid, p, owner) {
+            continue;
+        }
+        for ((mut copy, point), (expected, _)) in before.into_iter().zip(transforms(&grid)) {
+            assert!(play(&mut copy, point(p), owner));
+            assert_eq!(copy.encode(), expected.encode());
+            assert_eq!(copy.hash(), expected.hash());
+            assert_eq!(copy.chain(), grid.chain());
+        }
+    }
+}
+
+#[test]
+fn transforming_during_a_cascade() {
+    let mut rng = Rng(0x1F2E_3D4C);
+    let mut checked = 0;
+    while checked < 50 {
+        let mut grid = random_position(&mut rng, Point::new(4, 4));
+        let p = Point::new(rng.next(4) as i32, rng.next(4) as i32);
+        let owner = Owner::new(0, PLAYERS).unwrap();
+        let mut state = match grid.add_marble(p, owner, CELLSIZE) {
+            Ok((state @ State::Animating(_), _)) => state,
+            _ => continue,
+        };
+        state = grid.step(state, 0.5, CELLSIZE).0;
+        for turns in 0..4 {
+            let mut copy = grid.rotated(turns, CELLSIZE);
+            let mut original = grid.rotated(0, CELLSIZE);
+            let (mut a, mut b) = (state, state);
+            for _ in 0..100 {
+                a = original.step(a, 0.5, CELLSIZE).0;
+                b = copy.step(b, 0.5, CELLSIZE).0;
+            }
+            assert!(matches!((a, b), (State::AcceptingInput, State::AcceptingInput)));
+            assert_eq!(copy.encode(), original.rotated(turns, CELLSIZE).encode());
+            // Marbles rest in the same slots after turning
+            let mut expected: Vec<_> = original.rotated(turns, CELLSIZE).marbles().map(|m| (m.id(), m.get_pos())).collect();
+            let mut positions: Vec<_> = copy.marbles().map(|m| (m.id(), m.get_pos())).collect();
+            expected.sort_by_key(|&(id, _)| id);
+            positions.sort_by_key(|&(id, _)| id);
+            assert_eq!(positions, expected);
+        }
+        checked += 1;
+    }
+}
+
+#[test]
+fn transforms_are_undone() {
+    let mut rng = Rng(0xABCD);
+    let grid = random_position(&mut rng, Point::new(5, 3));
+    let turned = grid.rotated(1, CELLSIZE);
+    assert_eq!(turned.dim(), Point::new(3, 5));
+    assert_eq!(turned.rotated(3, CELLSIZE).encode(), grid.encode());
+    assert_eq!(grid.rotated(2, CELLSIZE).rotated(2, CELLSIZE).encode(), grid.encode());
+    assert_eq!(grid.mirrored(true, CELLSIZE).mirrored(true, CELLSIZE).encode(), grid.encode());
+    assert_eq!(grid.mirrored(false, CELLSIZE).rotated(2, CELLSIZE).encode(), grid.mirrored(true, CELLSIZE).encode());
+
+    let mut positions: Vec<_> = grid.marbles().map(|m| (m.id(), m.get_pos())).collect();
+    let mut turned_back: Vec<_> = grid.rotated(1, CELLSIZE).rotated(1, CELLSIZE).rotated(2, CELLSIZE)
+        .marbles().map(|m| (m.id(), m.get_pos())).collect();
+    positions.sort_by_key(|&(id, _)| id);
+    turned_back.sort_by_key(|&(id, _)| id);
+    assert_eq!(turned_back, positions);
+
+    // Cells keep their owner and count
+    let p = Point::new(3, 1);
+    let cell = grid.cell(p);
+    let moved = turned.cell(rotate_point(p, grid.dim(), 1));
+    assert_eq!((moved.owner(), moved.marbles().count()), (cell.owner(), cell.marbles().count()));
+}
+
+#[test]
+fn spread_bias_only_changes_where_marbles_rest() {
+    let mut rng = Rng(0x00B1_A5ED);
+    let mut moved = 0;
+    for _ in 0..200 {
+        let grid = random_position(&mut rng, Point::new(5, 4));
+        let p = Point::new(rng.next(5) as i32, rng.next(4) as i32);
+        let owner = Owner::new(rng.next(PLAYERS), PLAYERS).unwrap();
+        let mut results = Vec::new();
+        for bias in SpreadBias::ALL {
+            let mut copy = grid.rotated(0, CELLSIZE);
+            copy.set_spread_bias(bias);
+            if !play(&mut copy, p, owner) {
+                break;
+            }
+            let mut positions: Vec<_> = copy.marbles().map(|m| (m.id(), m.get_pos())).collect();
+            positions.sort_by_key(|&(id, _)| id);
+            results.push((copy.encode(), copy.hash(), copy.chain(), positions));
+        }
+        if results.len() < 2 {
+            continue;
+        }
+        let (a, b) = (&results[0], &results[1]);
+        assert_eq!((&a.0, a.1, a.2), (&b.0, b.1, b.2));
+        moved += (a.3 != b.3) as usize;
+    }
+    // The bias matters for some of the cascades
+    assert!(moved > 0);
+}
+
+#[test]
+fn positions_turn_with_their_cells() {
+    let dim = Point::new(3, 3);
+    let size = Subpixel::new(300.0, 300.0);
+    for turns in 0..4 {
+        for x in 0..3 {
+            for y in 0..3 {
+                let center = |p: Point| Subpixel::new(p.re as f32 * 100.0 + 50.0, p.im as f32 * 100.0 + 50.0);
+                let p = Point::new(x, y);
+                assert_eq!(rotate_subpixel(center(p), size, turns), center(rotate_point(p, dim, turns)));
+            }
+        }
+    }
+    // Boards that are not square are only turned upside down
+    let corner = Subpixel::new(10.0, 20.0);
+    assert_eq!(rotate_subpixel(corner, Subpixel::new(500.0, 300.0), 2), Subpixel::new(490.0, 280.0));
+}
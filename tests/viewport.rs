@@ -0,0 +1,90 @@
+use chain::grid::{Point, Subpixel};
+use chain::render::Viewport;
+
+// A 20x15 board of 40 pixel cells, which takes 800x600 pixels at zoom 1
+fn viewport() -> Viewport {
+    Viewport::new(Point::new(20, 15), Point::new(40, 40))
+}
+
+fn close(a: Subpixel, b: Subpixel) -> bool {
+    (a - b).norm() < 0.01
+}
+
+#[test]
+fn unzoomed_boards_fill_their_area() {
+    let viewport = viewport();
+    assert!(viewport.fits());
+    assert_eq!(viewport.area(), Subpixel::new(800.0, 600.0));
+    let p = Subpixel::new(123.0, 456.0);
+    assert_eq!(viewport.to_board(p), Some(p));
+    assert_eq!(viewport.to_screen(p), p);
+    // The sidebar is right of the area and not part of the board
+    assert_eq!(viewport.to_board(Subpixel::new(800.0, 10.0)), None);
+    assert_eq!(viewport.to_board(Subpixel::new(-1.0, 10.0)), None);
+}
+
+#[test]
+fn zooming_keeps_the_point_below_the_mouse() {
+    let mut viewport = viewport();
+    let mouse = Subpixel::new(300.0, 200.0);
+    let below = viewport.to_board(mouse).unwrap();
+    for steps in [2, 3, -1, -2, 4] {
+        viewport.zoom_at(mouse, steps);
+        assert!(close(viewport.to_board(mouse).unwrap(), below), "after {} steps", steps);
+        assert!(close(viewport.to_screen(below), mouse));
+    }
+    assert!(!viewport.fits());
+    // Positions right of the area are still the sidebar, even with the board zoomed in below it
+    assert!(viewport.zoom() > 1.0);
+    assert_eq!(viewport.to_board(Subpixel::new(850.0, 200.0)), None);
+}
+
+#[test]
+fn zoom_is_limited() {
+    let mut viewport = viewport();
+    viewport.zoom_at(Subpixel::new(400.0, 300.0), 100);
+    let max = viewport.zoom();
+    viewport.zoom_at(Subpixel::new(400.0, 300.0), 1);
+    assert_eq!(viewport.zoom(), max);
+    viewport.zoom_at(Subpixel::new(400.0, 300.0), -100);
+    assert!(viewport.zoom() < 1.0 && viewport.zoom() > 0.0);
+}
+
+#[test]
+fn boards_can_not_be_moved_out_of_sight() {
+    for steps in [-4, 0, 3, 12] {
+        for (dx, dy) in [(1e6, 0.0), (-1e6, 0.0), (0.0, 1e6), (0.0, -1e6), (-1e6, 1e6)] {
+            let mut viewport = viewport();
+            viewport.zoom_at(Subpixel::new(400.0, 300.0), steps);
+            viewport.pan(Subpixel::new(dx, dy));
+            // At least one cell stays within the area
+            let (start, end) = (viewport.to_screen(Subpixel::new(0.0, 0.0)), viewport.to_screen(viewport.area()));
+            assert!(end.re >= 40.0 && end.im >= 40.0, "{} {} {}", steps, dx, dy);
+            assert!(start.re <= 760.0 && start.im <= 560.0, "{} {} {}", steps, dx, dy);
+        }
+    }
+}
+
+#[test]
+fn panning_moves_what_is_below_the_mouse() {
+    let mut viewport = viewport();
+    viewport.zoom_at(Subpixel::new(400.0, 300.0), 3);
+    let mouse = Subpixel::new(400.0, 300.0);
+    let before = viewport.to_board(mouse).unwrap();
+    viewport.pan(Subpixel::new(50.0, -20.0));
+    let after = viewport.to_board(mouse).unwrap();
+    assert!(close((before - after) * viewport.zoom(), Subpixel::new(50.0, -20.0)));
+}
+
+#[test]
+fn centering_and_rescaling_show_the_same_part() {
+    let mut viewport = viewport();
+    viewport.zoom_at(Subpixel::new(0.0, 0.0), 4);
+    let target = Subpixel::new(500.0, 250.0);
+    viewport.center_on(target);
+    assert!(close(viewport.to_board(Subpixel::new(400.0, 300.0)).unwrap(), target));
+    // Rendered at twice the size, the same part of the board is shown
+    let rescaled = viewport.rescaled(Point::new(20, 15), Point::new(80, 80));
+    assert_eq!(rescaled.zoom(), viewport.zoom());
+    assert!(close(rescaled.to_board(Subpixel::new(800.0, 600.0)).unwrap(), target * 2.0));
+}
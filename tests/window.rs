@@ -0,0 +1,40 @@
+use chain::render::{fit_window, render_scale, ScreenMap};
+
+#[test]
+fn board_is_scaled_by_whole_factors_that_fit() {
+    assert_eq!(render_scale((600, 400), (600, 400), true), 1);
+    assert_eq!(render_scale((1300, 850), (600, 400), true), 2);
+    // The smaller factor of both directions is used
+    assert_eq!(render_scale((3000, 850), (600, 400), true), 2);
+    assert_eq!(render_scale((300, 200), (600, 400), true), 1);
+    assert_eq!(render_scale((10000, 10000), (600, 400), true), 4);
+    assert_eq!(render_scale((1300, 850), (600, 400), false), 1);
+}
+
+#[test]
+fn window_fits_the_display() {
+    assert_eq!(fit_window((600, 400), (1920, 1080)), (600, 400));
+    assert_eq!(fit_window((2400, 1000), (1200, 1000)), (1200, 500));
+    assert_eq!(fit_window((1000, 2000), (1200, 1000)), (500, 1000));
+}
+
+#[test]
+fn mouse_positions_map_to_drawn_pixels() {
+    // HiDPI display with twice as many pixels as the window is large
+    let hidpi = ScreenMap { window: (800, 600), output: (1600, 1200), logical: None };
+    assert_eq!(hidpi.to_drawing(0, 0), Some((0, 0)));
+    assert_eq!(hidpi.to_drawing(400, 300), Some((800, 600)));
+    assert_eq!(hidpi.to_drawing(800, 300), None);
+
+    // Board of 700x400 logical pixels on a HiDPI display in a window that is too wide, leaving bars
+    // left and right. SDL already maps the mouse to the board, with positions on the bars outside.
+    let letterboxed = ScreenMap { window: (1000, 400), output: (2000, 800), logical: Some((700, 400)) };
+    assert_eq!(letterboxed.to_drawing(0, 0), Some((0, 0)));
+    assert_eq!(letterboxed.to_drawing(699, 399), Some((699, 399)));
+    assert_eq!(letterboxed.to_drawing(-1, 200), None);
+    assert_eq!(letterboxed.to_drawing(700, 200), None);
+
+    // Minimized windows have no size
+    let minimized = ScreenMap { window: (0, 0), output: (0, 0), logical: Some((700, 400)) };
+    assert_eq!(minimized.to_drawing(0, 0), None);
+}